@@ -0,0 +1,25 @@
+-- SONO payment channel state, persisted so restarts don't forget off-chain spend
+-- amounts are U256 raw TXT units stored as decimal strings
+CREATE TABLE IF NOT EXISTS sono_channels (
+    user_address TEXT PRIMARY KEY,
+    deposit TEXT NOT NULL,
+    spent TEXT NOT NULL DEFAULT '0',
+    -- off-chain state nonce, never decreases (even across close/reopen)
+    nonce BIGINT NOT NULL DEFAULT 0,
+    closed BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_sono_channels_open
+  ON sono_channels(user_address) WHERE closed = FALSE;
+
+-- every state update the service has signed (usable by the user in a dispute)
+CREATE TABLE IF NOT EXISTS sono_channel_states (
+    user_address TEXT NOT NULL REFERENCES sono_channels(user_address),
+    spent TEXT NOT NULL,
+    nonce BIGINT NOT NULL,
+    signature BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_address, nonce)
+);
@@ -0,0 +1,6 @@
+-- auto-crawl scheduler: optionally queue TTS when crawled content changes
+ALTER TABLE sites ADD COLUMN IF NOT EXISTS auto_tts BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE sites ADD COLUMN IF NOT EXISTS tts_voice TEXT NOT NULL DEFAULT 'serena';
+
+CREATE INDEX IF NOT EXISTS idx_sites_auto_crawl
+  ON sites(last_crawled_at NULLS FIRST) WHERE auto_crawl = TRUE;
@@ -0,0 +1,35 @@
+-- Transparency log of approved worker measurements.
+-- Append-only: every entry is hash-chained to the previous one and signed
+-- by the log key, so clients can audit which builds the API will talk to.
+CREATE TABLE IF NOT EXISTS worker_measurements (
+    seq BIGINT PRIMARY KEY,
+    -- 'add' or 'revoke'
+    action TEXT NOT NULL CHECK (action IN ('add', 'revoke')),
+    -- 'sev-snp' or 'tdx'
+    tee_type TEXT NOT NULL,
+    -- SNP: launch measurement hex; TDX: mrtd[:rtmr0[:rtmr1[:rtmr2[:rtmr3]]]] hex
+    measurement TEXT NOT NULL,
+    version TEXT NOT NULL DEFAULT '',
+    git_commit TEXT NOT NULL DEFAULT '',
+    note TEXT NOT NULL DEFAULT '',
+    -- unix seconds, part of the signed entry
+    timestamp BIGINT NOT NULL,
+    prev_hash TEXT NOT NULL,
+    entry_hash TEXT NOT NULL UNIQUE,
+    -- ed25519 signature over entry_hash (hex)
+    signature TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_worker_measurements_lookup
+  ON worker_measurements(tee_type, measurement);
+
+CREATE OR REPLACE FUNCTION worker_measurements_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'worker_measurements is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS worker_measurements_no_rewrite ON worker_measurements;
+CREATE TRIGGER worker_measurements_no_rewrite
+    BEFORE UPDATE OR DELETE ON worker_measurements
+    FOR EACH ROW EXECUTE FUNCTION worker_measurements_append_only();
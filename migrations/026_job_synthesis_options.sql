@@ -0,0 +1,8 @@
+-- Per-job synthesis parameters, carried through to the worker request.
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS language TEXT NOT NULL DEFAULT 'auto';
+-- SynthesisOptions as JSON text: speed, instruct (qwen), cfg_scale (vibevoice)
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS synthesis_options TEXT;
+
+-- 'kokoro' is no longer served; old rows run on the default engine
+ALTER TABLE jobs ALTER COLUMN engine SET DEFAULT 'qwen';
+COMMENT ON COLUMN jobs.engine IS 'TTS engine: qwen, vibevoice, or vibevoice-streaming';
@@ -0,0 +1,15 @@
+-- GPU workers the API routes to, in addition to the static WORKER_URLS.
+-- Every API instance polls this table and updates its pool live.
+CREATE TABLE IF NOT EXISTS workers (
+    name TEXT PRIMARY KEY,
+    speech_url TEXT NOT NULL,
+    llm_url TEXT NOT NULL,
+    -- ip:port of the worker's QUIC listener; NULL for HTTP-only workers
+    quic_addr TEXT,
+    -- max concurrent requests; NULL uses WORKER_CAPACITY
+    capacity INTEGER CHECK (capacity > 0),
+    -- draining workers finish in-flight work but get no new requests
+    draining BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
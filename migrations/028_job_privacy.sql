@@ -0,0 +1,6 @@
+-- Per-job privacy policy: 'private' jobs only run over an attested Noise
+-- session to the worker, never the plaintext HTTP fallback.
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS privacy TEXT NOT NULL DEFAULT 'standard'
+    CHECK (privacy IN ('standard', 'private'));
+-- How the text reached the worker ('quic' or 'http'), set on completion
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS transport TEXT;
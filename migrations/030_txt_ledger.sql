@@ -0,0 +1,114 @@
+-- Double-entry TXT ledger. Every movement of TXT is a transaction whose
+-- entries sum to zero; users.txt_balance is a cache of each user
+-- account's entries, kept in step in the same DB transaction.
+-- Amounts are raw TXT units; a positive entry adds to the account.
+
+CREATE TABLE IF NOT EXISTS ledger_accounts (
+    id BIGSERIAL PRIMARY KEY,
+    -- a user's custodial balance
+    user_id UUID UNIQUE REFERENCES users(id),
+    -- system accounts: 'external' (money in/out: card payments, on-chain
+    -- transfers), 'revenue' (usage), 'channels' (spent from payment
+    -- channels, not yet settled on-chain)
+    system TEXT UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK ((user_id IS NULL) <> (system IS NULL))
+);
+
+INSERT INTO ledger_accounts (system) VALUES ('external'), ('revenue'), ('channels')
+ON CONFLICT DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS ledger_transactions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- posting the same key twice is a no-op, e.g. 'job:<id>:charge'
+    idempotency_key TEXT NOT NULL UNIQUE,
+    kind TEXT NOT NULL CHECK (kind IN (
+        'opening', 'credit', 'charge', 'refund', 'withdrawal', 'reversal', 'channel_settlement'
+    )),
+    -- user the transaction is for, if any
+    user_id UUID REFERENCES users(id),
+    job_id TEXT,
+    -- EVM address of the payment channel involved, if any
+    channel_address TEXT,
+    memo TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_ledger_transactions_user ON ledger_transactions(user_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_ledger_transactions_job ON ledger_transactions(job_id) WHERE job_id IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS ledger_entries (
+    id BIGSERIAL PRIMARY KEY,
+    transaction_id UUID NOT NULL REFERENCES ledger_transactions(id),
+    account_id BIGINT NOT NULL REFERENCES ledger_accounts(id),
+    amount BIGINT NOT NULL CHECK (amount <> 0)
+);
+
+CREATE INDEX IF NOT EXISTS idx_ledger_entries_transaction ON ledger_entries(transaction_id);
+CREATE INDEX IF NOT EXISTS idx_ledger_entries_account ON ledger_entries(account_id);
+
+CREATE OR REPLACE FUNCTION ledger_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION '% is append-only', TG_TABLE_NAME;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS ledger_transactions_no_rewrite ON ledger_transactions;
+CREATE TRIGGER ledger_transactions_no_rewrite
+    BEFORE UPDATE OR DELETE ON ledger_transactions
+    FOR EACH ROW EXECUTE FUNCTION ledger_append_only();
+
+DROP TRIGGER IF EXISTS ledger_entries_no_rewrite ON ledger_entries;
+CREATE TRIGGER ledger_entries_no_rewrite
+    BEFORE UPDATE OR DELETE ON ledger_entries
+    FOR EACH ROW EXECUTE FUNCTION ledger_append_only();
+
+-- a transaction's entries must balance by the time the DB transaction commits
+CREATE OR REPLACE FUNCTION ledger_entries_balanced() RETURNS TRIGGER AS $$
+BEGIN
+    IF (SELECT SUM(amount) FROM ledger_entries WHERE transaction_id = NEW.transaction_id) <> 0 THEN
+        RAISE EXCEPTION 'ledger transaction % does not balance', NEW.transaction_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS ledger_entries_balanced ON ledger_entries;
+CREATE CONSTRAINT TRIGGER ledger_entries_balanced
+    AFTER INSERT ON ledger_entries
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW EXECUTE FUNCTION ledger_entries_balanced();
+
+CREATE OR REPLACE VIEW ledger_balances AS
+SELECT a.id AS account_id, a.user_id, a.system, COALESCE(SUM(e.amount), 0)::BIGINT AS balance
+FROM ledger_accounts a
+LEFT JOIN ledger_entries e ON e.account_id = a.id
+GROUP BY a.id;
+
+-- The custodial balance the ledger caches. Code read it before any
+-- migration created it, so a fresh database doesn't have it yet.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS txt_balance BIGINT NOT NULL DEFAULT 0;
+
+-- Carry over what exists: each custodial balance becomes an opening
+-- balance, paid in from outside the ledger.
+INSERT INTO ledger_accounts (user_id)
+SELECT id FROM users WHERE txt_balance <> 0
+ON CONFLICT DO NOTHING;
+
+INSERT INTO ledger_transactions (idempotency_key, kind, user_id, memo)
+SELECT 'opening:' || id, 'opening', id, 'custodial balance before the ledger'
+FROM users
+WHERE txt_balance <> 0;
+
+INSERT INTO ledger_entries (transaction_id, account_id, amount)
+SELECT t.id, a.id, u.txt_balance
+FROM users u
+JOIN ledger_transactions t ON t.idempotency_key = 'opening:' || u.id
+JOIN ledger_accounts a ON a.user_id = u.id
+WHERE u.txt_balance <> 0
+UNION ALL
+SELECT t.id, a.id, -u.txt_balance
+FROM users u
+JOIN ledger_transactions t ON t.idempotency_key = 'opening:' || u.id
+JOIN ledger_accounts a ON a.system = 'external'
+WHERE u.txt_balance <> 0;
@@ -0,0 +1,8 @@
+-- Jobs are billed by a hold at submission, settled on the synthesized
+-- duration. Held TXT sits in the 'holds' system account in between.
+INSERT INTO ledger_accounts (system) VALUES ('holds') ON CONFLICT DO NOTHING;
+
+ALTER TABLE ledger_transactions DROP CONSTRAINT IF EXISTS ledger_transactions_kind_check;
+ALTER TABLE ledger_transactions ADD CONSTRAINT ledger_transactions_kind_check CHECK (kind IN (
+    'opening', 'credit', 'charge', 'hold', 'settlement', 'refund', 'withdrawal', 'reversal', 'channel_settlement'
+));
@@ -0,0 +1,13 @@
+-- Off-chain channel charges and refunds made for a ledger transaction,
+-- keyed by that transaction's idempotency key and written in the same DB
+-- transaction as the channel state they change. An operation whose key is
+-- already here is not applied again, so retrying a refund (or charge)
+-- after a crash moves the channel once.
+CREATE TABLE IF NOT EXISTS sono_channel_ops (
+    idempotency_key TEXT PRIMARY KEY,
+    user_address TEXT NOT NULL REFERENCES sono_channels(user_address),
+    kind TEXT NOT NULL CHECK (kind IN ('charge', 'refund')),
+    -- raw TXT units as a decimal string, like sono_channels.spent
+    amount TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,8 @@
+-- Each claim of a queued job bumps `attempt`; the run holding the latest
+-- claim refreshes `updated_at` while it works, and only it may complete or
+-- fail the job. A job whose heartbeat stops is requeued.
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS attempt INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_jobs_processing_heartbeat
+  ON jobs(updated_at) WHERE status = 'processing';
@@ -1,10 +1,13 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use sonotxt_core::StorageConfig;
 
 #[derive(Parser, Debug, Clone)]
 #[command(name = "sonotxt-api")]
 #[command(about = "TTS API server", long_about = None)]
 pub struct Config {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     #[arg(long, env = "REDIS_URL", default_value = "redis://127.0.0.1:6379")]
     pub redis_url: String,
 
@@ -50,6 +53,10 @@ pub struct Config {
     #[arg(long, env = "MODEL_7B_MULTIPLIER", default_value = "2.0")]
     pub model_7b_multiplier: f64,
 
+    // per-voice price multipliers, e.g. "ryan=1.5,serena=1.2"; unlisted voices are 1.0
+    #[arg(long, env = "VOICE_MULTIPLIERS", default_value = "")]
+    pub voice_multipliers: String,
+
     #[arg(long, env = "CORS_ORIGINS", default_value = "")]
     pub cors_origins: String,
 
@@ -172,11 +179,67 @@ pub struct Config {
 
     /// Worker pool: comma-separated base URLs for GPU workers.
     /// Each URL is a speech service on :8080; LLM is derived as :8090.
-    /// When set, load-balances across all workers with health checks.
+    /// Load-balances across these and the workers registered through
+    /// `/admin/workers` (which take explicit endpoints), with health checks.
     /// Example: `http://1.2.3.4:8080,http://5.6.7.8:8080`
     #[arg(long, env = "WORKER_URLS")]
     pub worker_urls: Option<String>,
 
+    /// UDP port of the reverse-connect QUIC listener, for workers behind NAT
+    /// that dial the API (worker API_QUIC_ADDR). Needs WORKER_ENROLL_TOKEN.
+    #[arg(long, env = "REVERSE_QUIC_PORT")]
+    pub reverse_quic_port: Option<u16>,
+
+    /// Token workers present to join the pool over the reverse listener
+    #[arg(long, env = "WORKER_ENROLL_TOKEN")]
+    pub worker_enroll_token: Option<String>,
+
+    /// Auto-crawl scheduler: seconds between checks for due sites
+    #[arg(long, env = "CRAWL_SCHEDULER_INTERVAL_SECS", default_value = "300")]
+    pub crawl_scheduler_interval: u64,
+
+    /// Max concurrent requests each GPU worker is sent by the pool
+    #[arg(long, env = "WORKER_CAPACITY", default_value = "1")]
+    pub worker_capacity: u64,
+
+    /// Max TTS jobs the job worker runs at once (further bounded by worker capacity)
+    #[arg(long, env = "JOB_CONCURRENCY", default_value = "8")]
+    pub job_concurrency: usize,
+
+    /// Reject workers that only offer insecure (non-TEE) attestation
+    #[arg(long, env = "ATTESTATION_REQUIRE_TEE", default_value = "false")]
+    pub attestation_require_tee: bool,
+
+    /// PEM bundle with the AMD ARK and ASK (plus optional VCEKs) for SEV-SNP
+    #[arg(long, env = "SNP_CERT_BUNDLE")]
+    pub snp_cert_bundle: Option<String>,
+
+    /// Comma-separated hex launch measurements accepted from SEV-SNP workers
+    #[arg(long, env = "SNP_MEASUREMENTS", default_value = "")]
+    pub snp_measurements: String,
+
+    /// JSON collateral bundle (Intel root CA, TCB info, QE identity, CRLs) for TDX
+    #[arg(long, env = "TDX_COLLATERAL")]
+    pub tdx_collateral: Option<String>,
+
+    /// Comma-separated `mrtd[:rtmr0[:rtmr1[:rtmr2[:rtmr3]]]]` hex entries accepted from TDX workers
+    #[arg(long, env = "TDX_MEASUREMENTS", default_value = "")]
+    pub tdx_measurements: String,
+
+    /// Hex ed25519 seed signing the measurement transparency log. When set,
+    /// approved worker measurements come from the log instead of the lists above.
+    #[arg(long, env = "MEASUREMENT_LOG_KEY")]
+    pub measurement_log_key: Option<String>,
+
+    /// Noise handshake with workers: nk (anonymous API), kk or xk (API
+    /// authenticates with NOISE_STATIC_KEY; workers must list its public key)
+    #[arg(long, env = "NOISE_PATTERN", default_value = "nk")]
+    pub noise_pattern: sonotxt_core::noise::HandshakePattern,
+
+    /// Hex X25519 static private key of this API (required for kk/xk)
+    #[arg(long, env = "NOISE_STATIC_KEY")]
+    pub noise_static_key: Option<String>,
+
     // SONO pricing
     /// Base SONO price in USD (default $0.01)
     #[arg(long, env = "SONO_PRICE_USD", default_value = "0.01")]
@@ -191,6 +254,13 @@ pub struct Config {
     pub sono_price_interval: u64,
 }
 
+/// One-off tasks run instead of the server
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Check users.txt_balance against the TXT ledger and exit
+    Reconcile,
+}
+
 impl Config {
     pub fn from_env() -> Self {
         dotenvy::dotenv().ok();
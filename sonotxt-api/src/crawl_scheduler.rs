@@ -0,0 +1,312 @@
+//! Auto-crawl scheduler — re-crawls sites with `auto_crawl` enabled.
+//!
+//! A site is due once `last_crawled_at + crawl_frequency_hours` has passed.
+//! A new `content_versions` row is written only when the crawled text hash
+//! changes; sites with `auto_tts` also get a TTS job queued for the new text.
+
+use crate::services::crawler::crawl_site;
+use crate::AppState;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Max sites claimed per tick, so one slow batch can't starve the loop.
+const BATCH_SIZE: i64 = 20;
+
+pub async fn run(state: Arc<AppState>) {
+    let interval = Duration::from_secs(state.config.crawl_scheduler_interval);
+    info!("crawl scheduler: checking for due sites every {}s", interval.as_secs());
+
+    loop {
+        match crawl_due(&state).await {
+            Ok(0) => {}
+            Ok(n) => info!("crawl scheduler: crawled {} sites", n),
+            Err(e) => error!("crawl scheduler error: {:?}", e),
+        }
+        sleep(interval).await;
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct DueSite {
+    id: Uuid,
+    account_id: Uuid,
+    url: String,
+    selector: Option<String>,
+    auto_tts: bool,
+    tts_voice: String,
+}
+
+/// Claim and crawl due sites. Returns how many were crawled.
+///
+/// `last_crawled_at` is bumped when a site is claimed, not when the crawl
+/// succeeds, so a failing site waits a full period before the next attempt.
+async fn crawl_due(state: &Arc<AppState>) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let sites = claim_due(&state.db).await?;
+    let count = sites.len();
+    for site in sites {
+        if let Err(e) = crawl_one(state, &site).await {
+            warn!("auto-crawl failed for site {} ({}): {:?}", site.id, site.url, e);
+        }
+    }
+
+    Ok(count)
+}
+
+/// Claim up to `BATCH_SIZE` due sites, least recently crawled first
+async fn claim_due(db: &PgPool) -> Result<Vec<DueSite>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        UPDATE sites
+        SET last_crawled_at = NOW()
+        WHERE id IN (
+            SELECT id FROM sites
+            WHERE auto_crawl = TRUE
+              AND (last_crawled_at IS NULL
+                   OR last_crawled_at + make_interval(hours => crawl_frequency_hours) <= NOW())
+            ORDER BY last_crawled_at NULLS FIRST
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, account_id, url, selector, auto_tts, tts_voice
+        "#,
+    )
+    .bind(BATCH_SIZE)
+    .fetch_all(db)
+    .await
+}
+
+async fn crawl_one(state: &Arc<AppState>, site: &DueSite) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let text = crawl_site(&site.url, site.selector.as_deref()).await?;
+    let Some(content_id) = store_text(&state.db, site, &text).await? else {
+        return Ok(());
+    };
+
+    if site.auto_tts {
+        enqueue_tts(state, site, content_id, &text).await?;
+    }
+
+    Ok(())
+}
+
+/// Store a site's crawled text with a new `content_versions` row, unless
+/// it hashes the same as what is stored. Returns the content id if the
+/// text changed.
+async fn store_text(db: &PgPool, site: &DueSite, text: &str) -> Result<Option<Uuid>, sqlx::Error> {
+    let text_hash = blake3::hash(text.as_bytes()).to_hex().to_string();
+    let word_count = text.split_whitespace().count() as i32;
+
+    let existing: Option<(Uuid, String)> =
+        sqlx::query_as("SELECT id, text_hash FROM content WHERE site_id = $1")
+            .bind(site.id)
+            .fetch_optional(db)
+            .await?;
+
+    if matches!(existing, Some((_, ref old_hash)) if *old_hash == text_hash) {
+        return Ok(None);
+    }
+
+    let mut tx = db.begin().await?;
+
+    let content_id = match existing {
+        Some((id, _)) => {
+            sqlx::query("UPDATE content SET text_content = $1, text_hash = $2, word_count = $3 WHERE id = $4")
+                .bind(text)
+                .bind(&text_hash)
+                .bind(word_count)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+            id
+        }
+        None => {
+            let id = Uuid::new_v4();
+            sqlx::query(
+                "INSERT INTO content (id, site_id, url, text_content, text_hash, word_count) VALUES ($1, $2, $3, $4, $5, $6)",
+            )
+            .bind(id)
+            .bind(site.id)
+            .bind(&site.url)
+            .bind(text)
+            .bind(&text_hash)
+            .bind(word_count)
+            .execute(&mut *tx)
+            .await?;
+            id
+        }
+    };
+
+    sqlx::query(
+        "INSERT INTO content_versions (content_id, text_content, text_hash, word_count, version_type) VALUES ($1, $2, $3, $4, 'crawl')",
+    )
+    .bind(content_id)
+    .bind(text)
+    .bind(&text_hash)
+    .bind(word_count)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    info!("site {} changed: {} words", site.id, word_count);
+    Ok(Some(content_id))
+}
+
+/// Queue a paid TTS job for changed content, charged to the site owner.
+async fn enqueue_tts(
+    state: &Arc<AppState>,
+    site: &DueSite,
+    content_id: Uuid,
+    text: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if text.len() > state.config.max_content_size {
+        warn!("site {} content too large for auto-tts ({} bytes)", site.id, text.len());
+        return Ok(());
+    }
+    let char_count = text.chars().count();
+
+    let api_key: Option<String> = sqlx::query_scalar(
+        "SELECT key FROM api_keys WHERE account_id = $1 AND revoked = FALSE ORDER BY created_at DESC LIMIT 1",
+    )
+    .bind(site.account_id)
+    .fetch_optional(&state.db)
+    .await?;
+
+    let Some(api_key) = api_key else {
+        warn!("site {} has auto_tts but owner has no active api key", site.id);
+        return Ok(());
+    };
+
+    let engine = crate::services::engines::for_job(None).name();
+    let estimate = crate::services::pricing::RateCard::from_config(&state.config)
+        .estimate(char_count, engine, &site.tts_voice, None);
+    let price = crate::services::billing::current_price(state.sono.as_deref()).await;
+
+    let job_id = Uuid::new_v4().to_string();
+
+    let mut tx = state.db.begin().await?;
+    let hold = match crate::services::billing::place_hold(
+        &mut tx,
+        state.sono.as_deref(),
+        site.account_id,
+        None,
+        crate::services::billing::usd_to_txt(estimate.hold_usd, &price),
+        &job_id,
+    ).await {
+        Ok(hold) => hold,
+        Err(e) => {
+            warn!("site {} auto-tts skipped: {}", site.id, e);
+            return Ok(());
+        }
+    };
+
+    let char_count = char_count as i32;
+    let estimated_duration_ms = (char_count as f64 * crate::models::MS_PER_CHAR) as i32;
+    let estimated_cost = estimate.usd;
+
+    sqlx::query(
+        "INSERT INTO jobs (id, content_id, api_key, voice, status, cost, is_free_tier, char_count, estimated_duration_ms, engine, priority) VALUES ($1, $2, $3, $4, $5, $6, FALSE, $7, $8, $9, 50)",
+    )
+    .bind(&job_id)
+    .bind(content_id)
+    .bind(&api_key)
+    .bind(&site.tts_voice)
+    .bind(hold.job_status())
+    .bind(estimated_cost)
+    .bind(char_count)
+    .bind(estimated_duration_ms)
+    .bind(engine)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    if let Err(e) = crate::services::billing::charge_hold(&state.db, state.sono.as_deref(), &job_id, &hold).await {
+        warn!("site {} auto-tts job {} dropped: {}", site.id, job_id, e);
+        return Ok(());
+    }
+
+    crate::notify_job(state, &job_id).await;
+
+    info!("site {} auto-tts job {} queued", site.id, job_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn account(db: &PgPool) -> Uuid {
+        sqlx::query_scalar("INSERT INTO accounts DEFAULT VALUES RETURNING id")
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    async fn site(db: &PgPool, account_id: Uuid, auto_crawl: bool, crawled_hours_ago: Option<i32>) -> Uuid {
+        sqlx::query_scalar(
+            r#"
+            INSERT INTO sites (account_id, url, auto_crawl, crawl_frequency_hours, last_crawled_at)
+            VALUES ($1, 'https://example.com', $2, 24, NOW() - make_interval(hours => $3))
+            RETURNING id
+            "#,
+        )
+        .bind(account_id)
+        .bind(auto_crawl)
+        .bind(crawled_hours_ago)
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_claims_due_sites_once(db: PgPool) {
+        let account_id = account(&db).await;
+        let stale = site(&db, account_id, true, Some(25)).await;
+        let never = site(&db, account_id, true, None).await;
+        site(&db, account_id, true, Some(1)).await;
+        site(&db, account_id, false, None).await;
+
+        let mut claimed: Vec<Uuid> = claim_due(&db).await.unwrap().iter().map(|s| s.id).collect();
+        claimed.sort();
+        let mut due = vec![never, stale];
+        due.sort();
+        assert_eq!(claimed, due);
+
+        // Claiming bumped them, so they aren't due again until next period
+        assert!(claim_due(&db).await.unwrap().is_empty());
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_version_only_on_changed_text(db: PgPool) {
+        let account_id = account(&db).await;
+        let id = site(&db, account_id, true, None).await;
+        let site = DueSite {
+            id,
+            account_id,
+            url: "https://example.com".into(),
+            selector: None,
+            auto_tts: false,
+            tts_voice: "serena".into(),
+        };
+
+        let content_id = store_text(&db, &site, "first text").await.unwrap().unwrap();
+        assert_eq!(store_text(&db, &site, "first text").await.unwrap(), None);
+        assert_eq!(store_text(&db, &site, "second text").await.unwrap(), Some(content_id));
+
+        let versions: Vec<String> = sqlx::query_scalar(
+            "SELECT text_content FROM content_versions WHERE content_id = $1 ORDER BY created_at, text_content",
+        )
+        .bind(content_id)
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        assert_eq!(versions, vec!["first text", "second text"]);
+
+        let current: String = sqlx::query_scalar("SELECT text_content FROM content WHERE site_id = $1")
+            .bind(id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(current, "second text");
+    }
+}
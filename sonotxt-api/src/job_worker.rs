@@ -1,43 +1,182 @@
-//! TTS job processor — polls DB queue, synthesizes via worker pool, uploads to storage.
+//! TTS job processor — claims jobs from the DB queue, synthesizes via worker pool,
+//! uploads to storage.
 //!
 //! Replaces the old monolithic worker.rs that was split into sonotxt-worker.
-//! This runs inside sonotxt-api and uses the WorkerPool (HTTP to speech service)
-//! instead of calling local python directly.
+//! This runs inside sonotxt-api and uses the WorkerPool (QUIC+Noise to the
+//! worker, HTTP fallback unless the job is private) instead of calling local
+//! python directly. The transport each job used is recorded on it.
+//! A paid job's hold is settled on the duration of the audio it produced,
+//! or refunded if it fails; charges, settlements and refunds missed by a
+//! crash or a failed channel write are swept up on startup and every minute
+//! after, also when no worker pool is configured.
+//!
+//! Cancellation: the API marks a job 'cancelled' and refunds it. A running
+//! job polls its status and, once cancelled, drops its synthesis, which
+//! cancels the request on the worker.
+//!
+//! Concurrency: each pass claims as many jobs as there are free slots, where
+//! slots = min(JOB_CONCURRENCY, total capacity of healthy workers) minus jobs
+//! already running. Jobs run in parallel; the pool routes each to a worker
+//! with spare capacity. The loop wakes on `notify_job`, on a job finishing,
+//! or after a poll interval as a fallback.
+//!
+//! Claims: claiming a job bumps its `attempt`, and the run refreshes the
+//! job's `updated_at` heartbeat while it works. Jobs whose heartbeat has
+//! stopped (the task or the process died) are requeued on startup and every
+//! minute after. Completing or failing a job requires the attempt it was
+//! claimed with, so a run that lost its claim can't finish a job another
+//! run is working on.
 
+use crate::services::billing;
+use crate::services::engines::{self, Synthesis};
+use crate::services::pricing::RateCard;
+use crate::services::worker_pool::{Privacy, TtsRequest, WorkerPool};
 use crate::AppState;
 use sonotxt_core::{StorageBackend, StorageService};
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::time::{sleep, Duration};
+use tokio::sync::Notify;
+use tokio::time::Duration;
 use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Fallback poll interval for jobs inserted without a notification.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often a running job checks whether it has been cancelled.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often a running job refreshes its heartbeat.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A 'processing' job whose heartbeat is older than this has no live run.
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How often jobs stuck in 'processing' are put back in the queue and
+/// missed billing is swept up.
+const ZOMBIE_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
 
 pub async fn run(state: Arc<AppState>) {
-    info!("job worker: polling DB for queued TTS jobs");
+    let Some(pool) = state.workers.clone() else {
+        // Nothing to run jobs on, but paid jobs may still need billing
+        warn!("job worker: no worker pool configured, only sweeping billing");
+        loop {
+            sweep_billing(&state).await;
+            tokio::time::sleep(ZOMBIE_SWEEP_INTERVAL).await;
+        }
+    };
 
-    let storage = StorageService::new(state.config.storage_config()).await;
+    let max_concurrency = state.config.job_concurrency.max(1);
+    info!("job worker: claiming queued TTS jobs (max {} concurrent)", max_concurrency);
+
+    let storage = Arc::new(StorageService::new(state.config.storage_config()).await);
     if let Err(e) = storage.ensure_bucket_exists().await {
         error!("failed to create audio bucket: {:?}", e);
     }
 
-    // Recover zombie jobs on startup
-    if let Err(e) = recover_zombies(&state).await {
+    // Recover zombie jobs and sweep billing on startup, then every ZOMBIE_SWEEP_INTERVAL
+    if let Err(e) = recover_zombies(&state.db).await {
         error!("failed to recover zombie jobs: {:?}", e);
     }
+    sweep_billing(&state).await;
+    let mut last_sweep = tokio::time::Instant::now();
+
+    let active = Arc::new(AtomicUsize::new(0));
+    let finished = Arc::new(Notify::new());
 
     loop {
-        match process_next(&state, &storage).await {
-            Ok(true) => continue, // processed a job, check for more immediately
-            Ok(false) => {}       // no jobs, wait
-            Err(e) => error!("job worker error: {:?}", e),
+        if last_sweep.elapsed() >= ZOMBIE_SWEEP_INTERVAL {
+            if let Err(e) = recover_zombies(&state.db).await {
+                error!("failed to recover zombie jobs: {:?}", e);
+            }
+            sweep_billing(&state).await;
+            last_sweep = tokio::time::Instant::now();
+        }
+
+        let slots = free_slots(&pool, max_concurrency, active.load(Ordering::Acquire));
+
+        if slots > 0 {
+            match claim_jobs(&state.db, slots).await {
+                Ok(claims) => {
+                    let claimed = claims.len();
+                    for (job_id, attempt) in claims {
+                        active.fetch_add(1, Ordering::AcqRel);
+                        let state = state.clone();
+                        let storage = storage.clone();
+                        let active = active.clone();
+                        let finished = finished.clone();
+
+                        tokio::spawn(async move {
+                            if let Err(e) = run_job(&state, &storage, &job_id, attempt).await {
+                                error!("job {} error: {:?}", job_id, e);
+                                mark_failed(&state, &job_id, attempt, "Internal error").await;
+                            }
+                            active.fetch_sub(1, Ordering::AcqRel);
+                            finished.notify_one();
+                        });
+                    }
+                    // Filled every slot — there may be more queued work
+                    if claimed == slots {
+                        continue;
+                    }
+                }
+                Err(e) => error!("job worker error: {:?}", e),
+            }
+        }
+
+        tokio::select! {
+            _ = pool.job_notified() => {}
+            _ = finished.notified() => {}
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
         }
-        sleep(Duration::from_secs(2)).await;
     }
 }
 
-async fn recover_zombies(state: &AppState) -> Result<(), sqlx::Error> {
+/// Charge, refund, return and settle what earlier runs missed: a crash
+/// between committing a job and billing it, or a channel write that failed.
+async fn sweep_billing(state: &AppState) {
+    let sono = state.sono.as_deref();
+    match billing::charge_pending(&state.db, sono).await {
+        Ok(0) => {}
+        Ok(n) => warn!("charged {} channel holds missed earlier", n),
+        Err(e) => error!("failed to charge pending channel holds: {:?}", e),
+    }
+    match billing::refund_pending(&state.db, sono).await {
+        Ok(0) => {}
+        Ok(n) => warn!("refunded {} failed jobs missed earlier", n),
+        Err(e) => error!("failed to refund pending jobs: {:?}", e),
+    }
+    match billing::return_pending(&state.db, sono).await {
+        Ok(0) => {}
+        Ok(n) => warn!("returned TXT to {} payment channels missed earlier", n),
+        Err(e) => error!("failed to return pending channel refunds: {:?}", e),
+    }
+    let rates = RateCard::from_config(&state.config);
+    match billing::settle_pending(&state.db, sono, &rates).await {
+        Ok(0) => {}
+        Ok(n) => warn!("settled {} completed jobs missed earlier", n),
+        Err(e) => error!("failed to settle pending jobs: {:?}", e),
+    }
+}
+
+/// Jobs that can be started now without exceeding the configured limit
+/// or the combined capacity of healthy workers.
+fn free_slots(pool: &WorkerPool, max_concurrency: usize, active: usize) -> usize {
+    max_concurrency
+        .min(pool.total_capacity())
+        .saturating_sub(active)
+}
+
+/// Requeue jobs whose run died: 'processing' with a heartbeat that hasn't
+/// been refreshed for `HEARTBEAT_TIMEOUT`. Jobs still running, here or on
+/// another API instance, keep their heartbeat fresh and are left alone.
+async fn recover_zombies(db: &PgPool) -> Result<(), sqlx::Error> {
     let recovered = sqlx::query(
-        "UPDATE jobs SET status = 'queued' WHERE status = 'processing' AND created_at < NOW() - INTERVAL '5 minutes'"
+        "UPDATE jobs SET status = 'queued' WHERE status = 'processing' AND COALESCE(updated_at, started_at) < NOW() - make_interval(secs => $1)"
     )
-    .execute(&state.db)
+    .bind(HEARTBEAT_TIMEOUT.as_secs_f64())
+    .execute(db)
     .await?;
 
     if recovered.rows_affected() > 0 {
@@ -52,32 +191,86 @@ struct JobRow {
     text_content: Option<String>,
     voice: String,
     storage_type: Option<String>,
-    content_id: Option<i64>,
+    content_id: Option<Uuid>,
+    engine: Option<String>,
+    language: String,
+    /// `SynthesisOptions` as JSON
+    synthesis_options: Option<String>,
+    /// `Privacy`: 'standard' or 'private'
+    privacy: String,
 }
 
-/// Returns true if a job was processed.
-async fn process_next(state: &AppState, storage: &StorageService) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
-    let job: Option<JobRow> = sqlx::query_as(
+/// Claim up to `limit` queued jobs, highest priority first. Only ids (and
+/// the attempt each was claimed as) are returned, so a row that doesn't
+/// load fails on its own in `run_job` instead of stranding the whole batch
+/// in 'processing'.
+async fn claim_jobs(db: &PgPool, limit: usize) -> Result<Vec<(String, i32)>, sqlx::Error> {
+    sqlx::query_as(
         r#"
         UPDATE jobs
-        SET status = 'processing', started_at = NOW()
-        WHERE id = (
+        SET status = 'processing', started_at = NOW(), updated_at = NOW(), attempt = attempt + 1
+        WHERE id IN (
             SELECT id FROM jobs
             WHERE status = 'queued'
             ORDER BY priority DESC, created_at ASC
-            LIMIT 1
+            LIMIT $1
             FOR UPDATE SKIP LOCKED
         )
-        RETURNING id, text_content, voice, storage_type, content_id
+        RETURNING id, attempt
         "#
     )
-    .fetch_optional(&state.db)
+    .bind(limit as i64)
+    .fetch_all(db)
+    .await
+}
+
+/// Load a claimed job and run it, keeping its heartbeat fresh. Stops if
+/// the claim is lost.
+async fn run_job(state: &AppState, storage: &StorageService, job_id: &str, attempt: i32) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let job: JobRow = sqlx::query_as(
+        "SELECT id, text_content, voice, storage_type, content_id, engine, language, synthesis_options, privacy FROM jobs WHERE id = $1",
+    )
+    .bind(job_id)
+    .fetch_one(&state.db)
     .await?;
+    tokio::select! {
+        result = process_job(state, storage, job, attempt) => result,
+        _ = heartbeat(state, job_id, attempt) => {
+            warn!("job {} lost its claim (attempt {}), stopping", job_id, attempt);
+            Ok(())
+        }
+    }
+}
 
-    let Some(job) = job else {
-        return Ok(false);
-    };
+/// Refresh a running job's heartbeat every `HEARTBEAT_INTERVAL`. Resolves
+/// once the job is no longer 'processing' under this attempt: cancelled,
+/// or requeued and claimed again.
+async fn heartbeat(state: &AppState, job_id: &str, attempt: i32) {
+    let mut tick = tokio::time::interval(HEARTBEAT_INTERVAL);
+    tick.tick().await;
+    loop {
+        tick.tick().await;
+        match refresh_heartbeat(&state.db, job_id, attempt).await {
+            Ok(false) => return,
+            Ok(true) => {}
+            Err(e) => warn!("job {}: heartbeat failed: {:?}", job_id, e),
+        }
+    }
+}
 
+/// False if the job is no longer 'processing' under `attempt`
+async fn refresh_heartbeat(db: &PgPool, job_id: &str, attempt: i32) -> Result<bool, sqlx::Error> {
+    let refreshed = sqlx::query(
+        "UPDATE jobs SET updated_at = NOW() WHERE id = $1 AND attempt = $2 AND status = 'processing'",
+    )
+    .bind(job_id)
+    .bind(attempt)
+    .execute(db)
+    .await?;
+    Ok(refreshed.rows_affected() > 0)
+}
+
+async fn process_job(state: &AppState, storage: &StorageService, job: JobRow, attempt: i32) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     info!("processing job: {}", job.id);
 
     // Get text content
@@ -90,26 +283,52 @@ async fn process_next(state: &AppState, storage: &StorageService) -> Result<bool
             .await?;
         row.0
     } else {
-        mark_failed(&state.db, &job.id, "No content").await;
-        return Ok(true);
+        mark_failed(state, &job.id, attempt, "No content").await;
+        return Ok(());
+    };
+
+    let options = match job.synthesis_options.as_deref().map(serde_json::from_str).transpose() {
+        Ok(options) => options.unwrap_or_default(),
+        Err(e) => {
+            mark_failed(state, &job.id, attempt, &format!("Invalid synthesis options: {}", e)).await;
+            return Ok(());
+        }
+    };
+    let privacy: Privacy = match job.privacy.parse() {
+        Ok(privacy) => privacy,
+        Err(e) => {
+            mark_failed(state, &job.id, attempt, &e).await;
+            return Ok(());
+        }
     };
 
-    let voice = &job.voice;
     let storage_type = job.storage_type.as_deref().unwrap_or(&state.config.default_storage);
     let backend = StorageBackend::from(storage_type);
 
     // Route through worker pool
     let pool = state.workers.as_ref().ok_or("no workers")?;
-    let tts_req = crate::services::worker_pool::TtsRequest {
-        text: text.clone(),
-        speaker: voice.to_string(),
-        language: "auto".to_string(),
-        api_key: None,
+    let engine = engines::for_job(job.engine.as_deref());
+    // Private jobs only ever leave over an attested Noise session
+    let tts_req = TtsRequest {
+        privacy,
+        ..engine.request(text, Synthesis {
+            voice: job.voice.clone(),
+            language: job.language.clone(),
+            options,
+        })
     };
 
     let start = std::time::Instant::now();
 
-    match pool.tts(tts_req).await {
+    let synthesized = tokio::select! {
+        result = pool.tts(tts_req) => result,
+        _ = cancelled(state, &job.id) => {
+            info!("job {} cancelled", job.id);
+            return Ok(());
+        }
+    };
+
+    match synthesized {
         Ok(result) => {
             let runtime_ms = start.elapsed().as_millis() as i32;
             let filename = format!("{}.wav", job.id);
@@ -117,8 +336,8 @@ async fn process_next(state: &AppState, storage: &StorageService) -> Result<bool
 
             match storage.upload(&filename, &result.audio_data, content_type, backend).await {
                 Ok(upload) => {
-                    sqlx::query(
-                        "UPDATE jobs SET status = 'completed', audio_url = $1, duration_seconds = $2, actual_runtime_ms = $3, storage_type = $4, ipfs_cid = $5, pinning_cost = $6, completed_at = NOW() WHERE id = $7"
+                    let completed = sqlx::query(
+                        "UPDATE jobs SET status = 'completed', audio_url = $1, duration_seconds = $2, actual_runtime_ms = $3, storage_type = $4, ipfs_cid = $5, pinning_cost = $6, transport = $7, completed_at = NOW(), updated_at = NOW() WHERE id = $8 AND attempt = $9 AND status = 'processing'"
                     )
                     .bind(&upload.url)
                     .bind(result.duration_seconds)
@@ -126,34 +345,136 @@ async fn process_next(state: &AppState, storage: &StorageService) -> Result<bool
                     .bind(&upload.storage_type)
                     .bind(&upload.ipfs_cid)
                     .bind(upload.pinning_cost)
+                    .bind(result.transport.as_str())
                     .bind(&job.id)
+                    .bind(attempt)
                     .execute(&state.db)
                     .await?;
+                    if completed.rows_affected() == 0 {
+                        info!("job {} was cancelled or reclaimed before it completed", job.id);
+                        return Ok(());
+                    }
 
                     info!(
-                        "job {} completed: {:.1}s audio, {}ms runtime",
-                        job.id, result.duration_seconds, runtime_ms
+                        "job {} completed: {:.1}s audio, {}ms runtime, via {}",
+                        job.id, result.duration_seconds, runtime_ms, result.transport.as_str()
                     );
+
+                    settle(state, &job.id, engine.name(), &job.voice, result.duration_seconds).await;
                 }
                 Err(e) => {
                     error!("upload failed for job {}: {:?}", job.id, e);
-                    mark_failed(&state.db, &job.id, "Upload failed").await;
+                    mark_failed(state, &job.id, attempt, "Upload failed").await;
                 }
             }
         }
         Err(e) => {
             error!("TTS failed for job {}: {}", job.id, e);
-            mark_failed(&state.db, &job.id, &format!("TTS: {}", e)).await;
+            mark_failed(state, &job.id, attempt, &format!("TTS: {}", e)).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves once the job has been cancelled through the API.
+async fn cancelled(state: &AppState, job_id: &str) {
+    let mut tick = tokio::time::interval(CANCEL_POLL_INTERVAL);
+    loop {
+        tick.tick().await;
+        let status: Result<Option<String>, _> = sqlx::query_scalar("SELECT status FROM jobs WHERE id = $1")
+            .bind(job_id)
+            .fetch_optional(&state.db)
+            .await;
+        if matches!(status, Ok(Some(s)) if s == "cancelled") {
+            return;
         }
     }
+}
 
-    Ok(true)
+/// Bill a completed job for the audio it produced and release the rest of
+/// its hold. Unpaid jobs have no hold and are left alone.
+async fn settle(state: &AppState, job_id: &str, engine: &str, voice: &str, duration_seconds: f64) {
+    let rates = RateCard::from_config(&state.config);
+    let price = billing::current_price(state.sono.as_deref()).await;
+    let cost = billing::usd_to_txt(rates.cost(duration_seconds, engine, voice), &price);
+    if let Err(e) = billing::settle_job(&state.db, state.sono.as_deref(), job_id, cost).await {
+        error!("settling job {} failed: {:?}", job_id, e);
+    }
 }
 
-async fn mark_failed(db: &sqlx::PgPool, job_id: &str, reason: &str) {
-    let _ = sqlx::query("UPDATE jobs SET status = 'failed', error_message = $1, completed_at = NOW() WHERE id = $2")
+/// Fail a job that is still running under `attempt` and refund what it
+/// was holding.
+async fn mark_failed(state: &AppState, job_id: &str, attempt: i32, reason: &str) {
+    let failed = sqlx::query("UPDATE jobs SET status = 'failed', error_message = $1, completed_at = NOW(), updated_at = NOW() WHERE id = $2 AND attempt = $3 AND status = 'processing'")
         .bind(reason)
         .bind(job_id)
-        .execute(db)
+        .bind(attempt)
+        .execute(&state.db)
         .await;
+    if !matches!(failed, Ok(r) if r.rows_affected() > 0) {
+        return;
+    }
+    if let Err(e) = billing::refund_job(&state.db, state.sono.as_deref(), job_id).await {
+        error!("refund for job {} failed: {:?}", job_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn queue(db: &PgPool, id: &str) {
+        sqlx::query("INSERT INTO jobs (id, api_key, text_content, status) VALUES ($1, 'key', 'hello', 'queued')")
+            .bind(id)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    async fn status(db: &PgPool, id: &str) -> String {
+        sqlx::query_scalar("SELECT status FROM jobs WHERE id = $1")
+            .bind(id)
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_sweep_requeues_only_stopped_heartbeats(db: PgPool) {
+        queue(&db, "running").await;
+        queue(&db, "dead").await;
+        let mut claims = claim_jobs(&db, 10).await.unwrap();
+        claims.sort();
+        assert_eq!(claims, vec![("dead".to_string(), 1), ("running".to_string(), 1)]);
+
+        // Both started long ago; only "running" is still beating
+        sqlx::query("UPDATE jobs SET started_at = NOW() - INTERVAL '1 hour', updated_at = NOW() - INTERVAL '1 hour'")
+            .execute(&db)
+            .await
+            .unwrap();
+        assert!(refresh_heartbeat(&db, "running", 1).await.unwrap());
+
+        recover_zombies(&db).await.unwrap();
+        assert_eq!(status(&db, "running").await, "processing");
+        assert_eq!(status(&db, "dead").await, "queued");
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_reclaimed_job_ignores_the_old_run(db: PgPool) {
+        queue(&db, "job").await;
+        assert_eq!(claim_jobs(&db, 1).await.unwrap(), vec![("job".to_string(), 1)]);
+
+        // The first run stalls past the timeout and the job is claimed again
+        sqlx::query("UPDATE jobs SET updated_at = NOW() - INTERVAL '1 hour'")
+            .execute(&db)
+            .await
+            .unwrap();
+        recover_zombies(&db).await.unwrap();
+        assert_eq!(claim_jobs(&db, 1).await.unwrap(), vec![("job".to_string(), 2)]);
+
+        // The first run finds out from its heartbeat; the second keeps going
+        assert!(!refresh_heartbeat(&db, "job", 1).await.unwrap());
+        assert!(refresh_heartbeat(&db, "job", 2).await.unwrap());
+    }
 }
@@ -1,5 +1,6 @@
 pub mod auth;
 pub mod config;
+pub mod crawl_scheduler;
 pub mod extractors;
 pub mod job_worker;
 pub mod routes;
@@ -40,6 +41,8 @@ pub struct AppState {
     pub sono: Option<Arc<services::sono::SonoService>>,
     /// GPU worker pool with load balancing and health checks
     pub workers: Option<Arc<services::worker_pool::WorkerPool>>,
+    /// Transparency log of approved worker measurements (if configured)
+    pub measurements: Option<Arc<services::measurement_log::MeasurementLog>>,
 }
 
 fn build_cors(origins: &str) -> CorsLayer {
@@ -75,6 +78,7 @@ pub fn build_app(state: Arc<AppState>) -> Router {
         .merge(routes::audio::routes())
         .nest("/api/voice", routes::converse::routes())
         .nest("/api/sono", routes::sono::routes())
+        .nest("/api/attestation", routes::attestation::routes())
         .nest("/api/auth/passkey", routes::passkey::routes())
         .nest("/api/contacts", routes::contacts::routes())
         .nest("/api/provider", routes::provider::routes())
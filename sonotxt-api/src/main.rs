@@ -1,8 +1,13 @@
+use sonotxt_api::config::Command;
 use sonotxt_api::{build_app, AppState, Config};
 use sonotxt_api::services::payments::assethub::{AssetHubListener, DepositHandler};
 use sonotxt_api::services::payments::penumbra::PenumbraListener;
 use sonotxt_api::services::sono::{SonoConfig, SonoService};
+use sonotxt_api::services::ledger;
+use sonotxt_api::services::measurement_log::MeasurementLog;
+use sonotxt_api::services::quic_pool::{AttestationPolicy, NoiseIdentity};
 use sonotxt_api::services::worker_pool::WorkerPool;
+use sonotxt_api::services::worker_registry;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -35,6 +40,10 @@ async fn main() {
         tracing::warn!("migration check: {} (continuing anyway — schema may already be up to date)", e);
     }
 
+    if let Some(Command::Reconcile) = config.command {
+        std::process::exit(reconcile(&db).await);
+    }
+
     let http = reqwest::Client::builder()
         .user_agent("sonotxt/1.0")
         .timeout(std::time::Duration::from_secs(config.request_timeout))
@@ -54,16 +63,41 @@ async fn main() {
             service = %cfg.service_key.address(),
             "SONO payment channels enabled"
         );
-        Arc::new(SonoService::new(cfg))
+        Arc::new(SonoService::new(cfg, db.clone()))
     });
 
-    // Initialize worker pool (if WORKER_URLS configured)
-    // Connects QUIC+Noise to each worker for encrypted transport.
-    let workers = match config.worker_urls.as_ref() {
-        Some(urls) => Some(Arc::new(WorkerPool::new(urls, http.clone()).await)),
+    // Initialize measurement transparency log (if MEASUREMENT_LOG_KEY configured)
+    let measurements = match config.measurement_log_key.as_deref() {
+        Some(seed) => {
+            let key = MeasurementLog::key_from_hex(seed).expect("Invalid MEASUREMENT_LOG_KEY");
+            let log = MeasurementLog::open(db.clone(), key)
+                .await
+                .expect("Measurement log failed verification");
+            Some(Arc::new(log))
+        }
         None => None,
     };
 
+    // Initialize worker pool from WORKER_URLS plus the worker registry.
+    // Connects QUIC+Noise to each worker for encrypted transport.
+    let workers = {
+        let attestation = AttestationPolicy::from_config(&config, measurements.clone())
+            .expect("Invalid attestation config");
+        let identity = NoiseIdentity::from_config(&config).expect("Invalid noise config");
+        let pool = WorkerPool::new(
+            config.worker_urls.as_deref().unwrap_or(""),
+            http.clone(),
+            config.worker_capacity,
+            Arc::new(attestation),
+            Arc::new(identity),
+        )
+        .await;
+        if let Err(e) = worker_registry::sync(&db, &pool).await {
+            tracing::error!("failed to load worker registry: {}", e);
+        }
+        Some(Arc::new(pool))
+    };
+
     let state = Arc::new(AppState {
         config: config.clone(),
         redis,
@@ -72,14 +106,20 @@ async fn main() {
         payments: Arc::new(RwLock::new(payments)),
         sono,
         workers,
+        measurements,
     });
 
-    // Spawn worker pool health checker (every 10s)
+    // Spawn worker pool health checker (every 10s), picking up registry
+    // changes made through other API instances on the way
     if let Some(ref pool) = state.workers {
         let pool_len = pool.len();
         let pool_bg = pool.clone();
+        let db = state.db.clone();
         tokio::spawn(async move {
             loop {
+                if let Err(e) = worker_registry::sync(&db, &pool_bg).await {
+                    tracing::warn!("worker registry sync failed: {}", e);
+                }
                 pool_bg.health_check().await;
                 tokio::time::sleep(std::time::Duration::from_secs(10)).await;
             }
@@ -87,15 +127,34 @@ async fn main() {
         tracing::info!("worker pool health checker started ({} workers)", pool_len);
     }
 
-    // Spawn TTS job processor — polls DB queue, routes through worker pool
+    // Reverse-connect listener for workers behind NAT
+    if let (Some(pool), Some(port)) = (&state.workers, config.reverse_quic_port) {
+        match config.worker_enroll_token.clone() {
+            Some(token) => {
+                let addr = std::net::SocketAddr::from(([0, 0, 0, 0], port));
+                tokio::spawn(pool.clone().listen_reverse(addr, token));
+            }
+            None => tracing::error!("REVERSE_QUIC_PORT set without WORKER_ENROLL_TOKEN, listener disabled"),
+        }
+    }
+
+    // Spawn TTS job processor — polls DB queue, routes through worker pool.
+    // Without a pool it only sweeps up billing for jobs paid earlier.
+    let job_state = state.clone();
+    tokio::spawn(async move {
+        sonotxt_api::job_worker::run(job_state).await;
+    });
     if state.workers.is_some() {
-        let job_state = state.clone();
-        tokio::spawn(async move {
-            sonotxt_api::job_worker::run(job_state).await;
-        });
         tracing::info!("TTS job worker started");
     }
 
+    // Spawn auto-crawl scheduler — re-crawls sites with auto_crawl enabled
+    let crawl_state = state.clone();
+    tokio::spawn(async move {
+        sonotxt_api::crawl_scheduler::run(crawl_state).await;
+    });
+    tracing::info!("auto-crawl scheduler started");
+
     // Spawn Asset Hub deposit listener (if enabled)
     if config.assethub_listener_enabled {
         let listener_state = state.clone();
@@ -193,3 +252,36 @@ async fn spawn_assethub_listener(state: Arc<AppState>) -> Result<(), Box<dyn std
 
     Ok(())
 }
+
+/// Exit code: 0 if the ledger and `users.txt_balance` agree, 1 if not, 2 on error.
+async fn reconcile(db: &sqlx::PgPool) -> i32 {
+    let report = match ledger::reconcile(db).await {
+        Ok(report) => report,
+        Err(e) => {
+            tracing::error!("reconciliation failed: {}", e);
+            return 2;
+        }
+    };
+    for drift in &report.drift {
+        tracing::warn!(
+            user = %drift.user_id,
+            txt_balance = drift.txt_balance,
+            ledger = drift.ledger_balance,
+            "balance drift"
+        );
+    }
+    for id in &report.unbalanced {
+        tracing::warn!(transaction = %id, "unbalanced ledger transaction");
+    }
+    if report.is_clean() {
+        tracing::info!("ledger reconciled: no drift");
+        0
+    } else {
+        tracing::warn!(
+            drift = report.drift.len(),
+            unbalanced = report.unbalanced.len(),
+            "ledger out of step"
+        );
+        1
+    }
+}
@@ -1,6 +1,6 @@
 use axum::{
-    extract::State,
-    routing::post,
+    extract::{Path, State},
+    routing::{delete, get, post},
     Json, Router,
 };
 use axum_extra::{
@@ -13,7 +13,18 @@ use std::sync::Arc;
 use subtle::ConstantTimeEq;
 use uuid::Uuid;
 
-use crate::{auth::api_key::ApiKey, error::Result, routes::embed::generate_embed_signature, AppState};
+use sonotxt_core::protocol::TeeType;
+
+use crate::{
+    auth::api_key::ApiKey,
+    error::Result,
+    routes::embed::generate_embed_signature,
+    services::ledger::{self, Reconciliation},
+    services::measurement_log::{self, Action, EntryInfo, LogEntry},
+    services::worker_pool::WorkerSpec,
+    services::worker_registry::{self, RegisteredWorker},
+    AppState,
+};
 
 #[derive(Debug, Serialize)]
 struct CreateApiKeyResponse {
@@ -41,6 +52,13 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/admin/embed-sig", post(create_embed_sig))
         .route("/admin/vault/seal-stripe", post(seal_stripe_secrets))
         .route("/admin/vault/status", post(vault_status))
+        .route("/admin/measurements", post(add_measurement))
+        .route("/admin/measurements/revoke", post(revoke_measurement))
+        .route("/admin/workers", get(list_workers).post(register_worker))
+        .route("/admin/workers/:name", delete(remove_worker))
+        .route("/admin/workers/:name/drain", post(drain_worker))
+        .route("/admin/workers/:name/resume", post(resume_worker))
+        .route("/admin/ledger/reconcile", get(reconcile_ledger))
 }
 
 async fn create_api_key(
@@ -278,3 +296,226 @@ async fn vault_status(
         secrets,
     }))
 }
+
+#[derive(Debug, Deserialize)]
+struct MeasurementRequest {
+    tee_type: TeeType,
+    /// SNP: 48-byte hex; TDX: mrtd[:rtmr0[:rtmr1[:rtmr2[:rtmr3]]]] hex
+    measurement: String,
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    git_commit: String,
+    #[serde(default)]
+    note: String,
+}
+
+/// Approve a worker measurement (appends an `add` entry to the log)
+async fn add_measurement(
+    State(state): State<Arc<AppState>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Json(req): Json<MeasurementRequest>,
+) -> Result<Json<LogEntry>> {
+    append_measurement(&state, auth.token(), Action::Add, req).await
+}
+
+/// Revoke a previously approved worker measurement
+async fn revoke_measurement(
+    State(state): State<Arc<AppState>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Json(req): Json<MeasurementRequest>,
+) -> Result<Json<LogEntry>> {
+    append_measurement(&state, auth.token(), Action::Revoke, req).await
+}
+
+async fn append_measurement(
+    state: &AppState,
+    token: &str,
+    action: Action,
+    req: MeasurementRequest,
+) -> Result<Json<LogEntry>> {
+    // verify admin token
+    let is_valid = match &state.config.admin_token {
+        Some(admin) => {
+            let a = admin.as_bytes();
+            let b = token.as_bytes();
+            a.len() == b.len() && a.ct_eq(b).into()
+        }
+        None => false,
+    };
+
+    if !is_valid {
+        return Err(crate::error::ApiError::Unauthorized);
+    }
+
+    let log = state.measurements.as_ref()
+        .ok_or_else(|| crate::error::ApiError::Internal("measurement log not configured".into()))?;
+
+    let tee_type = measurement_log::tee_name(req.tee_type)
+        .ok_or_else(|| crate::error::ApiError::InvalidRequest("insecure workers have no measurement".into()))?;
+    let measurement = measurement_log::canonical_measurement(req.tee_type, &req.measurement)
+        .map_err(|e| crate::error::ApiError::InvalidRequest(e.to_string()))?;
+
+    if action == Action::Revoke
+        && !log.active().await?.iter().any(|(t, m)| t == tee_type && *m == measurement)
+    {
+        return Err(crate::error::ApiError::NotFound);
+    }
+
+    let info = EntryInfo {
+        version: req.version,
+        git_commit: req.git_commit,
+        note: req.note,
+    };
+    let entry = log
+        .append(action, req.tee_type, &measurement, info)
+        .await
+        .map_err(|e| crate::error::ApiError::Internal(format!("measurement log append failed: {}", e)))?;
+
+    Ok(Json(entry))
+}
+
+fn require_admin(state: &AppState, token: &str) -> Result<()> {
+    let is_valid = match &state.config.admin_token {
+        Some(admin) => {
+            let a = admin.as_bytes();
+            let b = token.as_bytes();
+            a.len() == b.len() && a.ct_eq(b).into()
+        }
+        None => false,
+    };
+
+    if !is_valid {
+        return Err(crate::error::ApiError::Unauthorized);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct RegisterWorkerRequest {
+    name: String,
+    speech_url: String,
+    llm_url: String,
+    /// ip:port of the worker's QUIC listener; omit for HTTP-only workers
+    #[serde(default)]
+    quic_addr: Option<String>,
+    /// Max concurrent requests; defaults to WORKER_CAPACITY
+    #[serde(default)]
+    capacity: Option<u64>,
+}
+
+impl RegisterWorkerRequest {
+    fn spec(self) -> Result<WorkerSpec> {
+        let invalid = |msg: String| crate::error::ApiError::InvalidRequest(msg);
+        if self.name.is_empty() || self.name.contains('/') {
+            return Err(invalid(format!("invalid worker name {:?}", self.name)));
+        }
+        for url in [&self.speech_url, &self.llm_url] {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(invalid(format!("invalid URL {}", url)));
+            }
+        }
+        let quic_addr = self
+            .quic_addr
+            .as_deref()
+            .map(|a| a.parse().map_err(|_| invalid(format!("quic_addr must be ip:port, got {}", a))))
+            .transpose()?;
+        if self.capacity == Some(0) || self.capacity.is_some_and(|c| c > i32::MAX as u64) {
+            return Err(invalid("capacity out of range".into()));
+        }
+        Ok(WorkerSpec {
+            name: self.name,
+            speech_url: self.speech_url.trim_end_matches('/').to_string(),
+            llm_url: self.llm_url.trim_end_matches('/').to_string(),
+            quic_addr,
+            capacity: self.capacity,
+            draining: false,
+        })
+    }
+}
+
+/// Registry contents (live health is on `/api/workers`)
+async fn list_workers(
+    State(state): State<Arc<AppState>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> Result<Json<Vec<RegisteredWorker>>> {
+    require_admin(&state, auth.token())?;
+    Ok(Json(worker_registry::list(&state.db).await?))
+}
+
+/// Register a worker, or update the endpoints of an existing one
+async fn register_worker(
+    State(state): State<Arc<AppState>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Json(req): Json<RegisterWorkerRequest>,
+) -> Result<Json<RegisteredWorker>> {
+    require_admin(&state, auth.token())?;
+    let worker = worker_registry::upsert(&state.db, &req.spec()?).await?;
+    apply_registry(&state).await?;
+    Ok(Json(worker))
+}
+
+/// Stop sending new requests to a worker; in-flight ones finish
+async fn drain_worker(
+    State(state): State<Arc<AppState>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Path(name): Path<String>,
+) -> Result<Json<RegisteredWorker>> {
+    set_draining(&state, auth.token(), &name, true).await
+}
+
+/// Route new requests to a drained worker again
+async fn resume_worker(
+    State(state): State<Arc<AppState>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Path(name): Path<String>,
+) -> Result<Json<RegisteredWorker>> {
+    set_draining(&state, auth.token(), &name, false).await
+}
+
+async fn set_draining(
+    state: &AppState,
+    token: &str,
+    name: &str,
+    draining: bool,
+) -> Result<Json<RegisteredWorker>> {
+    require_admin(state, token)?;
+    let worker = worker_registry::set_draining(&state.db, name, draining)
+        .await?
+        .ok_or(crate::error::ApiError::NotFound)?;
+    apply_registry(state).await?;
+    Ok(Json(worker))
+}
+
+/// Remove a worker from the registry and the pool
+async fn remove_worker(
+    State(state): State<Arc<AppState>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    require_admin(&state, auth.token())?;
+    if !worker_registry::remove(&state.db, &name).await? {
+        return Err(crate::error::ApiError::NotFound);
+    }
+    apply_registry(&state).await?;
+    Ok(Json(serde_json::json!({ "removed": name })))
+}
+
+/// Users whose cached balance disagrees with the ledger, and ledger
+/// transactions that don't balance
+async fn reconcile_ledger(
+    State(state): State<Arc<AppState>>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> Result<Json<Reconciliation>> {
+    require_admin(&state, auth.token())?;
+    Ok(Json(ledger::reconcile(&state.db).await?))
+}
+
+/// Apply registry changes to this instance's pool now, rather than on the
+/// next health check round.
+async fn apply_registry(state: &AppState) -> Result<()> {
+    if let Some(ref pool) = state.workers {
+        worker_registry::sync(&state.db, pool).await?;
+    }
+    Ok(())
+}
@@ -3,10 +3,11 @@ use axum::{
     extract::{Path, Query, State},
     http::header,
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{delete, get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
+use sonotxt_core::{Privacy, SynthesisOptions};
 use std::{collections::HashMap, sync::Arc};
 use uuid::Uuid;
 
@@ -14,7 +15,10 @@ use crate::{
     auth::{AuthenticatedUser, TtsUser, check_free_tier_limit, check_free_tier_limit_with, consume_free_tier, get_free_tier_remaining, hash_ip, FREE_TIER_DAILY_LIMIT, FREE_TIER_LOGGED_IN_LIMIT},
     error::Result,
     models::{JobStatus, ProcessRequest, ProcessResponse},
+    services::billing,
     services::content::extract_content,
+    services::engines::{self, Synthesis},
+    services::pricing::RateCard,
     AppState,
 };
 
@@ -27,10 +31,22 @@ struct TtsRequest {
     storage: Option<String>, // "minio" or "ipfs"
     #[serde(default = "default_engine")]
     engine: String, // "qwen" | "vibevoice" | "vibevoice-streaming"
+    #[serde(default = "default_language")]
+    language: String,
+    /// speed, instruct (qwen), cfg_scale (vibevoice)
+    #[serde(default)]
+    options: SynthesisOptions,
+    /// "standard" or "private" (encrypted worker session only)
+    #[serde(default)]
+    privacy: Privacy,
 }
 
 fn default_engine() -> String {
-    "qwen".to_string()
+    sonotxt_core::DEFAULT_ENGINE.to_string()
+}
+
+fn default_language() -> String {
+    "auto".to_string()
 }
 
 fn default_voice() -> String {
@@ -59,7 +75,8 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/extract", post(extract))
         .route("/status", get(status))
         .route("/voices", get(list_voices))
-        .route("/download/{job_id}", get(download_audio))
+        .route("/jobs/:job_id", delete(cancel_job))
+        .route("/download/:job_id", get(download_audio))
         .route("/free-balance", get(free_balance))
         .route("/workers", get(workers_status))
 }
@@ -138,7 +155,7 @@ async fn extract(
     let word_count = content.text.split_whitespace().count();
 
     Ok(Json(ExtractResponse {
-        char_count: content.text.len(),
+        char_count: content.text.chars().count(),
         word_count,
         title: content.title,
         text: content.text,
@@ -152,35 +169,34 @@ async fn process(
 ) -> Result<Json<ProcessResponse>> {
     let extracted = extract_content(&state, &req.url, req.selector.as_deref()).await?;
     let content = extracted.text;
-    let estimated_cost = (content.len() as f64) * state.config.cost_per_char;
 
-    let price = match &state.sono {
-        Some(sono) => sono.price.read().await.clone(),
-        None => crate::services::sono::PriceInfo::default(),
-    };
-    let txt_cost = crate::services::billing::txt_cost_for_chars(
-        content.len(), state.config.cost_per_char, &price,
-    );
+    let estimate = RateCard::from_config(&state.config)
+        .estimate_text(&content, engines::for_job(None).name(), &default_voice(), None);
+    let estimated_cost = estimate.usd;
+    let price = billing::current_price(state.sono.as_deref()).await;
+
+    let job_id = Uuid::new_v4().to_string();
 
-    crate::services::billing::check_and_charge(
-        &state.db,
+    let mut tx = state.db.begin().await?;
+    let hold = billing::place_hold(
+        &mut tx,
         state.sono.as_deref(),
         user.account_id,
         user.wallet_address.as_deref(),
-        txt_cost,
+        billing::usd_to_txt(estimate.hold_usd, &price),
+        &job_id,
     ).await?;
 
-    let job_id = Uuid::new_v4().to_string();
-
-    sqlx::query!(
-        "INSERT INTO jobs (id, api_key, text_content, status, cost) VALUES ($1, $2, $3, 'queued', $4)",
-        job_id,
-        user.api_key,
-        content.as_str(),
-        estimated_cost
-    )
-    .execute(&state.db)
-    .await?;
+    sqlx::query("INSERT INTO jobs (id, api_key, text_content, status, cost) VALUES ($1, $2, $3, $4, $5)")
+        .bind(&job_id)
+        .bind(&user.api_key)
+        .bind(content.as_str())
+        .bind(hold.job_status())
+        .bind(estimated_cost)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    billing::charge_hold(&state.db, state.sono.as_deref(), &job_id, &hold).await?;
 
     crate::notify_job(&state, &job_id).await;
 
@@ -221,54 +237,61 @@ async fn tts(
     };
 
     // validate engine
-    let engine = match req.engine.as_str() {
-        "qwen" | "vibevoice" | "vibevoice-streaming" => req.engine.clone(),
-        _ => default_engine(),
+    let engine = engines::for_job(Some(&req.engine)).name();
+    let synthesis = Synthesis {
+        voice: voice.clone(),
+        language: req.language.clone(),
+        options: req.options.clone(),
     };
 
     let job_id = Uuid::new_v4().to_string();
-    let char_count = text.len() as i32;
+    let char_count = text.chars().count() as i32;
 
     match user {
         TtsUser::Authenticated(auth_user) => {
             let estimated_duration_ms = (char_count as f64 * crate::models::MS_PER_CHAR) as i32;
             let storage_type = req.storage.as_deref();
 
-            // Get price and compute TXT cost
-            let price = match &state.sono {
-                Some(sono) => sono.price.read().await.clone(),
-                None => crate::services::sono::PriceInfo::default(),
-            };
-            let txt_cost = crate::services::billing::txt_cost_for_chars(
-                text.len(), state.config.cost_per_char, &price,
-            );
-            let estimated_cost = text.len() as f64 * state.config.cost_per_char;
+            // Price the expected audio; the hold is settled on the real duration
+            let estimate = RateCard::from_config(&state.config)
+                .estimate_text(text, engine, &voice, synthesis.options.speed);
+            let estimated_cost = estimate.usd;
+            let price = billing::current_price(state.sono.as_deref()).await;
+            let options = synthesis_options(engine, &synthesis)?;
 
             // Try TXT billing (custodial balance + payment channel)
-            match crate::services::billing::check_and_charge(
-                &state.db,
+            let mut tx = state.db.begin().await?;
+            match billing::place_hold(
+                &mut tx,
                 state.sono.as_deref(),
                 auth_user.account_id,
                 auth_user.wallet_address.as_deref(),
-                txt_cost,
+                billing::usd_to_txt(estimate.hold_usd, &price),
+                &job_id,
             ).await {
-                Ok(_charge) => {
-                    // Paid — create job at priority 50
-                    sqlx::query!(
-                        "INSERT INTO jobs (id, api_key, text_content, voice, status, cost, is_free_tier, char_count, estimated_duration_ms, storage_type, engine, priority) VALUES ($1, $2, $3, $4, 'queued', $5, FALSE, $6, $7, $8, $9, $10)",
-                        job_id,
-                        auth_user.api_key,
-                        text,
-                        voice,
-                        estimated_cost,
-                        char_count,
-                        estimated_duration_ms,
-                        storage_type,
-                        engine,
-                        50i32
+                Ok(hold) => {
+                    // Paid — create job at priority 50, in the transaction holding its TXT
+                    sqlx::query(
+                        "INSERT INTO jobs (id, api_key, text_content, voice, status, cost, is_free_tier, char_count, estimated_duration_ms, storage_type, engine, priority, language, synthesis_options, privacy) VALUES ($1, $2, $3, $4, $5, $6, FALSE, $7, $8, $9, $10, $11, $12, $13, $14)",
                     )
-                    .execute(&state.db)
+                    .bind(&job_id)
+                    .bind(&auth_user.api_key)
+                    .bind(&text)
+                    .bind(&voice)
+                    .bind(hold.job_status())
+                    .bind(estimated_cost)
+                    .bind(char_count)
+                    .bind(estimated_duration_ms)
+                    .bind(storage_type)
+                    .bind(engine)
+                    .bind(50i32)
+                    .bind(&synthesis.language)
+                    .bind(options)
+                    .bind(req.privacy.as_str())
+                    .execute(&mut *tx)
                     .await?;
+                    tx.commit().await?;
+                    billing::charge_hold(&state.db, state.sono.as_deref(), &job_id, &hold).await?;
 
                     crate::notify_job(&state, &job_id).await;
 
@@ -281,8 +304,18 @@ async fn tts(
                     }))
                 }
                 Err(_) => {
+                    // Roll back any partial hold and release the balance lock
+                    drop(tx);
+
                     // No TXT balance — fall back to logged-in free tier (1000 chars/day)
                     let user_hash = hash_ip(&auth_user.account_id.to_string());
+                    let engine_type = if voice.starts_with("en-") {
+                        "vibevoice-streaming"
+                    } else {
+                        "qwen"
+                    };
+                    let options = synthesis_options(engine_type, &synthesis)?;
+
                     let remaining = check_free_tier_limit_with(
                         &state.db, &user_hash, char_count, FREE_TIER_LOGGED_IN_LIMIT,
                     ).await?;
@@ -290,14 +323,9 @@ async fn tts(
                     consume_free_tier(&state.db, &user_hash, char_count).await?;
 
                     let storage_type: Option<&str> = Some("minio");
-                    let engine_type = if voice.starts_with("en-") {
-                        "vibevoice-streaming"
-                    } else {
-                        "qwen"
-                    };
 
                     sqlx::query(
-                        "INSERT INTO jobs (id, api_key, text_content, voice, status, cost, is_free_tier, char_count, estimated_duration_ms, storage_type, engine, priority) VALUES ($1, $2, $3, $4, 'queued', 0, TRUE, $5, $6, $7, $8, 10)",
+                        "INSERT INTO jobs (id, api_key, text_content, voice, status, cost, is_free_tier, char_count, estimated_duration_ms, storage_type, engine, priority, language, synthesis_options, privacy) VALUES ($1, $2, $3, $4, 'queued', 0, TRUE, $5, $6, $7, $8, 10, $9, $10, $11)",
                     )
                     .bind(&job_id)
                     .bind(&auth_user.api_key)
@@ -307,6 +335,9 @@ async fn tts(
                     .bind(estimated_duration_ms)
                     .bind(storage_type)
                     .bind(engine_type)
+                    .bind(&synthesis.language)
+                    .bind(options)
+                    .bind(req.privacy.as_str())
                     .execute(&state.db)
                     .await?;
 
@@ -324,6 +355,13 @@ async fn tts(
         }
 
         TtsUser::FreeTier { ip_hash } => {
+            let engine_type = if voice.starts_with("en-") {
+                "vibevoice-streaming"
+            } else {
+                "qwen"
+            };
+            let options = synthesis_options(engine_type, &synthesis)?;
+
             // check and consume free tier allowance
             let remaining = check_free_tier_limit(&state.db, &ip_hash, char_count).await?;
 
@@ -341,24 +379,22 @@ async fn tts(
             let estimated_duration_ms = (char_count as f64 * crate::models::MS_PER_CHAR) as i32;
             // free tier only gets minio, not ipfs (to avoid pinning costs)
             let storage_type: Option<&str> = Some("minio");
-            let engine_type = if voice.starts_with("en-") {
-                "vibevoice-streaming"
-            } else {
-                "qwen"
-            };
 
             // create job with ip_hash instead of api_key, priority 0 (free tier)
-            sqlx::query!(
-                "INSERT INTO jobs (id, ip_hash, text_content, voice, status, cost, is_free_tier, char_count, estimated_duration_ms, storage_type, engine, priority) VALUES ($1, $2, $3, $4, 'queued', 0, TRUE, $5, $6, $7, $8, 0)",
-                job_id,
-                ip_hash,
-                text,
-                voice,
-                char_count,
-                estimated_duration_ms,
-                storage_type,
-                engine_type
+            sqlx::query(
+                "INSERT INTO jobs (id, ip_hash, text_content, voice, status, cost, is_free_tier, char_count, estimated_duration_ms, storage_type, engine, priority, language, synthesis_options, privacy) VALUES ($1, $2, $3, $4, 'queued', 0, TRUE, $5, $6, $7, $8, 0, $9, $10, $11)",
             )
+            .bind(&job_id)
+            .bind(&ip_hash)
+            .bind(&text)
+            .bind(&voice)
+            .bind(char_count)
+            .bind(estimated_duration_ms)
+            .bind(storage_type)
+            .bind(engine_type)
+            .bind(&synthesis.language)
+            .bind(options)
+            .bind(req.privacy.as_str())
             .execute(&mut *tx)
             .await?;
 
@@ -377,6 +413,20 @@ async fn tts(
     }
 }
 
+/// Vet `synthesis` for `engine` and encode its options for
+/// `jobs.synthesis_options` (NULL when none are set).
+fn synthesis_options(engine: &str, synthesis: &Synthesis) -> Result<Option<String>> {
+    engines::for_job(Some(engine))
+        .validate(synthesis)
+        .map_err(crate::error::ApiError::InvalidRequest)?;
+    if synthesis.options == SynthesisOptions::default() {
+        return Ok(None);
+    }
+    serde_json::to_string(&synthesis.options)
+        .map(Some)
+        .map_err(|e| crate::error::ApiError::Internal(e.to_string()))
+}
+
 async fn status(
     State(state): State<Arc<AppState>>,
     Query(params): Query<HashMap<String, String>>,
@@ -419,6 +469,7 @@ async fn status(
         "failed" => Ok(Json(JobStatus::Failed {
             reason: job.error_message.unwrap_or_else(|| "Processing failed".into()),
         })),
+        "cancelled" => Ok(Json(JobStatus::Cancelled)),
         "processing" => {
             // Calculate progress based on elapsed time vs estimated
             let elapsed_seconds = job.started_at
@@ -460,6 +511,92 @@ async fn status(
     }
 }
 
+/// Cancel a queued or running job. It leaves the queue, or is stopped on
+/// its worker by the job worker, which watches for the status change. A
+/// paid job is refunded its hold; a free-tier job gives back its characters.
+/// Jobs the caller doesn't own are reported as not found.
+async fn cancel_job(
+    State(state): State<Arc<AppState>>,
+    Path(job_id): Path<String>,
+    user: TtsUser,
+) -> Result<Json<JobStatus>> {
+    let mut tx = state.db.begin().await?;
+
+    let job: Option<(String, bool, Option<i32>)> = match &user {
+        TtsUser::Authenticated(auth_user) => {
+            sqlx::query_as(
+                r#"
+                SELECT j.status, j.is_free_tier, j.char_count
+                FROM jobs j
+                WHERE j.id = $1 AND (
+                    j.api_key = $2
+                    OR j.user_id = $3
+                    OR j.api_key IN (SELECT key FROM api_keys WHERE account_id = $3)
+                    OR EXISTS (SELECT 1 FROM ledger_transactions t WHERE t.job_id = j.id AND t.user_id = $3)
+                )
+                FOR UPDATE OF j
+                "#,
+            )
+            .bind(&job_id)
+            .bind(&auth_user.api_key)
+            .bind(auth_user.account_id)
+            .fetch_optional(&mut *tx)
+            .await?
+        }
+        TtsUser::FreeTier { ip_hash } => {
+            sqlx::query_as(
+                "SELECT status, is_free_tier, char_count FROM jobs WHERE id = $1 AND ip_hash = $2 FOR UPDATE",
+            )
+            .bind(&job_id)
+            .bind(ip_hash)
+            .fetch_optional(&mut *tx)
+            .await?
+        }
+    };
+    let (status, is_free_tier, char_count) = job.ok_or(crate::error::ApiError::NotFound)?;
+
+    if status == "cancelled" {
+        return Ok(Json(JobStatus::Cancelled));
+    }
+    if !matches!(status.as_str(), "charging" | "queued" | "processing") {
+        return Err(crate::error::ApiError::InvalidRequest(format!("job is already {}", status)));
+    }
+
+    sqlx::query("UPDATE jobs SET status = 'cancelled', completed_at = NOW() WHERE id = $1")
+        .bind(&job_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Give back today's free-tier characters; an earlier day's were reset already
+    if is_free_tier {
+        let usage_hash = match &user {
+            TtsUser::Authenticated(auth_user) => hash_ip(&auth_user.account_id.to_string()),
+            TtsUser::FreeTier { ip_hash } => ip_hash.clone(),
+        };
+        sqlx::query(
+            r#"
+            UPDATE free_tier_usage SET chars_used = GREATEST(chars_used - $1, 0)
+            WHERE ip_hash = $2
+              AND last_reset = (SELECT created_at::date FROM jobs WHERE id = $3)
+            "#,
+        )
+        .bind(char_count.unwrap_or(0))
+        .bind(&usage_hash)
+        .bind(&job_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    // Paid jobs; a refund that fails here is retried by the job worker's billing sweep
+    if let Err(e) = billing::refund_job(&state.db, state.sono.as_deref(), &job_id).await {
+        tracing::error!("refund for cancelled job {} failed: {:?}", job_id, e);
+    }
+
+    Ok(Json(JobStatus::Cancelled))
+}
+
 #[derive(Debug, Serialize)]
 struct FreeBalanceResponse {
     remaining: i32,
@@ -535,3 +672,169 @@ async fn download_audio(
         .body(Body::from(bytes))
         .unwrap())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{Method, Request, StatusCode};
+    use clap::Parser;
+    use sqlx::PgPool;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::sync::RwLock;
+    use tower::ServiceExt;
+
+    /// Stand-in for redis that answers every command with OK and every GET
+    /// with a miss, so cached lookups fall through to the database
+    async fn empty_redis() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (read, mut write) = stream.into_split();
+                    let mut lines = BufReader::new(read).lines();
+                    // Commands arrive as an array of bulk strings
+                    while let Ok(Some(header)) = lines.next_line().await {
+                        let Some(count) = header.strip_prefix('*').and_then(|n| n.parse::<usize>().ok()) else {
+                            return;
+                        };
+                        let mut command = String::new();
+                        for n in 0..count {
+                            let (Ok(Some(_)), Ok(Some(arg))) = (lines.next_line().await, lines.next_line().await) else {
+                                return;
+                            };
+                            if n == 0 {
+                                command = arg.to_uppercase();
+                            }
+                        }
+                        let reply: &[u8] = if command == "GET" { b"$-1\r\n" } else { b"+OK\r\n" };
+                        if write.write_all(reply).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        format!("redis://{}", addr)
+    }
+
+    async fn test_state(db: PgPool) -> Arc<AppState> {
+        let mut config = crate::Config::parse_from(["sonotxt-api"]);
+        config.redis_url = empty_redis().await;
+        let redis = redis::Client::open(config.redis_url.as_str()).unwrap();
+        let redis = redis::aio::ConnectionManager::new(redis).await.unwrap();
+        let vault = hwpay::Vault::open(Some(b"test".as_slice())).unwrap();
+        Arc::new(AppState {
+            config,
+            redis,
+            http: reqwest::Client::new(),
+            db,
+            payments: Arc::new(RwLock::new(hwpay::PaymentProcessor::new(vault))),
+            sono: None,
+            workers: None,
+            measurements: None,
+        })
+    }
+
+    async fn cancel(app: &axum::Router, job_id: &str, ip: &str, api_key: Option<&str>) -> StatusCode {
+        let mut request = Request::builder()
+            .method(Method::DELETE)
+            .uri(format!("/api/jobs/{}", job_id))
+            .header("x-real-ip", ip);
+        if let Some(key) = api_key {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", key));
+        }
+        let response = app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        response.status()
+    }
+
+    async fn job_status(db: &PgPool, job_id: &str) -> String {
+        sqlx::query_scalar("SELECT status FROM jobs WHERE id = $1")
+            .bind(job_id)
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_cancel_job_route(db: PgPool) {
+        let ip_hash = hash_ip("203.0.113.7");
+        for (id, status) in [("job-1", "queued"), ("job-2", "completed")] {
+            sqlx::query(
+                "INSERT INTO jobs (id, text_content, status, is_free_tier, char_count, ip_hash) VALUES ($1, 'hello', $2, TRUE, 5, $3)",
+            )
+            .bind(id)
+            .bind(status)
+            .bind(&ip_hash)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+        sqlx::query("INSERT INTO free_tier_usage (ip_hash, chars_used) VALUES ($1, 12)")
+            .bind(&ip_hash)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let app = crate::build_app(test_state(db.clone()).await);
+
+        // Someone else's job doesn't exist as far as they can tell
+        assert_eq!(cancel(&app, "job-1", "198.51.100.1", None).await, StatusCode::NOT_FOUND);
+        assert_eq!(job_status(&db, "job-1").await, "queued");
+
+        assert_eq!(cancel(&app, "job-1", "203.0.113.7", None).await, StatusCode::OK);
+        assert_eq!(job_status(&db, "job-1").await, "cancelled");
+        let chars_used: i32 = sqlx::query_scalar("SELECT chars_used FROM free_tier_usage WHERE ip_hash = $1")
+            .bind(&ip_hash)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(chars_used, 7);
+
+        assert_eq!(cancel(&app, "job-2", "203.0.113.7", None).await, StatusCode::BAD_REQUEST);
+        assert_eq!(job_status(&db, "job-2").await, "completed");
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_cancel_paid_job_refunds_once(db: PgPool) {
+        let account_id: Uuid = sqlx::query_scalar("INSERT INTO accounts (email) VALUES ('payer@example.com') RETURNING id")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO users (id, email) VALUES ($1, 'payer@example.com')")
+            .bind(account_id)
+            .execute(&db)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO api_keys (key, account_id) VALUES ('sk_test', $1)")
+            .bind(account_id)
+            .execute(&db)
+            .await
+            .unwrap();
+        billing::credit_txt(&db, account_id, 1_000, "test", "opening").await.unwrap();
+
+        let mut tx = db.begin().await.unwrap();
+        billing::place_hold(&mut tx, None, account_id, None, 400, "job-1").await.unwrap();
+        sqlx::query("INSERT INTO jobs (id, api_key, text_content, status) VALUES ('job-1', 'sk_test', 'hello', 'queued')")
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+
+        let app = crate::build_app(test_state(db.clone()).await);
+        assert_eq!(cancel(&app, "job-1", "203.0.113.7", Some("sk_test")).await, StatusCode::OK);
+        assert_eq!(cancel(&app, "job-1", "203.0.113.7", Some("sk_test")).await, StatusCode::OK);
+
+        let refunds: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ledger_transactions WHERE idempotency_key = 'job:job-1:refund'")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(refunds, 1);
+        let balance: i64 = sqlx::query_scalar("SELECT txt_balance FROM users WHERE id = $1")
+            .bind(account_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(balance, 1_000);
+    }
+}
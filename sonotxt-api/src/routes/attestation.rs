@@ -0,0 +1,61 @@
+//! Worker attestation transparency
+//!
+//! GET /api/attestation/measurements — signed measurement log and the
+//!                                     currently approved worker builds
+
+use axum::{extract::State, routing::get, Json, Router};
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::{error::Result, services::measurement_log::LogEntry, AppState};
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/measurements", get(measurements))
+}
+
+#[derive(Debug, Serialize)]
+struct ApprovedMeasurement {
+    tee_type: String,
+    measurement: String,
+}
+
+#[derive(Debug, Serialize)]
+struct MeasurementsResponse {
+    enabled: bool,
+    /// ed25519 key that signs every entry (hex)
+    public_key: String,
+    /// entry_hash of the latest entry (hex), empty if the log is empty
+    head: String,
+    active: Vec<ApprovedMeasurement>,
+    entries: Vec<LogEntry>,
+}
+
+/// GET /api/attestation/measurements — full log, for clients to verify the
+/// hash chain and signatures themselves
+async fn measurements(State(state): State<Arc<AppState>>) -> Result<Json<MeasurementsResponse>> {
+    let Some(log) = &state.measurements else {
+        return Ok(Json(MeasurementsResponse {
+            enabled: false,
+            public_key: String::new(),
+            head: String::new(),
+            active: vec![],
+            entries: vec![],
+        }));
+    };
+
+    let entries = log.entries().await?;
+    let active = log
+        .active()
+        .await?
+        .into_iter()
+        .map(|(tee_type, measurement)| ApprovedMeasurement { tee_type, measurement })
+        .collect();
+
+    Ok(Json(MeasurementsResponse {
+        enabled: true,
+        public_key: hex::encode(log.public_key().as_bytes()),
+        head: entries.last().map(|e| e.entry_hash.clone()).unwrap_or_default(),
+        active,
+        entries,
+    }))
+}
@@ -5,18 +5,22 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use uuid::Uuid;
 
 use crate::{
     auth::TtsUser,
     error::{ApiError, Result},
     services::billing,
+    services::engines,
+    services::ledger::{self, Account, Kind, Posting},
+    services::pricing::{self, RateCard},
     AppState,
 };
 
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/billing/status", get(get_status))
-        .route("/billing/estimate", post(estimate_cost))
+        .route("/billing/estimate", get(rate_card).post(estimate_cost))
         .route("/billing/withdraw", post(withdraw_txt))
 }
 
@@ -88,36 +92,82 @@ async fn get_status(
     }))
 }
 
+#[derive(Debug, Serialize)]
+struct RateCardResponse {
+    #[serde(flatten)]
+    rates: RateCard,
+    /// Characters per second of audio that estimates assume
+    chars_per_second: f64,
+    /// Hold placed at submission, as a multiple of the estimate
+    hold_margin: f64,
+    /// Current TXT price in USD
+    txt_usd: f64,
+}
+
+/// GET /billing/estimate — what audio costs per engine and voice
+async fn rate_card(State(state): State<Arc<AppState>>) -> Json<RateCardResponse> {
+    let price = billing::current_price(state.sono.as_deref()).await;
+    Json(RateCardResponse {
+        rates: RateCard::from_config(&state.config),
+        chars_per_second: pricing::CHARS_PER_SECOND,
+        hold_margin: pricing::HOLD_MARGIN,
+        txt_usd: price.txt_usd_base,
+    })
+}
+
 #[derive(Debug, Deserialize)]
 struct EstimateRequest {
     chars: usize,
+    #[serde(default)]
+    engine: Option<String>,
+    #[serde(default)]
+    voice: Option<String>,
+    #[serde(default)]
+    speed: Option<f32>,
 }
 
 #[derive(Debug, Serialize)]
 struct EstimateResponse {
-    /// TXT cost in raw units
+    /// Expected TXT cost in raw units
     txt_cost: i64,
     txt_formatted: String,
     /// Equivalent USD cost
     usd_cost: f64,
+    /// TXT held at submission; the job is settled on its actual duration
+    txt_hold: i64,
+    hold_formatted: String,
+    /// Expected audio duration
+    estimated_seconds: f64,
+    /// Engine and voice multiplier applied
+    multiplier: f64,
 }
 
+/// POST /billing/estimate — expected cost and hold for a job
 async fn estimate_cost(
     State(state): State<Arc<AppState>>,
     Json(req): Json<EstimateRequest>,
 ) -> Result<Json<EstimateResponse>> {
-    let price = match &state.sono {
-        Some(sono) => sono.price.read().await.clone(),
-        None => crate::services::sono::PriceInfo::default(),
+    let engine = match req.engine.as_deref() {
+        Some(name) => engines::find(name)
+            .ok_or_else(|| ApiError::InvalidRequest(format!("unknown engine {}", name)))?,
+        None => engines::for_job(None),
     };
+    let voice = req.voice.as_deref().unwrap_or_default();
 
-    let txt_cost = billing::txt_cost_for_chars(req.chars, state.config.cost_per_char, &price);
-    let usd_cost = req.chars as f64 * state.config.cost_per_char;
+    let rates = RateCard::from_config(&state.config);
+    let estimate = rates.estimate(req.chars, engine.name(), voice, req.speed);
+    let price = billing::current_price(state.sono.as_deref()).await;
+    let txt_cost = billing::usd_to_txt(estimate.usd, &price);
+    let txt_hold = billing::usd_to_txt(estimate.hold_usd, &price);
 
     Ok(Json(EstimateResponse {
         txt_cost,
         txt_formatted: billing::format_txt(txt_cost),
-        usd_cost,
+        usd_cost: estimate.usd,
+        txt_hold,
+        hold_formatted: billing::format_txt(txt_hold),
+        estimated_seconds: estimate.seconds,
+        multiplier: rates.multiplier(engine.name(), voice),
     }))
 }
 
@@ -155,14 +205,18 @@ async fn withdraw_txt(
         .map_err(|e| ApiError::InvalidRequest(format!("invalid wallet address: {}", e)))?;
 
     // Deduct from custodial balance atomically
-    let new_balance: i64 = sqlx::query_scalar(
-        "UPDATE users SET txt_balance = txt_balance - $1 WHERE id = $2 AND txt_balance >= $1 RETURNING txt_balance",
-    )
-    .bind(req.amount)
-    .bind(user.account_id)
-    .fetch_optional(&state.db)
-    .await?
-    .ok_or(ApiError::InsufficientBalance)?;
+    let withdrawal_id = Uuid::new_v4();
+    let mut tx = state.db.begin().await?;
+    let posting = Posting::new(format!("withdrawal:{}", withdrawal_id), Kind::Withdrawal)
+        .user(user.account_id)
+        .memo(format!("to {}", evm_addr))
+        .transfer(Account::User(user.account_id), Account::External, req.amount);
+    ledger::post(&mut tx, &posting).await?;
+    let new_balance: i64 = sqlx::query_scalar("SELECT txt_balance FROM users WHERE id = $1")
+        .bind(user.account_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    tx.commit().await?;
 
     // Send TXT on-chain via contract transfer
     let txt_u256 = alloy::primitives::U256::from(req.amount as u128);
@@ -181,13 +235,20 @@ async fn withdraw_txt(
         }
         Err(e) => {
             // Rollback: re-credit the balance
-            let _ = sqlx::query(
-                "UPDATE users SET txt_balance = txt_balance + $1 WHERE id = $2",
-            )
-            .bind(req.amount)
-            .bind(user.account_id)
-            .execute(&state.db)
+            let reversal = Posting::new(format!("withdrawal:{}:reversal", withdrawal_id), Kind::Reversal)
+                .user(user.account_id)
+                .memo(format!("withdrawal failed: {}", e))
+                .transfer(Account::External, Account::User(user.account_id), req.amount);
+            let reversed = async {
+                let mut tx = state.db.begin().await?;
+                ledger::post(&mut tx, &reversal).await?;
+                tx.commit().await?;
+                Ok::<_, ApiError>(())
+            }
             .await;
+            if let Err(re) = reversed {
+                tracing::error!(user = %user.account_id, "TXT withdrawal {} not re-credited: {}", withdrawal_id, re);
+            }
 
             tracing::error!(user = %user.account_id, "TXT withdrawal failed: {}", e);
             Err(ApiError::Internal(format!("withdrawal failed: {}", e)))
@@ -1,6 +1,7 @@
 //! Voice conversation endpoint: audio in → ASR → LLM → TTS → audio out
 //!
-//! All GPU communication flows through the WorkerPool service layer.
+//! All GPU communication flows through the WorkerPool service layer,
+//! over the encrypted QUIC channel where the worker has one.
 //! No raw HTTP here — just Service calls with load balancing,
 //! timeouts, retries, and health checks composed in.
 //!
@@ -20,8 +21,9 @@ use std::sync::Arc;
 use tracing::{error, info};
 
 use crate::AppState;
+use sonotxt_core::DEFAULT_ENGINE;
 use crate::services::worker_pool::{
-    AsrRequest, LlmRequest, LlmMessage, TtsRequest, ServiceError,
+    AsrRequest, LlmRequest, LlmMessage, Privacy, SynthesisOptions, TtsRequest, ServiceError,
 };
 use axum::body::Body;
 use futures_util::StreamExt;
@@ -99,6 +101,7 @@ fn svc_err(e: ServiceError) -> StatusCode {
     match e {
         ServiceError::Timeout => StatusCode::GATEWAY_TIMEOUT,
         ServiceError::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        ServiceError::Unsupported(_) => StatusCode::UNPROCESSABLE_ENTITY,
         _ => StatusCode::BAD_GATEWAY,
     }
 }
@@ -147,9 +150,12 @@ async fn converse(
     for sentence in &llm_resp.sentences {
         let tts_start = std::time::Instant::now();
         match pool.tts(TtsRequest {
+            engine: DEFAULT_ENGINE.to_string(),
             text: sentence.clone(),
             speaker: req.speaker.clone(),
             language: req.language.clone(),
+            options: SynthesisOptions::default(),
+            privacy: Privacy::Standard,
             api_key: state.config.qwen_speech_api_key.clone(),
         }).await {
             Ok(resp) => {
@@ -230,57 +236,54 @@ async fn converse_stream(
 
         // Stream LLM sentences
         match pool.llm_stream(llm_req).await {
-            Ok(resp) => {
-                let mut byte_stream = resp.bytes_stream();
-                let mut buffer = String::new();
-
-                while let Some(chunk) = byte_stream.next().await {
-                    let chunk = match chunk {
-                        Ok(c) => c,
+            Ok(mut sentences) => {
+                while let Some(sentence) = sentences.next().await {
+                    let sentence = match sentence {
+                        Ok(s) => s,
                         Err(e) => {
-                            error!("llm stream chunk: {}", e);
+                            error!("llm stream: {}", e);
                             break;
                         }
                     };
-                    buffer.push_str(&String::from_utf8_lossy(&chunk));
-
-                    // Parse SSE lines from LLM
-                    while let Some(pos) = buffer.find("\n\n") {
-                        let line = buffer[..pos].to_string();
-                        buffer = buffer[pos + 2..].to_string();
-
-                        if let Some(data) = line.strip_prefix("data: ") {
-                            if let Ok(event) = serde_json::from_str::<serde_json::Value>(data) {
-                                if event["event"] == "sentence" {
-                                    let sentence = event["text"].as_str().unwrap_or("").to_string();
-                                    if sentence.is_empty() { continue; }
-
-                                    // Pipeline TTS immediately
-                                    match pool.tts(TtsRequest {
-                                        text: sentence.clone(),
-                                        speaker: speaker.clone(),
-                                        language: language.clone(),
-                                        api_key: api_key.clone(),
-                                    }).await {
-                                        Ok(tts_resp) => {
-                                            use base64::{engine::general_purpose::STANDARD, Engine};
-                                            let _ = tx.send(Ok(format!(
-                                                "data: {}\n\n",
-                                                serde_json::json!({
-                                                    "event": "audio",
-                                                    "sentence": sentence,
-                                                    "audio_base64": STANDARD.encode(&tts_resp.audio_data),
-                                                    "duration_seconds": tts_resp.duration_seconds,
-                                                })
-                                            ))).await;
-                                        }
-                                        Err(e) => {
-                                            error!("TTS \"{}\": {}", sentence, e);
-                                        }
-                                    }
-                                }
+
+                    // Pipeline TTS immediately. If the client goes away,
+                    // dropping the calls cancels them on the worker.
+                    let tts = pool.tts(TtsRequest {
+                        engine: DEFAULT_ENGINE.to_string(),
+                        text: sentence.clone(),
+                        speaker: speaker.clone(),
+                        language: language.clone(),
+                        options: SynthesisOptions::default(),
+                        privacy: Privacy::Standard,
+                        api_key: api_key.clone(),
+                    });
+                    let result = tokio::select! {
+                        result = tts => result,
+                        _ = tx.closed() => {
+                            info!("converse client disconnected");
+                            return;
+                        }
+                    };
+                    match result {
+                        Ok(tts_resp) => {
+                            use base64::{engine::general_purpose::STANDARD, Engine};
+                            let sent = tx.send(Ok(format!(
+                                "data: {}\n\n",
+                                serde_json::json!({
+                                    "event": "audio",
+                                    "sentence": sentence,
+                                    "audio_base64": STANDARD.encode(&tts_resp.audio_data),
+                                    "duration_seconds": tts_resp.duration_seconds,
+                                })
+                            ))).await;
+                            if sent.is_err() {
+                                info!("converse client disconnected");
+                                return;
                             }
                         }
+                        Err(e) => {
+                            error!("TTS \"{}\": {}", sentence, e);
+                        }
                     }
                 }
             }
@@ -352,9 +355,12 @@ async fn synthesize(
 ) -> Result<Response, StatusCode> {
     let pool = state.workers.as_ref().ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
     let resp = pool.tts(TtsRequest {
+        engine: DEFAULT_ENGINE.to_string(),
         text: req.text,
         speaker: req.speaker,
         language: req.language,
+        options: SynthesisOptions::default(),
+        privacy: Privacy::Standard,
         api_key: state.config.qwen_speech_api_key.clone(),
     }).await.map_err(|e| { error!("TTS: {}", e); svc_err(e) })?;
 
@@ -20,6 +20,10 @@ struct CreateSiteRequest {
     selector: Option<String>,
     auto_crawl: bool,
     crawl_frequency_hours: Option<i32>,
+    /// Queue a TTS job whenever an auto-crawl finds changed content
+    #[serde(default)]
+    auto_tts: bool,
+    tts_voice: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -61,15 +65,17 @@ async fn create_site(
 ) -> Result<Json<Site>> {
     let row = sqlx::query!(
         r#"
-        INSERT INTO sites (account_id, url, selector, auto_crawl, crawl_frequency_hours)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO sites (account_id, url, selector, auto_crawl, crawl_frequency_hours, auto_tts, tts_voice)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
         RETURNING id, url, selector, auto_crawl, last_crawled_at
         "#,
         user.account_id,
         req.url,
         req.selector,
         req.auto_crawl,
-        req.crawl_frequency_hours.unwrap_or(24)
+        req.crawl_frequency_hours.unwrap_or(24).max(1),
+        req.auto_tts,
+        req.tts_voice.as_deref().unwrap_or("serena")
     )
     .fetch_one(&state.db)
     .await
@@ -60,7 +60,7 @@ async fn handle_job_socket(socket: WebSocket, job_id: String, state: Arc<AppStat
 
         let is_terminal = matches!(
             &status,
-            JobStatus::Complete { .. } | JobStatus::Failed { .. }
+            JobStatus::Complete { .. } | JobStatus::Failed { .. } | JobStatus::Cancelled
         );
 
         let json = match serde_json::to_string(&status) {
@@ -127,6 +127,7 @@ async fn get_job_status(state: &AppState, job_id: &str) -> JobStatus {
         "failed" => JobStatus::Failed {
             reason: job.error_message.unwrap_or_else(|| "Processing failed".into()),
         },
+        "cancelled" => JobStatus::Cancelled,
         "processing" => {
             let elapsed_seconds = job.started_at.map(|started| {
                 let now = chrono::Utc::now();
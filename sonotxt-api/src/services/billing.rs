@@ -2,22 +2,37 @@
 //!
 //! TXT is the only balance unit. 1 TXT = 10^10 raw units, priced at $0.01.
 //! Users top up via Stripe (fiat) or on-chain purchase.
-//! TTS usage deducts TXT from custodial balance or payment channel.
+//! TTS usage deducts TXT from custodial balance or payment channel: a hold
+//! when the job is submitted, settled on the audio it produced (see
+//! `pricing`). Every balance change is posted to the double-entry ledger
+//! (see `ledger`); a job that fails or is cancelled gets its hold back.
 
-use sqlx::PgPool;
+use alloy::primitives::{Address, U256};
+use sqlx::{PgConnection, PgPool};
+use tracing::{info, warn};
 use uuid::Uuid;
 
 use crate::error::{ApiError, Result};
+use crate::services::engines;
+use crate::services::ledger::{self, Account, Kind, Posting};
+use crate::services::pricing::RateCard;
 use crate::services::sono::{PriceInfo, SonoService};
 
 /// 1 TXT = 10^10 raw units (10 decimals)
 pub const TXT_DECIMALS: u64 = 10_000_000_000;
 
-/// Convert character count to TXT cost (raw units)
-pub fn txt_cost_for_chars(char_count: usize, cost_per_char_usd: f64, price: &PriceInfo) -> i64 {
-    let usd_cost = char_count as f64 * cost_per_char_usd;
-    // usd_cost / txt_usd_base * 10^10
-    (usd_cost / price.txt_usd_base * TXT_DECIMALS as f64) as i64
+/// Convert a USD amount to TXT (raw units) at the base rate
+pub fn usd_to_txt(usd: f64, price: &PriceInfo) -> i64 {
+    // usd / txt_usd_base * 10^10
+    (usd / price.txt_usd_base * TXT_DECIMALS as f64) as i64
+}
+
+/// Current TXT price, or the default without a price oracle
+pub async fn current_price(sono: Option<&SonoService>) -> PriceInfo {
+    match sono {
+        Some(sono) => sono.price.read().await.clone(),
+        None => PriceInfo::default(),
+    }
 }
 
 /// Format raw TXT units to human-readable string
@@ -31,51 +46,79 @@ pub fn format_txt(raw: i64) -> String {
     }
 }
 
-/// Result of a charge operation
+/// What a hold took
 #[derive(Debug)]
-pub struct ChargeResult {
-    /// Amount charged from custodial DB balance
+pub struct Hold {
+    /// Amount held from custodial DB balance
     pub from_custodial: i64,
-    /// Amount charged from payment channel
+    /// Amount held from payment channel
     pub from_channel: i64,
+    /// Channel the channel part was taken from
+    pub channel_address: Option<Address>,
+}
+
+impl Hold {
+    /// Status to create the job with. A job whose channel part is still to
+    /// be charged isn't claimable until `charge_hold` has taken it, so it
+    /// can't finish (and be settled or refunded) before the channel pays.
+    pub fn job_status(&self) -> &'static str {
+        if self.from_channel > 0 { "charging" } else { "queued" }
+    }
+}
+
+fn hold_key(job_id: &str) -> String {
+    format!("job:{}:hold", job_id)
+}
+
+fn settle_key(job_id: &str) -> String {
+    format!("job:{}:settle", job_id)
+}
+
+fn refund_key(job_id: &str) -> String {
+    format!("job:{}:refund", job_id)
+}
+
+/// Jobs submitted before holds were charged their full price up front
+fn charge_key(job_id: &str) -> String {
+    format!("job:{}:charge", job_id)
 }
 
-/// Check balance and charge TXT for a TTS request.
+/// Check balance and hold TXT for a TTS job until it is settled.
 /// Tries custodial balance first, then payment channel.
-pub async fn check_and_charge(
-    db: &PgPool,
+///
+/// The hold is posted to the ledger on `conn`, which should be the
+/// transaction that creates the job: if it is dropped, the hold is undone
+/// with it. The channel part is only checked here; once that transaction
+/// has committed, `charge_hold` takes it from the channel.
+pub async fn place_hold(
+    conn: &mut PgConnection,
     sono: Option<&SonoService>,
     user_id: Uuid,
     wallet_address: Option<&str>,
-    txt_cost: i64,
-) -> Result<ChargeResult> {
-    if txt_cost <= 0 {
-        return Ok(ChargeResult { from_custodial: 0, from_channel: 0 });
+    txt_amount: i64,
+    job_id: &str,
+) -> Result<Hold> {
+    if txt_amount <= 0 {
+        return Ok(Hold { from_custodial: 0, from_channel: 0, channel_address: None });
     }
 
-    // Try custodial balance first
+    // Locked until the caller's transaction ends
     let custodial_balance: i64 = sqlx::query_scalar(
-        "SELECT txt_balance FROM users WHERE id = $1",
+        "SELECT txt_balance FROM users WHERE id = $1 FOR UPDATE",
     )
     .bind(user_id)
-    .fetch_optional(db)
+    .fetch_optional(&mut *conn)
     .await?
     .unwrap_or(0);
 
-    if custodial_balance >= txt_cost {
-        // Full charge from custodial
-        let rows = sqlx::query(
-            "UPDATE users SET txt_balance = txt_balance - $1 WHERE id = $2 AND txt_balance >= $1",
-        )
-        .bind(txt_cost)
-        .bind(user_id)
-        .execute(db)
-        .await?
-        .rows_affected();
-
-        if rows > 0 {
-            return Ok(ChargeResult { from_custodial: txt_cost, from_channel: 0 });
-        }
+    if custodial_balance >= txt_amount {
+        // Full hold from custodial
+        let posting = Posting::new(hold_key(job_id), Kind::Hold)
+            .user(user_id)
+            .job(job_id)
+            .transfer(Account::User(user_id), Account::Holds, txt_amount);
+        ledger::post(conn, &posting).await?;
+        return Ok(Hold { from_custodial: txt_amount, from_channel: 0, channel_address: None });
     }
 
     // Try payment channel (if wallet connected and sono configured)
@@ -83,46 +126,467 @@ pub async fn check_and_charge(
         let evm_addr = crate::routes::sono::ss58_to_h160(wallet_addr)
             .map_err(|_| ApiError::InsufficientBalance)?;
 
-        let channel_remaining = sono.remaining(&evm_addr).await;
-        let txt_cost_u256 = alloy::primitives::U256::from(txt_cost as u128);
+        // Custodial covers what it can, the channel the rest
+        let from_custodial = custodial_balance.max(0);
+        let from_channel = txt_amount - from_custodial;
+        let from_channel_u256 = U256::from(from_channel as u128);
 
-        // If custodial covers part, deduct what we can from custodial, rest from channel
-        if custodial_balance > 0 {
-            let from_custodial = custodial_balance;
-            let from_channel = txt_cost - custodial_balance;
-            let from_channel_u256 = alloy::primitives::U256::from(from_channel as u128);
+        if sono.remaining(&evm_addr).await >= from_channel_u256 {
+            let posting = Posting::new(hold_key(job_id), Kind::Hold)
+                .user(user_id)
+                .job(job_id)
+                .channel(evm_addr)
+                .entry(Account::User(user_id), -from_custodial)
+                .entry(Account::Channels, -from_channel)
+                .entry(Account::Holds, txt_amount);
+            ledger::post(conn, &posting).await?;
 
-            if channel_remaining >= from_channel_u256 {
-                // Deduct custodial part
-                sqlx::query("UPDATE users SET txt_balance = 0 WHERE id = $1")
-                    .bind(user_id)
-                    .execute(db)
-                    .await?;
+            return Ok(Hold { from_custodial, from_channel, channel_address: Some(evm_addr) });
+        }
+    }
+
+    Err(ApiError::InsufficientBalance)
+}
 
-                // Deduct channel part
-                sono.charge(&evm_addr, from_channel_u256)
-                    .await
-                    .map_err(|_| ApiError::InsufficientBalance)?;
+/// Take the channel part of a committed hold from the channel and queue the
+/// job. Keyed by the hold, so charging it again (see `charge_pending`) is a
+/// no-op. If the channel can't pay, the job fails and its hold is voided.
+pub async fn charge_hold(db: &PgPool, sono: Option<&SonoService>, job_id: &str, hold: &Hold) -> Result<()> {
+    let Some(address) = hold.channel_address.filter(|_| hold.from_channel > 0) else {
+        return Ok(());
+    };
+    let charged = match sono {
+        Some(sono) => sono
+            .charge(&address, U256::from(hold.from_channel as u128), &hold_key(job_id))
+            .await
+            .map_err(|e| e.to_string()),
+        None => Err("no payment channel service".to_string()),
+    };
+    if let Err(e) = charged {
+        warn!("job {}: charging channel failed ({}), voiding hold", job_id, e);
+        void_hold(db, job_id).await?;
+        return Err(ApiError::InsufficientBalance);
+    }
+    sqlx::query("UPDATE jobs SET status = 'queued' WHERE id = $1 AND status = 'charging'")
+        .bind(job_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Fail a job whose channel was never charged and give its hold back as
+/// it was recorded, without touching the channel.
+async fn void_hold(db: &PgPool, job_id: &str) -> Result<()> {
+    let mut tx = db.begin().await?;
+    sqlx::query(
+        "UPDATE jobs SET status = 'failed', error_message = 'Payment channel charge failed', completed_at = NOW() WHERE id = $1 AND status IN ('charging', 'queued', 'processing')",
+    )
+    .bind(job_id)
+    .execute(&mut *tx)
+    .await?;
+
+    // Already resolved, or charged up front: the job still fails
+    let taken = match take_unresolved(&mut tx, job_id, &["failed", "cancelled"]).await? {
+        Some(taken) if taken.kind == Kind::Hold.as_str() => taken,
+        _ => {
+            tx.commit().await?;
+            return Ok(());
+        }
+    };
+    let mut posting = Posting::new(refund_key(job_id), Kind::Refund)
+        .user(taken.user_id)
+        .job(job_id)
+        .memo("channel charge failed")
+        .entry(Account::User(taken.user_id), taken.custodial)
+        .entry(Account::Channels, taken.channel)
+        .entry(Account::Holds, -(taken.custodial + taken.channel));
+    if let Some(address) = taken.channel_address {
+        posting = posting.channel(address);
+    }
+    ledger::post(&mut tx, &posting).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// A job's hold (or up-front charge), locked for the rest of the transaction
+struct Taken {
+    id: Uuid,
+    /// 'hold' or 'charge'
+    kind: String,
+    user_id: Uuid,
+    custodial: i64,
+    channel: i64,
+    channel_address: Option<String>,
+}
+
+/// Lock what `job_id` was charged, if the job is in one of `statuses` and
+/// it hasn't been settled or refunded yet. Locking serializes concurrent
+/// settlements and refunds of the same job.
+async fn take_unresolved(conn: &mut PgConnection, job_id: &str, statuses: &[&str]) -> Result<Option<Taken>> {
+    let taken: Option<(Uuid, String, Option<Uuid>, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT t.id, t.kind, t.user_id, t.channel_address
+        FROM ledger_transactions t
+        JOIN jobs j ON j.id = t.job_id
+        WHERE t.idempotency_key IN ($1, $2) AND j.status = ANY($3)
+        FOR UPDATE OF t
+        "#,
+    )
+    .bind(hold_key(job_id))
+    .bind(charge_key(job_id))
+    .bind(statuses)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some((id, kind, Some(user_id), channel_address)) = taken else {
+        return Ok(None);
+    };
+    if ledger::posted(conn, &settle_key(job_id)).await? || ledger::posted(conn, &refund_key(job_id)).await? {
+        return Ok(None);
+    }
+
+    // What it took from the user and from the channel
+    let (custodial, channel): (i64, i64) = sqlx::query_as(
+        r#"
+        SELECT COALESCE(SUM(-e.amount) FILTER (WHERE a.user_id IS NOT NULL), 0)::BIGINT,
+               COALESCE(SUM(-e.amount) FILTER (WHERE a.system = 'channels'), 0)::BIGINT
+        FROM ledger_entries e
+        JOIN ledger_accounts a ON a.id = e.account_id
+        WHERE e.transaction_id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(Some(Taken { id, kind, user_id, custodial, channel, channel_address }))
+}
 
-                return Ok(ChargeResult { from_custodial, from_channel });
+/// Whether the channel part of `job_id`'s hold was actually taken from
+/// the channel. Until it is, there is nothing to give back to it.
+async fn hold_charged(conn: &mut PgConnection, job_id: &str) -> Result<bool> {
+    let charged = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM sono_channel_ops WHERE idempotency_key = $1)")
+        .bind(hold_key(job_id))
+        .fetch_one(conn)
+        .await?;
+    Ok(charged)
+}
+
+/// Whether `address` still has an open channel to give TXT back to.
+/// Without a channel service there is none.
+async fn channel_open(sono: Option<&SonoService>, address: Option<&str>) -> bool {
+    let address = address.and_then(|a| a.parse::<Address>().ok());
+    match (sono, address) {
+        (Some(sono), Some(address)) => sono.get_channel(&address).await.is_some(),
+        _ => false,
+    }
+}
+
+/// Move `amount` back into the channel for the ledger transaction `key`,
+/// which has already been posted (and committed) as returning it there.
+/// The channel records `key` with the refund, so doing this again is a
+/// no-op. If the channel has closed in the meantime, the amount is
+/// credited to the custodial balance instead; any other failure is left
+/// for `return_pending` to retry.
+async fn return_to_channel(
+    db: &PgPool,
+    sono: Option<&SonoService>,
+    user_id: Uuid,
+    address: &str,
+    amount: i64,
+    key: &str,
+) -> Result<()> {
+    if let (Some(sono), Ok(channel)) = (sono, address.parse::<Address>()) {
+        match sono.refund(&channel, U256::from(amount as u128), key).await {
+            Ok(_) => return Ok(()),
+            Err(e) if sono.get_channel(&channel).await.is_some() => {
+                return Err(ApiError::Internal(format!("returning TXT to channel {} for {}: {}", address, key, e)));
             }
+            Err(_) => {}
         }
+    }
 
-        // Full charge from channel
-        if channel_remaining >= txt_cost_u256 {
-            sono.charge(&evm_addr, txt_cost_u256)
-                .await
-                .map_err(|_| ApiError::InsufficientBalance)?;
+    warn!("channel {} closed before {} was returned, crediting custodial balance", address, key);
+    let posting = Posting::new(format!("{}:custodial", key), Kind::Refund)
+        .user(user_id)
+        .channel(address)
+        .memo("payment channel closed")
+        .transfer(Account::Channels, Account::User(user_id), amount);
+    let mut tx = db.begin().await?;
+    ledger::post(&mut tx, &posting).await?;
+    tx.commit().await?;
+    Ok(())
+}
 
-            return Ok(ChargeResult { from_custodial: 0, from_channel: txt_cost });
+/// Where a refund went
+#[derive(Debug)]
+pub struct Refund {
+    pub to_custodial: i64,
+    pub to_channel: i64,
+}
+
+/// Refund a failed or cancelled job what it was holding: the custodial
+/// part to the custodial balance, the channel part to the channel (or to
+/// the custodial balance if the channel is closed). A channel part that
+/// was never charged is only given back in the ledger. Returns None if
+/// there is nothing to refund: unpaid, not failed/cancelled, or already
+/// refunded.
+///
+/// The refund is posted and committed first; the channel is refunded after,
+/// keyed by the posting, so no DB lock is held while the channel is written
+/// and a channel refund that fails is retried by `return_pending`.
+pub async fn refund_job(db: &PgPool, sono: Option<&SonoService>, job_id: &str) -> Result<Option<Refund>> {
+    let mut tx = db.begin().await?;
+    let Some(taken) = take_unresolved(&mut tx, job_id, &["failed", "cancelled"]).await? else {
+        return Ok(None);
+    };
+
+    let charged = taken.channel > 0 && hold_charged(&mut tx, job_id).await?;
+    let mut refund = Refund { to_custodial: taken.custodial, to_channel: 0 };
+    if !charged || channel_open(sono, taken.channel_address.as_deref()).await {
+        refund.to_channel = taken.channel;
+    } else {
+        refund.to_custodial += taken.channel;
+    }
+
+    // A hold is still in `Holds`; an up-front charge went to revenue
+    let source = if taken.kind == Kind::Hold.as_str() { Account::Holds } else { Account::Revenue };
+    let mut posting = Posting::new(refund_key(job_id), Kind::Refund)
+        .user(taken.user_id)
+        .job(job_id)
+        .entry(Account::User(taken.user_id), refund.to_custodial)
+        .entry(Account::Channels, refund.to_channel)
+        .entry(source, -(taken.custodial + taken.channel));
+    if let (Some(address), true) = (&taken.channel_address, refund.to_channel > 0) {
+        posting = posting.channel(address);
+    }
+    ledger::post(&mut tx, &posting).await?;
+
+    tx.commit().await?;
+
+    if let (Some(address), true) = (&taken.channel_address, charged && refund.to_channel > 0) {
+        if let Err(e) = return_to_channel(db, sono, taken.user_id, address, refund.to_channel, &refund_key(job_id)).await {
+            warn!("job {}: {:?}, left for retry", job_id, e);
         }
     }
 
-    Err(ApiError::InsufficientBalance)
+    info!(
+        "job {} refunded: {} TXT custodial, {} TXT channel",
+        job_id,
+        format_txt(refund.to_custodial),
+        format_txt(refund.to_channel)
+    );
+    Ok(Some(refund))
+}
+
+/// How a hold was settled
+#[derive(Debug)]
+pub struct Settlement {
+    pub cost: i64,
+    pub released: i64,
+}
+
+/// Settle a completed job's hold on its actual cost (capped at the hold)
+/// and release the rest: to the channel first, since the hold took from
+/// it last, then to the custodial balance. A channel part that was never
+/// charged is only released in the ledger. Returns None if there is no
+/// open hold: unpaid, charged up front, not completed, or already settled.
+pub async fn settle_job(db: &PgPool, sono: Option<&SonoService>, job_id: &str, cost: i64) -> Result<Option<Settlement>> {
+    let mut tx = db.begin().await?;
+    let Some(taken) = take_unresolved(&mut tx, job_id, &["completed"]).await? else {
+        return Ok(None);
+    };
+    if taken.kind != Kind::Hold.as_str() {
+        return Ok(None);
+    }
+
+    let held = taken.custodial + taken.channel;
+    let cost = cost.clamp(0, held);
+    let released = held - cost;
+    let mut to_channel = released.min(taken.channel);
+    let mut to_custodial = released - to_channel;
+    let charged = taken.channel > 0 && hold_charged(&mut tx, job_id).await?;
+    if charged && !channel_open(sono, taken.channel_address.as_deref()).await {
+        to_custodial += to_channel;
+        to_channel = 0;
+    }
+
+    let mut posting = Posting::new(settle_key(job_id), Kind::Settlement)
+        .user(taken.user_id)
+        .job(job_id)
+        .entry(Account::Holds, -held)
+        .entry(Account::Revenue, cost)
+        .entry(Account::User(taken.user_id), to_custodial)
+        .entry(Account::Channels, to_channel);
+    if let (Some(address), true) = (&taken.channel_address, to_channel > 0) {
+        posting = posting.channel(address);
+    }
+    ledger::post(&mut tx, &posting).await?;
+
+    tx.commit().await?;
+
+    if let (Some(address), true) = (&taken.channel_address, charged && to_channel > 0) {
+        if let Err(e) = return_to_channel(db, sono, taken.user_id, address, to_channel, &settle_key(job_id)).await {
+            warn!("job {}: {:?}, left for retry", job_id, e);
+        }
+    }
+
+    info!(
+        "job {} settled: {} TXT of {} held (ledger {})",
+        job_id,
+        format_txt(cost),
+        format_txt(held),
+        taken.id
+    );
+    Ok(Some(Settlement { cost, released }))
+}
+
+/// Charge channel holds whose charge never happened, e.g. because the
+/// process died between creating the job and charging the channel, and
+/// queue jobs whose charge happened but weren't queued after it.
+pub async fn charge_pending(db: &PgPool, sono: Option<&SonoService>) -> Result<usize> {
+    let pending: Vec<(String, String, i64)> = sqlx::query_as(
+        r#"
+        SELECT t.job_id, t.channel_address,
+               COALESCE(SUM(-e.amount) FILTER (WHERE a.system = 'channels'), 0)::BIGINT
+        FROM ledger_transactions t
+        JOIN ledger_entries e ON e.transaction_id = t.id
+        JOIN ledger_accounts a ON a.id = e.account_id
+        WHERE t.kind = 'hold'
+          AND t.channel_address IS NOT NULL
+          AND NOT EXISTS (SELECT 1 FROM sono_channel_ops o WHERE o.idempotency_key = t.idempotency_key)
+          AND NOT EXISTS (
+              SELECT 1 FROM ledger_transactions r
+              WHERE r.idempotency_key IN ('job:' || t.job_id || ':refund', 'job:' || t.job_id || ':settle')
+          )
+        GROUP BY t.id
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut charged = 0;
+    for (job_id, address, from_channel) in pending {
+        let hold = Hold {
+            from_custodial: 0,
+            from_channel,
+            channel_address: address.parse().ok(),
+        };
+        if charge_hold(db, sono, &job_id, &hold).await.is_ok() {
+            charged += 1;
+        }
+    }
+    sqlx::query(
+        r#"
+        UPDATE jobs j SET status = 'queued'
+        WHERE j.status = 'charging'
+          AND EXISTS (SELECT 1 FROM sono_channel_ops o WHERE o.idempotency_key = 'job:' || j.id || ':hold')
+        "#,
+    )
+    .execute(db)
+    .await?;
+    Ok(charged)
+}
+
+/// Refund failed or cancelled jobs whose refund never happened, e.g.
+/// because the process died right after marking them.
+pub async fn refund_pending(db: &PgPool, sono: Option<&SonoService>) -> Result<usize> {
+    let pending: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT t.job_id
+        FROM ledger_transactions t
+        JOIN jobs j ON j.id = t.job_id
+        WHERE t.kind IN ('hold', 'charge')
+          AND j.status IN ('failed', 'cancelled')
+          AND NOT EXISTS (
+              SELECT 1 FROM ledger_transactions r
+              WHERE r.idempotency_key = 'job:' || t.job_id || ':refund'
+          )
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut refunded = 0;
+    for job_id in &pending {
+        if refund_job(db, sono, job_id).await?.is_some() {
+            refunded += 1;
+        }
+    }
+    Ok(refunded)
+}
+
+/// Return TXT to channels for refunds and settlements that were posted
+/// but whose channel refund never happened, e.g. because the channel
+/// service failed or the process died right after the posting committed.
+pub async fn return_pending(db: &PgPool, sono: Option<&SonoService>) -> Result<usize> {
+    let pending: Vec<(String, Uuid, String, i64)> = sqlx::query_as(
+        r#"
+        SELECT t.idempotency_key, t.user_id, t.channel_address, e.amount
+        FROM ledger_transactions t
+        JOIN ledger_entries e ON e.transaction_id = t.id
+        JOIN ledger_accounts a ON a.id = e.account_id
+        WHERE t.kind IN ('refund', 'settlement')
+          AND t.job_id IS NOT NULL
+          AND t.channel_address IS NOT NULL
+          AND t.user_id IS NOT NULL
+          AND a.system = 'channels'
+          AND e.amount > 0
+          -- only what the channel was actually charged; a voided hold never was
+          AND EXISTS (SELECT 1 FROM sono_channel_ops h WHERE h.idempotency_key = 'job:' || t.job_id || ':hold')
+          AND NOT EXISTS (SELECT 1 FROM sono_channel_ops o WHERE o.idempotency_key = t.idempotency_key)
+          AND NOT EXISTS (
+              SELECT 1 FROM ledger_transactions c
+              WHERE c.idempotency_key = t.idempotency_key || ':custodial'
+          )
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut returned = 0;
+    for (key, user_id, address, amount) in &pending {
+        match return_to_channel(db, sono, *user_id, address, *amount, key).await {
+            Ok(()) => returned += 1,
+            Err(e) => warn!("{:?}", e),
+        }
+    }
+    Ok(returned)
+}
+
+/// Settle completed jobs whose hold is still open, e.g. because the
+/// process died right after completing them.
+pub async fn settle_pending(db: &PgPool, sono: Option<&SonoService>, rates: &RateCard) -> Result<usize> {
+    let pending: Vec<(String, Option<f64>, Option<String>, String)> = sqlx::query_as(
+        r#"
+        SELECT t.job_id, j.duration_seconds, j.engine, j.voice
+        FROM ledger_transactions t
+        JOIN jobs j ON j.id = t.job_id
+        WHERE t.kind = 'hold'
+          AND j.status = 'completed'
+          AND NOT EXISTS (
+              SELECT 1 FROM ledger_transactions s
+              WHERE s.idempotency_key = 'job:' || t.job_id || ':settle'
+          )
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let price = current_price(sono).await;
+    let mut settled = 0;
+    for (job_id, duration, engine, voice) in &pending {
+        let engine = engines::for_job(engine.as_deref()).name();
+        let cost = usd_to_txt(rates.cost(duration.unwrap_or(0.0), engine, voice), &price);
+        if settle_job(db, sono, job_id, cost).await?.is_some() {
+            settled += 1;
+        }
+    }
+    Ok(settled)
 }
 
 /// Credit TXT to a user's custodial balance.
 /// Used by Stripe webhook, on-chain deposits, admin grants.
+/// Crediting the same `source` and `ref_id` again is a no-op.
 pub async fn credit_txt(
     db: &PgPool,
     user_id: Uuid,
@@ -130,27 +594,36 @@ pub async fn credit_txt(
     source: &str,
     ref_id: &str,
 ) -> Result<i64> {
+    if txt_amount <= 0 {
+        return Err(ApiError::InvalidRequest("credit must be positive".into()));
+    }
+
     let mut tx = db.begin().await?;
 
-    let new_balance: i64 = sqlx::query_scalar(
-        "UPDATE users SET txt_balance = txt_balance + $1 WHERE id = $2 RETURNING txt_balance",
-    )
-    .bind(txt_amount)
-    .bind(user_id)
-    .fetch_one(&mut *tx)
-    .await
-    .map_err(|_| ApiError::InternalError)?;
+    let description = format!("{} TXT from {} ({})", format_txt(txt_amount), source, ref_id);
+    let posting = Posting::new(format!("credit:{}:{}", source, ref_id), Kind::Credit)
+        .user(user_id)
+        .memo(description.clone())
+        .transfer(Account::External, Account::User(user_id), txt_amount);
 
-    // Log transaction
-    sqlx::query(
-        "INSERT INTO transactions (account_id, amount, type, description) VALUES ($1, $2, 'credit', $3)",
-    )
-    .bind(user_id)
-    .bind(txt_amount as f64 / TXT_DECIMALS as f64)
-    .bind(format!("{} TXT from {} ({})", format_txt(txt_amount), source, ref_id))
-    .execute(&mut *tx)
-    .await
-    .map_err(|_| ApiError::InternalError)?;
+    if ledger::post(&mut tx, &posting).await?.is_some() {
+        // Log transaction
+        sqlx::query(
+            "INSERT INTO transactions (account_id, amount, type, description) VALUES ($1, $2, 'credit', $3)",
+        )
+        .bind(user_id)
+        .bind(txt_amount as f64 / TXT_DECIMALS as f64)
+        .bind(description)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| ApiError::InternalError)?;
+    }
+
+    let new_balance: i64 = sqlx::query_scalar("SELECT txt_balance FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| ApiError::InternalError)?;
 
     tx.commit().await?;
 
@@ -191,3 +664,360 @@ pub async fn total_balance(
 
     Ok((custodial, channel))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::sono::ChannelState;
+
+    /// Alice's well-known dev account; its channel address is the first 20
+    /// bytes of the public key
+    const WALLET: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+
+    fn channel_address() -> Address {
+        crate::routes::sono::ss58_to_h160(WALLET).unwrap()
+    }
+
+    fn open_channel(deposit: u64) -> ChannelState {
+        ChannelState { user: channel_address(), deposit: U256::from(deposit), spent: U256::ZERO, nonce: 0 }
+    }
+
+    async fn user(db: &PgPool, balance: i64) -> Uuid {
+        let id: Uuid = sqlx::query_scalar("INSERT INTO users (email) VALUES ($1) RETURNING id")
+            .bind(format!("{}@example.com", Uuid::new_v4()))
+            .fetch_one(db)
+            .await
+            .unwrap();
+        // Credits are logged against the account sharing the user's id
+        sqlx::query("INSERT INTO accounts (id) VALUES ($1)")
+            .bind(id)
+            .execute(db)
+            .await
+            .unwrap();
+        if balance > 0 {
+            credit_txt(db, id, balance, "test", &id.to_string()).await.unwrap();
+        }
+        id
+    }
+
+    async fn balance(db: &PgPool, user_id: Uuid) -> i64 {
+        sqlx::query_scalar("SELECT txt_balance FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    async fn spent(sono: &SonoService) -> U256 {
+        sono.get_channel(&channel_address()).await.unwrap().spent
+    }
+
+    /// Queue a job holding `amount`, the way job submission does
+    async fn submit(db: &PgPool, sono: Option<&SonoService>, user_id: Uuid, job_id: &str, amount: i64) -> Result<Hold> {
+        let mut tx = db.begin().await.unwrap();
+        let hold = place_hold(&mut tx, sono, user_id, Some(WALLET), amount, job_id).await?;
+        sqlx::query("INSERT INTO jobs (id, api_key, text_content, status) VALUES ($1, 'key', 'hello', $2)")
+            .bind(job_id)
+            .bind(hold.job_status())
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+        charge_hold(db, sono, job_id, &hold).await?;
+        Ok(hold)
+    }
+
+    async fn job_status(db: &PgPool, job_id: &str) -> String {
+        sqlx::query_scalar("SELECT status FROM jobs WHERE id = $1")
+            .bind(job_id)
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    async fn set_status(db: &PgPool, job_id: &str, status: &str) {
+        sqlx::query("UPDATE jobs SET status = $1 WHERE id = $2")
+            .bind(status)
+            .bind(job_id)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    async fn postings(db: &PgPool, key: &str) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM ledger_transactions WHERE idempotency_key = $1")
+            .bind(key)
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    async fn assert_reconciled(db: &PgPool) {
+        assert!(ledger::reconcile(db).await.unwrap().is_clean());
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_refund_twice_posts_once(db: PgPool) {
+        let user_id = user(&db, 1_000).await;
+        submit(&db, None, user_id, "job-1", 400).await.unwrap();
+        assert_eq!(balance(&db, user_id).await, 600);
+
+        // Not failed yet: nothing to refund
+        assert!(refund_job(&db, None, "job-1").await.unwrap().is_none());
+
+        set_status(&db, "job-1", "failed").await;
+        let refund = refund_job(&db, None, "job-1").await.unwrap().unwrap();
+        assert_eq!((refund.to_custodial, refund.to_channel), (400, 0));
+        assert!(refund_job(&db, None, "job-1").await.unwrap().is_none());
+        assert_eq!(refund_pending(&db, None).await.unwrap(), 0);
+
+        assert_eq!(postings(&db, "job:job-1:refund").await, 1);
+        assert_eq!(balance(&db, user_id).await, 1_000);
+        assert_reconciled(&db).await;
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_retried_refund_returns_channel_once(db: PgPool) {
+        let sono = SonoService::for_test(db.clone(), &[open_channel(1_000)]).await;
+        let user_id = user(&db, 0).await;
+        submit(&db, Some(&sono), user_id, "job-1", 300).await.unwrap();
+        assert_eq!(spent(&sono).await, U256::from(300u64));
+        set_status(&db, "job-1", "failed").await;
+
+        // A process died after refunding the channel but before its ledger
+        // posting committed
+        sono.refund(&channel_address(), U256::from(300u64), "job:job-1:refund").await.unwrap();
+        assert_eq!(spent(&sono).await, U256::ZERO);
+
+        // The billing sweep posts the refund without refunding the channel again
+        assert_eq!(refund_pending(&db, Some(&sono)).await.unwrap(), 1);
+        assert_eq!(spent(&sono).await, U256::ZERO);
+        assert_eq!(return_pending(&db, Some(&sono)).await.unwrap(), 0);
+        assert_eq!(postings(&db, "job:job-1:refund").await, 1);
+        assert_eq!(balance(&db, user_id).await, 0);
+        assert_reconciled(&db).await;
+    }
+
+    async fn channel_ops(db: &PgPool, key: &str) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM sono_channel_ops WHERE idempotency_key = $1")
+            .bind(key)
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_failed_channel_return_is_retried_not_credited(db: PgPool) {
+        let sono = SonoService::for_test(db.clone(), &[open_channel(1_000)]).await;
+        let user_id = user(&db, 0).await;
+        submit(&db, Some(&sono), user_id, "job-1", 300).await.unwrap();
+        set_status(&db, "job-1", "failed").await;
+
+        // Refunded elsewhere in the meantime, so the channel can't take this one
+        sono.refund(&channel_address(), U256::from(300u64), "elsewhere").await.unwrap();
+
+        let refund = refund_job(&db, Some(&sono), "job-1").await.unwrap().unwrap();
+        assert_eq!((refund.to_custodial, refund.to_channel), (0, 300));
+        // Still owed to the channel, not turned into a custodial credit
+        assert_eq!(channel_ops(&db, "job:job-1:refund").await, 0);
+        assert_eq!(balance(&db, user_id).await, 0);
+        assert_eq!(postings(&db, "job:job-1:refund:custodial").await, 0);
+
+        // Once the channel can take it, the sweep returns it
+        sono.charge(&channel_address(), U256::from(300u64), "elsewhere:again").await.unwrap();
+        assert_eq!(return_pending(&db, Some(&sono)).await.unwrap(), 1);
+        assert_eq!(spent(&sono).await, U256::ZERO);
+        assert_eq!(return_pending(&db, Some(&sono)).await.unwrap(), 0);
+        assert_reconciled(&db).await;
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_uncharged_hold_is_not_returned_to_channel(db: PgPool) {
+        let sono = SonoService::for_test(db.clone(), &[open_channel(1_000)]).await;
+        let user_id = user(&db, 0).await;
+
+        // Holds placed, but the process died before charging the channel
+        let mut holds = Vec::new();
+        for (job_id, status) in [("job-1", "failed"), ("job-2", "completed")] {
+            let mut tx = db.begin().await.unwrap();
+            holds.push(place_hold(&mut tx, Some(&sono), user_id, Some(WALLET), 300, job_id).await.unwrap());
+            sqlx::query("INSERT INTO jobs (id, api_key, text_content, status) VALUES ($1, 'key', 'hello', $2)")
+                .bind(job_id)
+                .bind(status)
+                .execute(&mut *tx)
+                .await
+                .unwrap();
+            tx.commit().await.unwrap();
+        }
+
+        let refund = refund_job(&db, Some(&sono), "job-1").await.unwrap().unwrap();
+        assert_eq!((refund.to_custodial, refund.to_channel), (0, 300));
+        let settlement = settle_job(&db, Some(&sono), "job-2", 100).await.unwrap().unwrap();
+        assert_eq!(settlement.released, 200);
+
+        // Nothing is written to a channel that never paid
+        assert_eq!(channel_ops(&db, "job:job-1:refund").await, 0);
+        assert_eq!(channel_ops(&db, "job:job-2:settle").await, 0);
+        assert_eq!(spent(&sono).await, U256::ZERO);
+        assert_eq!(return_pending(&db, Some(&sono)).await.unwrap(), 0);
+        assert_eq!(balance(&db, user_id).await, 0);
+
+        // Should the charge land after all, the sweep gives it back
+        sono.charge(&channel_address(), U256::from(holds[0].from_channel as u128), &hold_key("job-1")).await.unwrap();
+        assert_eq!(return_pending(&db, Some(&sono)).await.unwrap(), 1);
+        assert_eq!(spent(&sono).await, U256::ZERO);
+        assert_reconciled(&db).await;
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_closed_channel_refunds_to_custodial(db: PgPool) {
+        let sono = SonoService::for_test(db.clone(), &[open_channel(1_000)]).await;
+        let user_id = user(&db, 100).await;
+        submit(&db, Some(&sono), user_id, "job-1", 300).await.unwrap();
+        assert_eq!(balance(&db, user_id).await, 0);
+        set_status(&db, "job-1", "failed").await;
+
+        // The channel has since been settled and closed
+        let restarted = SonoService::for_test(db.clone(), &[]).await;
+        let refund = refund_job(&db, Some(&restarted), "job-1").await.unwrap().unwrap();
+        assert_eq!((refund.to_custodial, refund.to_channel), (300, 0));
+        assert_eq!(balance(&db, user_id).await, 300);
+        assert_reconciled(&db).await;
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_settle_after_refund_is_noop(db: PgPool) {
+        let user_id = user(&db, 1_000).await;
+        submit(&db, None, user_id, "job-1", 400).await.unwrap();
+        set_status(&db, "job-1", "failed").await;
+        refund_job(&db, None, "job-1").await.unwrap().unwrap();
+
+        // A late completion can't bill the refunded hold
+        set_status(&db, "job-1", "completed").await;
+        assert!(settle_job(&db, None, "job-1", 100).await.unwrap().is_none());
+        assert_eq!(postings(&db, "job:job-1:settle").await, 0);
+        assert_eq!(balance(&db, user_id).await, 1_000);
+        assert_reconciled(&db).await;
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_hold_splits_custodial_and_channel(db: PgPool) {
+        let sono = SonoService::for_test(db.clone(), &[open_channel(1_000)]).await;
+        let user_id = user(&db, 100).await;
+
+        let hold = submit(&db, Some(&sono), user_id, "job-1", 300).await.unwrap();
+        assert_eq!((hold.from_custodial, hold.from_channel), (100, 200));
+        assert_eq!(hold.channel_address, Some(channel_address()));
+        assert_eq!(balance(&db, user_id).await, 0);
+        assert_eq!(spent(&sono).await, U256::from(200u64));
+
+        // Custodial plus what's left in the channel can't cover this one
+        let err = submit(&db, Some(&sono), user_id, "job-2", 900).await.unwrap_err();
+        assert!(matches!(err, ApiError::InsufficientBalance));
+        assert_reconciled(&db).await;
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_settle_releases_to_channel_first(db: PgPool) {
+        let sono = SonoService::for_test(db.clone(), &[open_channel(1_000)]).await;
+        let user_id = user(&db, 100).await;
+        submit(&db, Some(&sono), user_id, "job-1", 300).await.unwrap();
+        set_status(&db, "job-1", "completed").await;
+
+        let settlement = settle_job(&db, Some(&sono), "job-1", 50).await.unwrap().unwrap();
+        assert_eq!((settlement.cost, settlement.released), (50, 250));
+        // The channel gets its 200 back, the custodial balance the other 50
+        assert_eq!(spent(&sono).await, U256::ZERO);
+        assert_eq!(balance(&db, user_id).await, 50);
+        assert!(settle_job(&db, Some(&sono), "job-1", 50).await.unwrap().is_none());
+        assert_reconciled(&db).await;
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_settle_clamps_cost_to_hold(db: PgPool) {
+        let user_id = user(&db, 1_000).await;
+        submit(&db, None, user_id, "job-1", 300).await.unwrap();
+        set_status(&db, "job-1", "completed").await;
+
+        let settlement = settle_job(&db, None, "job-1", 500).await.unwrap().unwrap();
+        assert_eq!((settlement.cost, settlement.released), (300, 0));
+        assert_eq!(balance(&db, user_id).await, 700);
+        assert_reconciled(&db).await;
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_failed_charge_voids_hold_and_fails_job(db: PgPool) {
+        let sono = SonoService::for_test(db.clone(), &[open_channel(1_000)]).await;
+        let user_id = user(&db, 100).await;
+
+        let mut tx = db.begin().await.unwrap();
+        let hold = place_hold(&mut tx, Some(&sono), user_id, Some(WALLET), 300, "job-1").await.unwrap();
+        sqlx::query("INSERT INTO jobs (id, api_key, text_content, status) VALUES ('job-1', 'key', 'hello', $1)")
+            .bind(hold.job_status())
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+
+        // The channel is spent elsewhere before the hold is charged
+        sono.charge(&channel_address(), U256::from(900u64), "elsewhere").await.unwrap();
+
+        let err = charge_hold(&db, Some(&sono), "job-1", &hold).await.unwrap_err();
+        assert!(matches!(err, ApiError::InsufficientBalance));
+        assert_eq!(job_status(&db, "job-1").await, "failed");
+        assert_eq!(postings(&db, "job:job-1:refund").await, 1);
+        assert_eq!(balance(&db, user_id).await, 100);
+        assert_eq!(spent(&sono).await, U256::from(900u64));
+
+        // Nothing left to refund, charge or return
+        assert!(refund_job(&db, Some(&sono), "job-1").await.unwrap().is_none());
+        assert_eq!(charge_pending(&db, Some(&sono)).await.unwrap(), 0);
+        assert_eq!(return_pending(&db, Some(&sono)).await.unwrap(), 0);
+        assert_eq!(spent(&sono).await, U256::from(900u64));
+        assert_reconciled(&db).await;
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_failed_charge_fails_job_without_open_hold(db: PgPool) {
+        sqlx::query("INSERT INTO jobs (id, api_key, text_content, status) VALUES ('job-1', 'key', 'hello', 'queued')")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        // Nothing in the ledger to void, but the job mustn't run unpaid
+        let hold = Hold { from_custodial: 0, from_channel: 300, channel_address: Some(channel_address()) };
+        let err = charge_hold(&db, None, "job-1", &hold).await.unwrap_err();
+        assert!(matches!(err, ApiError::InsufficientBalance));
+        assert_eq!(job_status(&db, "job-1").await, "failed");
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_channel_hold_is_queued_once_charged(db: PgPool) {
+        let sono = SonoService::for_test(db.clone(), &[open_channel(1_000)]).await;
+        let user_id = user(&db, 100).await;
+
+        // Custodial-only holds are queued straight away
+        submit(&db, Some(&sono), user_id, "job-1", 50).await.unwrap();
+        assert_eq!(job_status(&db, "job-1").await, "queued");
+
+        let mut tx = db.begin().await.unwrap();
+        let hold = place_hold(&mut tx, Some(&sono), user_id, Some(WALLET), 300, "job-2").await.unwrap();
+        assert_eq!(hold.job_status(), "charging");
+        sqlx::query("INSERT INTO jobs (id, api_key, text_content, status) VALUES ('job-2', 'key', 'hello', $1)")
+            .bind(hold.job_status())
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+
+        // The process dies after the channel pays but before queueing the job
+        sono.charge(&channel_address(), U256::from(hold.from_channel as u128), &hold_key("job-2")).await.unwrap();
+        assert_eq!(job_status(&db, "job-2").await, "charging");
+
+        // The sweep queues it without charging the channel again
+        assert_eq!(charge_pending(&db, Some(&sono)).await.unwrap(), 0);
+        assert_eq!(job_status(&db, "job-2").await, "queued");
+        assert_eq!(spent(&sono).await, U256::from(250u64));
+        assert_reconciled(&db).await;
+    }
+}
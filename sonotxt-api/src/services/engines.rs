@@ -0,0 +1,198 @@
+//! TTS engine adapters.
+//!
+//! Every job names an engine (`jobs.engine`). Its adapter vets the job's
+//! voice, language and options when the job is created, so a bad
+//! combination is rejected up front instead of failing in the queue, and
+//! turns the job into a worker request when it runs. New engines are added
+//! to `ENGINES`; the job loop only ever talks to the trait.
+
+use sonotxt_core::{Privacy, SynthesisOptions, TtsRequest, DEFAULT_ENGINE};
+
+/// How a job asks for its text to be spoken.
+#[derive(Debug, Clone)]
+pub struct Synthesis {
+    pub voice: String,
+    pub language: String,
+    pub options: SynthesisOptions,
+}
+
+pub trait EngineAdapter: Send + Sync {
+    /// Name stored in `jobs.engine` and advertised by workers
+    fn name(&self) -> &'static str;
+
+    /// Reject a voice, language or option the engine can't honour.
+    fn validate(&self, job: &Synthesis) -> Result<(), String>;
+
+    /// Runs a 7B-class model, priced at `MODEL_7B_MULTIPLIER` rather than
+    /// `MODEL_1_5B_MULTIPLIER`.
+    fn large_model(&self) -> bool {
+        false
+    }
+
+    /// Worker request speaking `text` for a validated job.
+    fn request(&self, text: String, job: Synthesis) -> TtsRequest {
+        TtsRequest {
+            engine: self.name().to_string(),
+            text,
+            speaker: job.voice,
+            language: job.language,
+            options: job.options,
+            privacy: Privacy::default(),
+            api_key: None,
+        }
+    }
+}
+
+/// Speed factors every engine accepts.
+const SPEED_RANGE: std::ops::RangeInclusive<f32> = 0.5..=2.0;
+
+/// Qwen3-TTS: multilingual, takes a style instruction.
+pub struct Qwen;
+
+const QWEN_LANGUAGES: &[&str] = &[
+    "auto", "zh", "en", "ja", "ko", "de", "fr", "ru", "pt", "es", "it",
+];
+
+impl EngineAdapter for Qwen {
+    fn name(&self) -> &'static str {
+        "qwen"
+    }
+
+    fn validate(&self, job: &Synthesis) -> Result<(), String> {
+        check_language(self, QWEN_LANGUAGES, &job.language)?;
+        check_speed(&job.options)?;
+        if job.options.cfg_scale.is_some() {
+            return Err("qwen does not take cfg_scale".into());
+        }
+        Ok(())
+    }
+}
+
+/// VibeVoice, long-form or streaming: English and Chinese, guidance scale
+/// instead of style instructions.
+pub struct VibeVoice {
+    pub streaming: bool,
+}
+
+const VIBEVOICE_LANGUAGES: &[&str] = &["auto", "en", "zh"];
+
+/// Guidance scales VibeVoice produces usable audio with.
+const CFG_SCALE_RANGE: std::ops::RangeInclusive<f32> = 1.0..=3.0;
+
+impl EngineAdapter for VibeVoice {
+    fn name(&self) -> &'static str {
+        if self.streaming { "vibevoice-streaming" } else { "vibevoice" }
+    }
+
+    fn large_model(&self) -> bool {
+        !self.streaming
+    }
+
+    fn validate(&self, job: &Synthesis) -> Result<(), String> {
+        check_language(self, VIBEVOICE_LANGUAGES, &job.language)?;
+        check_speed(&job.options)?;
+        if job.options.instruct.is_some() {
+            return Err(format!("{} does not take instruct", self.name()));
+        }
+        if let Some(cfg) = job.options.cfg_scale {
+            if !CFG_SCALE_RANGE.contains(&cfg) {
+                return Err(format!(
+                    "cfg_scale must be between {} and {}",
+                    CFG_SCALE_RANGE.start(),
+                    CFG_SCALE_RANGE.end()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Registered engines.
+pub static ENGINES: &[&dyn EngineAdapter] = &[
+    &Qwen,
+    &VibeVoice { streaming: false },
+    &VibeVoice { streaming: true },
+];
+
+/// Adapter for `name`, if it is a known engine.
+pub fn find(name: &str) -> Option<&'static dyn EngineAdapter> {
+    ENGINES.iter().copied().find(|e| e.name() == name)
+}
+
+/// Adapter for a job's engine column; jobs from before the column (or with
+/// an engine since removed) run on the default engine.
+pub fn for_job(engine: Option<&str>) -> &'static dyn EngineAdapter {
+    engine
+        .and_then(find)
+        .or_else(|| find(DEFAULT_ENGINE))
+        .expect("default engine registered")
+}
+
+fn check_language(engine: &dyn EngineAdapter, supported: &[&str], language: &str) -> Result<(), String> {
+    if supported.contains(&language) {
+        Ok(())
+    } else {
+        Err(format!("{} does not support language {}", engine.name(), language))
+    }
+}
+
+fn check_speed(options: &SynthesisOptions) -> Result<(), String> {
+    match options.speed {
+        Some(speed) if !SPEED_RANGE.contains(&speed) => Err(format!(
+            "speed must be between {} and {}",
+            SPEED_RANGE.start(),
+            SPEED_RANGE.end()
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(language: &str, options: SynthesisOptions) -> Synthesis {
+        Synthesis {
+            voice: "serena".into(),
+            language: language.into(),
+            options,
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(find("vibevoice-streaming").unwrap().name(), "vibevoice-streaming");
+        assert!(find("kokoro").is_none());
+        // Old rows default to 'kokoro', which is gone
+        assert_eq!(for_job(Some("kokoro")).name(), DEFAULT_ENGINE);
+        assert_eq!(for_job(None).name(), DEFAULT_ENGINE);
+    }
+
+    #[test]
+    fn test_options_per_engine() {
+        let instruct = SynthesisOptions { instruct: Some("whisper".into()), ..Default::default() };
+        let cfg = SynthesisOptions { cfg_scale: Some(1.5), ..Default::default() };
+
+        assert!(Qwen.validate(&job("ja", instruct.clone())).is_ok());
+        assert!(Qwen.validate(&job("en", cfg.clone())).is_err());
+        assert!(Qwen.validate(&job("xx", Default::default())).is_err());
+
+        let vibe = VibeVoice { streaming: false };
+        assert!(vibe.validate(&job("en", cfg)).is_ok());
+        assert!(vibe.validate(&job("en", instruct)).is_err());
+        assert!(vibe.validate(&job("ja", Default::default())).is_err());
+
+        let fast = SynthesisOptions { speed: Some(4.0), ..Default::default() };
+        assert!(Qwen.validate(&job("auto", fast)).is_err());
+    }
+
+    #[test]
+    fn test_request_carries_job() {
+        let options = SynthesisOptions { speed: Some(1.2), ..Default::default() };
+        let req = find("vibevoice").unwrap().request("hello".into(), job("en", options.clone()));
+        assert_eq!(req.engine, "vibevoice");
+        assert_eq!(req.speaker, "serena");
+        assert_eq!(req.language, "en");
+        assert_eq!(req.options, options);
+    }
+}
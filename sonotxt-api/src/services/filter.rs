@@ -0,0 +1,482 @@
+//! Filters: the other half of Eriksen's "Your Server as a Function".
+//!
+//! A filter sees a request and the service behind it, and decides what to
+//! do with both: bound it in time, retry it, race a copy of it, count it.
+//! `service.and_then(filter)` wraps a service in a filter and is itself a
+//! service, so stacks are built inside-out:
+//!
+//!   leaf.and_then(Timeout).and_then(Inflight).and_then(Metrics)
+//!
+//! is one worker; `LoadBalance` turns a picker of such stacks into a
+//! service, which `Retry` or `Hedge` then wrap. None of the filters care
+//! what the request or reply is.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::services::worker_pool::{InflightGuard, Service, ServiceError, ServiceFuture, Worker};
+
+/// `Filter[Req, Rep] = (Req, Service[Req, Rep]) => Future[Rep]`
+pub trait Filter<Req: Send + 'static, Rep: Send + 'static>: Send + Sync + 'static {
+    fn apply(&self, req: Req, next: Arc<dyn Service<Req, Rep>>) -> ServiceFuture<Rep>;
+}
+
+/// A service wrapped in a filter.
+pub struct Filtered<Req, Rep, F> {
+    filter: F,
+    next: Arc<dyn Service<Req, Rep>>,
+}
+
+impl<Req: Send + 'static, Rep: Send + 'static, F: Filter<Req, Rep>> Service<Req, Rep> for Filtered<Req, Rep, F> {
+    fn call(&self, req: Req) -> ServiceFuture<Rep> {
+        self.filter.apply(req, self.next.clone())
+    }
+}
+
+pub trait ServiceExt<Req: Send + 'static, Rep: Send + 'static>: Service<Req, Rep> + Sized {
+    /// `filter` in front of this service.
+    fn and_then<F: Filter<Req, Rep>>(self, filter: F) -> Filtered<Req, Rep, F> {
+        Filtered { filter, next: Arc::new(self) }
+    }
+
+    fn boxed(self) -> Arc<dyn Service<Req, Rep>> {
+        Arc::new(self)
+    }
+}
+
+impl<Req: Send + 'static, Rep: Send + 'static, S: Service<Req, Rep>> ServiceExt<Req, Rep> for S {}
+
+// ── Load balancing ─────────────────────────────────────────────────
+
+type Picker<Req, Rep> = dyn Fn(&Req) -> Result<Arc<dyn Service<Req, Rep>>, ServiceError> + Send + Sync;
+
+/// Sends each request to the service `pick` chooses for it. Picking per
+/// call means a retry or a hedge lands wherever is least loaded by then.
+pub struct LoadBalance<Req, Rep> {
+    pick: Box<Picker<Req, Rep>>,
+}
+
+impl<Req, Rep> LoadBalance<Req, Rep> {
+    pub fn new(
+        pick: impl Fn(&Req) -> Result<Arc<dyn Service<Req, Rep>>, ServiceError> + Send + Sync + 'static,
+    ) -> Self {
+        Self { pick: Box::new(pick) }
+    }
+}
+
+impl<Req: Send + 'static, Rep: Send + 'static> Service<Req, Rep> for LoadBalance<Req, Rep> {
+    fn call(&self, req: Req) -> ServiceFuture<Rep> {
+        match (self.pick)(&req) {
+            Ok(service) => service.call(req),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+}
+
+// ── Timeout ────────────────────────────────────────────────────────
+
+/// Fail with `Timeout` (dropping, and so cancelling, the call) after a
+/// fixed time.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeout(pub Duration);
+
+impl<Req: Send + 'static, Rep: Send + 'static> Filter<Req, Rep> for Timeout {
+    fn apply(&self, req: Req, next: Arc<dyn Service<Req, Rep>>) -> ServiceFuture<Rep> {
+        let limit = self.0;
+        Box::pin(async move {
+            tokio::time::timeout(limit, next.call(req))
+                .await
+                .map_err(|_| ServiceError::Timeout)?
+        })
+    }
+}
+
+// ── Retry ──────────────────────────────────────────────────────────
+
+/// Caps retries at a fraction of requests, so a struggling pool isn't
+/// sent several times its normal load. Every request earns `ratio` of a
+/// retry; at most `reserve` retries are banked, and the bank starts full so
+/// a quiet pool can still retry.
+#[derive(Debug)]
+pub struct RetryBudget {
+    /// In thousandths of a retry
+    balance: AtomicI64,
+    deposit: i64,
+    cap: i64,
+}
+
+impl RetryBudget {
+    pub fn new(ratio: f64, reserve: u32) -> Self {
+        let cap = reserve as i64 * 1000;
+        Self {
+            balance: AtomicI64::new(cap),
+            deposit: (ratio * 1000.0) as i64,
+            cap,
+        }
+    }
+
+    fn deposit(&self) {
+        let _ = self.balance.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |b| {
+            Some((b + self.deposit).min(self.cap))
+        });
+    }
+
+    fn withdraw(&self) -> bool {
+        self.balance
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |b| (b >= 1000).then_some(b - 1000))
+            .is_ok()
+    }
+}
+
+/// Retry failed calls up to `max_retries` times while the budget allows.
+/// Rejections that another attempt can't fix (`Unsupported`, `Cancelled`)
+/// are returned as is.
+#[derive(Debug, Clone)]
+pub struct Retry {
+    pub max_retries: u32,
+    /// Pause between attempts
+    pub backoff: Duration,
+    pub budget: Arc<RetryBudget>,
+}
+
+impl<Req: Clone + Send + 'static, Rep: Send + 'static> Filter<Req, Rep> for Retry {
+    fn apply(&self, req: Req, next: Arc<dyn Service<Req, Rep>>) -> ServiceFuture<Rep> {
+        let retry = self.clone();
+        Box::pin(async move {
+            retry.budget.deposit();
+            let mut attempt = 0;
+            loop {
+                let err = match next.call(req.clone()).await {
+                    Ok(rep) => return Ok(rep),
+                    Err(e) => e,
+                };
+                let retryable = !matches!(err, ServiceError::Unsupported(_) | ServiceError::Cancelled);
+                if !retryable || attempt >= retry.max_retries {
+                    return Err(err);
+                }
+                if !retry.budget.withdraw() {
+                    warn!("attempt {} failed, retry budget exhausted: {}", attempt, err);
+                    return Err(err);
+                }
+                warn!("attempt {} failed, retrying: {}", attempt, err);
+                attempt += 1;
+                tokio::time::sleep(retry.backoff).await;
+            }
+        })
+    }
+}
+
+// ── Hedging ────────────────────────────────────────────────────────
+
+/// Successful latencies kept for the hedge cutoff.
+const HEDGE_WINDOW: usize = 128;
+
+/// Backup requests (Eriksen Appendix A): if a call hasn't answered by the
+/// `percentile` of recent latencies, send a copy and take whichever
+/// answers first. The loser is dropped, which cancels it.
+#[derive(Debug, Clone)]
+pub struct Hedge {
+    percentile: f64,
+    /// Cutoff floor, and the cutoff until latencies are known
+    min: Duration,
+    latencies: Arc<Mutex<VecDeque<Duration>>>,
+}
+
+impl Hedge {
+    pub fn new(percentile: f64, min: Duration) -> Self {
+        Self {
+            percentile: percentile.clamp(0.0, 1.0),
+            min,
+            latencies: Arc::default(),
+        }
+    }
+
+    fn cutoff(&self) -> Duration {
+        let latencies = self.latencies.lock().unwrap();
+        if latencies.is_empty() {
+            return self.min;
+        }
+        let mut sorted: Vec<_> = latencies.iter().copied().collect();
+        sorted.sort();
+        let idx = ((sorted.len() - 1) as f64 * self.percentile).round() as usize;
+        sorted[idx].max(self.min)
+    }
+
+    fn record(&self, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        if latencies.len() == HEDGE_WINDOW {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+}
+
+impl<Req: Clone + Send + 'static, Rep: Send + 'static> Filter<Req, Rep> for Hedge {
+    fn apply(&self, req: Req, next: Arc<dyn Service<Req, Rep>>) -> ServiceFuture<Rep> {
+        let hedge = self.clone();
+        Box::pin(async move {
+            let cutoff = hedge.cutoff();
+            let start = Instant::now();
+            let mut primary = next.call(req.clone());
+
+            let result = tokio::select! {
+                result = &mut primary => result,
+                _ = tokio::time::sleep(cutoff) => {
+                    info!("backup request fired after {}ms cutoff", cutoff.as_millis());
+                    // First success wins; a copy that fails waits for the other
+                    let mut backup = next.call(req);
+                    tokio::select! {
+                        result = &mut primary => match result {
+                            Ok(rep) => Ok(rep),
+                            Err(_) => backup.await,
+                        },
+                        result = &mut backup => match result {
+                            Ok(rep) => Ok(rep),
+                            Err(_) => primary.await,
+                        },
+                    }
+                }
+            };
+            if result.is_ok() {
+                hedge.record(start.elapsed());
+            }
+            result
+        })
+    }
+}
+
+// ── Per-worker bookkeeping ─────────────────────────────────────────
+
+/// Count the call in the worker's `inflight` while it runs, so the load
+/// balancer sees it.
+pub struct Inflight(pub Arc<Worker>);
+
+impl<Req: Send + 'static, Rep: Send + 'static> Filter<Req, Rep> for Inflight {
+    fn apply(&self, req: Req, next: Arc<dyn Service<Req, Rep>>) -> ServiceFuture<Rep> {
+        let guard = InflightGuard::new(self.0.clone());
+        Box::pin(async move {
+            let _guard = guard;
+            next.call(req).await
+        })
+    }
+}
+
+/// Per-worker request, failure and latency counters (shown in pool status,
+/// latency also used by the health check). `label` names the call in logs.
+pub struct Metrics {
+    pub worker: Arc<Worker>,
+    pub label: &'static str,
+}
+
+impl<Req: Send + 'static, Rep: Send + 'static> Filter<Req, Rep> for Metrics {
+    fn apply(&self, req: Req, next: Arc<dyn Service<Req, Rep>>) -> ServiceFuture<Rep> {
+        let worker = self.worker.clone();
+        let label = self.label;
+        Box::pin(async move {
+            worker.total_requests.fetch_add(1, Ordering::Relaxed);
+            let start = Instant::now();
+            let result = next.call(req).await;
+            match result {
+                Ok(_) => {
+                    worker.last_latency_ms.store(start.elapsed().as_millis() as u64, Ordering::Relaxed);
+                }
+                // Not the worker's fault
+                Err(ServiceError::Unsupported(_) | ServiceError::Cancelled) => {}
+                Err(ref e) => {
+                    worker.total_failures.fetch_add(1, Ordering::Relaxed);
+                    warn!("{} on {} failed: {}", label, worker.name, e);
+                }
+            }
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::worker_pool::WorkerSpec;
+    use std::sync::atomic::AtomicU32;
+
+    /// Fails the first `failures` calls, then echoes the request.
+    fn flaky(failures: u32, calls: Arc<AtomicU32>) -> impl Service<u32, u32> {
+        move |req: u32| {
+            let n = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if n < failures {
+                    Err(ServiceError::Failed(format!("call {}", n)))
+                } else {
+                    Ok(req)
+                }
+            }
+        }
+    }
+
+    fn retry(max_retries: u32, budget: RetryBudget) -> Retry {
+        Retry {
+            max_retries,
+            backoff: Duration::ZERO,
+            budget: Arc::new(budget),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let slow = |req: u32| async move {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(req)
+        };
+        let svc = slow.and_then(Timeout(Duration::from_millis(10)));
+        assert!(matches!(svc.call(1).await, Err(ServiceError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let svc = flaky(2, calls.clone()).and_then(retry(2, RetryBudget::new(0.0, 10)));
+        assert_eq!(svc.call(7).await.unwrap(), 7);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let calls = Arc::new(AtomicU32::new(0));
+        let svc = flaky(5, calls.clone()).and_then(retry(2, RetryBudget::new(0.0, 10)));
+        assert!(svc.call(7).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_budget() {
+        // One banked retry, and each request earns half of one
+        let calls = Arc::new(AtomicU32::new(0));
+        let svc = flaky(u32::MAX, calls.clone()).and_then(retry(1, RetryBudget::new(0.5, 1)));
+
+        assert!(svc.call(0).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        // The bank is empty and one request earns only half a retry
+        assert!(svc.call(0).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        // Second half: retry allowed again
+        assert!(svc.call(0).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_when_unsupported() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let unsupported = move |_: u32| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async { Err::<u32, _>(ServiceError::Unsupported("engine".into())) }
+        };
+        let svc = unsupported.and_then(retry(3, RetryBudget::new(1.0, 10)));
+        assert!(matches!(svc.call(0).await, Err(ServiceError::Unsupported(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_hedge_takes_faster_copy() {
+        // First call hangs, the backup answers
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let svc = move |req: u32| {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if n == 0 {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                }
+                Ok(req + n)
+            }
+        };
+        let hedge = Hedge::new(0.99, Duration::from_millis(10));
+        let svc = svc.and_then(hedge.clone());
+        assert_eq!(svc.call(10).await.unwrap(), 11);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(hedge.cutoff() >= Duration::from_millis(10));
+    }
+
+    #[tokio::test]
+    async fn test_hedge_failed_copy_waits_for_other() {
+        // The primary fails soon after the backup fires; the slower backup succeeds
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let svc = move |req: u32| {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if n == 0 {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    Err(ServiceError::Failed("primary".into()))
+                } else {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    Ok(req + n)
+                }
+            }
+        };
+        let svc = svc.and_then(Hedge::new(0.99, Duration::from_millis(10)));
+        assert_eq!(svc.call(10).await.unwrap(), 11);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Both fail: the error comes back
+        let failing = |_: u32| async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Err::<u32, _>(ServiceError::Unavailable)
+        };
+        let svc = failing.and_then(Hedge::new(0.99, Duration::from_millis(10)));
+        assert!(svc.call(0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_hedge_cutoff_tracks_latency() {
+        let hedge = Hedge::new(0.5, Duration::from_millis(5));
+        assert_eq!(hedge.cutoff(), Duration::from_millis(5));
+        for ms in [1, 20, 30, 40, 1000] {
+            hedge.record(Duration::from_millis(ms));
+        }
+        assert_eq!(hedge.cutoff(), Duration::from_millis(30));
+    }
+
+    #[tokio::test]
+    async fn test_load_balance_repicks_on_retry() {
+        let picks = Arc::new(AtomicU32::new(0));
+        let counter = picks.clone();
+        let lb = LoadBalance::new(move |_: &u32| {
+            // First pick is a broken backend, later ones work
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            Ok(if n == 0 {
+                (|_: u32| async { Err::<u32, _>(ServiceError::Unavailable) }).boxed()
+            } else {
+                (|req: u32| async move { Ok(req * 2) }).boxed()
+            })
+        });
+        let svc = lb.and_then(retry(1, RetryBudget::new(0.0, 1)));
+        assert_eq!(svc.call(21).await.unwrap(), 42);
+        assert_eq!(picks.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_worker_bookkeeping() {
+        let worker = Arc::new(Worker::new(WorkerSpec::from_url("http://127.0.0.1:8080"), false, 1));
+        let observed = worker.clone();
+        let leaf = move |req: u32| {
+            // The call is counted while it runs
+            let inflight = observed.inflight.load(Ordering::Relaxed);
+            async move {
+                if req == 0 {
+                    Err(ServiceError::Failed("boom".into()))
+                } else {
+                    Ok(inflight)
+                }
+            }
+        };
+        let svc = leaf
+            .and_then(Inflight(worker.clone()))
+            .and_then(Metrics { worker: worker.clone(), label: "test" });
+
+        assert_eq!(svc.call(1).await.unwrap(), 1);
+        assert!(svc.call(0).await.is_err());
+        assert_eq!(worker.inflight.load(Ordering::Relaxed), 0);
+        assert_eq!(worker.total_requests.load(Ordering::Relaxed), 2);
+        assert_eq!(worker.total_failures.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,344 @@
+//! Double-entry TXT ledger
+//!
+//! Every credit, charge, refund, withdrawal and channel settlement is posted
+//! as a transaction of entries that sum to zero. A user's custodial balance
+//! is the sum of their account's entries; `users.txt_balance` caches it and
+//! is only changed by `post`, in the same DB transaction as the entries.
+//! `reconcile` reports where the two have drifted apart.
+//!
+//! Each transaction has an idempotency key, so posting the same movement
+//! twice (a retried webhook, a repeated refund) records it once.
+
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::error::ApiError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LedgerError {
+    #[error("ledger transaction {0} does not balance")]
+    Unbalanced(String),
+    #[error("insufficient balance")]
+    InsufficientBalance,
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+}
+
+impl From<LedgerError> for ApiError {
+    fn from(e: LedgerError) -> Self {
+        match e {
+            LedgerError::InsufficientBalance => ApiError::InsufficientBalance,
+            e => ApiError::Internal(e.to_string()),
+        }
+    }
+}
+
+/// Where TXT is held
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Account {
+    /// A user's custodial balance
+    User(Uuid),
+    /// Outside the ledger: card payments, on-chain deposits and withdrawals
+    External,
+    /// Earned from usage
+    Revenue,
+    /// Spent from payment channels and not yet settled on-chain
+    Channels,
+    /// Held for jobs that haven't finished yet
+    Holds,
+}
+
+impl Account {
+    fn system(&self) -> Option<&'static str> {
+        match self {
+            Account::User(_) => None,
+            Account::External => Some("external"),
+            Account::Revenue => Some("revenue"),
+            Account::Channels => Some("channels"),
+            Account::Holds => Some("holds"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Custodial balance that predates the ledger
+    Opening,
+    Credit,
+    /// Up-front charge for a job, before holds
+    Charge,
+    /// Reserved for a job when it is submitted
+    Hold,
+    /// A finished job's hold turned into its actual cost
+    Settlement,
+    Refund,
+    Withdrawal,
+    /// Undoes a withdrawal that never left
+    Reversal,
+    ChannelSettlement,
+}
+
+impl Kind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Opening => "opening",
+            Kind::Credit => "credit",
+            Kind::Charge => "charge",
+            Kind::Hold => "hold",
+            Kind::Settlement => "settlement",
+            Kind::Refund => "refund",
+            Kind::Withdrawal => "withdrawal",
+            Kind::Reversal => "reversal",
+            Kind::ChannelSettlement => "channel_settlement",
+        }
+    }
+}
+
+/// A set of entries to post as one ledger transaction
+#[derive(Debug, Clone)]
+pub struct Posting {
+    pub key: String,
+    pub kind: Kind,
+    pub user_id: Option<Uuid>,
+    pub job_id: Option<String>,
+    pub channel_address: Option<String>,
+    pub memo: Option<String>,
+    pub entries: Vec<(Account, i64)>,
+}
+
+impl Posting {
+    pub fn new(key: impl Into<String>, kind: Kind) -> Self {
+        Self {
+            key: key.into(),
+            kind,
+            user_id: None,
+            job_id: None,
+            channel_address: None,
+            memo: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add `amount` to an account. Zero amounts are dropped.
+    pub fn entry(mut self, account: Account, amount: i64) -> Self {
+        if amount != 0 {
+            self.entries.push((account, amount));
+        }
+        self
+    }
+
+    /// Move `amount` from one account to another
+    pub fn transfer(self, from: Account, to: Account, amount: i64) -> Self {
+        self.entry(from, -amount).entry(to, amount)
+    }
+
+    pub fn user(mut self, user_id: Uuid) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    pub fn job(mut self, job_id: &str) -> Self {
+        self.job_id = Some(job_id.to_string());
+        self
+    }
+
+    pub fn channel(mut self, address: impl ToString) -> Self {
+        self.channel_address = Some(address.to_string());
+        self
+    }
+
+    pub fn memo(mut self, memo: impl Into<String>) -> Self {
+        self.memo = Some(memo.into());
+        self
+    }
+
+    /// Entries exist and sum to zero
+    pub fn is_balanced(&self) -> bool {
+        !self.entries.is_empty() && self.entries.iter().map(|(_, a)| *a as i128).sum::<i128>() == 0
+    }
+}
+
+/// Post a transaction on `conn` (normally the caller's DB transaction) and
+/// move `users.txt_balance` with it. Returns None if a transaction with
+/// the same key was already posted. Fails with `InsufficientBalance` if a
+/// user balance would go negative; the caller then drops its transaction.
+pub async fn post(conn: &mut PgConnection, posting: &Posting) -> Result<Option<Uuid>, LedgerError> {
+    if !posting.is_balanced() {
+        return Err(LedgerError::Unbalanced(posting.key.clone()));
+    }
+
+    let id: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        INSERT INTO ledger_transactions (idempotency_key, kind, user_id, job_id, channel_address, memo)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (idempotency_key) DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(&posting.key)
+    .bind(posting.kind.as_str())
+    .bind(posting.user_id)
+    .bind(&posting.job_id)
+    .bind(&posting.channel_address)
+    .bind(&posting.memo)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(id) = id else {
+        return Ok(None);
+    };
+
+    for (account, amount) in &posting.entries {
+        let account_id = account_id(conn, account).await?;
+        sqlx::query("INSERT INTO ledger_entries (transaction_id, account_id, amount) VALUES ($1, $2, $3)")
+            .bind(id)
+            .bind(account_id)
+            .bind(amount)
+            .execute(&mut *conn)
+            .await?;
+
+        if let Account::User(user_id) = account {
+            let rows = sqlx::query(
+                "UPDATE users SET txt_balance = txt_balance + $1 WHERE id = $2 AND txt_balance + $1 >= 0",
+            )
+            .bind(amount)
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+            if rows == 0 {
+                return Err(LedgerError::InsufficientBalance);
+            }
+        }
+    }
+
+    Ok(Some(id))
+}
+
+async fn account_id(conn: &mut PgConnection, account: &Account) -> Result<i64, LedgerError> {
+    // User accounts are opened on first use; system accounts by the migration
+    let id = match account {
+        Account::User(user_id) => {
+            sqlx::query_scalar(
+                r#"
+                INSERT INTO ledger_accounts (user_id) VALUES ($1)
+                ON CONFLICT (user_id) DO UPDATE SET user_id = EXCLUDED.user_id
+                RETURNING id
+                "#,
+            )
+            .bind(user_id)
+            .fetch_one(conn)
+            .await?
+        }
+        _ => {
+            sqlx::query_scalar("SELECT id FROM ledger_accounts WHERE system = $1")
+                .bind(account.system())
+                .fetch_one(conn)
+                .await?
+        }
+    };
+    Ok(id)
+}
+
+/// Whether a transaction with this key has been posted
+pub async fn posted(conn: &mut PgConnection, key: &str) -> Result<bool, LedgerError> {
+    let exists = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM ledger_transactions WHERE idempotency_key = $1)",
+    )
+    .bind(key)
+    .fetch_one(conn)
+    .await?;
+    Ok(exists)
+}
+
+/// A user whose cached balance disagrees with their ledger account
+#[derive(Debug, Serialize)]
+pub struct Drift {
+    pub user_id: Uuid,
+    pub txt_balance: i64,
+    pub ledger_balance: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Reconciliation {
+    pub drift: Vec<Drift>,
+    /// Transactions whose entries don't sum to zero
+    pub unbalanced: Vec<Uuid>,
+}
+
+impl Reconciliation {
+    pub fn is_clean(&self) -> bool {
+        self.drift.is_empty() && self.unbalanced.is_empty()
+    }
+}
+
+/// Compare every `users.txt_balance` with the ledger, and check that every
+/// transaction balances.
+pub async fn reconcile(db: &PgPool) -> Result<Reconciliation, LedgerError> {
+    let drift = sqlx::query_as::<_, (Uuid, i64, i64)>(
+        r#"
+        SELECT u.id, u.txt_balance, COALESCE(b.balance, 0)
+        FROM users u
+        LEFT JOIN ledger_balances b ON b.user_id = u.id
+        WHERE u.txt_balance <> COALESCE(b.balance, 0)
+        ORDER BY u.id
+        "#,
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|(user_id, txt_balance, ledger_balance)| Drift { user_id, txt_balance, ledger_balance })
+    .collect();
+
+    let unbalanced = sqlx::query_scalar(
+        "SELECT transaction_id FROM ledger_entries GROUP BY transaction_id HAVING SUM(amount) <> 0",
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(Reconciliation { drift, unbalanced })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_balances() {
+        let user = Uuid::new_v4();
+        let posting = Posting::new("credit:stripe:pi_1", Kind::Credit)
+            .user(user)
+            .transfer(Account::External, Account::User(user), 500);
+        assert!(posting.is_balanced());
+        assert_eq!(posting.entries, vec![(Account::External, -500), (Account::User(user), 500)]);
+    }
+
+    #[test]
+    fn test_split_charge_balances() {
+        let user = Uuid::new_v4();
+        let posting = Posting::new("job:1:charge", Kind::Charge)
+            .entry(Account::User(user), -300)
+            .entry(Account::Channels, -200)
+            .entry(Account::Revenue, 500);
+        assert!(posting.is_balanced());
+    }
+
+    #[test]
+    fn test_unbalanced_and_empty() {
+        let posting = Posting::new("x", Kind::Refund)
+            .entry(Account::Revenue, -100)
+            .entry(Account::Channels, 99);
+        assert!(!posting.is_balanced());
+
+        // zero entries are dropped, leaving nothing to post
+        let posting = Posting::new("y", Kind::Refund).transfer(Account::Revenue, Account::Channels, 0);
+        assert!(posting.entries.is_empty());
+        assert!(!posting.is_balanced());
+    }
+
+    #[test]
+    fn test_kind_names() {
+        assert_eq!(Kind::ChannelSettlement.as_str(), "channel_settlement");
+        assert_eq!(Kind::Opening.as_str(), "opening");
+    }
+}
@@ -0,0 +1,359 @@
+//! Transparency log of approved worker measurements.
+//!
+//! An append-only, hash-chained list of `add`/`revoke` entries in Postgres,
+//! each signed by the log's ed25519 key. The set of measurements a TEE
+//! worker may run is the replay of the whole log. `QuicWorkerConn::connect`
+//! consults it after verifying a quote, and the log is published so clients
+//! can audit which builds the service will talk to.
+//!
+//! Entry hash: `SHA-256(prev_hash || seq || action || tee_type || measurement
+//! || version || git_commit || note || timestamp)`, strings length-prefixed
+//! (u32 BE), integers u64 BE. The first entry's `prev_hash` is all zeros.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use sonotxt_core::attestation::tdx::{self, TdQuote};
+use sonotxt_core::protocol::TeeType;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct LogEntry {
+    pub seq: i64,
+    pub action: String,
+    pub tee_type: String,
+    pub measurement: String,
+    pub version: String,
+    pub git_commit: String,
+    pub note: String,
+    pub timestamp: i64,
+    pub prev_hash: String,
+    pub entry_hash: String,
+    pub signature: String,
+}
+
+/// Release metadata recorded with an entry.
+#[derive(Debug, Default)]
+pub struct EntryInfo {
+    pub version: String,
+    pub git_commit: String,
+    pub note: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Add,
+    Revoke,
+}
+
+impl Action {
+    fn as_str(self) -> &'static str {
+        match self {
+            Action::Add => "add",
+            Action::Revoke => "revoke",
+        }
+    }
+}
+
+const SELECT_ENTRIES: &str = r#"
+    SELECT seq, action, tee_type, measurement, version, git_commit, note,
+           timestamp, prev_hash, entry_hash, signature
+    FROM worker_measurements
+    ORDER BY seq
+"#;
+
+pub struct MeasurementLog {
+    db: PgPool,
+    key: SigningKey,
+}
+
+impl MeasurementLog {
+    /// Open the log and verify every stored entry against `key`, so a
+    /// rewritten table is caught at startup rather than trusted.
+    pub async fn open(db: PgPool, key: SigningKey) -> anyhow::Result<Self> {
+        let log = Self { db, key };
+        let entries = log.entries().await?;
+        verify_chain(&entries, &log.public_key())?;
+        tracing::info!(
+            "measurement log: {} entries, key {}",
+            entries.len(),
+            hex::encode(log.public_key().as_bytes())
+        );
+        Ok(log)
+    }
+
+    /// Parse a hex-encoded 32-byte ed25519 seed.
+    pub fn key_from_hex(seed: &str) -> anyhow::Result<SigningKey> {
+        let bytes: [u8; 32] = hex::decode(seed.trim())?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("measurement log key must be 32 bytes"))?;
+        Ok(SigningKey::from_bytes(&bytes))
+    }
+
+    pub fn public_key(&self) -> VerifyingKey {
+        self.key.verifying_key()
+    }
+
+    pub async fn entries(&self) -> sqlx::Result<Vec<LogEntry>> {
+        sqlx::query_as(SELECT_ENTRIES).fetch_all(&self.db).await
+    }
+
+    /// Append a signed entry. The table lock serializes appends so the
+    /// chain never forks.
+    pub async fn append(
+        &self,
+        action: Action,
+        tee: TeeType,
+        measurement: &str,
+        info: EntryInfo,
+    ) -> anyhow::Result<LogEntry> {
+        let tee_type = tee_name(tee).ok_or_else(|| anyhow::anyhow!("{:?} has no measurements", tee))?;
+        let measurement = canonical_measurement(tee, measurement)?;
+
+        let mut tx = self.db.begin().await?;
+        sqlx::query("LOCK TABLE worker_measurements IN SHARE ROW EXCLUSIVE MODE")
+            .execute(&mut *tx)
+            .await?;
+
+        let head: Option<(i64, String)> =
+            sqlx::query_as("SELECT seq, entry_hash FROM worker_measurements ORDER BY seq DESC LIMIT 1")
+                .fetch_optional(&mut *tx)
+                .await?;
+
+        if action == Action::Revoke {
+            let active = replay(&entries_in(&mut tx).await?);
+            if !active.iter().any(|(t, m)| t == tee_type && *m == measurement) {
+                anyhow::bail!("{} {} is not currently approved", tee_type, measurement);
+            }
+        }
+
+        let (seq, prev_hash) = match head {
+            Some((seq, hash)) => (seq + 1, hash),
+            None => (0, hex::encode([0u8; 32])),
+        };
+        let mut entry = LogEntry {
+            seq,
+            action: action.as_str().to_string(),
+            tee_type: tee_type.to_string(),
+            measurement,
+            version: info.version,
+            git_commit: info.git_commit,
+            note: info.note,
+            timestamp: chrono::Utc::now().timestamp(),
+            prev_hash,
+            entry_hash: String::new(),
+            signature: String::new(),
+        };
+        let hash = entry_hash(&entry)?;
+        entry.entry_hash = hex::encode(hash);
+        entry.signature = hex::encode(self.key.sign(&hash).to_bytes());
+
+        sqlx::query(
+            r#"
+            INSERT INTO worker_measurements
+                (seq, action, tee_type, measurement, version, git_commit, note,
+                 timestamp, prev_hash, entry_hash, signature)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+        )
+        .bind(entry.seq)
+        .bind(&entry.action)
+        .bind(&entry.tee_type)
+        .bind(&entry.measurement)
+        .bind(&entry.version)
+        .bind(&entry.git_commit)
+        .bind(&entry.note)
+        .bind(entry.timestamp)
+        .bind(&entry.prev_hash)
+        .bind(&entry.entry_hash)
+        .bind(&entry.signature)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        tracing::info!("measurement log #{}: {} {} {}", entry.seq, entry.action, entry.tee_type, entry.measurement);
+        Ok(entry)
+    }
+
+    /// Whether an SNP launch measurement is currently approved.
+    pub async fn allows_snp(&self, measurement: &[u8; 48]) -> sqlx::Result<bool> {
+        let measurement = hex::encode(measurement);
+        Ok(self.active().await?.iter().any(|(t, m)| *t == "sev-snp" && *m == measurement))
+    }
+
+    /// Whether a TDX quote's MRTD/RTMRs match a currently approved entry.
+    pub async fn allows_tdx(&self, quote: &TdQuote) -> sqlx::Result<bool> {
+        Ok(self
+            .active()
+            .await?
+            .iter()
+            .filter(|(t, _)| *t == "tdx")
+            .filter_map(|(_, m)| tdx::parse_measurements(m).ok())
+            .flatten()
+            .any(|m| m.matches(quote)))
+    }
+
+    /// Currently approved `(tee_type, measurement)` pairs.
+    pub async fn active(&self) -> sqlx::Result<Vec<(String, String)>> {
+        Ok(replay(&self.entries().await?))
+    }
+}
+
+async fn entries_in(conn: &mut sqlx::PgConnection) -> sqlx::Result<Vec<LogEntry>> {
+    sqlx::query_as(SELECT_ENTRIES).fetch_all(conn).await
+}
+
+/// Log name for a TEE type; None for types that carry no measurement.
+pub fn tee_name(tee: TeeType) -> Option<&'static str> {
+    match tee {
+        TeeType::SevSnp => Some("sev-snp"),
+        TeeType::Tdx => Some("tdx"),
+        TeeType::Insecure => None,
+    }
+}
+
+/// Normalize a measurement so equal values always compare equal as strings.
+pub fn canonical_measurement(tee: TeeType, measurement: &str) -> anyhow::Result<String> {
+    match tee {
+        TeeType::SevSnp => {
+            let bytes = hex::decode(measurement.trim().trim_start_matches("0x"))?;
+            anyhow::ensure!(bytes.len() == 48, "SNP measurement must be 48 bytes");
+            Ok(hex::encode(bytes))
+        }
+        TeeType::Tdx => match tdx::parse_measurements(measurement).map_err(anyhow::Error::msg)?.as_slice() {
+            [m] => Ok(m.to_string()),
+            _ => anyhow::bail!("expected exactly one TDX measurement"),
+        },
+        TeeType::Insecure => anyhow::bail!("insecure workers have no measurement"),
+    }
+}
+
+/// Currently approved `(tee_type, measurement)` pairs after replaying the log.
+fn replay(entries: &[LogEntry]) -> Vec<(String, String)> {
+    let mut active: Vec<(String, String)> = Vec::new();
+    for e in entries {
+        let key = (e.tee_type.clone(), e.measurement.clone());
+        match e.action.as_str() {
+            "add" if !active.contains(&key) => active.push(key),
+            "revoke" => active.retain(|k| *k != key),
+            _ => {}
+        }
+    }
+    active
+}
+
+fn entry_hash(e: &LogEntry) -> anyhow::Result<[u8; 32]> {
+    let mut h = Sha256::new();
+    h.update(hex::decode(&e.prev_hash)?);
+    h.update((e.seq as u64).to_be_bytes());
+    for field in [&e.action, &e.tee_type, &e.measurement, &e.version, &e.git_commit, &e.note] {
+        h.update((field.len() as u32).to_be_bytes());
+        h.update(field.as_bytes());
+    }
+    h.update((e.timestamp as u64).to_be_bytes());
+    Ok(h.finalize().into())
+}
+
+/// Check sequence numbers, hash links and signatures of a full log.
+pub fn verify_chain(entries: &[LogEntry], key: &VerifyingKey) -> anyhow::Result<()> {
+    let mut prev = hex::encode([0u8; 32]);
+    for (i, e) in entries.iter().enumerate() {
+        anyhow::ensure!(e.seq == i as i64, "measurement log gap at #{}", i);
+        anyhow::ensure!(e.prev_hash == prev, "measurement log #{} does not link to its predecessor", e.seq);
+
+        let hash = entry_hash(e)?;
+        anyhow::ensure!(hex::encode(hash) == e.entry_hash, "measurement log #{} hash mismatch", e.seq);
+
+        let signature = Signature::from_slice(&hex::decode(&e.signature)?)?;
+        key.verify(&hash, &signature)
+            .map_err(|_| anyhow::anyhow!("measurement log #{} has an invalid signature", e.seq))?;
+
+        prev = e.entry_hash.clone();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signed(key: &SigningKey, mut e: LogEntry) -> LogEntry {
+        let hash = entry_hash(&e).unwrap();
+        e.entry_hash = hex::encode(hash);
+        e.signature = hex::encode(key.sign(&hash).to_bytes());
+        e
+    }
+
+    fn entry(seq: i64, prev_hash: &str, action: &str, measurement: &str) -> LogEntry {
+        LogEntry {
+            seq,
+            action: action.into(),
+            tee_type: "sev-snp".into(),
+            measurement: measurement.into(),
+            version: "0.1.0".into(),
+            git_commit: "abc123".into(),
+            note: String::new(),
+            timestamp: 1_700_000_000 + seq,
+            prev_hash: prev_hash.into(),
+            entry_hash: String::new(),
+            signature: String::new(),
+        }
+    }
+
+    fn log(key: &SigningKey) -> Vec<LogEntry> {
+        let a = "aa".repeat(48);
+        let b = "bb".repeat(48);
+        let e0 = signed(key, entry(0, &hex::encode([0u8; 32]), "add", &a));
+        let e1 = signed(key, entry(1, &e0.entry_hash, "add", &b));
+        let e2 = signed(key, entry(2, &e1.entry_hash, "revoke", &a));
+        vec![e0, e1, e2]
+    }
+
+    #[test]
+    fn test_chain_verifies_and_replays() {
+        let key = SigningKey::from_bytes(&[1u8; 32]);
+        let entries = log(&key);
+
+        verify_chain(&entries, &key.verifying_key()).unwrap();
+        assert_eq!(replay(&entries), vec![("sev-snp".to_string(), "bb".repeat(48))]);
+    }
+
+    #[test]
+    fn test_rewritten_entry_rejected() {
+        let key = SigningKey::from_bytes(&[1u8; 32]);
+        let mut entries = log(&key);
+        entries[1].measurement = "cc".repeat(48);
+
+        assert!(verify_chain(&entries, &key.verifying_key()).is_err());
+    }
+
+    #[test]
+    fn test_dropped_entry_rejected() {
+        let key = SigningKey::from_bytes(&[1u8; 32]);
+        let mut entries = log(&key);
+        entries.remove(2);
+        entries.remove(0);
+
+        assert!(verify_chain(&entries, &key.verifying_key()).is_err());
+    }
+
+    #[test]
+    fn test_foreign_key_rejected() {
+        let key = SigningKey::from_bytes(&[1u8; 32]);
+        let other = SigningKey::from_bytes(&[2u8; 32]);
+
+        assert!(verify_chain(&log(&key), &other.verifying_key()).is_err());
+    }
+
+    #[test]
+    fn test_canonical_measurement() {
+        let upper = format!("0x{}", "AB".repeat(48));
+        assert_eq!(canonical_measurement(TeeType::SevSnp, &upper).unwrap(), "ab".repeat(48));
+        assert!(canonical_measurement(TeeType::SevSnp, "abcd").is_err());
+        assert!(canonical_measurement(TeeType::Insecure, "").is_err());
+
+        let mrtd = "11".repeat(48);
+        assert_eq!(canonical_measurement(TeeType::Tdx, &format!("{}:::", mrtd)).unwrap(), mrtd);
+    }
+}
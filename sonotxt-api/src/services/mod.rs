@@ -3,9 +3,14 @@ pub mod billing;
 pub mod content;
 pub mod crawler;
 pub mod crypto;
+pub mod engines;
+pub mod filter;
+pub mod ledger;
 
 pub mod magic_link;
+pub mod measurement_log;
 pub mod payments;
+pub mod pricing;
 pub mod seed_manager;
 pub mod tpm;
 pub mod user_auth;
@@ -13,4 +18,5 @@ pub mod wallet;
 pub mod passkey;
 pub mod sono;
 pub mod worker_pool;
+pub mod worker_registry;
 pub mod quic_pool;
@@ -0,0 +1,169 @@
+//! TTS pricing
+//!
+//! Speech is priced per minute of audio (`COST_PER_MINUTE`, USD), scaled by
+//! the engine's model class (`MODEL_1_5B_MULTIPLIER` / `MODEL_7B_MULTIPLIER`)
+//! and an optional per-voice multiplier (`VOICE_MULTIPLIERS`).
+//!
+//! How long the audio runs is only known once it exists, so a paid job is
+//! billed in two steps: a hold when it is submitted, sized from its text
+//! with some headroom, then a settlement on the synthesized duration that
+//! releases what the hold didn't need. A job never costs more than its hold.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::warn;
+
+use crate::config::Config;
+use crate::services::engines;
+
+/// Characters of text per second of speech at normal speed.
+pub const CHARS_PER_SECOND: f64 = 15.0;
+
+/// Headroom a hold keeps over the estimated cost, for slow voices and
+/// pauses the text doesn't show.
+pub const HOLD_MARGIN: f64 = 1.5;
+
+/// What audio costs, per engine and voice
+#[derive(Debug, Clone, Serialize)]
+pub struct RateCard {
+    /// USD per minute of audio at a multiplier of 1.0
+    pub usd_per_minute: f64,
+    /// Multiplier per engine
+    pub engines: BTreeMap<&'static str, f64>,
+    /// Multiplier per voice; voices not listed are 1.0
+    pub voices: BTreeMap<String, f64>,
+}
+
+/// Expected cost of a job before it runs
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Estimate {
+    /// Expected audio duration
+    pub seconds: f64,
+    /// Expected cost in USD
+    pub usd: f64,
+    /// Held at submission, in USD
+    pub hold_usd: f64,
+}
+
+impl RateCard {
+    pub fn from_config(config: &Config) -> Self {
+        let engines = engines::ENGINES
+            .iter()
+            .map(|e| {
+                let multiplier = if e.large_model() {
+                    config.model_7b_multiplier
+                } else {
+                    config.model_1_5b_multiplier
+                };
+                (e.name(), multiplier)
+            })
+            .collect();
+
+        Self {
+            usd_per_minute: config.cost_per_minute,
+            engines,
+            voices: parse_multipliers(&config.voice_multipliers),
+        }
+    }
+
+    pub fn multiplier(&self, engine: &str, voice: &str) -> f64 {
+        self.engines.get(engine).copied().unwrap_or(1.0) * self.voices.get(voice).copied().unwrap_or(1.0)
+    }
+
+    /// USD for `seconds` of audio
+    pub fn cost(&self, seconds: f64, engine: &str, voice: &str) -> f64 {
+        seconds.max(0.0) / 60.0 * self.usd_per_minute * self.multiplier(engine, voice)
+    }
+
+    /// Expected duration and cost of speaking `chars` characters
+    pub fn estimate(&self, chars: usize, engine: &str, voice: &str, speed: Option<f32>) -> Estimate {
+        let seconds = estimated_seconds(chars, speed);
+        let usd = self.cost(seconds, engine, voice);
+        Estimate { seconds, usd, hold_usd: usd * HOLD_MARGIN }
+    }
+
+    /// Expected duration and cost of speaking `text`, counted in
+    /// characters rather than bytes
+    pub fn estimate_text(&self, text: &str, engine: &str, voice: &str, speed: Option<f32>) -> Estimate {
+        self.estimate(text.chars().count(), engine, voice, speed)
+    }
+}
+
+/// Audio seconds `chars` characters of text come out as
+pub fn estimated_seconds(chars: usize, speed: Option<f32>) -> f64 {
+    let speed = speed.map(f64::from).filter(|s| *s > 0.0).unwrap_or(1.0);
+    chars as f64 / CHARS_PER_SECOND / speed
+}
+
+/// Parse "name=multiplier" pairs separated by commas, skipping bad ones
+fn parse_multipliers(spec: &str) -> BTreeMap<String, f64> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .filter_map(|pair| {
+            let parsed = pair
+                .split_once('=')
+                .and_then(|(name, m)| Some((name.trim().to_string(), m.trim().parse::<f64>().ok()?)))
+                .filter(|(name, m)| !name.is_empty() && m.is_finite() && *m >= 0.0);
+            if parsed.is_none() {
+                warn!("ignoring voice multiplier {:?}", pair);
+            }
+            parsed
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rates() -> RateCard {
+        RateCard {
+            usd_per_minute: 0.006,
+            engines: [("qwen", 1.0), ("vibevoice", 2.0)].into_iter().collect(),
+            voices: parse_multipliers("ryan=1.5, serena=1"),
+        }
+    }
+
+    #[test]
+    fn test_parse_multipliers() {
+        let voices = parse_multipliers(" ryan=1.5 ,bad, =2,neg=-1,serena=0.8,");
+        assert_eq!(voices.len(), 2);
+        assert_eq!(voices["ryan"], 1.5);
+        assert_eq!(voices["serena"], 0.8);
+        assert!(parse_multipliers("").is_empty());
+    }
+
+    #[test]
+    fn test_cost_applies_multipliers() {
+        let rates = rates();
+        assert!((rates.cost(60.0, "qwen", "serena") - 0.006).abs() < 1e-12);
+        assert!((rates.cost(60.0, "vibevoice", "ryan") - 0.018).abs() < 1e-12);
+        // unknown engine and voice are priced at 1.0
+        assert!((rates.cost(30.0, "other", "other") - 0.003).abs() < 1e-12);
+        assert_eq!(rates.cost(-5.0, "qwen", "serena"), 0.0);
+    }
+
+    #[test]
+    fn test_estimate_holds_margin_and_speed() {
+        let rates = rates();
+        let normal = rates.estimate(900, "qwen", "serena", None);
+        assert!((normal.seconds - 60.0).abs() < 1e-9);
+        assert!((normal.hold_usd - normal.usd * HOLD_MARGIN).abs() < 1e-12);
+
+        // half speed takes twice as long
+        let slow = rates.estimate(900, "qwen", "serena", Some(0.5));
+        assert!((slow.seconds - 120.0).abs() < 1e-9);
+        assert!(slow.usd > normal.usd);
+    }
+
+    #[test]
+    fn test_estimate_text_counts_chars() {
+        let rates = rates();
+        // Three bytes a character, priced the same as ASCII of equal length
+        let cjk = rates.estimate_text("你好世界。今天天气很好。", "qwen", "serena", None);
+        let ascii = rates.estimate_text("Hello world.", "qwen", "serena", None);
+        assert!((cjk.seconds - 12.0 / CHARS_PER_SECOND).abs() < 1e-9);
+        assert!((cjk.usd - ascii.usd).abs() < 1e-12);
+    }
+}
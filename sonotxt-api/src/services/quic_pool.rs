@@ -1,75 +1,236 @@
 //! QUIC-based worker connections for the API.
 //!
 //! Each QuicWorkerConn maintains a persistent QUIC connection to a worker,
-//! with a Noise_NK encrypted session. Used by WorkerPool as an alternative
+//! with a Noise encrypted session (NK, or KK/XK when the API authenticates
+//! with a static key). Used by WorkerPool as an alternative
 //! to HTTP when QUIC URLs are configured.
 //!
 //! Provides:
 //! - Job push notifications (replaces redis pub/sub)
 //! - Encrypted TTS inference (text never leaves encrypted channel)
+//! - Streaming encrypted TTS (sequenced audio chunks as segments finish)
+//! - Encrypted LLM chat (sentences streamed as they are generated)
 //! - Low-latency health checks over QUIC
+//!
+//! Workers behind NAT dial in instead (`accept`): once the QUIC connection
+//! is up the roles are the same, the API still verifies attestation and
+//! initiates Noise, and then asks the worker to enroll.
 
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::{error, info, warn};
-
-use sonotxt_core::noise::NoiseClient;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{debug, error, info, warn};
+
+use sonotxt_core::attestation::snp::SnpVerifier;
+use sonotxt_core::attestation::tdx::{self, TdxVerifier};
+use sonotxt_core::noise::{self, HandshakePattern, NoiseClient};
+use serde::de::DeserializeOwned;
 use sonotxt_core::protocol::{
-    AttestationBundle, EncryptedTtsRequest, EncryptedTtsResponse, Message, TeeType, WorkerHealth,
+    AttestationBundle, EncryptedLlmRequest, EncryptedTtsRequest, EncryptedTtsResponse, LlmEvent, Message,
+    StreamChunk, TeeType, WireFormat, WorkerCapabilities, WorkerEnrollment, WorkerHealth,
 };
-use sonotxt_core::quic::{read_message, write_message};
+use sonotxt_core::quic::{read_message, wire_format, write_message, FrameLimits};
+
+use crate::config::Config;
+use crate::services::measurement_log::MeasurementLog;
+
+/// Decrypted audio chunks from a streaming TTS request, in sequence order.
+pub type TtsChunkStream =
+    ReceiverStream<Result<StreamChunk, Box<dyn std::error::Error + Send + Sync>>>;
+
+/// Decrypted LLM events (sentences, then the final event), in sequence order.
+pub type LlmEventStream =
+    ReceiverStream<Result<LlmEvent, Box<dyn std::error::Error + Send + Sync>>>;
+
+/// What the API accepts as proof that a worker runs in a TEE.
+pub struct AttestationPolicy {
+    /// Accept `TeeType::Insecure` bundles (development only)
+    pub allow_insecure: bool,
+    /// SEV-SNP trust anchors and measurement allow-list; None rejects SNP
+    pub snp: Option<SnpVerifier>,
+    /// TDX collateral and MRTD/RTMR allow-list; None rejects TDX
+    pub tdx: Option<TdxVerifier>,
+    /// When set, approved measurements come from the transparency log
+    /// instead of the static allow-lists above
+    pub log: Option<Arc<MeasurementLog>>,
+}
+
+impl AttestationPolicy {
+    /// Build from config: cert/collateral bundle paths and measurement
+    /// allow-lists for each TEE type, or the measurement log if enabled.
+    pub fn from_config(
+        config: &Config,
+        log: Option<Arc<MeasurementLog>>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let snp = match config.snp_cert_bundle.as_deref() {
+            Some(path) => {
+                let pem = std::fs::read(path)?;
+                let measurements = sonotxt_core::attestation::parse_measurements(&config.snp_measurements)?;
+                if measurements.is_empty() && log.is_none() {
+                    warn!("SNP_MEASUREMENTS is empty: every SEV-SNP worker will be rejected");
+                }
+                let verifier = SnpVerifier::from_pem_bundle(&pem, &measurements)?;
+                info!("SEV-SNP attestation enabled (root {})", verifier.root_subject());
+                Some(verifier)
+            }
+            None => None,
+        };
+
+        let tdx = match config.tdx_collateral.as_deref() {
+            Some(path) => {
+                let json = std::fs::read(path)?;
+                let measurements = tdx::parse_measurements(&config.tdx_measurements)?;
+                if measurements.is_empty() && log.is_none() {
+                    warn!("TDX_MEASUREMENTS is empty: every TDX worker will be rejected");
+                }
+                let verifier = TdxVerifier::from_collateral(&json, measurements)?;
+                info!("TDX attestation enabled (root {})", verifier.root_subject());
+                Some(verifier)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            allow_insecure: !config.attestation_require_tee,
+            snp,
+            tdx,
+            log,
+        })
+    }
+}
+
+/// How the API authenticates itself in the Noise handshake.
+#[derive(Clone, Default)]
+pub struct NoiseIdentity {
+    pub pattern: HandshakePattern,
+    /// X25519 static private key (KK/XK)
+    pub static_key: Option<Vec<u8>>,
+}
+
+impl NoiseIdentity {
+    pub fn from_config(config: &Config) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let static_key = config
+            .noise_static_key
+            .as_deref()
+            .map(|k| hex::decode(k.trim()))
+            .transpose()?;
+
+        match (&static_key, config.noise_pattern.is_mutual()) {
+            (Some(key), true) => {
+                let public = noise::public_key(key)?;
+                info!("Noise {} with API key {}", config.noise_pattern, hex::encode(public));
+            }
+            (None, true) => {
+                return Err(format!("NOISE_PATTERN={} requires NOISE_STATIC_KEY", config.noise_pattern).into());
+            }
+            (Some(_), false) => warn!("NOISE_STATIC_KEY is ignored with NOISE_PATTERN=nk"),
+            (None, false) => {}
+        }
+
+        Ok(Self {
+            pattern: config.noise_pattern,
+            static_key,
+        })
+    }
+
+    fn client(&self) -> NoiseClient {
+        match (&self.static_key, self.pattern.is_mutual()) {
+            (Some(key), true) => NoiseClient::with_static_key(self.pattern, key),
+            _ => NoiseClient::new(),
+        }
+    }
+}
 
 /// A persistent QUIC connection to one worker with Noise encryption.
 pub struct QuicWorkerConn {
     endpoint: quinn::Endpoint,
     connection: quinn::Connection,
-    noise: RwLock<NoiseClient>,
+    noise: Arc<NoiseClient>,
+    /// Body encoding negotiated via ALPN
+    format: WireFormat,
+    /// Frame size limits for worker responses
+    limits: FrameLimits,
     addr: SocketAddr,
 }
 
 impl QuicWorkerConn {
     /// Connect to a worker, verify attestation, establish Noise session.
-    pub async fn connect(addr: SocketAddr) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn connect(
+        addr: SocketAddr,
+        policy: &AttestationPolicy,
+        identity: &NoiseIdentity,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let endpoint = sonotxt_core::quic::client_endpoint()?;
 
         info!("QUIC connecting to {}", addr);
         let connection = endpoint.connect(addr, "localhost")?.await?;
-        info!("QUIC connected to {}", addr);
+        info!("QUIC connected to {} ({:?} wire format)", addr, wire_format(&connection));
+        Self::establish(endpoint, connection, policy, identity).await
+    }
+
+    /// Take over a connection a worker dialed into the API's listener:
+    /// verify attestation and establish the Noise session as for `connect`.
+    pub async fn accept(
+        endpoint: quinn::Endpoint,
+        connection: quinn::Connection,
+        policy: &AttestationPolicy,
+        identity: &NoiseIdentity,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        info!(
+            "QUIC worker dialed in from {} ({:?} wire format)",
+            connection.remote_address(),
+            wire_format(&connection)
+        );
+        Self::establish(endpoint, connection, policy, identity).await
+    }
+
+    async fn establish(
+        endpoint: quinn::Endpoint,
+        connection: quinn::Connection,
+        policy: &AttestationPolicy,
+        identity: &NoiseIdentity,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let addr = connection.remote_address();
+        let format = wire_format(&connection);
 
         // Request attestation
-        let attestation = request_attestation(&connection).await?;
+        let attestation = request_attestation(&connection, format).await?;
         info!("attestation received: {:?}", attestation.tee_type);
 
-        verify_attestation(&attestation)?;
+        verify_attestation(&attestation, policy).await?;
         info!("attestation verified");
 
         // Noise handshake
-        let mut noise = NoiseClient::new();
+        let mut noise = identity.client();
         let handshake_msg = noise.initiate_handshake(&attestation.static_key)?;
         let (server_response, session_id) =
-            send_noise_handshake(&connection, &handshake_msg).await?;
-        noise.complete_handshake(&server_response, session_id)?;
+            send_noise_handshake(&connection, &handshake_msg, format).await?;
+        if let Some(finish) = noise.complete_handshake(&server_response, session_id)? {
+            send_noise_finish(&connection, &finish, session_id, format).await?;
+        }
 
         info!("Noise session established with {}", addr);
 
         Ok(Self {
             endpoint,
             connection,
-            noise: RwLock::new(noise),
+            noise: Arc::new(noise),
+            format,
+            limits: FrameLimits::default(),
             addr,
         })
     }
 
     /// Push job notification to worker (encrypted).
     pub async fn notify_job(&self, job_id: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let ciphertext = self.noise.write().await.encrypt(job_id.as_bytes())?;
+        let ciphertext = self.noise.encrypt(job_id.as_bytes())?;
 
         let (mut send, mut recv) = self.connection.open_bi().await?;
-        write_message(&mut send, &Message::JobNotify(ciphertext)).await?;
+        write_message(&mut send, &Message::JobNotify(ciphertext), self.format).await?;
         send.finish()?;
 
-        let response = read_message(&mut recv).await?;
+        let response = read_message(&mut recv, self.format, &self.limits).await?;
         match response {
             Message::JobAck { job_id: ack_id } => {
                 if ack_id != job_id {
@@ -86,24 +247,96 @@ impl QuicWorkerConn {
         &self,
         request: &EncryptedTtsRequest,
     ) -> Result<EncryptedTtsResponse, Box<dyn std::error::Error + Send + Sync>> {
-        let plaintext = serde_json::to_vec(request)?;
-        let ciphertext = self.noise.write().await.encrypt(&plaintext)?;
+        let plaintext = self.format.to_vec(request)?;
+        let ciphertext = self.noise.encrypt(&plaintext)?;
 
         let (mut send, mut recv) = self.connection.open_bi().await?;
-        write_message(&mut send, &Message::EncryptedRequest(ciphertext)).await?;
+        write_message(&mut send, &Message::EncryptedRequest(ciphertext), self.format).await?;
         send.finish()?;
 
-        let response = read_message(&mut recv).await?;
+        let pending = self.cancel_on_drop(request.request_id);
+        let response = read_message(&mut recv, self.format, &self.limits).await?;
+        pending.disarm();
         match response {
             Message::EncryptedResponse(encrypted) => {
-                let decrypted = self.noise.write().await.decrypt(&encrypted)?;
-                let tts_response: EncryptedTtsResponse = serde_json::from_slice(&decrypted)?;
+                let decrypted = self.noise.decrypt(&encrypted)?;
+                let tts_response: EncryptedTtsResponse = self.format.from_slice(&decrypted)?;
                 Ok(tts_response)
             }
             _ => Err("unexpected response type".into()),
         }
     }
 
+    /// Streaming encrypted TTS: the worker synthesizes the text segment by
+    /// segment and sends each as an encrypted `StreamChunk`, so playback can
+    /// start before the whole text is done. The stream ends after the chunk
+    /// with `is_final` set. Dropping the stream cancels the worker's synthesis.
+    pub async fn encrypted_tts_stream(
+        &self,
+        request: &EncryptedTtsRequest,
+    ) -> Result<TtsChunkStream, Box<dyn std::error::Error + Send + Sync>> {
+        let plaintext = self.format.to_vec(request)?;
+        let ciphertext = self.noise.encrypt(&plaintext)?;
+
+        let (mut send, recv) = self.connection.open_bi().await?;
+        write_message(&mut send, &Message::EncryptedStreamRequest(ciphertext), self.format).await?;
+        send.finish()?;
+
+        Ok(self.read_stream(recv, request.request_id))
+    }
+
+    /// Encrypted LLM chat: the conversation is encrypted end-to-end. Each
+    /// sentence of the reply arrives as an `LlmEvent` (as soon as it is
+    /// generated if `request.stream`), followed by a final event with the
+    /// full reply. Dropping the stream cancels the worker's generation.
+    pub async fn encrypted_llm(
+        &self,
+        request: &EncryptedLlmRequest,
+    ) -> Result<LlmEventStream, Box<dyn std::error::Error + Send + Sync>> {
+        let plaintext = self.format.to_vec(request)?;
+        let ciphertext = self.noise.encrypt(&plaintext)?;
+
+        let (mut send, recv) = self.connection.open_bi().await?;
+        write_message(&mut send, &Message::EncryptedLlmRequest(ciphertext), self.format).await?;
+        send.finish()?;
+
+        Ok(self.read_stream(recv, request.request_id))
+    }
+
+    /// Decrypt the events of a streamed response on a background task
+    /// until the final one. If the receiver is dropped first, the request
+    /// is cancelled on the worker.
+    fn read_stream<T: StreamEvent>(
+        &self,
+        mut recv: quinn::RecvStream,
+        request_id: [u8; 16],
+    ) -> ReceiverStream<Result<T, Box<dyn std::error::Error + Send + Sync>>> {
+        let noise = self.noise.clone();
+        let format = self.format;
+        let limits = self.limits;
+        let pending = self.cancel_on_drop(request_id);
+        let (tx, rx) = mpsc::channel(8);
+
+        tokio::spawn(async move {
+            let mut sequence = 0u32;
+            loop {
+                let result = read_stream_event::<T>(&mut recv, &noise, format, &limits, request_id, sequence).await;
+                let done = !matches!(result, Ok(ref event) if !event.is_final());
+                if tx.send(result).await.is_err() {
+                    // Receiver dropped mid-stream: `pending` cancels
+                    break;
+                }
+                if done {
+                    pending.disarm();
+                    break;
+                }
+                sequence += 1;
+            }
+        });
+
+        ReceiverStream::new(rx)
+    }
+
     /// Encrypted ASR: audio encrypted end-to-end via Noise channel.
     pub async fn encrypted_asr(
         &self,
@@ -117,94 +350,331 @@ impl QuicWorkerConn {
             audio_base64: audio_base64.to_string(),
         };
 
-        let plaintext = serde_json::to_vec(&request)?;
-        let ciphertext = self.noise.write().await.encrypt(&plaintext)?;
+        let plaintext = self.format.to_vec(&request)?;
+        let ciphertext = self.noise.encrypt(&plaintext)?;
 
         let (mut send, mut recv) = self.connection.open_bi().await?;
-        write_message(&mut send, &Message::EncryptedAsrRequest(ciphertext)).await?;
+        write_message(&mut send, &Message::EncryptedAsrRequest(ciphertext), self.format).await?;
         send.finish()?;
 
-        let response = read_message(&mut recv).await?;
+        let pending = self.cancel_on_drop(request_id);
+        let response = read_message(&mut recv, self.format, &self.limits).await?;
+        pending.disarm();
         match response {
             Message::EncryptedAsrResponse(encrypted) => {
-                let decrypted = self.noise.write().await.decrypt(&encrypted)?;
-                let asr_response: sonotxt_core::EncryptedAsrResponse = serde_json::from_slice(&decrypted)?;
+                let decrypted = self.noise.decrypt(&encrypted)?;
+                let asr_response: sonotxt_core::EncryptedAsrResponse = self.format.from_slice(&decrypted)?;
                 Ok(asr_response)
             }
             _ => Err("unexpected response type".into()),
         }
     }
 
+    /// Tell the worker to abort `request_id`. Fire and forget: the worker
+    /// sends no response.
+    pub async fn cancel(&self, request_id: [u8; 16]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        send_cancel(&self.connection, self.format, request_id).await
+    }
+
+    /// Guard that cancels `request_id` on the worker if dropped before
+    /// `disarm` — i.e. when the caller's future is dropped (client gone,
+    /// timeout, hedged request lost) while the worker is still busy.
+    fn cancel_on_drop(&self, request_id: [u8; 16]) -> CancelOnDrop {
+        CancelOnDrop {
+            connection: self.connection.clone(),
+            format: self.format,
+            request_id,
+            armed: true,
+        }
+    }
+
     /// Health check over QUIC (faster than HTTP, no TLS handshake).
     pub async fn health(&self) -> Result<WorkerHealth, Box<dyn std::error::Error + Send + Sync>> {
         let (mut send, mut recv) = self.connection.open_bi().await?;
-        write_message(&mut send, &Message::HealthRequest).await?;
+        write_message(&mut send, &Message::HealthRequest, self.format).await?;
         send.finish()?;
 
-        let response = read_message(&mut recv).await?;
+        let response = read_message(&mut recv, self.format, &self.limits).await?;
         match response {
             Message::HealthResponse(health) => Ok(health),
             _ => Err("unexpected response type".into()),
         }
     }
 
+    /// What the worker can serve. Fails on workers that predate
+    /// capability advertisement (they don't answer).
+    pub async fn capabilities(&self) -> Result<WorkerCapabilities, Box<dyn std::error::Error + Send + Sync>> {
+        let (mut send, mut recv) = self.connection.open_bi().await?;
+        write_message(&mut send, &Message::CapabilitiesRequest, self.format).await?;
+        send.finish()?;
+
+        let response = read_message(&mut recv, self.format, &self.limits).await?;
+        match response {
+            Message::Capabilities(capabilities) => Ok(capabilities),
+            _ => Err("unexpected response type".into()),
+        }
+    }
+
+    /// Name and token of a worker that dialed in (encrypted). Workers the
+    /// API dialed itself don't answer.
+    pub async fn enrollment(&self) -> Result<WorkerEnrollment, Box<dyn std::error::Error + Send + Sync>> {
+        let (mut send, mut recv) = self.connection.open_bi().await?;
+        write_message(&mut send, &Message::EnrollmentRequest, self.format).await?;
+        send.finish()?;
+
+        let response = read_message(&mut recv, self.format, &self.limits).await?;
+        match response {
+            Message::Enrollment(encrypted) => Ok(self.format.from_slice(&self.noise.decrypt(&encrypted)?)?),
+            _ => Err("unexpected response type".into()),
+        }
+    }
+
     pub fn remote_addr(&self) -> SocketAddr {
         self.addr
     }
+
+    /// Resolves when the connection closes (by either side, or by the idle
+    /// timeout), without borrowing `self`. The Noise session dies with it.
+    pub fn closed(&self) -> impl std::future::Future<Output = quinn::ConnectionError> + Send + 'static {
+        let connection = self.connection.clone();
+        async move { connection.closed().await }
+    }
+
+    /// Close the connection, e.g. after a failed health check, so the pool
+    /// reconnects with a fresh attestation and handshake.
+    pub fn close(&self, reason: &str) {
+        self.connection.close(0u32.into(), reason.as_bytes());
+    }
+}
+
+struct CancelOnDrop {
+    connection: quinn::Connection,
+    format: WireFormat,
+    request_id: [u8; 16],
+    armed: bool,
+}
+
+impl CancelOnDrop {
+    /// The response arrived; nothing to cancel.
+    fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else { return };
+        let (connection, format, request_id) = (self.connection.clone(), self.format, self.request_id);
+        runtime.spawn(async move {
+            if let Err(e) = send_cancel(&connection, format, request_id).await {
+                debug!("QUIC cancel failed: {}", e);
+            }
+        });
+    }
+}
+
+async fn send_cancel(
+    conn: &quinn::Connection,
+    format: WireFormat,
+    request_id: [u8; 16],
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (mut send, mut recv) = conn.open_bi().await?;
+    write_message(&mut send, &Message::Cancel { request_id }, format).await?;
+    send.finish()?;
+    // No response; wait for the worker to finish its side of the stream
+    recv.read_to_end(0).await?;
+    Ok(())
 }
 
 async fn request_attestation(
     conn: &quinn::Connection,
+    format: WireFormat,
 ) -> Result<AttestationBundle, Box<dyn std::error::Error + Send + Sync>> {
     let (mut send, mut recv) = conn.open_bi().await?;
-    write_message(&mut send, &Message::AttestationRequest).await?;
+    write_message(&mut send, &Message::AttestationRequest, format).await?;
     send.finish()?;
 
-    let msg = read_message(&mut recv).await?;
+    let msg = read_message(&mut recv, format, &FrameLimits::default()).await?;
     match msg {
         Message::Attestation(bundle) => Ok(bundle),
         _ => Err("expected attestation response".into()),
     }
 }
 
-fn verify_attestation(bundle: &AttestationBundle) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    use sha2::{Digest, Sha256};
+/// One message of a streamed response, sent in `sequence` order on the
+/// request's stream until `is_final`.
+trait StreamEvent: DeserializeOwned + Send + 'static {
+    /// Ciphertext, if `msg` is this event type
+    fn ciphertext(msg: Message) -> Option<Vec<u8>>;
+    fn request_id(&self) -> [u8; 16];
+    fn sequence(&self) -> u32;
+    fn is_final(&self) -> bool;
+}
 
-    // Verify binding: H(quote || static_key) == binding_sig
-    let mut hasher = Sha256::new();
-    hasher.update(&bundle.quote);
-    hasher.update(&bundle.static_key);
-    let expected = hasher.finalize();
+impl StreamEvent for StreamChunk {
+    fn ciphertext(msg: Message) -> Option<Vec<u8>> {
+        match msg {
+            Message::EncryptedStreamChunk(encrypted) => Some(encrypted),
+            _ => None,
+        }
+    }
+    fn request_id(&self) -> [u8; 16] {
+        self.request_id
+    }
+    fn sequence(&self) -> u32 {
+        self.sequence
+    }
+    fn is_final(&self) -> bool {
+        self.is_final
+    }
+}
 
-    if bundle.binding_sig != expected.as_slice() {
-        return Err("attestation binding signature mismatch".into());
+impl StreamEvent for LlmEvent {
+    fn ciphertext(msg: Message) -> Option<Vec<u8>> {
+        match msg {
+            Message::EncryptedLlmEvent(encrypted) => Some(encrypted),
+            _ => None,
+        }
+    }
+    fn request_id(&self) -> [u8; 16] {
+        self.request_id
+    }
+    fn sequence(&self) -> u32 {
+        self.sequence
+    }
+    fn is_final(&self) -> bool {
+        self.is_final
+    }
+}
+
+/// Read and decrypt the next event of a streamed response, checking it
+/// belongs to `request_id` and arrives in order.
+async fn read_stream_event<T: StreamEvent>(
+    recv: &mut quinn::RecvStream,
+    noise: &NoiseClient,
+    format: WireFormat,
+    limits: &FrameLimits,
+    request_id: [u8; 16],
+    expected_sequence: u32,
+) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+    let message = read_message(recv, format, limits).await?;
+    let encrypted = T::ciphertext(message).ok_or("unexpected response type")?;
+
+    let decrypted = noise.decrypt(&encrypted)?;
+    let event: T = format.from_slice(&decrypted)?;
+
+    if event.request_id() != request_id {
+        return Err("stream chunk request id mismatch".into());
+    }
+    if event.sequence() != expected_sequence {
+        return Err(format!(
+            "stream chunk out of order: expected {}, got {}",
+            expected_sequence,
+            event.sequence()
+        )
+        .into());
     }
+    Ok(event)
+}
+
+async fn verify_attestation(
+    bundle: &AttestationBundle,
+    policy: &AttestationPolicy,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    verify_binding(bundle)?;
 
     match bundle.tee_type {
         TeeType::Insecure => {
+            if !policy.allow_insecure {
+                return Err("insecure attestation rejected (TEE required)".into());
+            }
             warn!("accepting insecure attestation (development mode)");
             Ok(())
         }
         TeeType::SevSnp => {
-            // TODO: verify AMD SEV-SNP attestation chain
-            Err("SEV-SNP verification not yet implemented".into())
+            let verifier = policy
+                .snp
+                .as_ref()
+                .ok_or("SEV-SNP attestation not configured (set SNP_CERT_BUNDLE)")?;
+            let vcek = bundle.certs.first().map(|c| c.as_slice());
+            let report = match &policy.log {
+                Some(log) => {
+                    let report = verifier.verify_report(&bundle.quote, &bundle.static_key, vcek)?;
+                    if !log.allows_snp(&report.measurement).await? {
+                        return Err(format!(
+                            "SEV-SNP measurement {} is not in the measurement log",
+                            hex::encode(report.measurement)
+                        )
+                        .into());
+                    }
+                    report
+                }
+                None => verifier.verify(&bundle.quote, &bundle.static_key, vcek)?,
+            };
+            info!(
+                "SEV-SNP report verified: measurement={} tcb={:#x}",
+                hex::encode(report.measurement),
+                report.reported_tcb
+            );
+            Ok(())
         }
         TeeType::Tdx => {
-            // TODO: verify Intel TDX attestation chain
-            Err("TDX verification not yet implemented".into())
+            let verifier = policy
+                .tdx
+                .as_ref()
+                .ok_or("TDX attestation not configured (set TDX_COLLATERAL)")?;
+            let quote = match &policy.log {
+                Some(log) => {
+                    let quote = verifier.verify_quote(&bundle.quote, &bundle.static_key)?;
+                    if !log.allows_tdx(&quote).await? {
+                        return Err(format!(
+                            "TDX mrtd {} is not in the measurement log",
+                            hex::encode(quote.mr_td)
+                        )
+                        .into());
+                    }
+                    quote
+                }
+                None => verifier.verify(&bundle.quote, &bundle.static_key)?,
+            };
+            info!(
+                "TDX quote verified: mrtd={} rtmr0={}",
+                hex::encode(quote.mr_td),
+                hex::encode(quote.rtmr[0])
+            );
+            Ok(())
         }
     }
 }
 
+/// Verify binding: H(quote || static_key) == binding_sig
+fn verify_binding(bundle: &AttestationBundle) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    hasher.update(&bundle.quote);
+    hasher.update(&bundle.static_key);
+    let expected = hasher.finalize();
+
+    if bundle.binding_sig != expected.as_slice() {
+        return Err("attestation binding signature mismatch".into());
+    }
+    Ok(())
+}
+
 async fn send_noise_handshake(
     conn: &quinn::Connection,
     handshake_msg: &[u8],
+    format: WireFormat,
 ) -> Result<(Vec<u8>, [u8; 16]), Box<dyn std::error::Error + Send + Sync>> {
     let (mut send, mut recv) = conn.open_bi().await?;
-    write_message(&mut send, &Message::NoiseHandshake(handshake_msg.to_vec())).await?;
+    write_message(&mut send, &Message::NoiseHandshake(handshake_msg.to_vec()), format).await?;
     send.finish()?;
 
-    let msg = read_message(&mut recv).await?;
+    let msg = read_message(&mut recv, format, &FrameLimits::default()).await?;
     match msg {
         Message::NoiseHandshakeResponse {
             handshake,
@@ -220,3 +690,28 @@ async fn send_noise_handshake(
         _ => Err("expected handshake response".into()),
     }
 }
+
+async fn send_noise_finish(
+    conn: &quinn::Connection,
+    handshake_msg: &[u8],
+    session_id: [u8; 16],
+    format: WireFormat,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (mut send, mut recv) = conn.open_bi().await?;
+    write_message(
+        &mut send,
+        &Message::NoiseHandshakeFinish {
+            handshake: handshake_msg.to_vec(),
+            session_id: session_id.to_vec(),
+        },
+        format,
+    )
+    .await?;
+    send.finish()?;
+
+    match read_message(&mut recv, format, &FrameLimits::default()).await {
+        Ok(Message::NoiseHandshakeComplete) => Ok(()),
+        Ok(_) => Err("expected handshake completion".into()),
+        Err(e) => Err(format!("worker rejected API key: {}", e).into()),
+    }
+}
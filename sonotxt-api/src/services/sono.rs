@@ -7,6 +7,10 @@
 //! 2. Tracks cumulative spend per channel, signs state updates
 //! 3. Settles channels via cooperativeClose when user requests it
 //!
+//! Channel state is persisted to `sono_channels` on every charge, so a
+//! restart reloads spend and nonce instead of rebuilding them from chain.
+//! Nonces never go backwards: the DB write is guarded on the stored nonce.
+//!
 //! Price oracle:
 //! - Queries Asset Hub AssetConversion pallet for DOT/USDC pool reserves
 //! - Computes DOT price in USD → sonoPerDot
@@ -22,10 +26,13 @@ use alloy::{
     sol_types::SolEvent,
 };
 use anyhow::{Context, Result};
+use sqlx::{PgConnection, PgPool};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{error, info, warn};
 
+use crate::services::ledger::{self, Account, Kind, Posting};
+
 // Generate Rust bindings from Solidity ABI
 sol! {
     #[sol(rpc)]
@@ -154,6 +161,7 @@ impl Default for PriceInfo {
 /// SONO service — manages payment channels from the provider side
 pub struct SonoService {
     config: SonoConfig,
+    db: PgPool,
     /// Active channels: user address → state
     channels: Arc<RwLock<std::collections::HashMap<Address, ChannelState>>>,
     /// Current price info
@@ -161,7 +169,7 @@ pub struct SonoService {
 }
 
 impl SonoService {
-    pub fn new(config: SonoConfig) -> Self {
+    pub fn new(config: SonoConfig, db: PgPool) -> Self {
         let price_info = PriceInfo {
             txt_usd_base: config.sono_price_usd,
             txt_usd_fiat: config.sono_price_usd * (1.0 + config.fiat_premium),
@@ -169,6 +177,7 @@ impl SonoService {
         };
         Self {
             config,
+            db,
             channels: Arc::new(RwLock::new(std::collections::HashMap::new())),
             price: Arc::new(RwLock::new(price_info)),
         }
@@ -194,8 +203,16 @@ impl SonoService {
 
     /// Charge a user for service usage (off-chain)
     /// Returns the new cumulative spent amount, or error if insufficient
-    pub async fn charge(&self, user: &Address, amount: U256) -> Result<U256> {
+    ///
+    /// `key` is the idempotency key of the ledger transaction the charge
+    /// belongs to; a charge whose key is already recorded is not applied again.
+    pub async fn charge(&self, user: &Address, amount: U256, key: &str) -> Result<U256> {
         let mut channels = self.channels.write().await;
+        let mut tx = self.db.begin().await.context("charge channel")?;
+        if !record_op(&mut tx, key, user, "charge", amount).await? {
+            return Ok(channels.get(user).map_or(U256::ZERO, |ch| ch.spent));
+        }
+
         let ch = channels
             .get_mut(user)
             .context("no active channel")?;
@@ -205,11 +222,51 @@ impl SonoService {
             anyhow::bail!("insufficient channel balance");
         }
 
-        ch.spent = new_spent;
-        ch.nonce += 1;
+        // Persist before acknowledging: an unrecorded charge would be lost on restart.
+        // The write lock is held so DB writes land in nonce order.
+        let updated = ChannelState {
+            spent: new_spent,
+            nonce: ch.nonce + 1,
+            ..ch.clone()
+        };
+        persist_channel_on(&mut tx, &updated).await?;
+        tx.commit().await.context("charge channel")?;
+        *ch = updated;
         Ok(new_spent)
     }
 
+    /// Give back part of what a user was charged (off-chain), e.g. for a
+    /// failed job. Returns the new cumulative spent amount.
+    ///
+    /// `key` is the idempotency key of the ledger transaction the refund
+    /// belongs to. It is recorded with the channel state, and a refund whose
+    /// key is already recorded is not applied again.
+    pub async fn refund(&self, user: &Address, amount: U256, key: &str) -> Result<U256> {
+        let mut channels = self.channels.write().await;
+        let mut tx = self.db.begin().await.context("refund channel")?;
+        if !record_op(&mut tx, key, user, "refund", amount).await? {
+            return Ok(channels.get(user).map_or(U256::ZERO, |ch| ch.spent));
+        }
+
+        let ch = channels
+            .get_mut(user)
+            .context("no active channel")?;
+
+        if amount > ch.spent {
+            anyhow::bail!("refund exceeds channel spend");
+        }
+
+        let updated = ChannelState {
+            spent: ch.spent - amount,
+            nonce: ch.nonce + 1,
+            ..ch.clone()
+        };
+        persist_channel_on(&mut tx, &updated).await?;
+        tx.commit().await.context("refund channel")?;
+        *ch = updated;
+        Ok(ch.spent)
+    }
+
     /// Sign a state update for the user (they can verify off-chain or use for dispute)
     pub async fn sign_state(&self, user: &Address) -> Result<(U256, u64, Vec<u8>)> {
         let channels = self.channels.read().await;
@@ -237,7 +294,10 @@ impl SonoService {
             .sign_message(state_hash.as_slice())
             .await?;
 
-        Ok((ch.spent, ch.nonce, <[u8; 65]>::from(sig).to_vec()))
+        let sig = <[u8; 65]>::from(sig).to_vec();
+        record_signed_state(&self.db, ch, &sig).await?;
+
+        Ok((ch.spent, ch.nonce, sig))
     }
 
     /// Cooperatively close a channel on-chain
@@ -263,9 +323,13 @@ impl SonoService {
             "channel settled"
         );
 
+        let spent = ch.spent;
+
         // Remove from tracking
         drop(channels);
         self.channels.write().await.remove(user);
+        self.close_channel(user).await?;
+        self.record_settlement(user, spent, receipt.transaction_hash).await?;
 
         Ok(())
     }
@@ -279,7 +343,10 @@ impl SonoService {
             "SONO listener starting"
         );
 
-        // Sync existing channels from on-chain state
+        // Restore persisted spend, then reconcile with on-chain state
+        if let Err(e) = self.load_channels().await {
+            error!("failed to load persisted channels: {}", e);
+        }
         if let Err(e) = self.sync_channels().await {
             warn!("failed to sync channels on start: {}", e);
         }
@@ -551,6 +618,81 @@ impl SonoService {
         Ok(provider)
     }
 
+    /// Upsert a channel's state. Refuses to move the stored nonce backwards.
+    async fn persist_channel(&self, ch: &ChannelState) -> Result<()> {
+        let mut conn = self.db.acquire().await.context("persist channel state")?;
+        persist_channel_on(&mut conn, ch).await
+    }
+
+    /// Post what a settled channel paid on-chain to the ledger. Keyed by the
+    /// settlement transaction, so a cooperative close and the event it
+    /// emits are recorded once.
+    async fn record_settlement(&self, user: &Address, spent: U256, tx_hash: impl std::fmt::Display) -> Result<()> {
+        let spent = i64::try_from(spent).context("settled amount out of range")?;
+        if spent == 0 {
+            return Ok(());
+        }
+        let posting = Posting::new(format!("channel-settlement:{}", tx_hash), Kind::ChannelSettlement)
+            .channel(user)
+            .transfer(Account::External, Account::Channels, spent);
+        let mut tx = self.db.begin().await.context("begin settlement")?;
+        ledger::post(&mut tx, &posting).await?;
+        tx.commit().await.context("commit settlement")?;
+        Ok(())
+    }
+
+    /// Mark a channel closed. The row (and its nonce) is kept so a reopened
+    /// channel continues from the last nonce.
+    async fn close_channel(&self, user: &Address) -> Result<()> {
+        sqlx::query("UPDATE sono_channels SET closed = TRUE, updated_at = NOW() WHERE user_address = $1")
+            .bind(user.to_string())
+            .execute(&self.db)
+            .await
+            .context("close channel")?;
+        Ok(())
+    }
+
+    /// Nonce a reopened channel starts from: one past the user's last
+    /// persisted nonce, so no state signed on the new channel reuses a nonce
+    /// already signed on a closed one.
+    async fn reopen_nonce(&self, user: &Address) -> Result<u64> {
+        let nonce: Option<i64> = sqlx::query_scalar(
+            "SELECT nonce FROM sono_channels WHERE user_address = $1",
+        )
+        .bind(user.to_string())
+        .fetch_optional(&self.db)
+        .await
+        .context("load channel nonce")?;
+        Ok(nonce.map_or(0, |n| n as u64 + 1))
+    }
+
+    /// Load open channels from the DB into memory.
+    async fn load_channels(&self) -> Result<()> {
+        let rows: Vec<(String, String, String, i64)> = sqlx::query_as(
+            "SELECT user_address, deposit, spent, nonce FROM sono_channels WHERE closed = FALSE",
+        )
+        .fetch_all(&self.db)
+        .await
+        .context("load channels")?;
+
+        let mut channels = self.channels.write().await;
+        for (user, deposit, spent, nonce) in rows {
+            let (Ok(user), Ok(deposit), Ok(spent)) =
+                (user.parse::<Address>(), deposit.parse::<U256>(), spent.parse::<U256>())
+            else {
+                warn!(user = %user, "skipping malformed persisted channel");
+                continue;
+            };
+            channels.insert(user, ChannelState { user, deposit, spent, nonce: nonce as u64 });
+        }
+
+        info!(count = channels.len(), "loaded persisted channels");
+        Ok(())
+    }
+
+    /// Reconcile persisted channels and recent ChannelOpened events against
+    /// on-chain state. Spend and nonce take the larger of the two sides;
+    /// channels with no on-chain deposit are marked closed.
     async fn sync_channels(&self) -> Result<()> {
         let provider = self.make_provider().await?;
         let current_block = provider.get_block_number().await?;
@@ -567,38 +709,48 @@ impl SonoService {
 
         let logs = provider.get_logs(&filter).await?;
         let contract = SonoToken::new(self.config.contract, &provider);
-        let mut synced = 0;
 
+        let mut users: Vec<Address> = self.channels.read().await.keys().copied().collect();
         for log in &logs {
             if let Ok(event) = SonoToken::ChannelOpened::decode_log(&log.inner) {
-                if event.service != service_addr {
-                    continue;
+                if event.service == service_addr && !users.contains(&event.user) {
+                    users.push(event.user);
                 }
-                // Check current on-chain state (channel might be settled already)
-                let ch = contract
-                    .getChannel(event.user, service_addr)
-                    .call()
-                    .await;
-
-                if let Ok(on_chain) = ch {
-                    if on_chain.deposit > U256::ZERO {
-                        let mut channels = self.channels.write().await;
-                        channels.entry(event.user).or_insert_with(|| {
-                            synced += 1;
-                            ChannelState {
-                                user: event.user,
-                                deposit: on_chain.deposit,
-                                spent: on_chain.spent,
-                                nonce: on_chain.nonce,
-                            }
-                        });
-                    }
+            }
+        }
+
+        let mut synced = 0;
+        let mut closed = 0;
+
+        for user in users {
+            // Check current on-chain state (channel might be settled already)
+            let Ok(on_chain) = contract.getChannel(user, service_addr).call().await else {
+                continue;
+            };
+
+            let mut channels = self.channels.write().await;
+            if on_chain.deposit == U256::ZERO {
+                if channels.remove(&user).is_some() {
+                    drop(channels);
+                    self.close_channel(&user).await?;
+                    closed += 1;
                 }
+                continue;
             }
+
+            let (spent, nonce) = match channels.get(&user) {
+                Some(ch) => (ch.spent.max(on_chain.spent), ch.nonce.max(on_chain.nonce)),
+                None => (on_chain.spent, self.reopen_nonce(&user).await?.max(on_chain.nonce)),
+            };
+            let state = ChannelState { user, deposit: on_chain.deposit, spent, nonce };
+
+            self.persist_channel(&state).await?;
+            channels.insert(user, state);
+            synced += 1;
         }
 
-        if synced > 0 {
-            info!(count = synced, "synced existing channels from chain");
+        if synced > 0 || closed > 0 {
+            info!(synced = synced, closed = closed, "reconciled channels with chain");
         }
         Ok(())
     }
@@ -632,12 +784,19 @@ impl SonoService {
             if let Ok(event) = SonoToken::ChannelOpened::decode_log(&log.inner) {
                 if event.service == service_addr {
                     let mut channels = self.channels.write().await;
-                    channels.insert(event.user, ChannelState {
+                    // Lookback can replay opens we already track — keep their spend
+                    if channels.contains_key(&event.user) {
+                        continue;
+                    }
+                    // A reopened channel continues past the last persisted nonce
+                    let state = ChannelState {
                         user: event.user,
                         deposit: event.deposit,
                         spent: U256::ZERO,
-                        nonce: 0,
-                    });
+                        nonce: self.reopen_nonce(&event.user).await?,
+                    };
+                    self.persist_channel(&state).await?;
+                    channels.insert(event.user, state);
                     info!(user = %event.user, deposit = %event.deposit, "channel opened");
                     count += 1;
                 }
@@ -657,7 +816,12 @@ impl SonoService {
                 if event.service == service_addr {
                     let mut channels = self.channels.write().await;
                     if let Some(ch) = channels.get_mut(&event.user) {
-                        ch.deposit = event.total;
+                        let updated = ChannelState {
+                            deposit: event.total,
+                            ..ch.clone()
+                        };
+                        self.persist_channel(&updated).await?;
+                        *ch = updated;
                         info!(user = %event.user, total = %event.total, "channel topped up");
                         count += 1;
                     }
@@ -677,6 +841,10 @@ impl SonoService {
             if let Ok(event) = SonoToken::ChannelSettled::decode_log(&log.inner) {
                 if event.service == service_addr {
                     self.channels.write().await.remove(&event.user);
+                    self.close_channel(&event.user).await?;
+                    if let Some(hash) = log.transaction_hash {
+                        self.record_settlement(&event.user, event.spent, hash).await?;
+                    }
                     info!(user = %event.user, spent = %event.spent, "channel settled on-chain");
                     count += 1;
                 }
@@ -687,3 +855,182 @@ impl SonoService {
         Ok(count)
     }
 }
+
+/// `persist_channel` on a given connection, e.g. a transaction that also
+/// records the operation that changed the state.
+async fn persist_channel_on(conn: &mut PgConnection, ch: &ChannelState) -> Result<()> {
+    let rows = sqlx::query(
+        r#"
+        INSERT INTO sono_channels (user_address, deposit, spent, nonce, closed)
+        VALUES ($1, $2, $3, $4, FALSE)
+        ON CONFLICT (user_address) DO UPDATE
+        SET deposit = EXCLUDED.deposit,
+            spent = EXCLUDED.spent,
+            nonce = EXCLUDED.nonce,
+            closed = FALSE,
+            updated_at = NOW()
+        WHERE sono_channels.nonce <= EXCLUDED.nonce
+        "#,
+    )
+    .bind(ch.user.to_string())
+    .bind(ch.deposit.to_string())
+    .bind(ch.spent.to_string())
+    .bind(ch.nonce as i64)
+    .execute(conn)
+    .await
+    .context("persist channel state")?
+    .rows_affected();
+
+    if rows == 0 {
+        anyhow::bail!("stale channel state for {} (nonce {})", ch.user, ch.nonce);
+    }
+    Ok(())
+}
+
+/// Record a state the service signed. Re-signing the same state is fine;
+/// a different spend at a nonce already signed would hand out a signature
+/// nothing records, so it is refused.
+async fn record_signed_state(db: &PgPool, ch: &ChannelState, sig: &[u8]) -> Result<()> {
+    let recorded: String = sqlx::query_scalar(
+        r#"
+        INSERT INTO sono_channel_states (user_address, spent, nonce, signature)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (user_address, nonce) DO UPDATE SET user_address = EXCLUDED.user_address
+        RETURNING spent
+        "#,
+    )
+    .bind(ch.user.to_string())
+    .bind(ch.spent.to_string())
+    .bind(ch.nonce as i64)
+    .bind(sig)
+    .fetch_one(db)
+    .await
+    .context("record signed state")?;
+    if recorded != ch.spent.to_string() {
+        anyhow::bail!("nonce {} already signed for {} at a different spend", ch.nonce, ch.user);
+    }
+    Ok(())
+}
+
+/// Record a keyed charge or refund. False if `key` was already recorded,
+/// i.e. the operation has been applied before.
+async fn record_op(conn: &mut PgConnection, key: &str, user: &Address, kind: &str, amount: U256) -> Result<bool> {
+    let rows = sqlx::query(
+        r#"
+        INSERT INTO sono_channel_ops (idempotency_key, user_address, kind, amount)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (idempotency_key) DO NOTHING
+        "#,
+    )
+    .bind(key)
+    .bind(user.to_string())
+    .bind(kind)
+    .bind(amount.to_string())
+    .execute(conn)
+    .await
+    .context("record channel operation")?
+    .rows_affected();
+    Ok(rows > 0)
+}
+
+#[cfg(test)]
+impl SonoService {
+    /// A service with no chain behind it, tracking `channels` as if they
+    /// had been opened on-chain
+    pub(crate) async fn for_test(db: PgPool, channels: &[ChannelState]) -> Self {
+        let config = SonoConfig {
+            rpc_url: "http://127.0.0.1:1".into(),
+            contract: Address::ZERO,
+            service_key: PrivateKeySigner::random(),
+            poll_interval: 12,
+            price_rpc_url: String::new(),
+            sono_price_usd: 0.01,
+            fiat_premium: 0.10,
+            price_interval: 300,
+        };
+        let service = Self::new(config, db);
+        for ch in channels {
+            service.persist_channel(ch).await.unwrap();
+            service.channels.write().await.insert(ch.user, ch.clone());
+        }
+        service
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(user: Address, spent: u64, nonce: u64) -> ChannelState {
+        ChannelState { user, deposit: U256::from(1_000u64), spent: U256::from(spent), nonce }
+    }
+
+    async fn stored(db: &PgPool, user: &Address) -> (String, i64) {
+        sqlx::query_as("SELECT spent, nonce FROM sono_channels WHERE user_address = $1")
+            .bind(user.to_string())
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_nonce_survives_restart(db: PgPool) {
+        let user = Address::repeat_byte(1);
+        let sono = SonoService::for_test(db.clone(), &[channel(user, 0, 4)]).await;
+        sono.charge(&user, U256::from(100u64), "job:a:hold").await.unwrap();
+        sono.charge(&user, U256::from(50u64), "job:b:hold").await.unwrap();
+        sono.refund(&user, U256::from(100u64), "job:a:refund").await.unwrap();
+
+        // A restarted service picks up spend and nonce where they were
+        let restarted = SonoService::for_test(db.clone(), &[]).await;
+        restarted.load_channels().await.unwrap();
+        let ch = restarted.get_channel(&user).await.unwrap();
+        assert_eq!((ch.spent, ch.nonce), (U256::from(50u64), 7));
+
+        // and keeps counting up from there
+        restarted.charge(&user, U256::from(10u64), "job:c:hold").await.unwrap();
+        assert_eq!(stored(&db, &user).await, ("60".to_string(), 8));
+
+        // A channel reopened after closing starts past the last nonce
+        restarted.close_channel(&user).await.unwrap();
+        assert_eq!(restarted.reopen_nonce(&user).await.unwrap(), 9);
+        let again = SonoService::for_test(db.clone(), &[]).await;
+        again.load_channels().await.unwrap();
+        assert!(again.get_channel(&user).await.is_none());
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_stale_write_is_ignored(db: PgPool) {
+        let user = Address::repeat_byte(2);
+        let sono = SonoService::for_test(db.clone(), &[channel(user, 300, 5)]).await;
+
+        // An older state, e.g. from a slower writer, doesn't overwrite a newer one
+        assert!(sono.persist_channel(&channel(user, 100, 4)).await.is_err());
+        assert_eq!(stored(&db, &user).await, ("300".to_string(), 5));
+
+        // The same nonce or a later one is written
+        sono.persist_channel(&channel(user, 300, 5)).await.unwrap();
+        sono.persist_channel(&channel(user, 400, 6)).await.unwrap();
+        assert_eq!(stored(&db, &user).await, ("400".to_string(), 6));
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_signed_nonce_is_not_resigned_at_another_spend(db: PgPool) {
+        let user = Address::repeat_byte(3);
+        SonoService::for_test(db.clone(), &[channel(user, 200, 3)]).await;
+
+        record_signed_state(&db, &channel(user, 200, 3), &[1; 65]).await.unwrap();
+        // Signing the same state again is fine
+        record_signed_state(&db, &channel(user, 200, 3), &[2; 65]).await.unwrap();
+        // A different spend at that nonce is refused
+        assert!(record_signed_state(&db, &channel(user, 150, 3), &[3; 65]).await.is_err());
+
+        let (spent, signature): (String, Vec<u8>) =
+            sqlx::query_as("SELECT spent, signature FROM sono_channel_states WHERE user_address = $1 AND nonce = 3")
+                .bind(user.to_string())
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!((spent.as_str(), signature), ("200", vec![1; 65]));
+    }
+}
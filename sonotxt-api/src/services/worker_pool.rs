@@ -3,45 +3,77 @@
 //! Service[Req, Rep] = async fn(Req) -> Result<Rep>
 //! Filter[Req, Rep]  = fn(Req, Service) -> Future<Rep>
 //!
-//! Filters compose with `and_then` to build services from independent modules:
+//! Filters (`filter.rs`) compose with `and_then` to build services from
+//! independent modules. Each pool call is
 //!
-//!   let tts = load_balance(workers)
-//!       .and_then(inflight_tracker)
+//!   let worker = leaf(worker)
 //!       .and_then(timeout(180s))
-//!       .and_then(retry(2))
-//!       .and_then(metrics("tts"))
-//!       .and_then(backup_request(p99))
+//!       .and_then(inflight(worker))
+//!       .and_then(metrics(worker, "tts"))
+//!   let tts = load_balance(pick, worker)
+//!       .and_then(retry(1, budget))        // or backup_request(p99)
 //!
 //! All GPU worker communication flows through this module.
 //! Callers never touch HTTP directly — they call a Service.
 //!
-//! Transport: QUIC + Noise_NK (primary), HTTP (fallback).
+//! Transport: QUIC + Noise (primary), HTTP (fallback).
 //! QUIC connections are established on init and maintained with health checks.
+//! Workers behind NAT dial in through `listen_reverse` instead; they are
+//! QUIC-only and leave the pool when their connection drops.
 
+use futures::{Stream, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::future::Future;
 use std::pin::Pin;
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
+use tokio::sync::{Notify, RwLock};
+use subtle::ConstantTimeEq;
 use tracing::{error, info, warn};
 
-use crate::services::quic_pool::QuicWorkerConn;
+use crate::services::filter::{Hedge, Inflight, LoadBalance, Metrics, Retry, RetryBudget, ServiceExt, Timeout};
+use crate::services::quic_pool::{AttestationPolicy, LlmEventStream, NoiseIdentity, QuicWorkerConn};
+use sonotxt_core::protocol::{EncryptedLlmRequest, LlmEvent, WorkerCapabilities, WorkerHealth};
+use sonotxt_core::SentenceParser;
+
+/// First QUIC reconnect delay; doubles on each failure up to the max.
+const RECONNECT_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Retries allowed per request on average, and retries banked for a
+/// quiet pool (see `RetryBudget`).
+const RETRY_RATIO: f64 = 0.2;
+const RETRY_RESERVE: u32 = 10;
+
+/// Backup TTS requests fire after this percentile of recent latencies,
+/// but never sooner than the floor.
+const HEDGE_PERCENTILE: f64 = 0.99;
+const HEDGE_MIN: Duration = Duration::from_millis(500);
+
+/// Time a dialed-in worker gets to attest, handshake and enroll.
+const ENROLL_TIMEOUT: Duration = Duration::from_secs(30);
 
 // Re-export wire types from core so existing imports still resolve
 pub use sonotxt_core::{
-    ServiceError, TtsRequest, TtsResponse, AsrRequest, AsrResponse,
-    LlmRequest, LlmResponse, LlmMessage,
+    Privacy, ServiceError, SynthesisOptions, Transport, TtsRequest, TtsResponse, AsrRequest, AsrResponse,
+    LlmRequest, LlmResponse, LlmMessage, StreamChunk,
 };
 
+/// Reply of a `Service` call.
+pub type ServiceFuture<Rep> = Pin<Box<dyn Future<Output = Result<Rep, ServiceError>> + Send>>;
+
+/// Sentences of an LLM reply, as they are generated.
+pub type SentenceStream = Pin<Box<dyn Stream<Item = Result<String, ServiceError>> + Send>>;
+
 // ── Service trait ──────────────────────────────────────────────────
 
 /// The core abstraction: an async function from Req to Rep.
 /// `type Service[Req, Rep] = Req => Future[Rep]`
 pub trait Service<Req: Send + 'static, Rep: Send + 'static>: Send + Sync + 'static {
-    fn call(&self, req: Req) -> Pin<Box<dyn Future<Output = Result<Rep, ServiceError>> + Send>>;
+    fn call(&self, req: Req) -> ServiceFuture<Rep>;
 }
 
 /// Wrap any async fn as a Service.
@@ -52,7 +84,7 @@ where
     Rep: Send + 'static,
     Fut: Future<Output = Result<Rep, ServiceError>> + Send + 'static,
 {
-    fn call(&self, req: Req) -> Pin<Box<dyn Future<Output = Result<Rep, ServiceError>> + Send>> {
+    fn call(&self, req: Req) -> ServiceFuture<Rep> {
         Box::pin((self)(req))
     }
 }
@@ -60,48 +92,214 @@ where
 // ── Worker ─────────────────────────────────────────────────────────
 
 pub struct Worker {
+    /// Registry name; the speech URL for workers from `WORKER_URLS`
+    pub name: String,
+    /// From the worker registry (vs. the static `WORKER_URLS` list)
+    pub registered: bool,
+    /// Dialed in through the reverse listener: QUIC only, no HTTP fallback
+    pub reverse: bool,
+    /// Finishing in-flight work, not sent new requests
+    pub draining: AtomicBool,
     pub speech_url: String,
     pub llm_url: String,
     pub healthy: AtomicBool,
+    /// Max concurrent requests this worker should be sent
+    pub capacity: u64,
     pub inflight: AtomicU64,
     pub total_requests: AtomicU64,
     pub total_failures: AtomicU64,
     pub last_latency_ms: AtomicU64,
-    /// QUIC+Noise connection (primary transport). None if connection failed.
+    /// QUIC+Noise connection (primary transport). None while disconnected.
     pub quic: RwLock<Option<QuicWorkerConn>>,
+    /// QUIC address, None for SSH-tunneled (HTTP-only) workers
+    pub quic_addr: Option<SocketAddr>,
+    /// Mirrors `quic.is_some()` for the synchronous status endpoint
+    pub quic_connected: AtomicBool,
+    /// Successful QUIC connects after the first one
+    pub quic_reconnects: AtomicU64,
+    /// Last health report received over QUIC
+    pub last_health: Mutex<Option<WorkerHealth>>,
+    /// Advertised over QUIC on connect. None for HTTP-only workers and
+    /// workers that predate capabilities: those are assumed to serve anything.
+    pub capabilities: Mutex<Option<WorkerCapabilities>>,
+    /// The `maintain_quic` task (or, for a reverse worker, the task
+    /// watching its connection), aborted when the worker leaves the pool
+    quic_task: Mutex<Option<tokio::task::AbortHandle>>,
+}
+
+impl Worker {
+    /// A disconnected worker for `spec`; `capacity` applies if the spec
+    /// sets none.
+    pub fn new(spec: WorkerSpec, registered: bool, capacity: u64) -> Self {
+        Self {
+            name: spec.name,
+            registered,
+            reverse: false,
+            draining: AtomicBool::new(spec.draining),
+            speech_url: spec.speech_url,
+            llm_url: spec.llm_url,
+            healthy: AtomicBool::new(true),
+            capacity: spec.capacity.unwrap_or(capacity).max(1),
+            inflight: AtomicU64::new(0),
+            total_requests: AtomicU64::new(0),
+            total_failures: AtomicU64::new(0),
+            last_latency_ms: AtomicU64::new(0),
+            quic: RwLock::new(None),
+            quic_addr: spec.quic_addr,
+            quic_connected: AtomicBool::new(false),
+            quic_reconnects: AtomicU64::new(0),
+            last_health: Mutex::new(None),
+            capabilities: Mutex::new(None),
+            quic_task: Mutex::new(None),
+        }
+    }
+
+    /// Whether this worker can serve a TTS request for `engine`/`voice`/`language`.
+    pub fn supports(&self, engine: &str, voice: &str, language: &str) -> bool {
+        self.capabilities
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_none_or(|caps| caps.supports(engine, voice, language))
+    }
+
+    /// Configured capacity, or the worker's own limit once it advertised one.
+    pub fn effective_capacity(&self) -> u64 {
+        match *self.capabilities.lock().unwrap() {
+            Some(ref caps) => caps.max_concurrency.max(1) as u64,
+            None => self.capacity,
+        }
+    }
+
+    /// Requests running on the worker: ours, or its own last reported count
+    /// if higher (other API instances share it).
+    pub fn load(&self) -> u64 {
+        let reported = self
+            .last_health
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(0, |h| h.jobs_processing as u64);
+        self.inflight.load(Ordering::Relaxed).max(reported)
+    }
 }
 
 impl std::fmt::Debug for Worker {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Worker")
+            .field("name", &self.name)
             .field("speech_url", &self.speech_url)
             .field("llm_url", &self.llm_url)
             .field("healthy", &self.healthy.load(Ordering::Relaxed))
+            .field("capacity", &self.capacity)
+            .field("inflight", &self.inflight.load(Ordering::Relaxed))
             .finish()
     }
 }
 
+/// Where to reach a worker, as registered or listed in `WORKER_URLS`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkerSpec {
+    pub name: String,
+    pub speech_url: String,
+    pub llm_url: String,
+    /// None: HTTP only
+    pub quic_addr: Option<SocketAddr>,
+    /// None: the pool's default (`WORKER_CAPACITY`)
+    pub capacity: Option<u64>,
+    pub draining: bool,
+}
+
+impl WorkerSpec {
+    /// Spec for a `WORKER_URLS` entry, which only names the speech URL:
+    /// the LLM is assumed on port + 10 and QUIC on :4433 of the same host.
+    /// SSH-tunneled (localhost) workers get HTTP only, as QUIC needs
+    /// direct UDP access.
+    pub fn from_url(speech_url: &str) -> Self {
+        // http://1.2.3.4:8080 → http://1.2.3.4:8090
+        // http://127.0.0.1:28080 → http://127.0.0.1:28090
+        let llm_url = if let Some(colon_pos) = speech_url.rfind(':') {
+            if let Ok(port) = speech_url[colon_pos + 1..].parse::<u16>() {
+                format!("{}:{}", &speech_url[..colon_pos], port + 10)
+            } else {
+                speech_url.replace(":8080", ":8090")
+            }
+        } else {
+            speech_url.replace(":8080", ":8090")
+        };
+        let quic_addr = (!speech_url.contains("127.0.0.1") && !speech_url.contains("localhost"))
+            .then(|| derive_quic_addr(speech_url));
+        Self {
+            name: speech_url.to_string(),
+            speech_url: speech_url.to_string(),
+            llm_url,
+            quic_addr,
+            capacity: None,
+            draining: false,
+        }
+    }
+}
+
+/// Holds one unit of a worker's `inflight` count until dropped.
+/// Used for calls whose lifetime outlasts a single future (streams).
+pub struct InflightGuard(Arc<Worker>);
+
+impl InflightGuard {
+    pub fn new(worker: Arc<Worker>) -> Self {
+        worker.inflight.fetch_add(1, Ordering::Relaxed);
+        Self(worker)
+    }
+}
+
+impl Drop for InflightGuard {
+    fn drop(&mut self) {
+        self.0.inflight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 // ── Concrete Services (the leaf nodes) ─────────────────────────────
 
 /// TTS Service: sends text to a worker, gets back audio.
+/// QUIC first (encrypted; dropping the call cancels it on the worker),
+/// HTTP if the worker has no QUIC connection or it fails, unless the
+/// request is private.
+/// Transport only: bookkeeping, timeouts and retries are filters.
 pub struct TtsService {
     http: Client,
     worker: Arc<Worker>,
 }
 
 impl Service<TtsRequest, TtsResponse> for TtsService {
-    fn call(&self, req: TtsRequest) -> Pin<Box<dyn Future<Output = Result<TtsResponse, ServiceError>> + Send>> {
+    fn call(&self, req: TtsRequest) -> ServiceFuture<TtsResponse> {
         let http = self.http.clone();
         let url = format!("{}/synthesize", self.worker.speech_url);
         let worker = self.worker.clone();
 
         Box::pin(async move {
-            worker.inflight.fetch_add(1, Ordering::Relaxed);
-            worker.total_requests.fetch_add(1, Ordering::Relaxed);
+            if let Some(result) = tts_over_quic(&worker, &req).await {
+                return result;
+            }
+            if req.privacy == Privacy::Private {
+                return Err(ServiceError::Failed(format!(
+                    "private request: no encrypted session to {}",
+                    worker.name
+                )));
+            }
+            if worker.reverse {
+                return Err(ServiceError::Unavailable);
+            }
+
             let start = Instant::now();
 
             #[derive(Serialize)]
-            struct Body { text: String, speaker: String, language: String }
+            struct Body {
+                engine: String,
+                text: String,
+                speaker: String,
+                language: String,
+                #[serde(flatten)]
+                options: SynthesisOptions,
+            }
 
             let mut builder = http.post(&url)
                 .header("Content-Type", "application/json");
@@ -109,23 +307,23 @@ impl Service<TtsRequest, TtsResponse> for TtsService {
                 builder = builder.header("Authorization", format!("Bearer {}", key));
             }
 
-            let result = builder
-                .json(&Body { text: req.text, speaker: req.speaker, language: req.language })
+            let response = builder
+                .json(&Body {
+                    engine: req.engine,
+                    text: req.text,
+                    speaker: req.speaker,
+                    language: req.language,
+                    options: req.options,
+                })
                 .send()
-                .await;
+                .await
+                .map_err(|e| ServiceError::Failed(format!("http: {}", e)))?;
 
-            worker.inflight.fetch_sub(1, Ordering::Relaxed);
             let runtime_ms = start.elapsed().as_millis() as u64;
 
-            let response = result.map_err(|e| {
-                worker.total_failures.fetch_add(1, Ordering::Relaxed);
-                ServiceError::Failed(format!("http: {}", e))
-            })?;
-
             if !response.status().is_success() {
                 let status = response.status();
                 let body = response.text().await.unwrap_or_default();
-                worker.total_failures.fetch_add(1, Ordering::Relaxed);
                 return Err(ServiceError::Failed(format!("tts {}: {}", status, body)));
             }
 
@@ -147,39 +345,138 @@ impl Service<TtsRequest, TtsResponse> for TtsService {
                 format: "wav".to_string(),
                 duration_seconds,
                 runtime_ms,
+                transport: Transport::Http,
             })
         })
     }
 }
 
+/// TTS over the worker's QUIC connection. None means use HTTP instead:
+/// no connection, or the transport failed.
+async fn tts_over_quic(worker: &Worker, req: &TtsRequest) -> Option<Result<TtsResponse, ServiceError>> {
+    let quic_guard = worker.quic.read().await;
+    let quic = quic_guard.as_ref()?;
+
+    let mut request_id = [0u8; 16];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut request_id);
+    let request = sonotxt_core::EncryptedTtsRequest {
+        request_id,
+        text: req.text.clone(),
+        voice: req.speaker.clone(),
+        speed: req.options.speed.unwrap_or(1.0),
+        language: req.language.clone(),
+        engine: req.engine.clone(),
+        instruct: req.options.instruct.clone(),
+        cfg_scale: req.options.cfg_scale,
+    };
+
+    let start = Instant::now();
+    match quic.encrypted_tts(&request).await {
+        Ok(resp) => Some(match resp.error {
+            Some(err) => Err(ServiceError::Failed(err)),
+            None => Ok(TtsResponse {
+                audio_data: resp.audio,
+                format: resp.format,
+                duration_seconds: resp.duration_seconds,
+                runtime_ms: start.elapsed().as_millis() as u64,
+                transport: Transport::Quic,
+            }),
+        }),
+        Err(e) => {
+            warn!("QUIC TTS failed for {}, HTTP fallback: {}", worker.speech_url, e);
+            None
+        }
+    }
+}
+
+/// Start an LLM request on the worker's QUIC connection. None means use
+/// HTTP instead: no connection, or the request couldn't be sent.
+async fn llm_over_quic(worker: &Worker, req: &LlmRequest, stream: bool) -> Option<LlmEventStream> {
+    let quic_guard = worker.quic.read().await;
+    let quic = quic_guard.as_ref()?;
+
+    let mut request_id = [0u8; 16];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut request_id);
+    let request = EncryptedLlmRequest {
+        request_id,
+        messages: req.messages.clone(),
+        max_tokens: req.max_tokens,
+        temperature: req.temperature,
+        stream,
+    };
+
+    match quic.encrypted_llm(&request).await {
+        Ok(events) => Some(events),
+        Err(e) => {
+            warn!("QUIC LLM failed for {}, HTTP fallback: {}", worker.llm_url, e);
+            None
+        }
+    }
+}
+
+/// Collect the events of a QUIC LLM request into one response.
+async fn collect_llm(mut events: LlmEventStream, start: Instant) -> Result<LlmResponse, ServiceError> {
+    let mut sentences = Vec::new();
+    while let Some(event) = events.next().await {
+        let event = event.map_err(|e| ServiceError::Failed(format!("quic llm: {}", e)))?;
+        if let Some(err) = event.error {
+            return Err(ServiceError::Failed(err));
+        }
+        sentences.extend(event.sentence);
+        if event.is_final {
+            return Ok(LlmResponse {
+                sentences,
+                full_response: event.full_response.unwrap_or_default(),
+                tokens: event.tokens,
+                runtime_ms: start.elapsed().as_millis() as u64,
+            });
+        }
+    }
+    Err(ServiceError::Failed("quic llm: stream ended early".into()))
+}
+
 /// ASR Service: sends audio to a worker, gets back text.
+/// QUIC first (encrypted), HTTP fallback.
 pub struct AsrService {
     http: Client,
     worker: Arc<Worker>,
 }
 
 impl Service<AsrRequest, AsrResponse> for AsrService {
-    fn call(&self, req: AsrRequest) -> Pin<Box<dyn Future<Output = Result<AsrResponse, ServiceError>> + Send>> {
+    fn call(&self, req: AsrRequest) -> ServiceFuture<AsrResponse> {
         let http = self.http.clone();
         let url = format!("{}/transcribe_base64", self.worker.speech_url);
         let worker = self.worker.clone();
 
         Box::pin(async move {
-            worker.inflight.fetch_add(1, Ordering::Relaxed);
+            {
+                let quic_guard = worker.quic.read().await;
+                if let Some(ref quic) = *quic_guard {
+                    match quic.encrypted_asr(&req.audio_base64).await {
+                        Ok(resp) => {
+                            if let Some(err) = resp.error {
+                                return Err(ServiceError::Failed(err));
+                            }
+                            return Ok(AsrResponse { text: resp.text });
+                        }
+                        Err(e) => warn!("QUIC ASR failed for {}, HTTP fallback: {}", worker.speech_url, e),
+                    }
+                }
+            }
+            if worker.reverse {
+                return Err(ServiceError::Unavailable);
+            }
 
             #[derive(Serialize)]
             struct Body { audio_base64: String }
             #[derive(Deserialize)]
             struct Resp { text: String }
 
-            let result = http.post(&url)
+            let response = http.post(&url)
                 .json(&Body { audio_base64: req.audio_base64 })
                 .send()
-                .await;
-
-            worker.inflight.fetch_sub(1, Ordering::Relaxed);
-
-            let response = result.map_err(|e| ServiceError::Failed(format!("http: {}", e)))?;
+                .await
+                .map_err(|e| ServiceError::Failed(format!("http: {}", e)))?;
             if !response.status().is_success() {
                 return Err(ServiceError::Failed(format!("asr: {}", response.status())));
             }
@@ -191,35 +488,39 @@ impl Service<AsrRequest, AsrResponse> for AsrService {
 }
 
 /// LLM Service: sends messages to a worker, gets back sentences.
+/// QUIC first (the conversation stays encrypted), HTTP fallback.
 pub struct LlmService {
     http: Client,
     worker: Arc<Worker>,
 }
 
 impl Service<LlmRequest, LlmResponse> for LlmService {
-    fn call(&self, req: LlmRequest) -> Pin<Box<dyn Future<Output = Result<LlmResponse, ServiceError>> + Send>> {
+    fn call(&self, req: LlmRequest) -> ServiceFuture<LlmResponse> {
         let http = self.http.clone();
         let url = format!("{}/chat_sentences", self.worker.llm_url);
         let worker = self.worker.clone();
 
         Box::pin(async move {
-            worker.inflight.fetch_add(1, Ordering::Relaxed);
-            let _start = Instant::now();
+            let start = Instant::now();
+            if let Some(events) = llm_over_quic(&worker, &req, false).await {
+                return collect_llm(events, start).await;
+            }
+            if worker.reverse {
+                return Err(ServiceError::Unavailable);
+            }
 
             #[derive(Serialize)]
             struct Body { messages: Vec<LlmMessage>, max_tokens: u32, temperature: f64 }
             #[derive(Deserialize)]
             struct Resp { sentences: Vec<String>, full_response: String, tokens: Option<u32> }
 
-            let result = http.post(&url)
+            let response = http.post(&url)
                 .json(&Body { messages: req.messages, max_tokens: req.max_tokens, temperature: req.temperature })
                 .send()
-                .await;
-
-            worker.inflight.fetch_sub(1, Ordering::Relaxed);
-            let runtime_ms = _start.elapsed().as_millis() as u64;
+                .await
+                .map_err(|e| ServiceError::Failed(format!("http: {}", e)))?;
+            let runtime_ms = start.elapsed().as_millis() as u64;
 
-            let response = result.map_err(|e| ServiceError::Failed(format!("http: {}", e)))?;
             if !response.status().is_success() {
                 return Err(ServiceError::Failed(format!("llm: {}", response.status())));
             }
@@ -235,6 +536,25 @@ impl Service<LlmRequest, LlmResponse> for LlmService {
     }
 }
 
+/// One worker's stack around a leaf service:
+/// `leaf andThen timeout andThen inflight andThen metrics`.
+/// The timeout sits inside the bookkeeping so it counts as a failure.
+fn worker_stack<Req, Rep>(
+    leaf: impl Service<Req, Rep>,
+    worker: Arc<Worker>,
+    timeout: Duration,
+    label: &'static str,
+) -> Arc<dyn Service<Req, Rep>>
+where
+    Req: Send + 'static,
+    Rep: Send + 'static,
+{
+    leaf.and_then(Timeout(timeout))
+        .and_then(Inflight(worker.clone()))
+        .and_then(Metrics { worker, label })
+        .boxed()
+}
+
 // ── WorkerPool: the composed service ──────────────────────────────
 
 /// The pool IS the service. Callers don't pick workers manually —
@@ -243,13 +563,25 @@ impl Service<LlmRequest, LlmResponse> for LlmService {
 ///
 /// Transport priority: QUIC+Noise (encrypted, fast) → HTTP (fallback).
 pub struct WorkerPool {
-    workers: Vec<Arc<Worker>>,
+    /// Current members. Requests keep their `Arc<Worker>`, so a worker
+    /// removed mid-request finishes it.
+    workers: Mutex<Vec<Arc<Worker>>>,
     http: Client,
+    /// Capacity of workers that don't set their own
+    default_capacity: u64,
+    attestation: Arc<AttestationPolicy>,
+    identity: Arc<NoiseIdentity>,
     rr_counter: AtomicU64,
     tts_timeout: Duration,
     asr_timeout: Duration,
     llm_timeout: Duration,
     max_retries: u32,
+    /// Shared by all retrying calls
+    retry_budget: Arc<RetryBudget>,
+    /// Latencies for TTS backup requests
+    tts_hedge: Hedge,
+    /// Wakes the job worker when a job is enqueued
+    job_wakeup: Notify,
 }
 
 /// Health response from HTTP /health (fallback)
@@ -260,102 +592,289 @@ struct HealthResponse {
 }
 
 impl WorkerPool {
-    /// Create pool and connect QUIC to each worker.
+    /// Create pool and connect QUIC to each worker in `urls` (comma
+    /// separated, may be empty). More join later through the registry.
     /// QUIC connections are best-effort — workers that don't respond
     /// fall back to HTTP.
-    pub async fn new(urls: &str, http: Client) -> Self {
-        let workers: Vec<Arc<Worker>> = urls
-            .split(',')
-            .map(|u| u.trim())
-            .filter(|u| !u.is_empty())
-            .map(|speech_url| {
-                // Derive LLM URL: same host, port + 10
-                // http://1.2.3.4:8080 → http://1.2.3.4:8090
-                // http://127.0.0.1:28080 → http://127.0.0.1:28090
-                let llm_url = if let Some(colon_pos) = speech_url.rfind(':') {
-                    if let Ok(port) = speech_url[colon_pos + 1..].parse::<u16>() {
-                        format!("{}:{}", &speech_url[..colon_pos], port + 10)
-                    } else {
-                        speech_url.replace(":8080", ":8090")
-                    }
-                } else {
-                    speech_url.replace(":8080", ":8090")
+    pub async fn new(
+        urls: &str,
+        http: Client,
+        capacity: u64,
+        attestation: Arc<AttestationPolicy>,
+        identity: Arc<NoiseIdentity>,
+    ) -> Self {
+        let pool = Self {
+            workers: Mutex::new(Vec::new()),
+            http,
+            default_capacity: capacity.max(1),
+            attestation,
+            identity,
+            rr_counter: AtomicU64::new(0),
+            tts_timeout: Duration::from_secs(180),
+            asr_timeout: Duration::from_secs(30),
+            llm_timeout: Duration::from_secs(60),
+            max_retries: 1,
+            retry_budget: Arc::new(RetryBudget::new(RETRY_RATIO, RETRY_RESERVE)),
+            tts_hedge: Hedge::new(HEDGE_PERCENTILE, HEDGE_MIN),
+            job_wakeup: Notify::new(),
+        };
+
+        for url in urls.split(',').map(|u| u.trim()).filter(|u| !u.is_empty()) {
+            pool.insert(WorkerSpec::from_url(url), false);
+        }
+        info!("worker pool: {} workers", pool.len());
+
+        pool
+    }
+
+    // ── Membership ─────────────────────────────────────────────
+
+    /// Snapshot of all members, draining ones included.
+    fn workers(&self) -> Vec<Arc<Worker>> {
+        self.workers.lock().unwrap().clone()
+    }
+
+    /// Members that take new requests.
+    fn serving(&self) -> Vec<Arc<Worker>> {
+        self.workers()
+            .into_iter()
+            .filter(|w| !w.draining.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    /// Add a worker, replacing any member of the same name. Each
+    /// QUIC-capable worker gets a task that keeps its connection up,
+    /// reconnecting with a fresh attestation + handshake whenever it drops.
+    fn insert(&self, spec: WorkerSpec, registered: bool) {
+        let worker = Arc::new(Worker::new(spec, registered, self.default_capacity));
+        if let Some(addr) = worker.quic_addr {
+            let task = tokio::spawn(maintain_quic(
+                worker.clone(),
+                addr,
+                self.attestation.clone(),
+                self.identity.clone(),
+            ));
+            *worker.quic_task.lock().unwrap() = Some(task.abort_handle());
+        }
+        info!("  {}: speech={} llm={} quic={:?}", worker.name, worker.speech_url, worker.llm_url, worker.quic_addr);
+
+        let mut workers = self.workers.lock().unwrap();
+        if let Some(pos) = workers.iter().position(|w| w.name == worker.name) {
+            let old = std::mem::replace(&mut workers[pos], worker);
+            retire(&old);
+        } else {
+            workers.push(worker);
+        }
+    }
+
+    /// Remove a worker. Requests already sent to it run to completion.
+    pub fn remove(&self, name: &str) -> bool {
+        let mut workers = self.workers.lock().unwrap();
+        let Some(pos) = workers.iter().position(|w| w.name == name) else {
+            return false;
+        };
+        retire(&workers.remove(pos));
+        info!("worker {} removed from pool", name);
+        true
+    }
+
+    /// Stop (or resume) sending new requests to a worker.
+    pub fn set_draining(&self, name: &str, draining: bool) -> bool {
+        let workers = self.workers.lock().unwrap();
+        let Some(worker) = workers.iter().find(|w| w.name == name) else {
+            return false;
+        };
+        if worker.draining.swap(draining, Ordering::Relaxed) != draining {
+            info!("worker {} {}", name, if draining { "draining" } else { "serving again" });
+        }
+        true
+    }
+
+    /// Bring registered members in line with the registry: add new
+    /// workers, reconnect ones whose endpoints changed, drop ones no longer
+    /// listed. `WORKER_URLS` members are left alone.
+    pub fn sync_registry(&self, specs: Vec<WorkerSpec>) {
+        let current = self.workers();
+
+        for worker in current.iter().filter(|w| w.registered) {
+            if !specs.iter().any(|s| s.name == worker.name) {
+                self.remove(&worker.name);
+            }
+        }
+
+        for spec in specs {
+            match current.iter().find(|w| w.name == spec.name) {
+                Some(w) if !w.registered => {
+                    warn!("registry worker {} shadows a WORKER_URLS or dialed-in worker, ignored", spec.name);
+                }
+                Some(w) if self.same_endpoints(w, &spec) => {
+                    self.set_draining(&spec.name, spec.draining);
+                }
+                Some(_) => {
+                    info!("worker {} endpoints changed, reconnecting", spec.name);
+                    self.insert(spec, true);
+                }
+                None => {
+                    info!("worker {} joined the pool", spec.name);
+                    self.insert(spec, true);
+                }
+            }
+        }
+    }
+
+    /// Accept workers dialing in on `addr` (workers behind NAT). A worker
+    /// joins once it passes attestation and the Noise handshake and
+    /// presents `token`, under the name it enrolls with.
+    pub async fn listen_reverse(self: Arc<Self>, addr: SocketAddr, token: String) {
+        let endpoint = match sonotxt_core::quic::server_endpoint(addr) {
+            Ok(endpoint) => endpoint,
+            Err(e) => {
+                error!("reverse QUIC listener on {} failed: {}", addr, e);
+                return;
+            }
+        };
+        info!("reverse QUIC listener on {}", addr);
+        let token = Arc::new(token);
+
+        while let Some(incoming) = endpoint.accept().await {
+            let pool = self.clone();
+            let endpoint = endpoint.clone();
+            let token = token.clone();
+            tokio::spawn(async move {
+                let remote = incoming.remote_address();
+                let enroll = async {
+                    let quic = QuicWorkerConn::accept(endpoint, incoming.await?, &pool.attestation, &pool.identity).await?;
+                    let enrollment = quic.enrollment().await?;
+                    Ok::<_, Box<dyn std::error::Error + Send + Sync>>((quic, enrollment))
+                };
+                let (quic, enrollment) = match tokio::time::timeout(ENROLL_TIMEOUT, enroll).await {
+                    Ok(Ok(enrolled)) => enrolled,
+                    Ok(Err(e)) => return warn!("worker dialing in from {} failed to enroll: {}", remote, e),
+                    Err(_) => return warn!("worker dialing in from {} timed out enrolling", remote),
                 };
-                Arc::new(Worker {
-                    speech_url: speech_url.to_string(),
-                    llm_url,
-                    healthy: AtomicBool::new(true),
-                    inflight: AtomicU64::new(0),
-                    total_requests: AtomicU64::new(0),
-                    total_failures: AtomicU64::new(0),
-                    last_latency_ms: AtomicU64::new(0),
-                    quic: RwLock::new(None),
-                })
-            })
-            .collect();
 
-        info!("worker pool: {} workers", workers.len());
-
-        // QUIC connections are established when workers have direct UDP access.
-        // For SSH-tunneled workers (localhost), skip QUIC and use HTTP only.
-        for worker in &workers {
-            if !worker.speech_url.contains("127.0.0.1") && !worker.speech_url.contains("localhost") {
-                let w = worker.clone();
-                tokio::spawn(async move {
-                    let quic_addr = derive_quic_addr(&w.speech_url);
-                    match QuicWorkerConn::connect(quic_addr).await {
-                        Ok(conn) => {
-                            info!("QUIC connected: {} → {}", w.speech_url, quic_addr);
-                            *w.quic.write().await = Some(conn);
-                        }
-                        Err(e) => {
-                            warn!("QUIC connect failed for {} (HTTP fallback): {}", w.speech_url, e);
-                        }
-                    }
-                });
+                let (a, b) = (enrollment.token.as_bytes(), token.as_bytes());
+                if !(a.len() == b.len() && bool::from(a.ct_eq(b))) {
+                    warn!("worker {} from {} presented a bad enrollment token", enrollment.name, remote);
+                    return quic.close("enrollment rejected");
+                }
+                pool.attach(enrollment.name, quic).await;
+            });
+        }
+    }
+
+    /// Add an enrolled dialed-in worker. It stays in the pool while its
+    /// connection is up; a reconnect under the same name replaces it.
+    async fn attach(self: &Arc<Self>, name: String, quic: QuicWorkerConn) {
+        let capabilities = match quic.capabilities().await {
+            Ok(caps) => {
+                info!("worker {} capabilities: {:?}", name, caps);
+                Some(caps)
+            }
+            Err(e) => {
+                info!("worker {} advertises no capabilities, routing anything to it: {}", name, e);
+                None
             }
+        };
+
+        let mut workers = self.workers.lock().unwrap();
+        let existing = workers.iter().position(|w| w.name == name);
+        if existing.is_some_and(|pos| !workers[pos].reverse) {
+            warn!("dialed-in worker {} shadows a configured worker, rejected", name);
+            return quic.close("name taken");
         }
 
-        for w in &workers {
-            info!("  speech={} llm={}", w.speech_url, w.llm_url);
+        let label = format!("quic://{}", quic.remote_addr());
+        let closed = quic.closed();
+        let spec = WorkerSpec {
+            name,
+            speech_url: label.clone(),
+            llm_url: label,
+            quic_addr: None,
+            capacity: None,
+            draining: false,
+        };
+        let worker = Arc::new(Worker {
+            reverse: true,
+            quic: RwLock::new(Some(quic)),
+            quic_connected: AtomicBool::new(true),
+            capabilities: Mutex::new(capabilities),
+            ..Worker::new(spec, false, self.default_capacity)
+        });
+
+        let pool = Arc::downgrade(self);
+        let watched = worker.clone();
+        let task = tokio::spawn(async move {
+            let reason = closed.await;
+            watched.quic_connected.store(false, Ordering::Relaxed);
+            warn!("dialed-in worker {} disconnected: {}", watched.name, reason);
+            if let Some(pool) = pool.upgrade() {
+                pool.remove_exact(&watched);
+            }
+        });
+        *worker.quic_task.lock().unwrap() = Some(task.abort_handle());
+        info!("  {}: dialed in from {}", worker.name, worker.speech_url);
+
+        if let Some(pos) = existing {
+            let old = std::mem::replace(&mut workers[pos], worker);
+            retire(&old);
+        } else {
+            workers.push(worker);
         }
+    }
 
-        Self {
-            workers,
-            http,
-            rr_counter: AtomicU64::new(0),
-            tts_timeout: Duration::from_secs(180),
-            asr_timeout: Duration::from_secs(30),
-            llm_timeout: Duration::from_secs(60),
-            max_retries: 1,
+    /// Remove `worker` itself, not a later member of the same name.
+    fn remove_exact(&self, worker: &Arc<Worker>) {
+        let mut workers = self.workers.lock().unwrap();
+        if let Some(pos) = workers.iter().position(|w| Arc::ptr_eq(w, worker)) {
+            workers.remove(pos);
+            info!("worker {} removed from pool", worker.name);
         }
     }
 
+    fn same_endpoints(&self, worker: &Worker, spec: &WorkerSpec) -> bool {
+        worker.speech_url == spec.speech_url
+            && worker.llm_url == spec.llm_url
+            && worker.quic_addr == spec.quic_addr
+            && worker.capacity == spec.capacity.unwrap_or(self.default_capacity).max(1)
+    }
+
     // ── Composed service calls ──────────────────────────────────
 
     /// TTS: text → audio. Load balanced, with timeout and retry.
-    /// `loadBalance andThen timeout(180s) andThen retry(1)`
-    pub async fn tts(&self, req: TtsRequest) -> Result<TtsResponse, ServiceError> {
-        self.with_retry(|worker| {
-            let svc = TtsService { http: self.http.clone(), worker: worker.clone() };
-            let req = req.clone();
-            async move {
-                tokio::time::timeout(self.tts_timeout, svc.call(req))
-                    .await
-                    .map_err(|_| ServiceError::Timeout)?
-            }
-        }).await
+    pub async fn tts(self: &Arc<Self>, req: TtsRequest) -> Result<TtsResponse, ServiceError> {
+        self.tts_service().and_then(self.retry()).call(req).await
+    }
+
+    /// `loadBalance` over the workers serving the request's engine, voice
+    /// and language.
+    fn tts_service(self: &Arc<Self>) -> LoadBalance<TtsRequest, TtsResponse> {
+        let pool = self.clone();
+        LoadBalance::new(move |req: &TtsRequest| {
+            let worker = pool.pick_for(&req.engine, &req.speaker, &req.language, req.privacy)?;
+            let leaf = TtsService { http: pool.http.clone(), worker: worker.clone() };
+            Ok(worker_stack(leaf, worker, pool.tts_timeout, "tts"))
+        })
+    }
+
+    fn retry(&self) -> Retry {
+        Retry {
+            max_retries: self.max_retries,
+            backoff: Duration::from_millis(100),
+            budget: self.retry_budget.clone(),
+        }
     }
 
     /// Encrypted TTS: text encrypted end-to-end via Noise channel.
     /// Text never hits disk on the worker. For private inference.
     pub async fn encrypted_tts(
         &self,
+        engine: &str,
         text: &str,
         voice: &str,
         language: &str,
     ) -> Result<TtsResponse, ServiceError> {
-        let worker = self.pick().ok_or(ServiceError::Unavailable)?;
+        let worker = self.pick_for(engine, voice, language, Privacy::Private)?;
         let quic_guard = worker.quic.read().await;
         let quic = quic_guard.as_ref().ok_or(ServiceError::Unavailable)?;
 
@@ -368,6 +887,9 @@ impl WorkerPool {
             voice: voice.to_string(),
             speed: 1.0,
             language: language.to_string(),
+            engine: engine.to_string(),
+            instruct: None,
+            cfg_scale: None,
         };
 
         worker.inflight.fetch_add(1, Ordering::Relaxed);
@@ -401,14 +923,71 @@ impl WorkerPool {
             format: response.format,
             duration_seconds: response.duration_seconds,
             runtime_ms,
+            transport: Transport::Quic,
         })
     }
 
+    /// Streaming encrypted TTS: audio chunks arrive as the worker finishes
+    /// each text segment, so long texts start playing almost immediately.
+    /// QUIC only — there is no HTTP fallback for streaming.
+    pub async fn encrypted_tts_stream(
+        &self,
+        engine: &str,
+        text: &str,
+        voice: &str,
+        language: &str,
+    ) -> Result<impl Stream<Item = Result<StreamChunk, ServiceError>>, ServiceError> {
+        let worker = self.pick_for(engine, voice, language, Privacy::Private)?;
+
+        let mut request_id = [0u8; 16];
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut request_id);
+
+        let request = sonotxt_core::EncryptedTtsRequest {
+            request_id,
+            text: text.to_string(),
+            voice: voice.to_string(),
+            speed: 1.0,
+            language: language.to_string(),
+            engine: engine.to_string(),
+            instruct: None,
+            cfg_scale: None,
+        };
+
+        worker.total_requests.fetch_add(1, Ordering::Relaxed);
+
+        let stream = {
+            let quic_guard = worker.quic.read().await;
+            let quic = quic_guard.as_ref().ok_or(ServiceError::Unavailable)?;
+            quic.encrypted_tts_stream(&request).await.map_err(|e| {
+                worker.total_failures.fetch_add(1, Ordering::Relaxed);
+                ServiceError::Failed(format!("quic tts stream: {}", e))
+            })?
+        };
+
+        let guard = InflightGuard::new(worker);
+        Ok(stream.map(move |item| {
+            let chunk = item.map_err(|e| ServiceError::Failed(format!("quic tts stream: {}", e)));
+            match chunk {
+                Ok(StreamChunk { error: Some(err), .. }) => {
+                    guard.0.total_failures.fetch_add(1, Ordering::Relaxed);
+                    Err(ServiceError::Failed(err))
+                }
+                Ok(chunk) => Ok(chunk),
+                Err(e) => {
+                    guard.0.total_failures.fetch_add(1, Ordering::Relaxed);
+                    Err(e)
+                }
+            }
+        }))
+    }
+
     /// Push job notification to a worker over QUIC.
     /// Falls back silently if no QUIC connection (redis/poll will catch it).
     pub async fn notify_job(&self, job_id: &str) {
+        self.job_wakeup.notify_one();
+
         // Notify all connected workers (they compete via SELECT FOR UPDATE)
-        for worker in &self.workers {
+        for worker in &self.serving() {
             let quic_guard = worker.quic.read().await;
             if let Some(ref quic) = *quic_guard {
                 if let Err(e) = quic.notify_job(job_id).await {
@@ -418,49 +997,66 @@ impl WorkerPool {
         }
     }
 
-    /// ASR: audio → text. Tries QUIC (encrypted), falls back to HTTP.
-    pub async fn asr(&self, req: AsrRequest) -> Result<AsrResponse, ServiceError> {
-        let worker = self.pick().ok_or(ServiceError::Unavailable)?;
-
-        // Try QUIC first
-        {
-            let quic_guard = worker.quic.read().await;
-            if let Some(ref quic) = *quic_guard {
-                match tokio::time::timeout(
-                    self.asr_timeout,
-                    quic.encrypted_asr(&req.audio_base64),
-                ).await {
-                    Ok(Ok(resp)) => {
-                        if let Some(err) = resp.error {
-                            return Err(ServiceError::Failed(err));
-                        }
-                        return Ok(AsrResponse { text: resp.text });
-                    }
-                    Ok(Err(e)) => warn!("QUIC ASR failed, HTTP fallback: {}", e),
-                    Err(_) => warn!("QUIC ASR timeout, HTTP fallback"),
-                }
-            }
-        }
+    /// Resolves on the next `notify_job` (or immediately if one arrived
+    /// since the last wait). Used by the job worker instead of polling.
+    pub async fn job_notified(&self) {
+        self.job_wakeup.notified().await;
+    }
 
-        // HTTP fallback
-        let svc = AsrService { http: self.http.clone(), worker };
-        tokio::time::timeout(self.asr_timeout, svc.call(req))
-            .await
-            .map_err(|_| ServiceError::Timeout)?
+    /// ASR: audio → text. QUIC (encrypted) first, HTTP fallback.
+    /// Load balanced, with timeout and retry.
+    pub async fn asr(self: &Arc<Self>, req: AsrRequest) -> Result<AsrResponse, ServiceError> {
+        let pool = self.clone();
+        LoadBalance::new(move |_: &AsrRequest| {
+            let worker = pool.pick().ok_or(ServiceError::Unavailable)?;
+            let leaf = AsrService { http: pool.http.clone(), worker: worker.clone() };
+            Ok(worker_stack(leaf, worker, pool.asr_timeout, "asr"))
+        })
+        .and_then(self.retry())
+        .call(req)
+        .await
     }
 
-    /// LLM: messages → sentences. Load balanced, with timeout.
-    pub async fn llm(&self, req: LlmRequest) -> Result<LlmResponse, ServiceError> {
-        let worker = self.pick().ok_or(ServiceError::Unavailable)?;
-        let svc = LlmService { http: self.http.clone(), worker };
-        tokio::time::timeout(self.llm_timeout, svc.call(req))
-            .await
-            .map_err(|_| ServiceError::Timeout)?
+    /// LLM: messages → sentences. QUIC first (the conversation stays
+    /// encrypted), HTTP fallback. Load balanced, with timeout and retry.
+    pub async fn llm(self: &Arc<Self>, req: LlmRequest) -> Result<LlmResponse, ServiceError> {
+        let pool = self.clone();
+        LoadBalance::new(move |_: &LlmRequest| {
+            let worker = pool.pick().ok_or(ServiceError::Unavailable)?;
+            let leaf = LlmService { http: pool.http.clone(), worker: worker.clone() };
+            Ok(worker_stack(leaf, worker, pool.llm_timeout, "llm"))
+        })
+        .and_then(self.retry())
+        .call(req)
+        .await
     }
 
-    /// LLM streaming: returns a byte stream from /chat_stream (SSE).
-    pub async fn llm_stream(&self, req: LlmRequest) -> Result<reqwest::Response, ServiceError> {
+    /// LLM streaming: sentences as the model generates them. QUIC first,
+    /// falling back to the worker's /chat_stream (SSE) over HTTP.
+    /// Dropping the stream cancels generation on a QUIC worker.
+    pub async fn llm_stream(&self, req: LlmRequest) -> Result<SentenceStream, ServiceError> {
         let worker = self.pick().ok_or(ServiceError::Unavailable)?;
+        worker.total_requests.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(events) = llm_over_quic(&worker, &req, true).await {
+            let guard = InflightGuard::new(worker);
+            return Ok(Box::pin(events.filter_map(move |event| {
+                let item = match event {
+                    Err(e) => Some(Err(ServiceError::Failed(format!("quic llm: {}", e)))),
+                    Ok(LlmEvent { error: Some(err), .. }) => Some(Err(ServiceError::Failed(err))),
+                    Ok(event) => event.sentence.map(Ok),
+                };
+                if matches!(item, Some(Err(_))) {
+                    guard.0.total_failures.fetch_add(1, Ordering::Relaxed);
+                }
+                futures::future::ready(item)
+            })));
+        }
+
+        // HTTP fallback
+        if worker.reverse {
+            return Err(ServiceError::Unavailable);
+        }
         worker.inflight.fetch_add(1, Ordering::Relaxed);
 
         #[derive(Serialize)]
@@ -478,77 +1074,85 @@ impl WorkerPool {
         if !resp.status().is_success() {
             return Err(ServiceError::Failed(format!("llm_stream: {}", resp.status())));
         }
-        Ok(resp)
-    }
-
-    /// Backup request pattern (Eriksen Appendix A).
-    pub async fn tts_with_backup(&self, req: TtsRequest) -> Result<TtsResponse, ServiceError> {
-        let primary = self.pick().ok_or(ServiceError::Unavailable)?;
-        let backup_worker = self.pick_different(&primary);
-
-        let primary_svc = TtsService { http: self.http.clone(), worker: primary.clone() };
-        let primary_req = req.clone();
-
-        if let Some(backup) = backup_worker {
-            let cutoff = Duration::from_millis(primary.last_latency_ms.load(Ordering::Relaxed).max(500));
-            let backup_svc = TtsService { http: self.http.clone(), worker: backup };
-            let backup_req = req;
-
-            let primary_fut = primary_svc.call(primary_req);
 
-            tokio::select! {
-                result = primary_fut => result,
-                _ = tokio::time::sleep(cutoff) => {
-                    info!("backup request fired after {}ms cutoff", cutoff.as_millis());
-                    backup_svc.call(backup_req).await
-                }
-            }
-        } else {
-            tokio::time::timeout(self.tts_timeout, primary_svc.call(primary_req))
-                .await
-                .map_err(|_| ServiceError::Timeout)?
-        }
+        let mut parser = SentenceParser::default();
+        Ok(Box::pin(resp.bytes_stream().flat_map(move |chunk| {
+            let sentences: Vec<_> = match chunk {
+                Ok(bytes) => parser.push(&bytes).into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(ServiceError::Failed(format!("llm stream: {}", e)))],
+            };
+            futures::stream::iter(sentences)
+        })))
     }
 
-    // ── Retry logic ────────────────────────────────────────────
-
-    async fn with_retry<F, Fut>(&self, f: F) -> Result<TtsResponse, ServiceError>
-    where
-        F: Fn(Arc<Worker>) -> Fut,
-        Fut: Future<Output = Result<TtsResponse, ServiceError>>,
-    {
-        let mut last_err = ServiceError::Unavailable;
-
-        for attempt in 0..=self.max_retries {
-            let worker = self.pick().ok_or(ServiceError::Unavailable)?;
-
-            match f(worker.clone()).await {
-                Ok(resp) => return Ok(resp),
-                Err(e) => {
-                    worker.total_failures.fetch_add(1, Ordering::Relaxed);
-                    warn!("attempt {}: {} failed: {}", attempt, worker.speech_url, e);
-                    last_err = e;
-                    if attempt < self.max_retries {
-                        tokio::time::sleep(Duration::from_millis(100)).await;
-                    }
-                }
-            }
+    /// Backup request pattern (Eriksen Appendix A): if a request hasn't
+    /// answered within the p99 of recent latencies, race a backup. The
+    /// backup is load balanced like any request, so it lands on another
+    /// worker unless the others are busier. The loser is dropped, which
+    /// cancels it on its worker. Without a second worker to race, a plain
+    /// `tts`.
+    pub async fn tts_with_backup(self: &Arc<Self>, req: TtsRequest) -> Result<TtsResponse, ServiceError> {
+        let candidates = self
+            .serving()
+            .iter()
+            .filter(|w| w.healthy.load(Ordering::Relaxed) && w.supports(&req.engine, &req.speaker, &req.language))
+            .count();
+        if candidates < 2 {
+            return self.tts(req).await;
         }
-
-        Err(last_err)
+        self.tts_service().and_then(self.tts_hedge.clone()).call(req).await
     }
 
     // ── Load balancing ─────────────────────────────────────────
 
     /// Least-loaded among healthy workers, round-robin tiebreak.
+    /// Workers below their capacity are preferred over saturated ones.
     pub fn pick(&self) -> Option<Arc<Worker>> {
-        let healthy: Vec<_> = self.workers.iter()
+        self.pick_among(|_| true)
+    }
+
+    /// Like `pick`, but only among workers that serve `engine` with
+    /// `voice` in `language` and, for private requests, have an attested
+    /// QUIC session up. `Unsupported` if no worker serves the request at
+    /// all, `Unavailable` if the ones that do are all down.
+    pub fn pick_for(
+        &self,
+        engine: &str,
+        voice: &str,
+        language: &str,
+        privacy: Privacy,
+    ) -> Result<Arc<Worker>, ServiceError> {
+        let serves = |w: &Worker| w.supports(engine, voice, language);
+        if !self.serving().iter().any(|w| serves(w)) {
+            return Err(ServiceError::Unsupported(format!(
+                "no worker serves engine {} with voice {} ({})",
+                engine, voice, language
+            )));
+        }
+        let private = privacy == Privacy::Private;
+        self.pick_among(|w| serves(w) && (!private || w.quic_connected.load(Ordering::Relaxed)))
+            .ok_or(ServiceError::Unavailable)
+    }
+
+    fn pick_among(&self, eligible: impl Fn(&Worker) -> bool) -> Option<Arc<Worker>> {
+        let serving = self.serving();
+        let candidates: Vec<_> = serving.iter().filter(|w| eligible(w)).collect();
+        let healthy: Vec<_> = candidates.iter()
             .filter(|w| w.healthy.load(Ordering::Relaxed))
+            .copied()
             .collect();
 
+        let has_room: Vec<_> = healthy.iter()
+            .filter(|w| w.load() < w.effective_capacity())
+            .copied()
+            .collect();
+        if !has_room.is_empty() {
+            return self.pick_from(&has_room);
+        }
+
         let pool = if healthy.is_empty() {
             warn!("no healthy workers, trying all");
-            &self.workers
+            &candidates
         } else {
             return self.pick_from(&healthy);
         };
@@ -560,42 +1164,43 @@ impl WorkerPool {
 
     fn pick_from(&self, workers: &[&Arc<Worker>]) -> Option<Arc<Worker>> {
         if workers.is_empty() { return None; }
-        let min_load = workers.iter().map(|w| w.inflight.load(Ordering::Relaxed)).min().unwrap_or(0);
+        let min_load = workers.iter().map(|w| w.load()).min().unwrap_or(0);
         let least: Vec<_> = workers.iter()
-            .filter(|w| w.inflight.load(Ordering::Relaxed) == min_load)
+            .filter(|w| w.load() == min_load)
             .collect();
         let idx = self.rr_counter.fetch_add(1, Ordering::Relaxed) as usize % least.len();
         Some((*least[idx]).clone())
     }
 
-    fn pick_different(&self, exclude: &Arc<Worker>) -> Option<Arc<Worker>> {
-        let others: Vec<_> = self.workers.iter()
-            .filter(|w| w.healthy.load(Ordering::Relaxed) && !Arc::ptr_eq(w, exclude))
-            .collect();
-        if others.is_empty() { return None; }
-        let idx = self.rr_counter.fetch_add(1, Ordering::Relaxed) as usize % others.len();
-        Some(others[idx].clone())
-    }
-
     // ── Health checking ────────────────────────────────────────
 
     /// Health check all workers. Prefers QUIC (faster), falls back to HTTP.
     pub async fn health_check(&self) {
-        for worker in &self.workers {
+        for worker in &self.workers() {
             let start = Instant::now();
 
-            // Try QUIC health first
+            // Try QUIC health first. A failure on an open connection means
+            // the transport is broken: close it so `maintain_quic` reconnects.
             let quic_health = {
                 let quic_guard = worker.quic.read().await;
-                if let Some(ref quic) = *quic_guard {
-                    quic.health().await.ok()
-                } else {
-                    None
+                match *quic_guard {
+                    Some(ref quic) => match quic.health().await {
+                        Ok(health) => Some(health),
+                        Err(e) => {
+                            warn!("QUIC health failed for {}, reconnecting: {}", worker.speech_url, e);
+                            quic.close("health check failed");
+                            None
+                        }
+                    },
+                    None => None,
                 }
             };
+            *worker.last_health.lock().unwrap() = quic_health.clone();
 
             let (speech_ok, llm_ok) = if let Some(health) = quic_health {
                 (health.speech_ok, health.llm_ok)
+            } else if worker.reverse {
+                (false, false)
             } else {
                 // HTTP fallback
                 let speech_ok = self.check_health_http(&worker.speech_url, "speech").await;
@@ -611,9 +1216,6 @@ impl WorkerPool {
 
             if was && !now { warn!("worker {} DOWN", worker.speech_url); }
             if !was && now { info!("worker {} recovered", worker.speech_url); }
-
-            // QUIC reconnect is disabled for SSH-tunneled workers (no UDP path).
-            // When workers have direct UDP access, re-enable this.
         }
     }
 
@@ -639,23 +1241,118 @@ impl WorkerPool {
     // ── Monitoring ─────────────────────────────────────────────
 
     pub fn status(&self) -> Vec<WorkerStatus> {
-        self.workers.iter().map(|w| WorkerStatus {
+        self.workers().iter().map(|w| WorkerStatus {
+            name: w.name.clone(),
+            draining: w.draining.load(Ordering::Relaxed),
             speech_url: w.speech_url.clone(),
             llm_url: w.llm_url.clone(),
             healthy: w.healthy.load(Ordering::Relaxed),
+            capacity: w.effective_capacity(),
             inflight: w.inflight.load(Ordering::Relaxed),
             total_requests: w.total_requests.load(Ordering::Relaxed),
             total_failures: w.total_failures.load(Ordering::Relaxed),
             latency_ms: w.last_latency_ms.load(Ordering::Relaxed),
+            transport: if w.quic_connected.load(Ordering::Relaxed) { "quic" } else { "http" },
+            quic_addr: w.quic_addr.map(|a| a.to_string()),
+            quic_reconnects: w.quic_reconnects.load(Ordering::Relaxed),
+            health: w.last_health.lock().unwrap().clone(),
+            capabilities: w.capabilities.lock().unwrap().clone(),
         }).collect()
     }
 
+    /// Combined capacity of healthy workers (all workers if none are healthy,
+    /// matching `pick`'s fallback). Draining workers don't count.
+    pub fn total_capacity(&self) -> usize {
+        let serving = self.serving();
+        let healthy: u64 = serving.iter()
+            .filter(|w| w.healthy.load(Ordering::Relaxed))
+            .map(|w| w.effective_capacity())
+            .sum();
+        if healthy > 0 {
+            return healthy as usize;
+        }
+        serving.iter().map(|w| w.effective_capacity()).sum::<u64>() as usize
+    }
+
     pub fn healthy_count(&self) -> usize {
-        self.workers.iter().filter(|w| w.healthy.load(Ordering::Relaxed)).count()
+        self.workers().iter().filter(|w| w.healthy.load(Ordering::Relaxed)).count()
     }
 
     pub fn len(&self) -> usize {
-        self.workers.len()
+        self.workers.lock().unwrap().len()
+    }
+}
+
+/// Keep one worker's QUIC connection up for the life of the process.
+///
+/// Connects (attestation + Noise handshake), waits for the connection to
+/// close, then reconnects. Failed attempts back off exponentially from
+/// `RECONNECT_BACKOFF_MIN` to `RECONNECT_BACKOFF_MAX`; requests fall back to
+/// HTTP (or fail, for QUIC-only calls) while disconnected.
+async fn maintain_quic(
+    worker: Arc<Worker>,
+    addr: SocketAddr,
+    attestation: Arc<AttestationPolicy>,
+    identity: Arc<NoiseIdentity>,
+) {
+    let mut backoff = RECONNECT_BACKOFF_MIN;
+    let mut connected_before = false;
+
+    loop {
+        match QuicWorkerConn::connect(addr, &attestation, &identity).await {
+            Ok(conn) => {
+                info!("QUIC connected: {} → {}", worker.speech_url, addr);
+                if connected_before {
+                    worker.quic_reconnects.fetch_add(1, Ordering::Relaxed);
+                }
+                connected_before = true;
+                let since = Instant::now();
+
+                match conn.capabilities().await {
+                    Ok(caps) => {
+                        info!("worker {} capabilities: {:?}", worker.speech_url, caps);
+                        *worker.capabilities.lock().unwrap() = Some(caps);
+                    }
+                    Err(e) => info!("worker {} advertises no capabilities, routing anything to it: {}", worker.speech_url, e),
+                }
+
+                let closed = conn.closed();
+                *worker.quic.write().await = Some(conn);
+                worker.quic_connected.store(true, Ordering::Relaxed);
+
+                let reason = closed.await;
+                worker.quic_connected.store(false, Ordering::Relaxed);
+                *worker.quic.write().await = None;
+                warn!("QUIC connection to {} lost: {}", worker.speech_url, reason);
+
+                // A connection that dies right after the handshake counts as a
+                // failed attempt, so a flapping worker is not hammered.
+                if since.elapsed() < RECONNECT_BACKOFF_MAX {
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(RECONNECT_BACKOFF_MAX);
+                } else {
+                    backoff = RECONNECT_BACKOFF_MIN;
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "QUIC connect failed for {} (HTTP fallback, retry in {}s): {}",
+                    worker.speech_url,
+                    backoff.as_secs(),
+                    e
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(RECONNECT_BACKOFF_MAX);
+            }
+        }
+    }
+}
+
+/// Stop keeping a departed worker's QUIC connection up. The connection
+/// itself closes once the last request holding the worker finishes.
+fn retire(worker: &Worker) {
+    if let Some(task) = worker.quic_task.lock().unwrap().take() {
+        task.abort();
     }
 }
 
@@ -674,11 +1371,22 @@ fn derive_quic_addr(speech_url: &str) -> std::net::SocketAddr {
 
 #[derive(Serialize, Debug)]
 pub struct WorkerStatus {
+    pub name: String,
+    pub draining: bool,
     pub speech_url: String,
     pub llm_url: String,
     pub healthy: bool,
+    pub capacity: u64,
     pub inflight: u64,
     pub total_requests: u64,
     pub total_failures: u64,
     pub latency_ms: u64,
+    /// "quic" while a QUIC+Noise session is up, otherwise "http"
+    pub transport: &'static str,
+    pub quic_addr: Option<String>,
+    pub quic_reconnects: u64,
+    /// Worker-reported health from the last QUIC health check
+    pub health: Option<WorkerHealth>,
+    /// Advertised engines, voices and languages; None serves anything
+    pub capabilities: Option<WorkerCapabilities>,
 }
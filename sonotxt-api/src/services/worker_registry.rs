@@ -0,0 +1,119 @@
+//! Persisted worker registry.
+//!
+//! Workers on rented GPUs come and go, so besides the static `WORKER_URLS`
+//! the pool takes members from the `workers` table. Admin routes write the
+//! table and apply the change to this instance's pool right away; every
+//! instance also re-reads it on each health check round, so the whole
+//! fleet converges without a restart.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::warn;
+
+use crate::services::worker_pool::{WorkerPool, WorkerSpec};
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct RegisteredWorker {
+    pub name: String,
+    pub speech_url: String,
+    pub llm_url: String,
+    pub quic_addr: Option<String>,
+    pub capacity: Option<i32>,
+    pub draining: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl RegisteredWorker {
+    pub fn spec(&self) -> Result<WorkerSpec, String> {
+        let quic_addr = self
+            .quic_addr
+            .as_deref()
+            .map(|a| a.parse().map_err(|_| format!("invalid QUIC address {}", a)))
+            .transpose()?;
+        Ok(WorkerSpec {
+            name: self.name.clone(),
+            speech_url: self.speech_url.clone(),
+            llm_url: self.llm_url.clone(),
+            quic_addr,
+            capacity: self.capacity.map(|c| c as u64),
+            draining: self.draining,
+        })
+    }
+}
+
+const COLUMNS: &str =
+    "name, speech_url, llm_url, quic_addr, capacity, draining, created_at, updated_at";
+
+pub async fn list(db: &PgPool) -> sqlx::Result<Vec<RegisteredWorker>> {
+    sqlx::query_as(&format!("SELECT {} FROM workers ORDER BY name", COLUMNS))
+        .fetch_all(db)
+        .await
+}
+
+/// Register a worker, or update its endpoints if the name is taken.
+/// Re-registering keeps the drain state.
+pub async fn upsert(db: &PgPool, spec: &WorkerSpec) -> sqlx::Result<RegisteredWorker> {
+    sqlx::query_as(&format!(
+        r#"
+        INSERT INTO workers (name, speech_url, llm_url, quic_addr, capacity)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (name) DO UPDATE SET
+            speech_url = EXCLUDED.speech_url,
+            llm_url = EXCLUDED.llm_url,
+            quic_addr = EXCLUDED.quic_addr,
+            capacity = EXCLUDED.capacity,
+            updated_at = NOW()
+        RETURNING {}
+        "#,
+        COLUMNS
+    ))
+    .bind(&spec.name)
+    .bind(&spec.speech_url)
+    .bind(&spec.llm_url)
+    .bind(spec.quic_addr.map(|a| a.to_string()))
+    .bind(spec.capacity.map(|c| c as i32))
+    .fetch_one(db)
+    .await
+}
+
+/// Returns None if no worker has that name.
+pub async fn set_draining(
+    db: &PgPool,
+    name: &str,
+    draining: bool,
+) -> sqlx::Result<Option<RegisteredWorker>> {
+    sqlx::query_as(&format!(
+        "UPDATE workers SET draining = $2, updated_at = NOW() WHERE name = $1 RETURNING {}",
+        COLUMNS
+    ))
+    .bind(name)
+    .bind(draining)
+    .fetch_optional(db)
+    .await
+}
+
+/// Returns false if no worker has that name.
+pub async fn remove(db: &PgPool, name: &str) -> sqlx::Result<bool> {
+    let result = sqlx::query("DELETE FROM workers WHERE name = $1")
+        .bind(name)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Load the registry into `pool`. Rows that don't parse are skipped.
+pub async fn sync(db: &PgPool, pool: &WorkerPool) -> sqlx::Result<()> {
+    let specs = list(db)
+        .await?
+        .iter()
+        .filter_map(|row| {
+            row.spec()
+                .map_err(|e| warn!("registry worker {} skipped: {}", row.name, e))
+                .ok()
+        })
+        .collect();
+    pool.sync_registry(specs);
+    Ok(())
+}
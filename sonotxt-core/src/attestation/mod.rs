@@ -0,0 +1,258 @@
+//! TEE attestation verification for worker quotes.
+//!
+//! A worker proves it runs inside a TEE by sending a hardware-signed quote
+//! whose report data commits to its Noise static key (see [`report_data`]).
+//! The API verifies the quote's certificate chain back to a vendor root it
+//! has configured locally, then checks the launch measurement against an
+//! allow-list before starting a Noise handshake.
+
+pub mod snp;
+pub mod tdx;
+
+use sha2::{Digest, Sha256};
+use std::time::SystemTime;
+use x509_cert::der::asn1::ObjectIdentifier;
+use x509_cert::der::Encode;
+use x509_cert::crl::CertificateList;
+use x509_cert::Certificate;
+
+/// Errors from quote parsing and verification.
+#[derive(Debug, thiserror::Error)]
+pub enum AttestationError {
+    #[error("malformed quote: {0}")]
+    Malformed(String),
+
+    #[error("invalid certificate: {0}")]
+    Certificate(String),
+
+    #[error("certificate chain does not verify: {0}")]
+    Chain(String),
+
+    #[error("quote signature does not verify")]
+    Signature,
+
+    #[error("report data does not commit to the worker's static key")]
+    KeyBinding,
+
+    #[error("measurement {0} is not allow-listed")]
+    Measurement(String),
+
+    #[error("policy violation: {0}")]
+    Policy(String),
+}
+
+/// The 64-byte report data a worker must put in its quote:
+/// `SHA-256(noise_static_key) || 0^32`.
+pub fn report_data(static_key: &[u8]) -> [u8; 64] {
+    let mut data = [0u8; 64];
+    data[..32].copy_from_slice(&Sha256::digest(static_key));
+    data
+}
+
+/// Parse a comma-separated list of hex measurements, as used in config.
+pub fn parse_measurements(list: &str) -> Result<Vec<Vec<u8>>, String> {
+    list.split(',')
+        .map(|m| m.trim())
+        .filter(|m| !m.is_empty())
+        .map(|m| hex_decode(m).ok_or_else(|| format!("invalid hex measurement: {}", m)))
+        .collect()
+}
+
+pub(crate) fn hex_decode(s: &str) -> Option<Vec<u8>> {
+    hex::decode(s.trim_start_matches("0x")).ok()
+}
+
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+    hex::encode(bytes)
+}
+
+// ── X.509 helpers ─────────────────────────────────────────────────
+
+const OID_RSASSA_PSS: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.10");
+const OID_ECDSA_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+const OID_ECDSA_SHA384: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.3");
+
+pub(crate) fn is_self_signed(cert: &Certificate) -> bool {
+    cert.tbs_certificate.subject == cert.tbs_certificate.issuer
+}
+
+/// Check that `cert` was issued and signed by `issuer` and is currently valid.
+pub(crate) fn verify_issued_by(cert: &Certificate, issuer: &Certificate) -> Result<(), AttestationError> {
+    if cert.tbs_certificate.issuer != issuer.tbs_certificate.subject {
+        return Err(AttestationError::Chain(format!(
+            "issuer {} does not match {}",
+            cert.tbs_certificate.issuer, issuer.tbs_certificate.subject
+        )));
+    }
+    check_validity(cert)?;
+
+    let tbs = cert
+        .tbs_certificate
+        .to_der()
+        .map_err(|e| AttestationError::Certificate(e.to_string()))?;
+    let signature = cert
+        .signature
+        .as_bytes()
+        .ok_or_else(|| AttestationError::Certificate("unaligned signature".into()))?;
+
+    if verify_signed_by(&cert.signature_algorithm.oid, &tbs, signature, issuer)? {
+        Ok(())
+    } else {
+        Err(AttestationError::Chain(format!(
+            "signature on {} does not verify",
+            cert.tbs_certificate.subject
+        )))
+    }
+}
+
+/// Check that `crl` was signed by `issuer` and has not passed its next update.
+pub(crate) fn verify_crl(crl: &CertificateList, issuer: &Certificate) -> Result<(), AttestationError> {
+    let tbs_list = &crl.tbs_cert_list;
+    if tbs_list.issuer != issuer.tbs_certificate.subject {
+        return Err(AttestationError::Chain(format!(
+            "CRL issuer {} does not match {}",
+            tbs_list.issuer, issuer.tbs_certificate.subject
+        )));
+    }
+    if let Some(next_update) = tbs_list.next_update {
+        if SystemTime::now() > next_update.to_system_time() {
+            return Err(AttestationError::Chain(format!("CRL from {} is stale", tbs_list.issuer)));
+        }
+    }
+
+    let tbs = tbs_list
+        .to_der()
+        .map_err(|e| AttestationError::Certificate(e.to_string()))?;
+    let signature = crl
+        .signature
+        .as_bytes()
+        .ok_or_else(|| AttestationError::Certificate("unaligned CRL signature".into()))?;
+
+    if verify_signed_by(&crl.signature_algorithm.oid, &tbs, signature, issuer)? {
+        Ok(())
+    } else {
+        Err(AttestationError::Chain(format!(
+            "signature on CRL from {} does not verify",
+            tbs_list.issuer
+        )))
+    }
+}
+
+/// Fail if `cert` appears in `crl`. The CRL must already be verified.
+pub(crate) fn check_not_revoked(cert: &Certificate, crl: &CertificateList) -> Result<(), AttestationError> {
+    let revoked = crl
+        .tbs_cert_list
+        .revoked_certificates
+        .iter()
+        .flatten()
+        .any(|r| r.serial_number == cert.tbs_certificate.serial_number);
+    if revoked {
+        return Err(AttestationError::Chain(format!(
+            "{} has been revoked",
+            cert.tbs_certificate.subject
+        )));
+    }
+    Ok(())
+}
+
+/// Parse a CRL given as PEM (`X509 CRL`) or hex-encoded DER, as Intel PCS serves both.
+pub(crate) fn parse_crl(data: &str) -> Result<CertificateList, AttestationError> {
+    use x509_cert::der::Decode;
+
+    let der = if data.trim_start().starts_with("-----BEGIN") {
+        x509_cert::der::pem::decode_vec(data.as_bytes())
+            .map_err(|e| AttestationError::Certificate(e.to_string()))?
+            .1
+    } else {
+        hex_decode(data.trim()).ok_or_else(|| AttestationError::Certificate("CRL is neither PEM nor hex".into()))?
+    };
+    CertificateList::from_der(&der).map_err(|e| AttestationError::Certificate(e.to_string()))
+}
+
+fn verify_signed_by(
+    algorithm: &ObjectIdentifier,
+    message: &[u8],
+    signature: &[u8],
+    issuer: &Certificate,
+) -> Result<bool, AttestationError> {
+    let issuer_key = issuer
+        .tbs_certificate
+        .subject_public_key_info
+        .subject_public_key
+        .as_bytes()
+        .ok_or_else(|| AttestationError::Certificate("unaligned public key".into()))?;
+
+    match *algorithm {
+        OID_RSASSA_PSS => Ok(verify_rsa_pss_sha384(issuer_key, message, signature)),
+        OID_ECDSA_SHA256 => Ok(verify_ecdsa_p256_der(issuer_key, message, signature)),
+        OID_ECDSA_SHA384 => Ok(verify_ecdsa_p384_der(issuer_key, message, signature)),
+        oid => Err(AttestationError::Certificate(format!(
+            "unsupported signature algorithm {}",
+            oid
+        ))),
+    }
+}
+
+fn check_validity(cert: &Certificate) -> Result<(), AttestationError> {
+    let validity = &cert.tbs_certificate.validity;
+    let now = SystemTime::now();
+    if now < validity.not_before.to_system_time() || now > validity.not_after.to_system_time() {
+        return Err(AttestationError::Chain(format!(
+            "{} is outside its validity period",
+            cert.tbs_certificate.subject
+        )));
+    }
+    Ok(())
+}
+
+fn verify_rsa_pss_sha384(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    use rsa::pkcs1::DecodeRsaPublicKey;
+    use rsa::signature::Verifier;
+
+    let Ok(key) = rsa::RsaPublicKey::from_pkcs1_der(public_key) else {
+        return false;
+    };
+    let Ok(signature) = rsa::pss::Signature::try_from(signature) else {
+        return false;
+    };
+    rsa::pss::VerifyingKey::<sha2::Sha384>::new(key)
+        .verify(message, &signature)
+        .is_ok()
+}
+
+fn verify_ecdsa_p384_der(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    use p384::ecdsa::signature::Verifier;
+
+    let Ok(key) = p384::ecdsa::VerifyingKey::from_sec1_bytes(public_key) else {
+        return false;
+    };
+    let Ok(signature) = p384::ecdsa::Signature::from_der(signature) else {
+        return false;
+    };
+    key.verify(message, &signature).is_ok()
+}
+
+fn verify_ecdsa_p256_der(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    use p256::ecdsa::signature::Verifier;
+
+    let Ok(key) = p256::ecdsa::VerifyingKey::from_sec1_bytes(public_key) else {
+        return false;
+    };
+    let Ok(signature) = p256::ecdsa::Signature::from_der(signature) else {
+        return false;
+    };
+    key.verify(message, &signature).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_measurements() {
+        assert_eq!(parse_measurements("0xabcd, 01").unwrap(), vec![vec![0xab, 0xcd], vec![0x01]]);
+        assert!(parse_measurements("abc").is_err());
+        // multi-byte characters are rejected, not sliced through
+        assert!(parse_measurements("aé0").is_err());
+    }
+}
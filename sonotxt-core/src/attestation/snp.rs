@@ -0,0 +1,596 @@
+//! AMD SEV-SNP attestation report verification.
+//!
+//! The report is the 1184-byte `ATTESTATION_REPORT` structure from the SNP
+//! firmware ABI. It is signed (ECDSA P-384 / SHA-384) by the chip's VCEK,
+//! whose certificate chains to the AMD Signing Key (ASK) and the AMD Root
+//! Key (ARK). ARK and ASK come from a locally configured PEM bundle — they
+//! are the trust anchors and are never taken from the worker.
+
+use x509_cert::der::Decode;
+use x509_cert::Certificate;
+
+use super::{hex_encode, is_self_signed, verify_issued_by, AttestationError};
+
+pub const REPORT_SIZE: usize = 0x4A0;
+
+/// Signed region: everything before the signature.
+const SIGNED_LEN: usize = 0x2A0;
+/// `SIGNATURE_ALGO` value for ECDSA P-384 with SHA-384.
+const SIG_ALGO_ECDSA_P384_SHA384: u32 = 1;
+/// Guest policy bit 19: debugging allowed (memory readable by the host).
+const POLICY_DEBUG: u64 = 1 << 19;
+
+/// Fields of an SNP attestation report relevant to verification.
+#[derive(Debug, Clone)]
+pub struct SnpReport {
+    pub version: u32,
+    pub guest_svn: u32,
+    pub policy: u64,
+    pub vmpl: u32,
+    pub signature_algo: u32,
+    pub reported_tcb: u64,
+    pub report_data: [u8; 64],
+    pub measurement: [u8; 48],
+    pub host_data: [u8; 32],
+    pub chip_id: [u8; 64],
+    /// ECDSA r, big-endian (the report stores it little-endian, zero-padded to 72 bytes)
+    pub signature_r: [u8; 48],
+    /// ECDSA s, big-endian
+    pub signature_s: [u8; 48],
+}
+
+impl SnpReport {
+    pub fn parse(bytes: &[u8]) -> Result<Self, AttestationError> {
+        if bytes.len() != REPORT_SIZE {
+            return Err(AttestationError::Malformed(format!(
+                "SNP report is {} bytes, expected {}",
+                bytes.len(),
+                REPORT_SIZE
+            )));
+        }
+
+        Ok(Self {
+            version: u32_at(bytes, 0x00),
+            guest_svn: u32_at(bytes, 0x04),
+            policy: u64_at(bytes, 0x08),
+            vmpl: u32_at(bytes, 0x30),
+            signature_algo: u32_at(bytes, 0x34),
+            reported_tcb: u64_at(bytes, 0x180),
+            report_data: array_at(bytes, 0x50),
+            measurement: array_at(bytes, 0x90),
+            host_data: array_at(bytes, 0xC0),
+            chip_id: array_at(bytes, 0x1A0),
+            signature_r: le_scalar(&bytes[0x2A0..0x2A0 + 72])?,
+            signature_s: le_scalar(&bytes[0x2E8..0x2E8 + 72])?,
+        })
+    }
+
+    pub fn debug_allowed(&self) -> bool {
+        self.policy & POLICY_DEBUG != 0
+    }
+}
+
+/// Verifies SNP reports against a local ARK/ASK bundle and a measurement allow-list.
+pub struct SnpVerifier {
+    ark: Certificate,
+    ask: Certificate,
+    /// VCEKs shipped in the local bundle (used when the worker sends none)
+    vceks: Vec<Certificate>,
+    measurements: Vec<[u8; 48]>,
+}
+
+impl SnpVerifier {
+    /// Build from a PEM bundle holding the ARK (self-signed), the ASK it
+    /// issued, and optionally VCEK certificates for known chips.
+    ///
+    /// An empty measurement allow-list rejects every report.
+    pub fn from_pem_bundle(pem: &[u8], measurements: &[Vec<u8>]) -> Result<Self, AttestationError> {
+        let certs = Certificate::load_pem_chain(pem)
+            .map_err(|e| AttestationError::Certificate(e.to_string()))?;
+        Self::from_certs(certs, measurements)
+    }
+
+    pub fn from_certs(certs: Vec<Certificate>, measurements: &[Vec<u8>]) -> Result<Self, AttestationError> {
+        let (roots, rest): (Vec<_>, Vec<_>) = certs.into_iter().partition(is_self_signed);
+        let ark = match <[Certificate; 1]>::try_from(roots) {
+            Ok([ark]) => ark,
+            Err(roots) => {
+                return Err(AttestationError::Certificate(format!(
+                    "bundle must contain exactly one self-signed ARK, found {}",
+                    roots.len()
+                )))
+            }
+        };
+        verify_issued_by(&ark, &ark)?;
+
+        let (asks, vceks): (Vec<_>, Vec<_>) = rest
+            .into_iter()
+            .partition(|c| c.tbs_certificate.issuer == ark.tbs_certificate.subject);
+        let ask = asks
+            .into_iter()
+            .next()
+            .ok_or_else(|| AttestationError::Certificate("bundle has no ASK issued by the ARK".into()))?;
+        verify_issued_by(&ask, &ark)?;
+
+        let measurements = measurements
+            .iter()
+            .map(|m| {
+                <[u8; 48]>::try_from(m.as_slice()).map_err(|_| {
+                    AttestationError::Policy(format!("SNP measurement must be 48 bytes, got {}", m.len()))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { ark, ask, vceks, measurements })
+    }
+
+    /// Verify a raw report for a worker with the given Noise static key.
+    ///
+    /// `vcek_der` is the VCEK certificate supplied by the worker (from the
+    /// extended report); when absent, VCEKs from the local bundle are tried.
+    pub fn verify(
+        &self,
+        report_bytes: &[u8],
+        static_key: &[u8],
+        vcek_der: Option<&[u8]>,
+    ) -> Result<SnpReport, AttestationError> {
+        let report = self.verify_report(report_bytes, static_key, vcek_der)?;
+        if !self.measurements.contains(&report.measurement) {
+            return Err(AttestationError::Measurement(hex_encode(&report.measurement)));
+        }
+        Ok(report)
+    }
+
+    /// Everything [`verify`](Self::verify) checks except the measurement
+    /// allow-list, for callers that keep their own (e.g. a measurement log).
+    pub fn verify_report(
+        &self,
+        report_bytes: &[u8],
+        static_key: &[u8],
+        vcek_der: Option<&[u8]>,
+    ) -> Result<SnpReport, AttestationError> {
+        let report = SnpReport::parse(report_bytes)?;
+
+        if report.signature_algo != SIG_ALGO_ECDSA_P384_SHA384 {
+            return Err(AttestationError::Malformed(format!(
+                "unsupported signature algorithm {}",
+                report.signature_algo
+            )));
+        }
+
+        let supplied = vcek_der
+            .map(|der| Certificate::from_der(der).map_err(|e| AttestationError::Certificate(e.to_string())))
+            .transpose()?;
+        let candidates: Vec<&Certificate> = supplied.iter().chain(self.vceks.iter()).collect();
+        if candidates.is_empty() {
+            return Err(AttestationError::Chain("no VCEK certificate available".into()));
+        }
+
+        let mut last_err = AttestationError::Signature;
+        let mut verified = false;
+        for vcek in candidates {
+            match verify_issued_by(vcek, &self.ask).and_then(|_| verify_report_signature(&report, report_bytes, vcek)) {
+                Ok(()) => {
+                    verified = true;
+                    break;
+                }
+                Err(e) => last_err = e,
+            }
+        }
+        if !verified {
+            return Err(last_err);
+        }
+
+        if report.report_data != super::report_data(static_key) {
+            return Err(AttestationError::KeyBinding);
+        }
+
+        if report.debug_allowed() {
+            return Err(AttestationError::Policy("guest policy allows debugging".into()));
+        }
+
+        Ok(report)
+    }
+
+    /// Subject of the configured root, for logging.
+    pub fn root_subject(&self) -> String {
+        self.ark.tbs_certificate.subject.to_string()
+    }
+}
+
+fn verify_report_signature(
+    report: &SnpReport,
+    report_bytes: &[u8],
+    vcek: &Certificate,
+) -> Result<(), AttestationError> {
+    use p384::ecdsa::signature::Verifier;
+
+    let public_key = vcek
+        .tbs_certificate
+        .subject_public_key_info
+        .subject_public_key
+        .as_bytes()
+        .ok_or_else(|| AttestationError::Certificate("unaligned VCEK public key".into()))?;
+    let key = p384::ecdsa::VerifyingKey::from_sec1_bytes(public_key)
+        .map_err(|_| AttestationError::Certificate("VCEK key is not P-384".into()))?;
+    let signature = p384::ecdsa::Signature::from_scalars(report.signature_r, report.signature_s)
+        .map_err(|_| AttestationError::Signature)?;
+
+    key.verify(&report_bytes[..SIGNED_LEN], &signature)
+        .map_err(|_| AttestationError::Signature)
+}
+
+// ── Field decoding ────────────────────────────────────────────────
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(array_at(bytes, offset))
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(array_at(bytes, offset))
+}
+
+fn array_at<const N: usize>(bytes: &[u8], offset: usize) -> [u8; N] {
+    let mut out = [0u8; N];
+    out.copy_from_slice(&bytes[offset..offset + N]);
+    out
+}
+
+/// Convert a 72-byte little-endian, zero-padded scalar to 48 bytes big-endian.
+fn le_scalar(bytes: &[u8]) -> Result<[u8; 48], AttestationError> {
+    if bytes[48..].iter().any(|&b| b != 0) {
+        return Err(AttestationError::Malformed("signature scalar exceeds 384 bits".into()));
+    }
+    let mut out = [0u8; 48];
+    for (i, b) in bytes[..48].iter().enumerate() {
+        out[47 - i] = *b;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p384::ecdsa::signature::Signer;
+    use p384::pkcs8::DecodePrivateKey;
+    use rcgen::{BasicConstraints, CertificateParams, DistinguishedName, DnType, IsCa, KeyPair, PKCS_ECDSA_P384_SHA384};
+
+    const MEASUREMENT: [u8; 48] = [0x5a; 48];
+
+    /// Synthetic ARK → ASK → VCEK chain with P-384 keys throughout, quick
+    /// to generate. `AmdChain` below signs with RSA-PSS as AMD's roots do.
+    struct TestChain {
+        ark: rcgen::Certificate,
+        ask: rcgen::Certificate,
+        vcek: rcgen::Certificate,
+        vcek_key: KeyPair,
+    }
+
+    fn params(cn: &str, ca: bool) -> CertificateParams {
+        let mut params = CertificateParams::new(vec![]).unwrap();
+        let mut dn = DistinguishedName::new();
+        dn.push(DnType::CommonName, cn);
+        params.distinguished_name = dn;
+        if ca {
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        }
+        params
+    }
+
+    fn chain() -> TestChain {
+        let ark_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384).unwrap();
+        let ask_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384).unwrap();
+        let vcek_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384).unwrap();
+
+        let ark = params("ARK-Test", true).self_signed(&ark_key).unwrap();
+        let ask = params("SEV-Test", true).signed_by(&ask_key, &ark, &ark_key).unwrap();
+        let vcek = params("SEV-VCEK", false).signed_by(&vcek_key, &ask, &ask_key).unwrap();
+
+        TestChain { ark, ask, vcek, vcek_key }
+    }
+
+    fn bundle(chain: &TestChain) -> Vec<u8> {
+        format!("{}{}", chain.ark.pem(), chain.ask.pem()).into_bytes()
+    }
+
+    fn report(static_key: &[u8], policy: u64, signer: &KeyPair) -> Vec<u8> {
+        let mut bytes = vec![0u8; REPORT_SIZE];
+        bytes[0x00..0x04].copy_from_slice(&2u32.to_le_bytes());
+        bytes[0x08..0x10].copy_from_slice(&policy.to_le_bytes());
+        bytes[0x34..0x38].copy_from_slice(&SIG_ALGO_ECDSA_P384_SHA384.to_le_bytes());
+        bytes[0x50..0x90].copy_from_slice(&crate::attestation::report_data(static_key));
+        bytes[0x90..0xC0].copy_from_slice(&MEASUREMENT);
+        sign(&mut bytes, signer);
+        bytes
+    }
+
+    fn sign(bytes: &mut [u8], signer: &KeyPair) {
+        let key = p384::ecdsa::SigningKey::from_pkcs8_der(&signer.serialize_der()).unwrap();
+        let signature: p384::ecdsa::Signature = key.sign(&bytes[..SIGNED_LEN]);
+        let (r, s) = signature.split_bytes();
+        for i in 0..48 {
+            bytes[0x2A0 + i] = r[47 - i];
+            bytes[0x2E8 + i] = s[47 - i];
+        }
+    }
+
+    fn verifier(chain: &TestChain) -> SnpVerifier {
+        SnpVerifier::from_pem_bundle(&bundle(chain), &[MEASUREMENT.to_vec()]).unwrap()
+    }
+
+    #[test]
+    fn test_valid_report_verifies() {
+        let chain = chain();
+        let static_key = [7u8; 32];
+        let bytes = report(&static_key, 0x30000, &chain.vcek_key);
+
+        let parsed = verifier(&chain)
+            .verify(&bytes, &static_key, Some(chain.vcek.der()))
+            .unwrap();
+        assert_eq!(parsed.measurement, MEASUREMENT);
+        assert_eq!(parsed.version, 2);
+    }
+
+    #[test]
+    fn test_vcek_from_local_bundle() {
+        let chain = chain();
+        let static_key = [7u8; 32];
+        let bytes = report(&static_key, 0, &chain.vcek_key);
+
+        let pem = format!("{}{}", String::from_utf8(bundle(&chain)).unwrap(), chain.vcek.pem());
+        let verifier = SnpVerifier::from_pem_bundle(pem.as_bytes(), &[MEASUREMENT.to_vec()]).unwrap();
+        assert!(verifier.verify(&bytes, &static_key, None).is_ok());
+    }
+
+    #[test]
+    fn test_tampered_report_rejected() {
+        let chain = chain();
+        let static_key = [7u8; 32];
+        let mut bytes = report(&static_key, 0, &chain.vcek_key);
+        bytes[0x90] ^= 1;
+
+        let err = verifier(&chain).verify(&bytes, &static_key, Some(chain.vcek.der())).unwrap_err();
+        assert!(matches!(err, AttestationError::Signature));
+    }
+
+    #[test]
+    fn test_wrong_static_key_rejected() {
+        let chain = chain();
+        let bytes = report(&[7u8; 32], 0, &chain.vcek_key);
+
+        let err = verifier(&chain).verify(&bytes, &[8u8; 32], Some(chain.vcek.der())).unwrap_err();
+        assert!(matches!(err, AttestationError::KeyBinding));
+    }
+
+    #[test]
+    fn test_unknown_measurement_rejected() {
+        let chain = chain();
+        let static_key = [7u8; 32];
+        let bytes = report(&static_key, 0, &chain.vcek_key);
+
+        let verifier = SnpVerifier::from_pem_bundle(&bundle(&chain), &[vec![0u8; 48]]).unwrap();
+        let err = verifier.verify(&bytes, &static_key, Some(chain.vcek.der())).unwrap_err();
+        assert!(matches!(err, AttestationError::Measurement(_)));
+    }
+
+    #[test]
+    fn test_debug_policy_rejected() {
+        let chain = chain();
+        let static_key = [7u8; 32];
+        let bytes = report(&static_key, POLICY_DEBUG, &chain.vcek_key);
+
+        let err = verifier(&chain).verify(&bytes, &static_key, Some(chain.vcek.der())).unwrap_err();
+        assert!(matches!(err, AttestationError::Policy(_)));
+    }
+
+    #[test]
+    fn test_vcek_from_other_chain_rejected() {
+        let chain = chain();
+        let (rogue_vcek, rogue_key) = rogue_vcek();
+        let static_key = [7u8; 32];
+        let bytes = report(&static_key, 0, &rogue_key);
+
+        let err = verifier(&chain).verify(&bytes, &static_key, Some(rogue_vcek.der())).unwrap_err();
+        assert!(matches!(err, AttestationError::Chain(_)));
+    }
+
+    /// A VCEK signed by a rogue ASK that reuses the real ASK's name.
+    fn rogue_vcek() -> (rcgen::Certificate, KeyPair) {
+        let rogue_ark_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384).unwrap();
+        let rogue_ark = params("ARK-Test", true).self_signed(&rogue_ark_key).unwrap();
+        let rogue_ask_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384).unwrap();
+        let rogue_ask = params("SEV-Test", true)
+            .signed_by(&rogue_ask_key, &rogue_ark, &rogue_ark_key)
+            .unwrap();
+        let vcek_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384).unwrap();
+        let vcek = params("SEV-VCEK", false)
+            .signed_by(&vcek_key, &rogue_ask, &rogue_ask_key)
+            .unwrap();
+        (vcek, vcek_key)
+    }
+
+    /// Chain shaped like AMD's: ARK and ASK hold RSA keys and sign with
+    /// RSASSA-PSS/SHA-384 (salt 48), the VCEK holds a P-384 key. rcgen can't
+    /// sign RSA-PSS, so its certificates are re-keyed and re-signed here.
+    /// AMD's keys are 4096-bit; 2048 keeps key generation quick in tests.
+    struct AmdChain {
+        ark: Certificate,
+        ask: Certificate,
+        vcek: Certificate,
+        vcek_key: KeyPair,
+    }
+
+    /// RSASSA-PSS-params as AMD encodes them: SHA-384, MGF1 with SHA-384, salt 48
+    const PSS_SHA384_PARAMS: &str = "3034a00f300d06096086480165030402020500a11c301a06092a864886f70d010108300d06096086480165030402020500a203020130";
+
+    fn resign(cert: &rcgen::Certificate, key: Option<&rsa::RsaPrivateKey>, signer: &rsa::RsaPrivateKey) -> Certificate {
+        use rsa::pkcs8::EncodePublicKey;
+        use rsa::signature::{RandomizedSigner, SignatureEncoding};
+        use x509_cert::der::{asn1::BitString, Any, Encode};
+        use x509_cert::spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfoOwned};
+
+        let mut cert = Certificate::from_der(cert.der()).unwrap();
+        if let Some(key) = key {
+            let spki = key.to_public_key().to_public_key_der().unwrap();
+            cert.tbs_certificate.subject_public_key_info = SubjectPublicKeyInfoOwned::from_der(spki.as_bytes()).unwrap();
+        }
+        let algorithm = AlgorithmIdentifierOwned {
+            oid: crate::attestation::OID_RSASSA_PSS,
+            parameters: Some(Any::from_der(&hex::decode(PSS_SHA384_PARAMS).unwrap()).unwrap()),
+        };
+        cert.tbs_certificate.signature = algorithm.clone();
+        cert.signature_algorithm = algorithm;
+
+        let tbs = cert.tbs_certificate.to_der().unwrap();
+        let signature = rsa::pss::BlindedSigningKey::<sha2::Sha384>::new(signer.clone())
+            .sign_with_rng(&mut rand::thread_rng(), &tbs);
+        cert.signature = BitString::from_bytes(&signature.to_bytes()).unwrap();
+        cert
+    }
+
+    /// Generated once: RSA key generation is slow in debug builds
+    fn amd_chain() -> &'static AmdChain {
+        static CHAIN: std::sync::OnceLock<AmdChain> = std::sync::OnceLock::new();
+        CHAIN.get_or_init(generate_amd_chain)
+    }
+
+    fn generate_amd_chain() -> AmdChain {
+        let mut rng = rand::thread_rng();
+        let ark_rsa = rsa::RsaPrivateKey::new(&mut rng, 2048).unwrap();
+        let ask_rsa = rsa::RsaPrivateKey::new(&mut rng, 2048).unwrap();
+
+        // Names, validity and extensions from rcgen; keys and signatures replaced
+        let ark_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384).unwrap();
+        let ask_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384).unwrap();
+        let vcek_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384).unwrap();
+        let ark = params("ARK-Milan", true).self_signed(&ark_key).unwrap();
+        let ask = params("SEV-Milan", true).signed_by(&ask_key, &ark, &ark_key).unwrap();
+        let vcek = params("SEV-VCEK", false).signed_by(&vcek_key, &ask, &ask_key).unwrap();
+
+        AmdChain {
+            ark: resign(&ark, Some(&ark_rsa), &ark_rsa),
+            ask: resign(&ask, Some(&ask_rsa), &ark_rsa),
+            vcek: resign(&vcek, None, &ask_rsa),
+            vcek_key,
+        }
+    }
+
+    fn pem_bundle(certs: &[&Certificate]) -> Vec<u8> {
+        use x509_cert::der::{pem::LineEnding, EncodePem};
+        certs
+            .iter()
+            .map(|c| c.to_pem(LineEnding::LF).unwrap())
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    #[test]
+    fn test_rsa_pss_chain_verifies() {
+        use x509_cert::der::Encode;
+
+        let chain = amd_chain();
+        let static_key = [7u8; 32];
+        let bytes = report(&static_key, 0x30000, &chain.vcek_key);
+
+        let verifier = SnpVerifier::from_pem_bundle(&pem_bundle(&[&chain.ark, &chain.ask]), &[MEASUREMENT.to_vec()]).unwrap();
+        let vcek = chain.vcek.to_der().unwrap();
+        let parsed = verifier.verify(&bytes, &static_key, Some(&vcek)).unwrap();
+        assert_eq!(parsed.measurement, MEASUREMENT);
+    }
+
+    #[test]
+    fn test_tampered_rsa_pss_chain_rejected() {
+        use x509_cert::der::{asn1::BitString, Encode};
+
+        fn tamper(cert: &Certificate) -> Certificate {
+            let mut cert = cert.clone();
+            let mut signature = cert.signature.raw_bytes().to_vec();
+            signature[10] ^= 1;
+            cert.signature = BitString::from_bytes(&signature).unwrap();
+            cert
+        }
+
+        let chain = amd_chain();
+        let static_key = [7u8; 32];
+        let bytes = report(&static_key, 0, &chain.vcek_key);
+
+        // ASK whose RSA-PSS signature no longer matches the ARK
+        let err = SnpVerifier::from_pem_bundle(&pem_bundle(&[&chain.ark, &tamper(&chain.ask)]), &[MEASUREMENT.to_vec()])
+            .err()
+            .unwrap();
+        assert!(matches!(err, AttestationError::Chain(_)));
+
+        // VCEK whose RSA-PSS signature no longer matches the ASK
+        let verifier = SnpVerifier::from_pem_bundle(&pem_bundle(&[&chain.ark, &chain.ask]), &[MEASUREMENT.to_vec()]).unwrap();
+        let vcek = tamper(&chain.vcek).to_der().unwrap();
+        let err = verifier.verify(&bytes, &static_key, Some(&vcek)).unwrap_err();
+        assert!(matches!(err, AttestationError::Chain(_)));
+    }
+
+    /// `ATTESTATION_REPORT` layout from the SEV-SNP firmware ABI spec
+    /// (table "ATTESTATION_REPORT Structure"): name, offset, length.
+    const SPEC_LAYOUT: &[(&str, usize, usize)] = &[
+        ("VERSION", 0x00, 4),
+        ("GUEST_SVN", 0x04, 4),
+        ("POLICY", 0x08, 8),
+        ("FAMILY_ID", 0x10, 16),
+        ("IMAGE_ID", 0x20, 16),
+        ("VMPL", 0x30, 4),
+        ("SIGNATURE_ALGO", 0x34, 4),
+        ("CURRENT_TCB", 0x38, 8),
+        ("PLATFORM_INFO", 0x40, 8),
+        ("KEY_FLAGS", 0x48, 4),
+        ("REPORT_DATA", 0x50, 64),
+        ("MEASUREMENT", 0x90, 48),
+        ("HOST_DATA", 0xC0, 32),
+        ("ID_KEY_DIGEST", 0xE0, 48),
+        ("AUTHOR_KEY_DIGEST", 0x110, 48),
+        ("REPORT_ID", 0x140, 32),
+        ("REPORT_ID_MA", 0x160, 32),
+        ("REPORTED_TCB", 0x180, 8),
+        ("CHIP_ID", 0x1A0, 64),
+        ("COMMITTED_TCB", 0x1E0, 8),
+        ("LAUNCH_TCB", 0x1F0, 8),
+        ("SIGNATURE_R", 0x2A0, 48),
+        ("SIGNATURE_S", 0x2E8, 48),
+    ];
+
+    #[test]
+    fn test_fields_at_spec_offsets() {
+        // Every field filled with its own byte, so a field read at a wrong
+        // offset picks up a neighbour's byte (or a zero gap)
+        let mut bytes = vec![0u8; REPORT_SIZE];
+        let fill = |name: &str| SPEC_LAYOUT.iter().position(|(n, _, _)| *n == name).unwrap() as u8 + 1;
+        for (name, offset, len) in SPEC_LAYOUT {
+            bytes[*offset..offset + len].fill(fill(name));
+        }
+
+        let report = SnpReport::parse(&bytes).unwrap();
+        let word = |name| u32::from_le_bytes([fill(name); 4]);
+        let quad = |name| u64::from_le_bytes([fill(name); 8]);
+        assert_eq!(report.version, word("VERSION"));
+        assert_eq!(report.guest_svn, word("GUEST_SVN"));
+        assert_eq!(report.policy, quad("POLICY"));
+        assert_eq!(report.vmpl, word("VMPL"));
+        assert_eq!(report.signature_algo, word("SIGNATURE_ALGO"));
+        assert_eq!(report.reported_tcb, quad("REPORTED_TCB"));
+        assert_eq!(report.report_data, [fill("REPORT_DATA"); 64]);
+        assert_eq!(report.measurement, [fill("MEASUREMENT"); 48]);
+        assert_eq!(report.host_data, [fill("HOST_DATA"); 32]);
+        assert_eq!(report.chip_id, [fill("CHIP_ID"); 64]);
+        assert_eq!(report.signature_r, [fill("SIGNATURE_R"); 48]);
+        assert_eq!(report.signature_s, [fill("SIGNATURE_S"); 48]);
+
+        // The signed region ends where the signature starts; the report is 1184 bytes
+        assert_eq!(SIGNED_LEN, 0x2A0);
+        assert_eq!(REPORT_SIZE, 1184);
+    }
+
+    #[test]
+    fn test_short_report_rejected() {
+        assert!(matches!(
+            SnpReport::parse(&[0u8; 100]),
+            Err(AttestationError::Malformed(_))
+        ));
+    }
+}
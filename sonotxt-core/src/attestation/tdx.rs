@@ -0,0 +1,1067 @@
+//! Intel TDX DCAP quote (v4) verification.
+//!
+//! A v4 quote is a 48-byte header and a 584-byte TD report, signed by an
+//! attestation key (ECDSA P-256). That key is certified by the Quoting
+//! Enclave, whose SGX report is in turn signed by the platform's PCK
+//! certificate, chaining to the Intel SGX Root CA.
+//!
+//! The verifier works offline from a collateral bundle fetched from Intel
+//! PCS ahead of time: root CA, TCB signing chain, TCB info, QE identity and
+//! CRLs. See [`TdxVerifier::from_collateral`] for the format.
+
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use sha2::{Digest, Sha256};
+use x509_cert::crl::CertificateList;
+use x509_cert::der::asn1::ObjectIdentifier;
+use x509_cert::der::{Any, Decode, DecodePem, Encode, Reader, SliceReader};
+use x509_cert::Certificate;
+
+use super::{
+    check_not_revoked, hex_decode, hex_encode, is_self_signed, parse_crl, verify_crl, verify_issued_by,
+    AttestationError,
+};
+
+const HEADER_LEN: usize = 48;
+const TD_REPORT_LEN: usize = 584;
+/// Header and TD report: the region covered by the quote signature.
+const SIGNED_LEN: usize = HEADER_LEN + TD_REPORT_LEN;
+const SGX_REPORT_LEN: usize = 384;
+
+const QUOTE_VERSION: u16 = 4;
+const ATT_KEY_ECDSA_P256: u16 = 2;
+const TEE_TYPE_TDX: u32 = 0x81;
+const CERT_DATA_PCK_CHAIN: u16 = 5;
+const CERT_DATA_QE_REPORT: u16 = 6;
+
+/// TD_ATTRIBUTES bit 0: the TD is debuggable (host can read its memory).
+const TD_ATTR_DEBUG: u64 = 1;
+
+/// TCB statuses accepted for the platform, TDX module and quoting enclave.
+const ACCEPTED_TCB_STATUS: &[&str] = &["UpToDate", "SWHardeningNeeded"];
+
+const OID_SGX_EXTENSIONS: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1");
+const OID_SGX_TCB: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.2");
+const OID_SGX_PCESVN: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.2.17");
+const OID_SGX_FMSPC: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.4");
+
+// ── Quote ─────────────────────────────────────────────────────────
+
+/// A parsed TDX v4 quote.
+#[derive(Debug, Clone)]
+pub struct TdQuote {
+    pub version: u16,
+    pub att_key_type: u16,
+    pub tee_type: u32,
+    pub tee_tcb_svn: [u8; 16],
+    pub mr_seam: [u8; 48],
+    pub mr_signer_seam: [u8; 48],
+    pub seam_attributes: u64,
+    pub td_attributes: u64,
+    pub xfam: u64,
+    pub mr_td: [u8; 48],
+    pub mr_config_id: [u8; 48],
+    pub mr_owner: [u8; 48],
+    pub mr_owner_config: [u8; 48],
+    pub rtmr: [[u8; 48]; 4],
+    pub report_data: [u8; 64],
+    signature: [u8; 64],
+    attestation_key: [u8; 64],
+    qe_report: Vec<u8>,
+    qe_report_signature: [u8; 64],
+    qe_auth_data: Vec<u8>,
+    pck_chain_pem: Vec<u8>,
+}
+
+impl TdQuote {
+    pub fn parse(bytes: &[u8]) -> Result<Self, AttestationError> {
+        let mut r = Cursor { bytes, pos: 0 };
+
+        let version = r.u16()?;
+        let att_key_type = r.u16()?;
+        let tee_type = r.u32()?;
+        r.take(HEADER_LEN - 8)?; // QE/PCE SVN, QE vendor id, user data
+
+        let tee_tcb_svn = r.array()?;
+        let mr_seam = r.array()?;
+        let mr_signer_seam = r.array()?;
+        let seam_attributes = u64::from_le_bytes(r.array()?);
+        let td_attributes = u64::from_le_bytes(r.array()?);
+        let xfam = u64::from_le_bytes(r.array()?);
+        let mr_td = r.array()?;
+        let mr_config_id = r.array()?;
+        let mr_owner = r.array()?;
+        let mr_owner_config = r.array()?;
+        let rtmr = [r.array()?, r.array()?, r.array()?, r.array()?];
+        let report_data = r.array()?;
+
+        let sig_data_len = r.u32()? as usize;
+        let mut s = Cursor { bytes: r.take(sig_data_len)?, pos: 0 };
+        let signature = s.array()?;
+        let attestation_key = s.array()?;
+
+        if s.u16()? != CERT_DATA_QE_REPORT {
+            return Err(AttestationError::Malformed("expected QE report certification data".into()));
+        }
+        let qe_cert_len = s.u32()? as usize;
+        let mut q = Cursor { bytes: s.take(qe_cert_len)?, pos: 0 };
+        let qe_report = q.take(SGX_REPORT_LEN)?.to_vec();
+        let qe_report_signature = q.array()?;
+        let auth_len = q.u16()? as usize;
+        let qe_auth_data = q.take(auth_len)?.to_vec();
+
+        if q.u16()? != CERT_DATA_PCK_CHAIN {
+            return Err(AttestationError::Malformed("expected PCK certificate chain".into()));
+        }
+        let chain_len = q.u32()? as usize;
+        let pck_chain_pem = q.take(chain_len)?.to_vec();
+
+        Ok(Self {
+            version,
+            att_key_type,
+            tee_type,
+            tee_tcb_svn,
+            mr_seam,
+            mr_signer_seam,
+            seam_attributes,
+            td_attributes,
+            xfam,
+            mr_td,
+            mr_config_id,
+            mr_owner,
+            mr_owner_config,
+            rtmr,
+            report_data,
+            signature,
+            attestation_key,
+            qe_report,
+            qe_report_signature,
+            qe_auth_data,
+            pck_chain_pem,
+        })
+    }
+
+    pub fn debug_allowed(&self) -> bool {
+        self.td_attributes & TD_ATTR_DEBUG != 0
+    }
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], AttestationError> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| AttestationError::Malformed("TDX quote truncated".into()))?;
+        let out = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], AttestationError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    fn u16(&mut self) -> Result<u16, AttestationError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, AttestationError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+}
+
+// ── Measurements ──────────────────────────────────────────────────
+
+/// An allowed TD: MRTD plus optional RTMR values (None = not checked).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TdxMeasurement {
+    pub mr_td: [u8; 48],
+    pub rtmr: [Option<[u8; 48]>; 4],
+}
+
+impl TdxMeasurement {
+    pub fn matches(&self, quote: &TdQuote) -> bool {
+        self.mr_td == quote.mr_td
+            && self
+                .rtmr
+                .iter()
+                .zip(&quote.rtmr)
+                .all(|(want, got)| want.as_ref().is_none_or(|w| w == got))
+    }
+}
+
+/// Canonical `mrtd[:rtmr0[:rtmr1[:rtmr2[:rtmr3]]]]` form, trailing unchecked RTMRs omitted.
+impl std::fmt::Display for TdxMeasurement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex_encode(&self.mr_td))?;
+        let used = self.rtmr.iter().rposition(|r| r.is_some()).map_or(0, |i| i + 1);
+        for rtmr in &self.rtmr[..used] {
+            write!(f, ":{}", rtmr.as_ref().map(|r| hex_encode(r)).unwrap_or_default())?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a comma-separated list of `mrtd[:rtmr0[:rtmr1[:rtmr2[:rtmr3]]]]`
+/// entries in hex. An empty RTMR field is not checked.
+pub fn parse_measurements(list: &str) -> Result<Vec<TdxMeasurement>, String> {
+    fn field(hex: &str) -> Result<[u8; 48], String> {
+        hex_decode(hex)
+            .and_then(|b| <[u8; 48]>::try_from(b).ok())
+            .ok_or_else(|| format!("invalid 48-byte hex measurement: {}", hex))
+    }
+
+    list.split(',')
+        .map(|m| m.trim())
+        .filter(|m| !m.is_empty())
+        .map(|entry| {
+            let mut fields = entry.split(':');
+            let mr_td = field(fields.next().unwrap_or_default())?;
+            let mut rtmr = [None; 4];
+            for slot in rtmr.iter_mut() {
+                match fields.next() {
+                    Some("") | None => {}
+                    Some(hex) => *slot = Some(field(hex)?),
+                }
+            }
+            if fields.next().is_some() {
+                return Err(format!("too many RTMR fields: {}", entry));
+            }
+            Ok(TdxMeasurement { mr_td, rtmr })
+        })
+        .collect()
+}
+
+// ── Collateral ────────────────────────────────────────────────────
+
+/// On-disk collateral bundle (JSON). `tcb_info` and `qe_identity` are the
+/// PCS responses verbatim; their signatures cover the exact JSON text.
+#[derive(Deserialize)]
+struct CollateralBundle {
+    /// Intel SGX Root CA (PEM)
+    root_ca: String,
+    /// TCB Signing certificate, optionally followed by the root (PEM)
+    tcb_signing_chain: String,
+    tcb_info: SignedJson,
+    qe_identity: SignedJson,
+    /// Root CA CRL (PEM or hex DER)
+    root_ca_crl: String,
+    /// PCK Platform/Processor CA CRLs (PEM or hex DER)
+    pck_crls: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SignedJson {
+    #[serde(alias = "tcbInfo", alias = "enclaveIdentity")]
+    body: Box<RawValue>,
+    signature: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TcbInfo {
+    id: String,
+    next_update: String,
+    fmspc: String,
+    tdx_module: Option<ModuleIdentity>,
+    #[serde(default)]
+    tdx_module_identities: Vec<ModuleIdentity>,
+    tcb_levels: Vec<TcbLevel>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModuleIdentity {
+    #[serde(default)]
+    id: String,
+    mrsigner: String,
+    attributes: String,
+    attributes_mask: String,
+    #[serde(default)]
+    tcb_levels: Vec<IsvTcbLevel>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TcbLevel {
+    tcb: Tcb,
+    tcb_status: String,
+}
+
+#[derive(Deserialize)]
+struct Tcb {
+    sgxtcbcomponents: Vec<TcbComponent>,
+    pcesvn: u16,
+    #[serde(default)]
+    tdxtcbcomponents: Vec<TcbComponent>,
+}
+
+#[derive(Deserialize)]
+struct TcbComponent {
+    svn: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EnclaveIdentity {
+    id: String,
+    next_update: String,
+    miscselect: String,
+    miscselect_mask: String,
+    attributes: String,
+    attributes_mask: String,
+    mrsigner: String,
+    isvprodid: u16,
+    tcb_levels: Vec<IsvTcbLevel>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IsvTcbLevel {
+    tcb: IsvTcb,
+    tcb_status: String,
+}
+
+#[derive(Deserialize)]
+struct IsvTcb {
+    isvsvn: u16,
+}
+
+/// TCB values the PCK certificate attests for the platform.
+struct PckTcb {
+    cpusvn: [u8; 16],
+    pcesvn: u16,
+    fmspc: Vec<u8>,
+}
+
+// ── Verifier ──────────────────────────────────────────────────────
+
+/// Verifies TDX quotes against offline collateral and an MRTD/RTMR allow-list.
+pub struct TdxVerifier {
+    root: Certificate,
+    root_crl: CertificateList,
+    pck_crls: Vec<CertificateList>,
+    tcb_info: TcbInfo,
+    qe_identity: EnclaveIdentity,
+    measurements: Vec<TdxMeasurement>,
+}
+
+impl TdxVerifier {
+    /// Build from a JSON collateral bundle:
+    ///
+    /// ```json
+    /// {
+    ///   "root_ca": "-----BEGIN CERTIFICATE-----...",
+    ///   "tcb_signing_chain": "-----BEGIN CERTIFICATE-----...",
+    ///   "tcb_info": { "tcbInfo": { ... }, "signature": "..." },
+    ///   "qe_identity": { "enclaveIdentity": { ... }, "signature": "..." },
+    ///   "root_ca_crl": "...",
+    ///   "pck_crls": ["..."]
+    /// }
+    /// ```
+    ///
+    /// Signatures on the collateral are checked here; freshness is checked
+    /// per quote. An empty measurement allow-list rejects every quote.
+    pub fn from_collateral(json: &[u8], measurements: Vec<TdxMeasurement>) -> Result<Self, AttestationError> {
+        let bundle: CollateralBundle =
+            serde_json::from_slice(json).map_err(|e| AttestationError::Malformed(format!("collateral: {}", e)))?;
+
+        let root = Certificate::from_pem(bundle.root_ca.as_bytes())
+            .map_err(|e| AttestationError::Certificate(e.to_string()))?;
+        if !is_self_signed(&root) {
+            return Err(AttestationError::Certificate("root_ca is not self-signed".into()));
+        }
+        verify_issued_by(&root, &root)?;
+
+        let root_crl = parse_crl(&bundle.root_ca_crl)?;
+        verify_crl(&root_crl, &root)?;
+
+        let signer = Certificate::load_pem_chain(bundle.tcb_signing_chain.as_bytes())
+            .map_err(|e| AttestationError::Certificate(e.to_string()))?
+            .into_iter()
+            .find(|c| !is_self_signed(c))
+            .ok_or_else(|| AttestationError::Certificate("no TCB signing certificate".into()))?;
+        verify_issued_by(&signer, &root)?;
+        check_not_revoked(&signer, &root_crl)?;
+
+        verify_json_signature(&bundle.tcb_info, &signer)?;
+        verify_json_signature(&bundle.qe_identity, &signer)?;
+
+        let tcb_info: TcbInfo = serde_json::from_str(bundle.tcb_info.body.get())
+            .map_err(|e| AttestationError::Malformed(format!("tcbInfo: {}", e)))?;
+        if tcb_info.id != "TDX" {
+            return Err(AttestationError::Malformed(format!("tcbInfo is for {}, not TDX", tcb_info.id)));
+        }
+        let qe_identity: EnclaveIdentity = serde_json::from_str(bundle.qe_identity.body.get())
+            .map_err(|e| AttestationError::Malformed(format!("enclaveIdentity: {}", e)))?;
+        if qe_identity.id != "TD_QE" {
+            return Err(AttestationError::Malformed(format!(
+                "enclaveIdentity is for {}, not TD_QE",
+                qe_identity.id
+            )));
+        }
+
+        let pck_crls = bundle
+            .pck_crls
+            .iter()
+            .map(|c| parse_crl(c))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { root, root_crl, pck_crls, tcb_info, qe_identity, measurements })
+    }
+
+    /// Verify a raw v4 quote for a worker with the given Noise static key.
+    pub fn verify(&self, quote_bytes: &[u8], static_key: &[u8]) -> Result<TdQuote, AttestationError> {
+        let quote = self.verify_quote(quote_bytes, static_key)?;
+        if !self.measurements.iter().any(|m| m.matches(&quote)) {
+            return Err(AttestationError::Measurement(hex_encode(&quote.mr_td)));
+        }
+        Ok(quote)
+    }
+
+    /// Everything [`verify`](Self::verify) checks except the MRTD/RTMR
+    /// allow-list, for callers that keep their own (e.g. a measurement log).
+    pub fn verify_quote(&self, quote_bytes: &[u8], static_key: &[u8]) -> Result<TdQuote, AttestationError> {
+        let quote = TdQuote::parse(quote_bytes)?;
+
+        if quote.version != QUOTE_VERSION || quote.tee_type != TEE_TYPE_TDX {
+            return Err(AttestationError::Malformed(format!(
+                "not a TDX v4 quote (version {}, tee type {:#x})",
+                quote.version, quote.tee_type
+            )));
+        }
+        if quote.att_key_type != ATT_KEY_ECDSA_P256 {
+            return Err(AttestationError::Malformed(format!(
+                "unsupported attestation key type {}",
+                quote.att_key_type
+            )));
+        }
+
+        // PCK chain → configured root, with revocation
+        let chain = Certificate::load_pem_chain(&quote.pck_chain_pem)
+            .map_err(|e| AttestationError::Certificate(e.to_string()))?;
+        let [pck, pck_ca, ..] = chain.as_slice() else {
+            return Err(AttestationError::Chain("PCK chain needs leaf and intermediate".into()));
+        };
+        verify_issued_by(pck_ca, &self.root)?;
+        check_not_revoked(pck_ca, &self.root_crl)?;
+        verify_issued_by(pck, pck_ca)?;
+        let pck_crl = self
+            .pck_crls
+            .iter()
+            .find(|crl| crl.tbs_cert_list.issuer == pck_ca.tbs_certificate.subject)
+            .ok_or_else(|| AttestationError::Chain(format!("no CRL for {}", pck_ca.tbs_certificate.subject)))?;
+        verify_crl(pck_crl, pck_ca)?;
+        check_not_revoked(pck, pck_crl)?;
+
+        // PCK signs the QE report; the QE report commits to the attestation key
+        let pck_key = spki_bytes(pck)?;
+        if !verify_p256_raw(pck_key, &quote.qe_report, &quote.qe_report_signature) {
+            return Err(AttestationError::Signature);
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(quote.attestation_key);
+        hasher.update(&quote.qe_auth_data);
+        let qe_report_data = &quote.qe_report[320..384];
+        if qe_report_data[..32] != hasher.finalize()[..] || qe_report_data[32..].iter().any(|&b| b != 0) {
+            return Err(AttestationError::Chain("QE report does not commit to the attestation key".into()));
+        }
+
+        // Attestation key signs header + TD report
+        let mut att_key = [0u8; 65];
+        att_key[0] = 0x04;
+        att_key[1..].copy_from_slice(&quote.attestation_key);
+        if !verify_p256_raw(&att_key, &quote_bytes[..SIGNED_LEN], &quote.signature) {
+            return Err(AttestationError::Signature);
+        }
+
+        self.check_qe_identity(&quote.qe_report)?;
+        self.check_tcb(&quote, &pck_tcb(pck)?)?;
+
+        if quote.report_data != super::report_data(static_key) {
+            return Err(AttestationError::KeyBinding);
+        }
+
+        if quote.debug_allowed() {
+            return Err(AttestationError::Policy("TD attributes allow debugging".into()));
+        }
+
+        Ok(quote)
+    }
+
+    /// Subject of the configured root, for logging.
+    pub fn root_subject(&self) -> String {
+        self.root.tbs_certificate.subject.to_string()
+    }
+
+    fn check_qe_identity(&self, qe_report: &[u8]) -> Result<(), AttestationError> {
+        let id = &self.qe_identity;
+        check_fresh("QE identity", &id.next_update)?;
+
+        let miscselect = &qe_report[16..20];
+        let attributes = &qe_report[48..64];
+        let mrsigner = &qe_report[128..160];
+        let isvprodid = u16::from_le_bytes([qe_report[256], qe_report[257]]);
+        let isvsvn = u16::from_le_bytes([qe_report[258], qe_report[259]]);
+
+        if !masked_eq(miscselect, &id.miscselect, &id.miscselect_mask)?
+            || !masked_eq(attributes, &id.attributes, &id.attributes_mask)?
+            || Some(mrsigner.to_vec()) != hex_decode(&id.mrsigner)
+            || isvprodid != id.isvprodid
+        {
+            return Err(AttestationError::Policy("quoting enclave does not match QE identity".into()));
+        }
+
+        let status = isv_tcb_status(&id.tcb_levels, isvsvn);
+        check_status("quoting enclave", status)
+    }
+
+    fn check_tcb(&self, quote: &TdQuote, pck: &PckTcb) -> Result<(), AttestationError> {
+        let info = &self.tcb_info;
+        check_fresh("TCB info", &info.next_update)?;
+
+        if hex_decode(&info.fmspc).as_deref() != Some(pck.fmspc.as_slice()) {
+            return Err(AttestationError::Policy("TCB info FMSPC does not match the platform".into()));
+        }
+
+        // TDX module: TDX 1.0 uses tdxModule; newer modules (TEE_TCB_SVN[1] > 0)
+        // are matched by versioned identity and rated by their own TCB levels.
+        let module_version = quote.tee_tcb_svn[1];
+        let module = if module_version > 0 {
+            let id = format!("TDX_{:02X}", module_version);
+            let module = info
+                .tdx_module_identities
+                .iter()
+                .find(|m| m.id == id)
+                .ok_or_else(|| AttestationError::Policy(format!("no TCB info for TDX module {}", id)))?;
+            check_status("TDX module", isv_tcb_status(&module.tcb_levels, quote.tee_tcb_svn[0] as u16))?;
+            Some(module)
+        } else {
+            info.tdx_module.as_ref()
+        };
+        if let Some(module) = module {
+            if Some(quote.mr_signer_seam.to_vec()) != hex_decode(&module.mrsigner)
+                || !masked_eq(&quote.seam_attributes.to_le_bytes(), &module.attributes, &module.attributes_mask)?
+            {
+                return Err(AttestationError::Policy("TDX module does not match TCB info".into()));
+            }
+        }
+
+        let tdx_from = if module_version > 0 { 2 } else { 0 };
+        let status = info
+            .tcb_levels
+            .iter()
+            .find(|level| {
+                level.tcb.sgxtcbcomponents.len() == 16
+                    && level.tcb.sgxtcbcomponents.iter().zip(pck.cpusvn).all(|(c, svn)| svn >= c.svn)
+                    && pck.pcesvn >= level.tcb.pcesvn
+                    && level
+                        .tcb
+                        .tdxtcbcomponents
+                        .iter()
+                        .zip(quote.tee_tcb_svn)
+                        .skip(tdx_from)
+                        .all(|(c, svn)| svn >= c.svn)
+            })
+            .map(|level| level.tcb_status.as_str());
+        check_status("platform", status)
+    }
+}
+
+fn isv_tcb_status(levels: &[IsvTcbLevel], isvsvn: u16) -> Option<&str> {
+    levels
+        .iter()
+        .find(|level| isvsvn >= level.tcb.isvsvn)
+        .map(|level| level.tcb_status.as_str())
+}
+
+fn check_status(what: &str, status: Option<&str>) -> Result<(), AttestationError> {
+    match status {
+        Some(s) if ACCEPTED_TCB_STATUS.contains(&s) => Ok(()),
+        Some(s) => Err(AttestationError::Policy(format!("{} TCB status is {}", what, s))),
+        None => Err(AttestationError::Policy(format!("{} TCB is below every known level", what))),
+    }
+}
+
+fn check_fresh(what: &str, next_update: &str) -> Result<(), AttestationError> {
+    let next = chrono::DateTime::parse_from_rfc3339(next_update)
+        .map_err(|e| AttestationError::Malformed(format!("{} nextUpdate: {}", what, e)))?;
+    if chrono::Utc::now() > next {
+        return Err(AttestationError::Policy(format!("{} collateral expired at {}", what, next_update)));
+    }
+    Ok(())
+}
+
+/// `value & mask == expected & mask`, with `expected`/`mask` as hex.
+fn masked_eq(value: &[u8], expected: &str, mask: &str) -> Result<bool, AttestationError> {
+    let (Some(expected), Some(mask)) = (hex_decode(expected), hex_decode(mask)) else {
+        return Err(AttestationError::Malformed("invalid hex in collateral".into()));
+    };
+    if expected.len() != value.len() || mask.len() != value.len() {
+        return Err(AttestationError::Malformed("collateral field has the wrong length".into()));
+    }
+    Ok(value
+        .iter()
+        .zip(&expected)
+        .zip(&mask)
+        .all(|((v, e), m)| v & m == e & m))
+}
+
+fn verify_json_signature(signed: &SignedJson, signer: &Certificate) -> Result<(), AttestationError> {
+    let signature = hex_decode(&signed.signature)
+        .and_then(|s| <[u8; 64]>::try_from(s).ok())
+        .ok_or_else(|| AttestationError::Malformed("collateral signature must be 64 bytes hex".into()))?;
+    if verify_p256_raw(spki_bytes(signer)?, signed.body.get().as_bytes(), &signature) {
+        Ok(())
+    } else {
+        Err(AttestationError::Chain("collateral signature does not verify".into()))
+    }
+}
+
+/// ECDSA P-256/SHA-256 with a raw `r || s` signature, as used inside quotes.
+fn verify_p256_raw(public_key: &[u8], message: &[u8], signature: &[u8; 64]) -> bool {
+    use p256::ecdsa::signature::Verifier;
+
+    let Ok(key) = p256::ecdsa::VerifyingKey::from_sec1_bytes(public_key) else {
+        return false;
+    };
+    let Ok(signature) = p256::ecdsa::Signature::from_slice(signature) else {
+        return false;
+    };
+    key.verify(message, &signature).is_ok()
+}
+
+fn spki_bytes(cert: &Certificate) -> Result<&[u8], AttestationError> {
+    cert.tbs_certificate
+        .subject_public_key_info
+        .subject_public_key
+        .as_bytes()
+        .ok_or_else(|| AttestationError::Certificate("unaligned public key".into()))
+}
+
+// ── PCK certificate SGX extension ─────────────────────────────────
+
+fn der_err(e: x509_cert::der::Error) -> AttestationError {
+    AttestationError::Certificate(format!("PCK SGX extension: {}", e))
+}
+
+/// Decode `SEQUENCE OF SEQUENCE { OID, ANY }`, the shape of the SGX extension.
+fn oid_entries(der: &[u8]) -> Result<Vec<(ObjectIdentifier, Any)>, AttestationError> {
+    Vec::<Any>::from_der(der)
+        .map_err(der_err)?
+        .into_iter()
+        .map(|item| {
+            let mut reader = SliceReader::new(item.value()).map_err(der_err)?;
+            let oid = ObjectIdentifier::decode(&mut reader).map_err(der_err)?;
+            let value = Any::decode(&mut reader).map_err(der_err)?;
+            reader.finish(()).map_err(der_err)?;
+            Ok((oid, value))
+        })
+        .collect()
+}
+
+fn pck_tcb(pck: &Certificate) -> Result<PckTcb, AttestationError> {
+    let ext = pck
+        .tbs_certificate
+        .extensions
+        .iter()
+        .flatten()
+        .find(|e| e.extn_id == OID_SGX_EXTENSIONS)
+        .ok_or_else(|| AttestationError::Certificate("PCK certificate has no SGX extension".into()))?;
+
+    let mut cpusvn = [0u8; 16];
+    let mut pcesvn = None;
+    let mut fmspc = None;
+
+    for (oid, value) in oid_entries(ext.extn_value.as_bytes())? {
+        if oid == OID_SGX_FMSPC {
+            fmspc = Some(value.value().to_vec());
+        } else if oid == OID_SGX_TCB {
+            for (oid, value) in oid_entries(&value.to_der().map_err(der_err)?)? {
+                let der = value.to_der().map_err(der_err)?;
+                if oid == OID_SGX_PCESVN {
+                    pcesvn = Some(u16::from_der(&der).map_err(der_err)?);
+                } else if let Some(i) = oid
+                    .as_bytes()
+                    .strip_prefix(OID_SGX_TCB.as_bytes())
+                    .and_then(|arc| arc.first())
+                    .filter(|&&arc| (1..=16).contains(&arc))
+                {
+                    cpusvn[*i as usize - 1] = u8::from_der(&der).map_err(der_err)?;
+                }
+            }
+        }
+    }
+
+    match (pcesvn, fmspc) {
+        (Some(pcesvn), Some(fmspc)) => Ok(PckTcb { cpusvn, pcesvn, fmspc }),
+        _ => Err(AttestationError::Certificate("PCK SGX extension lacks TCB or FMSPC".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::signature::Signer;
+    use p256::pkcs8::DecodePrivateKey;
+    use rcgen::{
+        BasicConstraints, CertificateParams, CertificateRevocationListParams, CustomExtension,
+        DistinguishedName, DnType, IsCa, KeyIdMethod, KeyPair, RevokedCertParams, SerialNumber,
+        PKCS_ECDSA_P256_SHA256,
+    };
+    use serde_json::json;
+
+    const MR_TD: [u8; 48] = [0x11; 48];
+    const RTMR0: [u8; 48] = [0x22; 48];
+    const QE_MRSIGNER: [u8; 32] = [0xdc; 32];
+    const FMSPC: [u8; 6] = [0x00, 0x90, 0x6e, 0xd5, 0x00, 0x00];
+    const PCK_SERIAL: [u8; 4] = [0x01, 0x02, 0x03, 0x04];
+
+    /// Synthetic Intel-shaped PKI: root → PCK CA → PCK, root → TCB signer.
+    struct Fixture {
+        root: rcgen::Certificate,
+        root_key: KeyPair,
+        pck_ca: rcgen::Certificate,
+        pck_ca_key: KeyPair,
+        pck: rcgen::Certificate,
+        pck_key: KeyPair,
+        tcb_signer: rcgen::Certificate,
+        tcb_signer_key: KeyPair,
+        att_key: KeyPair,
+    }
+
+    fn params(cn: &str, ca: bool) -> CertificateParams {
+        let mut params = CertificateParams::new(vec![]).unwrap();
+        let mut dn = DistinguishedName::new();
+        dn.push(DnType::CommonName, cn);
+        params.distinguished_name = dn;
+        if ca {
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        }
+        params
+    }
+
+    fn key() -> KeyPair {
+        KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).unwrap()
+    }
+
+    fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        match content.len() {
+            n if n < 0x80 => out.push(n as u8),
+            n if n < 0x100 => out.extend([0x81, n as u8]),
+            n => out.extend([0x82, (n >> 8) as u8, n as u8]),
+        }
+        out.extend_from_slice(content);
+        out
+    }
+
+    fn entry(oid: &str, value: Vec<u8>) -> Vec<u8> {
+        let oid = ObjectIdentifier::new(oid).unwrap().to_der().unwrap();
+        tlv(0x30, &[oid, value].concat())
+    }
+
+    fn sgx_extension(cpusvn: u8, pcesvn: u16) -> Vec<u8> {
+        let mut tcb: Vec<Vec<u8>> = (1..=16)
+            .map(|i| entry(&format!("1.2.840.113741.1.13.1.2.{}", i), (cpusvn as u32).to_der().unwrap()))
+            .collect();
+        tcb.push(entry("1.2.840.113741.1.13.1.2.17", (pcesvn as u32).to_der().unwrap()));
+        tcb.push(entry("1.2.840.113741.1.13.1.2.18", tlv(0x04, &[cpusvn; 16])));
+
+        tlv(
+            0x30,
+            &[
+                entry("1.2.840.113741.1.13.1.1", tlv(0x04, &[0xaa; 16])),
+                entry("1.2.840.113741.1.13.1.2", tlv(0x30, &tcb.concat())),
+                entry("1.2.840.113741.1.13.1.3", tlv(0x04, &[0, 0])),
+                entry("1.2.840.113741.1.13.1.4", tlv(0x04, &FMSPC)),
+            ]
+            .concat(),
+        )
+    }
+
+    fn fixture(cpusvn: u8) -> Fixture {
+        let root_key = key();
+        let root = params("Intel SGX Root CA", true).self_signed(&root_key).unwrap();
+        let pck_ca_key = key();
+        let pck_ca = params("Intel SGX PCK Platform CA", true)
+            .signed_by(&pck_ca_key, &root, &root_key)
+            .unwrap();
+
+        let pck_key = key();
+        let mut pck_params = params("Intel SGX PCK Certificate", false);
+        pck_params.serial_number = Some(SerialNumber::from_slice(&PCK_SERIAL));
+        pck_params.custom_extensions = vec![CustomExtension::from_oid_content(
+            &[1, 2, 840, 113741, 1, 13, 1],
+            sgx_extension(cpusvn, 11),
+        )];
+        let pck = pck_params.signed_by(&pck_key, &pck_ca, &pck_ca_key).unwrap();
+
+        let tcb_signer_key = key();
+        let tcb_signer = params("Intel SGX TCB Signing", false)
+            .signed_by(&tcb_signer_key, &root, &root_key)
+            .unwrap();
+
+        Fixture { root, root_key, pck_ca, pck_ca_key, pck, pck_key, tcb_signer, tcb_signer_key, att_key: key() }
+    }
+
+    fn crl(issuer: &rcgen::Certificate, key: &KeyPair, revoked: &[[u8; 4]]) -> String {
+        CertificateRevocationListParams {
+            this_update: rcgen::date_time_ymd(2024, 1, 1),
+            next_update: rcgen::date_time_ymd(2099, 1, 1),
+            crl_number: SerialNumber::from(1u64),
+            issuing_distribution_point: None,
+            revoked_certs: revoked
+                .iter()
+                .map(|serial| RevokedCertParams {
+                    serial_number: SerialNumber::from_slice(serial),
+                    revocation_time: rcgen::date_time_ymd(2024, 1, 1),
+                    reason_code: None,
+                    invalidity_date: None,
+                })
+                .collect(),
+            key_identifier_method: KeyIdMethod::Sha256,
+        }
+        .signed_by(issuer, key)
+        .unwrap()
+        .pem()
+        .unwrap()
+    }
+
+    fn sign_raw(key: &KeyPair, message: &[u8]) -> [u8; 64] {
+        let key = p256::ecdsa::SigningKey::from_pkcs8_der(&key.serialize_der()).unwrap();
+        let signature: p256::ecdsa::Signature = key.sign(message);
+        signature.to_bytes().into()
+    }
+
+    fn signed(f: &Fixture, field: &str, body: serde_json::Value) -> serde_json::Value {
+        let signature = sign_raw(&f.tcb_signer_key, body.to_string().as_bytes());
+        json!({ field: body, "signature": hex_encode(&signature) })
+    }
+
+    fn collateral(f: &Fixture, revoked: &[[u8; 4]]) -> Vec<u8> {
+        let levels = |sgx: u8, status: &str| {
+            json!({
+                "tcb": {
+                    "sgxtcbcomponents": vec![json!({ "svn": sgx }); 16],
+                    "pcesvn": 11,
+                    "tdxtcbcomponents": vec![json!({ "svn": 0 }); 16],
+                },
+                "tcbDate": "2024-01-01T00:00:00Z",
+                "tcbStatus": status,
+            })
+        };
+        let tcb_info = json!({
+            "id": "TDX",
+            "version": 3,
+            "issueDate": "2024-01-01T00:00:00Z",
+            "nextUpdate": "2099-01-01T00:00:00Z",
+            "fmspc": hex_encode(&FMSPC),
+            "pceId": "0000",
+            "tcbType": 0,
+            "tcbEvaluationDataNumber": 17,
+            "tdxModule": {
+                "mrsigner": hex_encode(&[0u8; 48]),
+                "attributes": "0000000000000000",
+                "attributesMask": "ffffffffffffffff",
+            },
+            "tcbLevels": [levels(5, "UpToDate"), levels(2, "OutOfDate")],
+        });
+        let qe_identity = json!({
+            "id": "TD_QE",
+            "version": 2,
+            "issueDate": "2024-01-01T00:00:00Z",
+            "nextUpdate": "2099-01-01T00:00:00Z",
+            "miscselect": "00000000",
+            "miscselectMask": "ffffffff",
+            "attributes": "11000000000000000000000000000000",
+            "attributesMask": "fbffffffffffffff0000000000000000",
+            "mrsigner": hex_encode(&QE_MRSIGNER),
+            "isvprodid": 2,
+            "tcbLevels": [{ "tcb": { "isvsvn": 4 }, "tcbDate": "2024-01-01T00:00:00Z", "tcbStatus": "UpToDate" }],
+        });
+
+        json!({
+            "root_ca": f.root.pem(),
+            "tcb_signing_chain": format!("{}{}", f.tcb_signer.pem(), f.root.pem()),
+            "tcb_info": signed(f, "tcbInfo", tcb_info),
+            "qe_identity": signed(f, "enclaveIdentity", qe_identity),
+            "root_ca_crl": crl(&f.root, &f.root_key, &[]),
+            "pck_crls": [crl(&f.pck_ca, &f.pck_ca_key, revoked)],
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    fn quote(f: &Fixture, static_key: &[u8], td_attributes: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(QUOTE_VERSION.to_le_bytes());
+        out.extend(ATT_KEY_ECDSA_P256.to_le_bytes());
+        out.extend(TEE_TYPE_TDX.to_le_bytes());
+        out.extend([0u8; HEADER_LEN - 8]);
+
+        let mut body = [0u8; TD_REPORT_LEN];
+        body[0] = 3; // TEE_TCB_SVN, TDX 1.0 module
+        body[120..128].copy_from_slice(&td_attributes.to_le_bytes());
+        body[136..184].copy_from_slice(&MR_TD);
+        body[328..376].copy_from_slice(&RTMR0);
+        body[520..584].copy_from_slice(&crate::attestation::report_data(static_key));
+        out.extend(body);
+
+        let att_pub = p256::ecdsa::SigningKey::from_pkcs8_der(&f.att_key.serialize_der())
+            .unwrap()
+            .verifying_key()
+            .to_encoded_point(false);
+        let att_pub = &att_pub.as_bytes()[1..];
+        let qe_auth_data = [0x5au8; 32];
+
+        let mut qe_report = [0u8; SGX_REPORT_LEN];
+        qe_report[48] = 0x11;
+        qe_report[128..160].copy_from_slice(&QE_MRSIGNER);
+        qe_report[256..258].copy_from_slice(&2u16.to_le_bytes());
+        qe_report[258..260].copy_from_slice(&4u16.to_le_bytes());
+        let mut hasher = Sha256::new();
+        hasher.update(att_pub);
+        hasher.update(qe_auth_data);
+        qe_report[320..352].copy_from_slice(&hasher.finalize());
+
+        let chain = format!("{}{}{}", f.pck.pem(), f.pck_ca.pem(), f.root.pem()).into_bytes();
+        let mut qe_cert = Vec::new();
+        qe_cert.extend(qe_report);
+        qe_cert.extend(sign_raw(&f.pck_key, &qe_report));
+        qe_cert.extend((qe_auth_data.len() as u16).to_le_bytes());
+        qe_cert.extend(qe_auth_data);
+        qe_cert.extend(CERT_DATA_PCK_CHAIN.to_le_bytes());
+        qe_cert.extend((chain.len() as u32).to_le_bytes());
+        qe_cert.extend(chain);
+
+        let mut sig_data = Vec::new();
+        sig_data.extend(sign_raw(&f.att_key, &out[..SIGNED_LEN]));
+        sig_data.extend(att_pub);
+        sig_data.extend(CERT_DATA_QE_REPORT.to_le_bytes());
+        sig_data.extend((qe_cert.len() as u32).to_le_bytes());
+        sig_data.extend(qe_cert);
+
+        out.extend((sig_data.len() as u32).to_le_bytes());
+        out.extend(sig_data);
+        out
+    }
+
+    fn allow(rtmr0: Option<[u8; 48]>) -> Vec<TdxMeasurement> {
+        vec![TdxMeasurement { mr_td: MR_TD, rtmr: [rtmr0, None, None, None] }]
+    }
+
+    fn verifier(f: &Fixture) -> TdxVerifier {
+        TdxVerifier::from_collateral(&collateral(f, &[]), allow(Some(RTMR0))).unwrap()
+    }
+
+    #[test]
+    fn test_valid_quote_verifies() {
+        let f = fixture(5);
+        let static_key = [7u8; 32];
+        let parsed = verifier(&f).verify(&quote(&f, &static_key, 0), &static_key).unwrap();
+        assert_eq!(parsed.mr_td, MR_TD);
+        assert_eq!(parsed.rtmr[0], RTMR0);
+    }
+
+    #[test]
+    fn test_tampered_quote_rejected() {
+        let f = fixture(5);
+        let static_key = [7u8; 32];
+        let mut bytes = quote(&f, &static_key, 0);
+        bytes[HEADER_LEN + 136] ^= 1;
+
+        let err = verifier(&f).verify(&bytes, &static_key).unwrap_err();
+        assert!(matches!(err, AttestationError::Signature));
+    }
+
+    #[test]
+    fn test_wrong_static_key_rejected() {
+        let f = fixture(5);
+        let bytes = quote(&f, &[7u8; 32], 0);
+
+        let err = verifier(&f).verify(&bytes, &[8u8; 32]).unwrap_err();
+        assert!(matches!(err, AttestationError::KeyBinding));
+    }
+
+    #[test]
+    fn test_unlisted_rtmr_rejected() {
+        let f = fixture(5);
+        let static_key = [7u8; 32];
+        let bytes = quote(&f, &static_key, 0);
+
+        let strict = TdxVerifier::from_collateral(&collateral(&f, &[]), allow(Some([0u8; 48]))).unwrap();
+        let err = strict.verify(&bytes, &static_key).unwrap_err();
+        assert!(matches!(err, AttestationError::Measurement(_)));
+
+        let mrtd_only = TdxVerifier::from_collateral(&collateral(&f, &[]), allow(None)).unwrap();
+        assert!(mrtd_only.verify(&bytes, &static_key).is_ok());
+    }
+
+    #[test]
+    fn test_debug_td_rejected() {
+        let f = fixture(5);
+        let static_key = [7u8; 32];
+        let bytes = quote(&f, &static_key, TD_ATTR_DEBUG);
+
+        let err = verifier(&f).verify(&bytes, &static_key).unwrap_err();
+        assert!(matches!(err, AttestationError::Policy(_)));
+    }
+
+    #[test]
+    fn test_revoked_pck_rejected() {
+        let f = fixture(5);
+        let static_key = [7u8; 32];
+        let verifier = TdxVerifier::from_collateral(&collateral(&f, &[PCK_SERIAL]), allow(None)).unwrap();
+
+        let err = verifier.verify(&quote(&f, &static_key, 0), &static_key).unwrap_err();
+        assert!(matches!(err, AttestationError::Chain(_)));
+    }
+
+    #[test]
+    fn test_out_of_date_tcb_rejected() {
+        let f = fixture(3);
+        let static_key = [7u8; 32];
+
+        let err = verifier(&f).verify(&quote(&f, &static_key, 0), &static_key).unwrap_err();
+        assert!(matches!(err, AttestationError::Policy(ref m) if m.contains("OutOfDate")));
+    }
+
+    #[test]
+    fn test_other_root_rejected() {
+        let f = fixture(5);
+        let other = fixture(5);
+        let static_key = [7u8; 32];
+
+        let err = verifier(&other).verify(&quote(&f, &static_key, 0), &static_key).unwrap_err();
+        assert!(matches!(err, AttestationError::Chain(_)));
+    }
+
+    #[test]
+    fn test_tampered_collateral_rejected() {
+        let f = fixture(5);
+        let json = String::from_utf8(collateral(&f, &[])).unwrap().replace("UpToDate", "OutOfDate");
+        assert!(TdxVerifier::from_collateral(json.as_bytes(), allow(None)).is_err());
+    }
+
+    #[test]
+    fn test_parse_measurements() {
+        let mrtd = "11".repeat(48);
+        let rtmr1 = "33".repeat(48);
+        let list = parse_measurements(&format!("{}::{} , {}", mrtd, rtmr1, mrtd)).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].rtmr, [None, Some([0x33; 48]), None, None]);
+        assert_eq!(list[1].rtmr, [None; 4]);
+        assert_eq!(list[0].to_string(), format!("{}::{}", mrtd, rtmr1));
+        assert_eq!(list[1].to_string(), mrtd);
+        assert!(parse_measurements("abcd").is_err());
+    }
+}
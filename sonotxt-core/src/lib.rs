@@ -1,3 +1,4 @@
+pub mod attestation;
 pub mod config;
 pub mod error;
 pub mod models;
@@ -11,6 +12,6 @@ pub use config::StorageConfig;
 pub use error::{ApiError, Result};
 pub use models::{JobStatus, ProcessRequest, ProcessResponse, MS_PER_CHAR};
 pub use noise::{NoiseClient, NoiseServer};
-pub use protocol::{AttestationBundle, EncryptedTtsRequest, EncryptedTtsResponse, EncryptedAsrRequest, EncryptedAsrResponse, Message, StreamChunk, TeeType, WorkerHealth};
+pub use protocol::{AttestationBundle, EncryptedTtsRequest, EncryptedTtsResponse, EncryptedAsrRequest, EncryptedAsrResponse, EncryptedLlmRequest, LlmEvent, Message, StreamChunk, TeeType, WireFormat, WorkerCapabilities, WorkerEnrollment, WorkerHealth};
 pub use storage::{StorageBackend, StorageService, UploadResult};
-pub use worker_types::{ServiceError, TtsRequest, TtsResponse, AsrRequest, AsrResponse, LlmRequest, LlmResponse, LlmMessage};
+pub use worker_types::{DEFAULT_ENGINE, Privacy, ServiceError, SynthesisOptions, Transport, TtsRequest, TtsResponse, AsrRequest, AsrResponse, LlmRequest, LlmResponse, LlmMessage, SentenceParser};
@@ -48,4 +48,6 @@ pub enum JobStatus {
         ipfs_cid: Option<String>,
     },
     Failed { reason: String },
+    /// Cancelled by its owner before it finished
+    Cancelled,
 }
@@ -1,90 +1,454 @@
-//! Noise_NK_25519_ChaChaPoly_SHA256 session management.
+//! Noise_{NK,KK,XK}_25519_ChaChaPoly_SHA256 session management.
 //!
-//! NK pattern: the client knows the server's static key (from attestation).
-//! Server authenticates to client; client is anonymous (API identity comes
-//! from the job queue, not from the transport).
+//! In every pattern the client knows the server's static key (from
+//! attestation), so the server always authenticates to the client.
+//!
+//! - NK: client is anonymous (API identity comes from the job queue, not
+//!   from the transport). Anyone who can reach the worker can use it.
+//! - KK: client static key is known to the server in advance. The server
+//!   tries each authorized key against the first message. 1 round trip.
+//! - XK: client static key is sent encrypted in a third message and checked
+//!   against the authorized keys. Hides the API identity from observers.
 //!
 //! Large messages are chunked to fit Noise's 65535-byte frame limit.
+//!
+//! Each message carries the nonce of its first chunk (negotiated in the
+//! handshake payload), so requests on parallel QUIC streams may be
+//! encrypted and decrypted in any order; a sliding window rejects replays.
+//! The nonce and chunk count are repeated inside the first chunk, and every
+//! chunk says whether it is the last, so a message can't be truncated or
+//! stitched together from chunks of other messages.
+//! Peers that don't offer explicit nonces get the legacy sequential format.
 
-use snow::{Builder, HandshakeState, Keypair, TransportState};
+use snow::params::DHChoice;
+use snow::resolvers::{CryptoResolver, DefaultResolver};
+use snow::{Builder, HandshakeState, Keypair, StatelessTransportState, TransportState};
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
-const NOISE_PATTERN: &str = "Noise_NK_25519_ChaChaPoly_SHA256";
+/// X25519 key length (public and private).
+pub const KEY_LEN: usize = 32;
 /// Leave room for auth tag (16 bytes) + overhead
 const MAX_CHUNK_SIZE: usize = 65000;
 
+// ── Handshake pattern ──────────────────────────────────────────
+
+/// Noise handshake pattern between API (initiator) and worker (responder).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HandshakePattern {
+    /// Anonymous client
+    #[default]
+    Nk,
+    /// Client key known to the server in advance
+    Kk,
+    /// Client key transmitted in the third handshake message
+    Xk,
+}
+
+impl HandshakePattern {
+    fn params(&self) -> &'static str {
+        match self {
+            Self::Nk => "Noise_NK_25519_ChaChaPoly_SHA256",
+            Self::Kk => "Noise_KK_25519_ChaChaPoly_SHA256",
+            Self::Xk => "Noise_XK_25519_ChaChaPoly_SHA256",
+        }
+    }
+
+    /// Whether the client authenticates with a static key.
+    pub fn is_mutual(&self) -> bool {
+        !matches!(self, Self::Nk)
+    }
+}
+
+impl FromStr for HandshakePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "nk" => Ok(Self::Nk),
+            "kk" => Ok(Self::Kk),
+            "xk" => Ok(Self::Xk),
+            other => Err(format!("unknown noise pattern: {} (expected nk, kk or xk)", other)),
+        }
+    }
+}
+
+impl fmt::Display for HandshakePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Nk => "nk",
+            Self::Kk => "kk",
+            Self::Xk => "xk",
+        })
+    }
+}
+
+// ── Static keys ────────────────────────────────────────────────
+
+/// Generate a fresh X25519 static keypair.
+pub fn generate_keypair() -> Result<Keypair, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(Builder::new(HandshakePattern::Nk.params().parse()?).generate_keypair()?)
+}
+
+/// Derive the X25519 public key for a static private key.
+pub fn public_key(private_key: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    if private_key.len() != KEY_LEN {
+        return Err(format!("static key must be {} bytes, got {}", KEY_LEN, private_key.len()).into());
+    }
+    let mut dh = DefaultResolver
+        .resolve_dh(&DHChoice::Curve25519)
+        .ok_or("no X25519 implementation")?;
+    dh.set(private_key);
+    Ok(dh.pubkey().to_vec())
+}
+
+/// Parse a comma-separated list of hex X25519 public keys.
+pub fn parse_keys(list: &str) -> Result<Vec<[u8; KEY_LEN]>, Box<dyn std::error::Error + Send + Sync>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .map(|k| {
+            let bytes = crate::attestation::hex_decode(k).ok_or_else(|| format!("invalid hex key: {}", k))?;
+            <[u8; KEY_LEN]>::try_from(bytes.as_slice())
+                .map_err(|_| format!("key must be {} bytes: {}", KEY_LEN, k).into())
+        })
+        .collect()
+}
+
+// ── Session transport ──────────────────────────────────────────
+
+/// Handshake payload byte offering/accepting explicit nonces. Peers that
+/// predate explicit nonces send an empty payload and ignore ours, and the
+/// session falls back to sequential nonces. (1 was explicit nonces without
+/// authenticated framing; peers offering it fall back too.)
+const EXPLICIT_NONCES: u8 = 2;
+
+/// Flags byte opening every explicit-nonce chunk's plaintext.
+const CHUNK_FIRST: u8 = 1;
+const CHUNK_FINAL: u8 = 2;
+/// Plaintext framing of an explicit-nonce message's first chunk:
+/// `[flags u8][base_nonce u64][num_chunks u32]`; later chunks have only
+/// the flags byte.
+const FIRST_CHUNK_HEADER: usize = 13;
+/// Number of recent nonces remembered for replay detection.
+const REPLAY_WINDOW: u64 = 1 << 16;
+
+/// An established Noise session.
+///
+/// With explicit nonces every message carries the nonce of its first chunk,
+/// so concurrent streams on one connection can encrypt and decrypt in any
+/// order without serializing on the cipher state; only the replay window
+/// takes a (short) lock. Sessions are independent of each other.
+pub struct Session {
+    transport: Transport,
+}
+
+enum Transport {
+    /// `[num_chunks u32][base_nonce u64]` then chunks sealed with
+    /// `base_nonce + i`, each opening with its flags (and the first with
+    /// the header again, see `FIRST_CHUNK_HEADER`), so the header in the
+    /// clear is checked against what the chunks authenticate. Senders
+    /// reserve nonces atomically.
+    Explicit {
+        cipher: StatelessTransportState,
+        send_nonce: AtomicU64,
+        replay: Mutex<ReplayWindow>,
+    },
+    /// `[num_chunks u32]` then chunks under implicit sequential nonces
+    /// (legacy peers): messages must be decrypted in the order sent.
+    Sequential(Mutex<TransportState>),
+}
+
+impl Session {
+    fn new(handshake: HandshakeState, explicit_nonces: bool) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let transport = if explicit_nonces {
+            Transport::Explicit {
+                cipher: handshake.into_stateless_transport_mode()?,
+                send_nonce: AtomicU64::new(0),
+                replay: Mutex::new(ReplayWindow::default()),
+            }
+        } else {
+            Transport::Sequential(Mutex::new(handshake.into_transport_mode()?))
+        };
+        Ok(Self { transport })
+    }
+
+    /// Whether the peer negotiated explicit nonces.
+    pub fn explicit_nonces(&self) -> bool {
+        matches!(self.transport, Transport::Explicit { .. })
+    }
+
+    /// Encrypt plaintext → chunked ciphertext.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut ciphertext = vec![0u8; MAX_CIPHERTEXT_CHUNK];
+
+        match &self.transport {
+            Transport::Explicit { cipher, send_nonce, .. } => {
+                // Room for the framing; an empty message is still one chunk
+                let mut chunks: Vec<&[u8]> = plaintext.chunks(MAX_CHUNK_SIZE - FIRST_CHUNK_HEADER).collect();
+                if chunks.is_empty() {
+                    chunks.push(&[]);
+                }
+                let num_chunks = chunks.len() as u32;
+                let base = send_nonce.fetch_add(num_chunks as u64, Ordering::Relaxed);
+
+                let mut result = Vec::with_capacity(plaintext.len() + 12 + chunks.len() * 21 + FIRST_CHUNK_HEADER);
+                result.extend_from_slice(&num_chunks.to_le_bytes());
+                result.extend_from_slice(&base.to_le_bytes());
+                let mut framed = Vec::with_capacity(MAX_CHUNK_SIZE);
+                for (i, (nonce, chunk)) in (base..).zip(chunks).enumerate() {
+                    framed.clear();
+                    let mut flags = 0;
+                    if i == 0 {
+                        flags |= CHUNK_FIRST;
+                    }
+                    if i + 1 == num_chunks as usize {
+                        flags |= CHUNK_FINAL;
+                    }
+                    framed.push(flags);
+                    if i == 0 {
+                        framed.extend_from_slice(&base.to_le_bytes());
+                        framed.extend_from_slice(&num_chunks.to_le_bytes());
+                    }
+                    framed.extend_from_slice(chunk);
+                    let len = cipher.write_message(nonce, &framed, &mut ciphertext)?;
+                    push_chunk(&mut result, &ciphertext[..len]);
+                }
+                Ok(result)
+            }
+            Transport::Sequential(transport) => {
+                let chunks: Vec<&[u8]> = plaintext.chunks(MAX_CHUNK_SIZE).collect();
+                let mut result = Vec::with_capacity(plaintext.len() + 4 + chunks.len() * 20);
+                result.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+                let mut transport = transport.lock().unwrap();
+                for chunk in chunks {
+                    let len = transport.write_message(chunk, &mut ciphertext)?;
+                    push_chunk(&mut result, &ciphertext[..len]);
+                }
+                Ok(result)
+            }
+        }
+    }
+
+    /// Decrypt chunked ciphertext → plaintext.
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut decryptor = StreamDecryptor::new();
+        let plaintext = decryptor.feed(self, data)?;
+        decryptor.finish()?;
+        Ok(plaintext)
+    }
+
+    /// Decrypt ciphertext that arrives in pieces (see `StreamDecryptor`).
+    pub fn decrypt_piece(
+        &self,
+        decryptor: &mut StreamDecryptor,
+        piece: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        decryptor.feed(self, piece)
+    }
+
+    fn header_len(&self) -> usize {
+        if self.explicit_nonces() {
+            12
+        } else {
+            4
+        }
+    }
+
+    fn open_chunk(
+        &self,
+        nonce: u64,
+        chunk: &[u8],
+        out: &mut [u8],
+    ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        match &self.transport {
+            Transport::Explicit { cipher, replay, .. } => {
+                if !replay.lock().unwrap().check(nonce) {
+                    return Err("replayed nonce".into());
+                }
+                let len = cipher.read_message(nonce, chunk, out)?;
+                // Re-checked under the same lock as the update, in case the
+                // same message was being decrypted concurrently
+                if !replay.lock().unwrap().accept(nonce) {
+                    return Err("replayed nonce".into());
+                }
+                Ok(len)
+            }
+            Transport::Sequential(transport) => Ok(transport.lock().unwrap().read_message(chunk, out)?),
+        }
+    }
+}
+
+fn push_chunk(out: &mut Vec<u8>, ciphertext: &[u8]) {
+    out.extend_from_slice(&(ciphertext.len() as u32).to_le_bytes());
+    out.extend_from_slice(ciphertext);
+}
+
+/// Sliding window over received nonces: anything at or above `next` is
+/// new, anything more than `REPLAY_WINDOW` below it is rejected as too old,
+/// and the bitmap tracks what in between has been seen.
+struct ReplayWindow {
+    next: u64,
+    seen: Vec<u64>,
+}
+
+impl Default for ReplayWindow {
+    fn default() -> Self {
+        Self {
+            next: 0,
+            seen: vec![0; (REPLAY_WINDOW / 64) as usize],
+        }
+    }
+}
+
+impl ReplayWindow {
+    fn slot(nonce: u64) -> (usize, u64) {
+        let bit = nonce % REPLAY_WINDOW;
+        ((bit / 64) as usize, 1 << (bit % 64))
+    }
+
+    fn check(&self, nonce: u64) -> bool {
+        if nonce == u64::MAX {
+            return false;
+        }
+        if nonce >= self.next {
+            return true;
+        }
+        if self.next - nonce > REPLAY_WINDOW {
+            return false;
+        }
+        let (word, mask) = Self::slot(nonce);
+        self.seen[word] & mask == 0
+    }
+
+    fn accept(&mut self, nonce: u64) -> bool {
+        if !self.check(nonce) {
+            return false;
+        }
+        if nonce >= self.next {
+            // Slots for next..=nonce still hold nonces a window ago
+            if nonce - self.next >= REPLAY_WINDOW {
+                self.seen.fill(0);
+            } else {
+                for n in self.next..=nonce {
+                    let (word, mask) = Self::slot(n);
+                    self.seen[word] &= !mask;
+                }
+            }
+            self.next = nonce + 1;
+        }
+        let (word, mask) = Self::slot(nonce);
+        self.seen[word] |= mask;
+        true
+    }
+}
+
 // ── Client session ─────────────────────────────────────────────
 
-/// Noise client (API side). One per QUIC connection.
+/// Noise client (API side). One per QUIC connection; once the handshake is
+/// complete it can be shared across concurrent streams.
 pub struct NoiseClient {
+    pattern: HandshakePattern,
+    /// Client static private key (KK/XK)
+    static_key: Option<Vec<u8>>,
+    /// Offer explicit nonces in the handshake
+    explicit_nonces: bool,
     handshake: Option<HandshakeState>,
-    transport: Option<TransportState>,
+    session: Option<Session>,
     session_id: Option<[u8; 16]>,
 }
 
 impl NoiseClient {
+    /// Anonymous client (NK).
     pub fn new() -> Self {
         Self {
+            pattern: HandshakePattern::Nk,
+            static_key: None,
+            explicit_nonces: true,
             handshake: None,
-            transport: None,
+            session: None,
             session_id: None,
         }
     }
 
+    /// Client authenticating with a static key (KK or XK).
+    pub fn with_static_key(pattern: HandshakePattern, private_key: &[u8]) -> Self {
+        Self {
+            pattern,
+            static_key: Some(private_key.to_vec()),
+            ..Self::new()
+        }
+    }
+
     /// Start handshake with server's static public key (from attestation).
     /// Returns the handshake message to send.
     pub fn initiate_handshake(
         &mut self,
         server_static_key: &[u8],
     ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
-        let builder = Builder::new(NOISE_PATTERN.parse()?);
-        let mut initiator = builder
-            .remote_public_key(server_static_key)
-            .build_initiator()?;
+        let mut builder = Builder::new(self.pattern.params().parse()?).remote_public_key(server_static_key);
+        if self.pattern.is_mutual() {
+            let key = self.static_key.as_deref().ok_or("pattern requires a client static key")?;
+            builder = builder.local_private_key(key);
+        }
+        let mut initiator = builder.build_initiator()?;
 
+        let offer: &[u8] = if self.explicit_nonces { &[EXPLICIT_NONCES] } else { &[] };
         let mut message = vec![0u8; 65535];
-        let len = initiator.write_message(&[], &mut message)?;
+        let len = initiator.write_message(offer, &mut message)?;
         message.truncate(len);
 
         self.handshake = Some(initiator);
         Ok(message)
     }
 
-    /// Complete handshake with server's response.
+    /// Complete handshake with server's response. For XK, returns the
+    /// final handshake message (→ s, se) that must be sent to the server.
     pub fn complete_handshake(
         &mut self,
         server_response: &[u8],
         session_id: [u8; 16],
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>> {
         let mut initiator = self
             .handshake
             .take()
             .ok_or("no handshake in progress")?;
 
         let mut payload = vec![0u8; 65535];
-        let _len = initiator.read_message(server_response, &mut payload)?;
+        let len = initiator.read_message(server_response, &mut payload)?;
+        let explicit_nonces = self.explicit_nonces && payload[..len] == [EXPLICIT_NONCES];
+
+        let finish = if initiator.is_handshake_finished() {
+            None
+        } else {
+            let mut message = vec![0u8; 65535];
+            let len = initiator.write_message(&[], &mut message)?;
+            message.truncate(len);
+            Some(message)
+        };
 
-        self.transport = Some(initiator.into_transport_mode()?);
+        self.session = Some(Session::new(initiator, explicit_nonces)?);
         self.session_id = Some(session_id);
-        Ok(())
+        Ok(finish)
+    }
+
+    /// Established session, None before the handshake completes.
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_ref()
     }
 
     /// Encrypt plaintext → chunked ciphertext.
-    pub fn encrypt(
-        &mut self,
-        plaintext: &[u8],
-    ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
-        let transport = self.transport.as_mut().ok_or("session not established")?;
-        encrypt_chunked(transport, plaintext)
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        self.session.as_ref().ok_or("session not established")?.encrypt(plaintext)
     }
 
     /// Decrypt chunked ciphertext → plaintext.
-    pub fn decrypt(
-        &mut self,
-        data: &[u8],
-    ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
-        let transport = self.transport.as_mut().ok_or("session not established")?;
-        decrypt_chunked(transport, data)
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        self.session.as_ref().ok_or("session not established")?.decrypt(data)
     }
 
     pub fn session_id(&self) -> Option<[u8; 16]> {
@@ -94,141 +458,330 @@ impl NoiseClient {
 
 // ── Server session manager ─────────────────────────────────────
 
+/// XK handshakes a server holds open waiting for their final message.
+const MAX_PENDING_HANDSHAKES: usize = 1024;
+/// How long an XK handshake may wait for its final message.
+const PENDING_HANDSHAKE_TTL: Duration = Duration::from_secs(30);
+
+/// An XK handshake waiting for the client's final message
+struct PendingHandshake {
+    responder: HandshakeState,
+    /// Whether explicit nonces were agreed
+    explicit_nonces: bool,
+    started: Instant,
+}
+
 /// Noise server (worker side). Manages multiple client sessions.
+///
+/// Shared by reference: the session map is only locked to insert, remove
+/// or look up a session, never around encryption.
 pub struct NoiseServer {
+    pattern: HandshakePattern,
     static_keypair: Keypair,
-    sessions: HashMap<[u8; 16], TransportState>,
+    /// Client static keys allowed to open sessions (KK/XK)
+    authorized_keys: Vec<[u8; KEY_LEN]>,
+    /// XK handshakes waiting for the client's final message, at most
+    /// `MAX_PENDING_HANDSHAKES`, each for up to `PENDING_HANDSHAKE_TTL`
+    pending: Mutex<HashMap<[u8; 16], PendingHandshake>>,
+    sessions: RwLock<HashMap<[u8; 16], Arc<Session>>>,
 }
 
 impl NoiseServer {
+    /// Server accepting anonymous clients (NK).
     pub fn new() -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let builder = Builder::new(NOISE_PATTERN.parse()?);
-        let static_keypair = builder.generate_keypair()?;
+        Self::with_authorized_keys(HandshakePattern::Nk, Vec::new())
+    }
+
+    /// Server that only accepts clients holding one of `authorized_keys`
+    /// (KK or XK). With NK the list is ignored.
+    pub fn with_authorized_keys(
+        pattern: HandshakePattern,
+        authorized_keys: Vec<[u8; KEY_LEN]>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Self::with_keypair(pattern, generate_keypair()?, authorized_keys)
+    }
+
+    /// Like `with_authorized_keys`, but with a fixed static private key
+    /// instead of a fresh one, so attestation bound to the key outlives
+    /// the process.
+    pub fn with_static_key(
+        pattern: HandshakePattern,
+        static_key: &[u8],
+        authorized_keys: Vec<[u8; KEY_LEN]>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let keypair = Keypair {
+            public: public_key(static_key)?,
+            private: static_key.to_vec(),
+        };
+        Self::with_keypair(pattern, keypair, authorized_keys)
+    }
+
+    fn with_keypair(
+        pattern: HandshakePattern,
+        static_keypair: Keypair,
+        authorized_keys: Vec<[u8; KEY_LEN]>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        if pattern.is_mutual() && authorized_keys.is_empty() {
+            return Err(format!("noise pattern {} requires at least one authorized client key", pattern).into());
+        }
 
         Ok(Self {
+            pattern,
             static_keypair,
-            sessions: HashMap::new(),
+            authorized_keys,
+            pending: Mutex::new(HashMap::new()),
+            sessions: RwLock::new(HashMap::new()),
         })
     }
 
+    pub fn pattern(&self) -> HandshakePattern {
+        self.pattern
+    }
+
     /// Static public key for attestation binding.
     pub fn static_public_key(&self) -> &[u8] {
         &self.static_keypair.public
     }
 
     /// Process client handshake. Returns (session_id, response_message).
+    ///
+    /// For XK the session is not usable until `finish_handshake` succeeds.
     pub fn process_handshake(
-        &mut self,
+        &self,
         client_msg: &[u8],
     ) -> Result<([u8; 16], Vec<u8>), Box<dyn std::error::Error + Send + Sync>> {
-        let builder = Builder::new(NOISE_PATTERN.parse()?);
-        let mut responder = builder
-            .local_private_key(&self.static_keypair.private)
-            .build_responder()?;
-
         let mut payload = vec![0u8; 65535];
-        let _len = responder.read_message(client_msg, &mut payload)?;
+        let (mut responder, len) = match self.pattern {
+            HandshakePattern::Kk => self.kk_responder(client_msg, &mut payload)?,
+            _ => {
+                let mut responder = self.responder(None)?;
+                let len = responder.read_message(client_msg, &mut payload)?;
+                (responder, len)
+            }
+        };
+        let explicit_nonces = payload[..len] == [EXPLICIT_NONCES];
 
+        let accept: &[u8] = if explicit_nonces { &[EXPLICIT_NONCES] } else { &[] };
         let mut response = vec![0u8; 65535];
-        let len = responder.write_message(&[], &mut response)?;
+        let len = responder.write_message(accept, &mut response)?;
         response.truncate(len);
 
-        let transport = responder.into_transport_mode()?;
-
         let mut session_id = [0u8; 16];
         rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut session_id);
 
-        self.sessions.insert(session_id, transport);
+        if responder.is_handshake_finished() {
+            let session = Session::new(responder, explicit_nonces)?;
+            self.sessions.write().unwrap().insert(session_id, Arc::new(session));
+        } else {
+            let mut pending = self.pending.lock().unwrap();
+            pending.retain(|_, p| p.started.elapsed() < PENDING_HANDSHAKE_TTL);
+            if pending.len() >= MAX_PENDING_HANDSHAKES {
+                return Err("too many handshakes in progress".into());
+            }
+            pending.insert(session_id, PendingHandshake { responder, explicit_nonces, started: Instant::now() });
+        }
         Ok((session_id, response))
     }
 
+    /// Process the client's final XK message (→ s, se) and check its
+    /// static key against the authorized keys.
+    pub fn finish_handshake(
+        &self,
+        session_id: &[u8; 16],
+        client_msg: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let PendingHandshake { mut responder, explicit_nonces, started } = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(session_id)
+            .ok_or("no handshake in progress")?;
+        if started.elapsed() >= PENDING_HANDSHAKE_TTL {
+            return Err("handshake expired".into());
+        }
+
+        let mut payload = vec![0u8; 65535];
+        let _len = responder.read_message(client_msg, &mut payload)?;
+
+        let remote = responder.get_remote_static().ok_or("client sent no static key")?;
+        if !self.authorized_keys.iter().any(|k| k.as_slice() == remote) {
+            return Err("unknown initiator".into());
+        }
+
+        let session = Session::new(responder, explicit_nonces)?;
+        self.sessions.write().unwrap().insert(*session_id, Arc::new(session));
+        Ok(())
+    }
+
+    /// KK: the first message is encrypted to (client static, server static),
+    /// so find the authorized key it decrypts under.
+    fn kk_responder(
+        &self,
+        client_msg: &[u8],
+        payload: &mut [u8],
+    ) -> Result<(HandshakeState, usize), Box<dyn std::error::Error + Send + Sync>> {
+        for key in &self.authorized_keys {
+            let mut responder = self.responder(Some(key))?;
+            if let Ok(len) = responder.read_message(client_msg, payload) {
+                return Ok((responder, len));
+            }
+        }
+        Err("unknown initiator".into())
+    }
+
+    fn responder(&self, remote_key: Option<&[u8]>) -> Result<HandshakeState, Box<dyn std::error::Error + Send + Sync>> {
+        let mut builder = Builder::new(self.pattern.params().parse()?).local_private_key(&self.static_keypair.private);
+        if let Some(key) = remote_key {
+            builder = builder.remote_public_key(key);
+        }
+        Ok(builder.build_responder()?)
+    }
+
+    /// Established session for `session_id`.
+    pub fn session(&self, session_id: &[u8; 16]) -> Result<Arc<Session>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.sessions.read().unwrap().get(session_id).ok_or("session not found")?.clone())
+    }
+
     pub fn decrypt(
-        &mut self,
+        &self,
         session_id: &[u8; 16],
         data: &[u8],
     ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
-        let transport = self.sessions.get_mut(session_id).ok_or("session not found")?;
-        decrypt_chunked(transport, data)
+        self.session(session_id)?.decrypt(data)
     }
 
     pub fn encrypt(
-        &mut self,
+        &self,
         session_id: &[u8; 16],
         plaintext: &[u8],
     ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
-        let transport = self.sessions.get_mut(session_id).ok_or("session not found")?;
-        encrypt_chunked(transport, plaintext)
+        self.session(session_id)?.encrypt(plaintext)
     }
 
-    pub fn remove_session(&mut self, session_id: &[u8; 16]) {
-        self.sessions.remove(session_id);
+    /// Decrypt the next piece of a streamed ciphertext for `session_id`.
+    pub fn decrypt_piece(
+        &self,
+        session_id: &[u8; 16],
+        decryptor: &mut StreamDecryptor,
+        piece: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        self.session(session_id)?.decrypt_piece(decryptor, piece)
     }
-}
 
-// ── Chunked encryption/decryption ──────────────────────────────
-
-fn encrypt_chunked(
-    transport: &mut TransportState,
-    plaintext: &[u8],
-) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
-    let mut result = Vec::new();
-    let chunks: Vec<&[u8]> = plaintext.chunks(MAX_CHUNK_SIZE).collect();
-    let num_chunks = chunks.len() as u32;
+    /// Whether the handshake for `session_id` has completed.
+    pub fn has_session(&self, session_id: &[u8; 16]) -> bool {
+        self.sessions.read().unwrap().contains_key(session_id)
+    }
 
-    result.extend_from_slice(&num_chunks.to_le_bytes());
+    pub fn remove_session(&self, session_id: &[u8; 16]) {
+        self.pending.lock().unwrap().remove(session_id);
+        self.sessions.write().unwrap().remove(session_id);
+    }
+}
 
-    for chunk in chunks {
-        let mut ciphertext = vec![0u8; chunk.len() + 16];
-        let len = transport.write_message(chunk, &mut ciphertext)?;
-        ciphertext.truncate(len);
+// ── Chunked decryption ─────────────────────────────────────────
 
-        result.extend_from_slice(&(ciphertext.len() as u32).to_le_bytes());
-        result.extend(ciphertext);
-    }
+/// Largest Noise chunk on the wire: plaintext chunk plus auth tag.
+const MAX_CIPHERTEXT_CHUNK: usize = MAX_CHUNK_SIZE + 16;
 
-    Ok(result)
+/// Parses the chunked ciphertext format incrementally: ciphertext is fed as
+/// it arrives and each Noise chunk is decrypted once complete, so at most
+/// one chunk of ciphertext is buffered.
+#[derive(Default)]
+pub struct StreamDecryptor {
+    buf: Vec<u8>,
+    /// Chunks still expected, once the header has arrived
+    chunks_left: Option<u32>,
+    /// Chunk count and base nonce from the header (explicit-nonce sessions)
+    num_chunks: u32,
+    base_nonce: u64,
+    /// Nonce of the next chunk (explicit-nonce sessions)
+    next_nonce: u64,
 }
 
-fn decrypt_chunked(
-    transport: &mut TransportState,
-    data: &[u8],
-) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
-    if data.len() < 4 {
-        return Err("invalid encrypted data".into());
+impl StreamDecryptor {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    let num_chunks = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
-    let mut offset = 4;
-    let mut result = Vec::new();
+    fn feed(&mut self, session: &Session, piece: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        self.buf.extend_from_slice(piece);
+        let mut plaintext = Vec::new();
+        let mut offset = 0;
 
-    for _ in 0..num_chunks {
-        if offset + 4 > data.len() {
-            return Err("truncated chunk header".into());
+        if self.chunks_left.is_none() {
+            let header_len = session.header_len();
+            if self.buf.len() < header_len {
+                return Ok(plaintext);
+            }
+            self.num_chunks = u32::from_le_bytes(self.buf[..4].try_into()?);
+            self.chunks_left = Some(self.num_chunks);
+            if header_len == 12 {
+                if self.num_chunks == 0 {
+                    return Err("message has no chunks".into());
+                }
+                self.base_nonce = u64::from_le_bytes(self.buf[4..12].try_into()?);
+                self.next_nonce = self.base_nonce;
+            }
+            offset = header_len;
         }
 
-        let chunk_len = u32::from_le_bytes([
-            data[offset],
-            data[offset + 1],
-            data[offset + 2],
-            data[offset + 3],
-        ]) as usize;
-        offset += 4;
+        while let Some(chunks_left @ 1..) = self.chunks_left {
+            let Some(len_bytes) = self.buf.get(offset..offset + 4) else { break };
+            let chunk_len = u32::from_le_bytes(len_bytes.try_into()?) as usize;
+            if chunk_len > MAX_CIPHERTEXT_CHUNK {
+                return Err("oversized chunk".into());
+            }
+            let Some(chunk) = self.buf.get(offset + 4..offset + 4 + chunk_len) else { break };
 
-        if offset + chunk_len > data.len() {
-            return Err("truncated chunk data".into());
-        }
+            let mut out = vec![0u8; chunk.len()];
+            let len = session.open_chunk(self.next_nonce, chunk, &mut out)?;
+            if session.explicit_nonces() {
+                let index = self.num_chunks - chunks_left;
+                plaintext.extend_from_slice(self.unframe(index, &out[..len])?);
+            } else {
+                plaintext.extend_from_slice(&out[..len]);
+            }
 
-        let chunk = &data[offset..offset + chunk_len];
-        offset += chunk_len;
+            offset += 4 + chunk_len;
+            self.next_nonce = self.next_nonce.checked_add(1).ok_or("nonce overflow")?;
+            self.chunks_left = Some(chunks_left - 1);
+        }
 
-        let mut plaintext = vec![0u8; chunk.len()];
-        let len = transport.read_message(chunk, &mut plaintext)?;
-        plaintext.truncate(len);
+        self.buf.drain(..offset);
+        if self.chunks_left == Some(0) && !self.buf.is_empty() {
+            return Err("data after final chunk".into());
+        }
+        Ok(plaintext)
+    }
 
-        result.extend(plaintext);
+    /// Check an explicit-nonce chunk's framing against the header and its
+    /// position, and return its data.
+    fn unframe<'a>(&self, index: u32, chunk: &'a [u8]) -> Result<&'a [u8], Box<dyn std::error::Error + Send + Sync>> {
+        let (&flags, rest) = chunk.split_first().ok_or("chunk has no framing")?;
+        if (flags & CHUNK_FIRST != 0) != (index == 0) || (flags & CHUNK_FINAL != 0) != (index + 1 == self.num_chunks) {
+            return Err("chunk out of place".into());
+        }
+        if index > 0 {
+            return Ok(rest);
+        }
+        let header = rest.get(..FIRST_CHUNK_HEADER - 1).ok_or("chunk has no framing")?;
+        let base = u64::from_le_bytes(header[..8].try_into()?);
+        let num_chunks = u32::from_le_bytes(header[8..].try_into()?);
+        if base != self.base_nonce || num_chunks != self.num_chunks {
+            return Err("message header does not match its chunks".into());
+        }
+        Ok(&rest[FIRST_CHUNK_HEADER - 1..])
     }
 
-    Ok(result)
+    /// Check the ciphertext ended exactly after its last chunk.
+    pub fn finish(self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self.chunks_left {
+            Some(0) if self.buf.is_empty() => Ok(()),
+            None => Err("invalid encrypted data".into()),
+            _ => Err("truncated chunk data".into()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -237,7 +790,7 @@ mod tests {
 
     #[test]
     fn test_noise_session_roundtrip() {
-        let mut server = NoiseServer::new().unwrap();
+        let server = NoiseServer::new().unwrap();
         let mut client = NoiseClient::new();
 
         // Client initiates with server's public key
@@ -268,7 +821,7 @@ mod tests {
 
     #[test]
     fn test_large_message_chunking() {
-        let mut server = NoiseServer::new().unwrap();
+        let server = NoiseServer::new().unwrap();
         let mut client = NoiseClient::new();
 
         let client_msg = client
@@ -285,4 +838,371 @@ mod tests {
         let decrypted = server.decrypt(&session_id, &encrypted).unwrap();
         assert_eq!(decrypted, big_data);
     }
+
+    fn mutual_handshake(
+        server: &NoiseServer,
+        client: &mut NoiseClient,
+    ) -> Result<[u8; 16], Box<dyn std::error::Error + Send + Sync>> {
+        let client_msg = client.initiate_handshake(server.static_public_key())?;
+        let (session_id, server_response) = server.process_handshake(&client_msg)?;
+        if let Some(finish) = client.complete_handshake(&server_response, session_id)? {
+            server.finish_handshake(&session_id, &finish)?;
+        }
+        Ok(session_id)
+    }
+
+    fn api_key() -> (Keypair, [u8; KEY_LEN]) {
+        let keypair = generate_keypair().unwrap();
+        let public: [u8; KEY_LEN] = keypair.public.as_slice().try_into().unwrap();
+        (keypair, public)
+    }
+
+    #[test]
+    fn test_mutual_patterns_roundtrip() {
+        for pattern in [HandshakePattern::Kk, HandshakePattern::Xk] {
+            let (_, other_pub) = api_key();
+            let (api, api_pub) = api_key();
+            let server = NoiseServer::with_authorized_keys(pattern, vec![other_pub, api_pub]).unwrap();
+            let mut client = NoiseClient::with_static_key(pattern, &api.private);
+
+            let session_id = mutual_handshake(&server, &mut client).unwrap();
+
+            let ciphertext = client.encrypt(b"hello from API").unwrap();
+            assert_eq!(server.decrypt(&session_id, &ciphertext).unwrap(), b"hello from API");
+            let encrypted = server.encrypt(&session_id, b"audio").unwrap();
+            assert_eq!(client.decrypt(&encrypted).unwrap(), b"audio");
+        }
+    }
+
+    #[test]
+    fn test_unknown_initiator_rejected() {
+        for pattern in [HandshakePattern::Kk, HandshakePattern::Xk] {
+            let (_, api_pub) = api_key();
+            let (intruder, _) = api_key();
+            let server = NoiseServer::with_authorized_keys(pattern, vec![api_pub]).unwrap();
+            let mut client = NoiseClient::with_static_key(pattern, &intruder.private);
+
+            let err = mutual_handshake(&server, &mut client).unwrap_err();
+            assert_eq!(err.to_string(), "unknown initiator", "{}", pattern);
+            assert!(server.sessions.read().unwrap().is_empty());
+            assert!(server.pending.lock().unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_anonymous_client_rejected() {
+        for pattern in [HandshakePattern::Kk, HandshakePattern::Xk] {
+            let (_, api_pub) = api_key();
+            let server = NoiseServer::with_authorized_keys(pattern, vec![api_pub]).unwrap();
+            let mut client = NoiseClient::new();
+
+            assert!(mutual_handshake(&server, &mut client).is_err());
+            assert!(server.sessions.read().unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_xk_session_unusable_before_finish() {
+        let (api, api_pub) = api_key();
+        let server = NoiseServer::with_authorized_keys(HandshakePattern::Xk, vec![api_pub]).unwrap();
+        let mut client = NoiseClient::with_static_key(HandshakePattern::Xk, &api.private);
+
+        let client_msg = client.initiate_handshake(server.static_public_key()).unwrap();
+        let (session_id, _) = server.process_handshake(&client_msg).unwrap();
+        assert!(server.decrypt(&session_id, &[0, 0, 0, 0]).is_err());
+
+        server.remove_session(&session_id);
+        assert!(server.pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pending_handshakes_capped_and_expire() {
+        let (api, api_pub) = api_key();
+        let server = NoiseServer::with_authorized_keys(HandshakePattern::Xk, vec![api_pub]).unwrap();
+        let initiate = || {
+            let mut client = NoiseClient::with_static_key(HandshakePattern::Xk, &api.private);
+            let client_msg = client.initiate_handshake(server.static_public_key()).unwrap();
+            (client, client_msg)
+        };
+
+        let mut first = None;
+        for _ in 0..MAX_PENDING_HANDSHAKES {
+            let (client, client_msg) = initiate();
+            let (session_id, response) = server.process_handshake(&client_msg).unwrap();
+            first.get_or_insert((client, session_id, response));
+        }
+        let (_, client_msg) = initiate();
+        let err = server.process_handshake(&client_msg).unwrap_err();
+        assert_eq!(err.to_string(), "too many handshakes in progress");
+
+        // An expired handshake can't be finished, and makes room for a new one
+        let (mut client, session_id, response) = first.unwrap();
+        server.pending.lock().unwrap().get_mut(&session_id).unwrap().started -= PENDING_HANDSHAKE_TTL;
+        let finish = client.complete_handshake(&response, session_id).unwrap().unwrap();
+        let err = server.finish_handshake(&session_id, &finish).unwrap_err();
+        assert_eq!(err.to_string(), "handshake expired");
+        assert!(server.process_handshake(&client_msg).is_ok());
+    }
+
+    #[test]
+    fn test_fixed_static_key() {
+        let (fixed, fixed_pub) = api_key();
+        let server = NoiseServer::with_static_key(HandshakePattern::Nk, &fixed.private, vec![]).unwrap();
+        assert_eq!(server.static_public_key(), fixed_pub);
+
+        let mut client = NoiseClient::new();
+        let client_msg = client.initiate_handshake(&fixed_pub).unwrap();
+        let (session_id, response) = server.process_handshake(&client_msg).unwrap();
+        client.complete_handshake(&response, session_id).unwrap();
+        let ciphertext = client.encrypt(b"hello from API").unwrap();
+        assert_eq!(server.decrypt(&session_id, &ciphertext).unwrap(), b"hello from API");
+
+        assert!(NoiseServer::with_static_key(HandshakePattern::Nk, &[0u8; 16], vec![]).is_err());
+    }
+
+    #[test]
+    fn test_mutual_pattern_requires_keys() {
+        assert!(NoiseServer::with_authorized_keys(HandshakePattern::Kk, vec![]).is_err());
+        assert!(NoiseServer::with_authorized_keys(HandshakePattern::Nk, vec![]).is_ok());
+    }
+
+    #[test]
+    fn test_key_parsing() {
+        let keypair = generate_keypair().unwrap();
+        assert_eq!(public_key(&keypair.private).unwrap(), keypair.public);
+
+        let hex: String = keypair.public.iter().map(|b| format!("{:02x}", b)).collect();
+        let keys = parse_keys(&format!(" {}, ,{}", hex, hex)).unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].as_slice(), keypair.public.as_slice());
+        assert!(parse_keys("abcd").is_err());
+        assert!(parse_keys("zz").is_err());
+
+        assert_eq!("KK".parse::<HandshakePattern>().unwrap(), HandshakePattern::Kk);
+        assert!("ik".parse::<HandshakePattern>().is_err());
+    }
+
+    #[test]
+    fn test_stream_decryptor_matches_decrypt() {
+        let server = NoiseServer::new().unwrap();
+        let mut client = NoiseClient::new();
+        let session_id = mutual_handshake(&server, &mut client).unwrap();
+
+        let big_data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let encrypted = client.encrypt(&big_data).unwrap();
+
+        // Odd piece size so headers and chunks straddle piece boundaries
+        let mut decryptor = StreamDecryptor::new();
+        let mut plaintext = Vec::new();
+        for piece in encrypted.chunks(7_777) {
+            plaintext.extend(server.decrypt_piece(&session_id, &mut decryptor, piece).unwrap());
+        }
+        decryptor.finish().unwrap();
+        assert_eq!(plaintext, big_data);
+    }
+
+    #[test]
+    fn test_stream_decryptor_rejects_bad_framing() {
+        let server = NoiseServer::new().unwrap();
+        let mut client = NoiseClient::new();
+        let session_id = mutual_handshake(&server, &mut client).unwrap();
+
+        // Truncated: last byte missing
+        let encrypted = client.encrypt(b"hello").unwrap();
+        let mut decryptor = StreamDecryptor::new();
+        server
+            .decrypt_piece(&session_id, &mut decryptor, &encrypted[..encrypted.len() - 1])
+            .unwrap();
+        assert!(decryptor.finish().is_err());
+
+        // Hostile chunk length is rejected before anything is buffered
+        let mut hostile = 1u32.to_le_bytes().to_vec();
+        hostile.extend_from_slice(&0u64.to_le_bytes());
+        hostile.extend_from_slice(&u32::MAX.to_le_bytes());
+        let mut decryptor = StreamDecryptor::new();
+        assert!(server.decrypt_piece(&session_id, &mut decryptor, &hostile).is_err());
+        assert!(server.decrypt(&session_id, &hostile).is_err());
+    }
+
+    fn nk_session(client_explicit: bool) -> (NoiseServer, NoiseClient, [u8; 16]) {
+        let server = NoiseServer::new().unwrap();
+        let mut client = NoiseClient::new();
+        client.explicit_nonces = client_explicit;
+        let session_id = mutual_handshake(&server, &mut client).unwrap();
+        (server, client, session_id)
+    }
+
+    #[test]
+    fn test_out_of_order_messages() {
+        let (server, client, session_id) = nk_session(true);
+        assert!(client.session().unwrap().explicit_nonces());
+
+        let first = client.encrypt(b"first").unwrap();
+        let second = client.encrypt(&vec![7u8; 150_000]).unwrap();
+        let third = client.encrypt(b"third").unwrap();
+
+        assert_eq!(server.decrypt(&session_id, &third).unwrap(), b"third");
+        assert_eq!(server.decrypt(&session_id, &first).unwrap(), b"first");
+        assert_eq!(server.decrypt(&session_id, &second).unwrap(), vec![7u8; 150_000]);
+    }
+
+    #[test]
+    fn test_replayed_message_rejected() {
+        let (server, client, session_id) = nk_session(true);
+
+        let ciphertext = client.encrypt(b"pay once").unwrap();
+        assert!(server.decrypt(&session_id, &ciphertext).is_ok());
+        let err = server.decrypt(&session_id, &ciphertext).unwrap_err();
+        assert_eq!(err.to_string(), "replayed nonce");
+
+        // Rewriting the base nonce does not help: the chunk no longer authenticates
+        let mut forged = ciphertext.clone();
+        forged[4..12].copy_from_slice(&100u64.to_le_bytes());
+        assert!(server.decrypt(&session_id, &forged).is_err());
+    }
+
+    /// Split an explicit-nonce message into its base nonce and chunks
+    fn split_message(ciphertext: &[u8]) -> (u64, Vec<Vec<u8>>) {
+        let num_chunks = u32::from_le_bytes(ciphertext[..4].try_into().unwrap());
+        let base = u64::from_le_bytes(ciphertext[4..12].try_into().unwrap());
+        let mut chunks = Vec::new();
+        let mut rest = &ciphertext[12..];
+        for _ in 0..num_chunks {
+            let len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+            chunks.push(rest[4..4 + len].to_vec());
+            rest = &rest[4 + len..];
+        }
+        assert!(rest.is_empty());
+        (base, chunks)
+    }
+
+    fn join_message(base: u64, chunks: &[&Vec<u8>]) -> Vec<u8> {
+        let mut message = (chunks.len() as u32).to_le_bytes().to_vec();
+        message.extend_from_slice(&base.to_le_bytes());
+        for chunk in chunks {
+            push_chunk(&mut message, chunk);
+        }
+        message
+    }
+
+    /// Three chunks
+    const LONG: usize = 150_000;
+
+    #[test]
+    fn test_truncated_message_rejected() {
+        let (server, client, session_id) = nk_session(true);
+        let (base, chunks) = split_message(&client.encrypt(&vec![1u8; LONG]).unwrap());
+        assert_eq!(chunks.len(), 3);
+
+        // Header rewritten to two chunks, third dropped
+        let truncated = join_message(base, &[&chunks[0], &chunks[1]]);
+        let err = server.decrypt(&session_id, &truncated).unwrap_err();
+        assert_eq!(err.to_string(), "message header does not match its chunks");
+
+        // Stream ends before the final chunk
+        let (server, client, session_id) = nk_session(true);
+        let ciphertext = client.encrypt(&vec![1u8; LONG]).unwrap();
+        let (_, chunks) = split_message(&ciphertext);
+        let cut = ciphertext.len() - chunks[2].len() - 4;
+        let mut decryptor = StreamDecryptor::new();
+        server.decrypt_piece(&session_id, &mut decryptor, &ciphertext[..cut]).unwrap();
+        assert!(decryptor.finish().is_err());
+    }
+
+    #[test]
+    fn test_reheadered_message_rejected() {
+        // The header pointed at a later chunk, delivering a suffix as a message
+        for skip in [1, 2] {
+            let (server, client, session_id) = nk_session(true);
+            let (base, chunks) = split_message(&client.encrypt(&vec![2u8; LONG]).unwrap());
+            let suffix: Vec<&Vec<u8>> = chunks[skip..].iter().collect();
+            let reheadered = join_message(base + skip as u64, &suffix);
+            let err = server.decrypt(&session_id, &reheadered).unwrap_err();
+            assert_eq!(err.to_string(), "chunk out of place");
+        }
+
+        // A message with no chunks at all
+        let (server, client, session_id) = nk_session(true);
+        let (base, _) = split_message(&client.encrypt(b"x").unwrap());
+        assert!(server.decrypt(&session_id, &join_message(base, &[])).is_err());
+    }
+
+    #[test]
+    fn test_spliced_messages_rejected() {
+        let (server, client, session_id) = nk_session(true);
+        let (a_base, a) = split_message(&client.encrypt(&vec![3u8; LONG]).unwrap());
+        let (b_base, b) = split_message(&client.encrypt(&vec![4u8; LONG]).unwrap());
+        // Consecutive nonces, so the chunks line up across the two messages
+        assert_eq!(b_base, a_base + 3);
+
+        // The end of one message joined to the start of the next
+        let spliced = join_message(a_base + 2, &[&a[2], &b[0]]);
+        assert!(server.decrypt(&session_id, &spliced).is_err());
+
+        // The whole of one message extended with a chunk of the next
+        let spliced = join_message(a_base, &[&a[0], &a[1], &a[2], &b[0]]);
+        assert!(server.decrypt(&session_id, &spliced).is_err());
+
+        // Untouched messages still decrypt
+        let (server, client, session_id) = nk_session(true);
+        let first = client.encrypt(&vec![3u8; LONG]).unwrap();
+        let second = client.encrypt(b"").unwrap();
+        assert_eq!(server.decrypt(&session_id, &second).unwrap(), b"");
+        assert_eq!(server.decrypt(&session_id, &first).unwrap(), vec![3u8; LONG]);
+    }
+
+    #[test]
+    fn test_replay_window() {
+        let mut window = ReplayWindow::default();
+        assert!(window.accept(5));
+        assert!(window.accept(2));
+        assert!(!window.accept(2));
+        assert!(!window.accept(5));
+        assert!(window.accept(REPLAY_WINDOW + 3));
+        // Slid out of the window
+        assert!(!window.accept(1));
+        // Slot reused by a newer nonce, old bit cleared
+        assert!(window.accept(REPLAY_WINDOW + 2));
+        assert!(!window.accept(u64::MAX));
+    }
+
+    #[test]
+    fn test_legacy_peer_uses_sequential_nonces() {
+        let (server, client, session_id) = nk_session(false);
+        assert!(!client.session().unwrap().explicit_nonces());
+        assert!(!server.session(&session_id).unwrap().explicit_nonces());
+
+        for i in 0..3u8 {
+            let ciphertext = client.encrypt(&[i; 10]).unwrap();
+            // Legacy framing: count, then chunks, no nonce
+            assert_eq!(ciphertext.len(), 4 + 4 + 10 + 16);
+            assert_eq!(server.decrypt(&session_id, &ciphertext).unwrap(), [i; 10]);
+            let reply = server.encrypt(&session_id, &[i; 3]).unwrap();
+            assert_eq!(client.decrypt(&reply).unwrap(), [i; 3]);
+        }
+    }
+
+    #[test]
+    fn test_concurrent_sessions_across_threads() {
+        let (server, client, session_id) = nk_session(true);
+        let (server, client) = (Arc::new(server), Arc::new(client));
+
+        let handles: Vec<_> = (0..8u8)
+            .map(|i| {
+                let (server, client) = (server.clone(), client.clone());
+                std::thread::spawn(move || {
+                    for j in 0..20u8 {
+                        let request = vec![i ^ j; 1000 + i as usize * 9000];
+                        let ciphertext = client.encrypt(&request).unwrap();
+                        assert_eq!(server.decrypt(&session_id, &ciphertext).unwrap(), request);
+                        let reply = server.encrypt(&session_id, &[i, j]).unwrap();
+                        assert_eq!(client.decrypt(&reply).unwrap(), [i, j]);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
 }
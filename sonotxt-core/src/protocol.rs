@@ -1,21 +1,31 @@
 //! QUIC wire protocol for API ↔ worker communication.
 //!
-//! All messages are length-prefixed JSON. The outer QUIC stream provides
-//! reliability; Noise_NK provides confidentiality and authentication.
+//! All messages are length-prefixed. The body encoding is negotiated per
+//! connection via ALPN (see `WireFormat`): compact binary for current
+//! peers, JSON for legacy ones. The outer QUIC stream provides reliability;
+//! Noise provides confidentiality and authentication.
 //!
 //! Flow:
-//!   1. API connects via QUIC
+//!   1. API connects via QUIC (or a NAT'd worker dials the API's reverse
+//!      listener; the roles below stay the same), ALPN picks the wire format
 //!   2. API requests attestation → worker sends static key + TEE quote
-//!   3. Noise_NK handshake (API knows worker's static key from attestation)
+//!   3. Noise handshake (API knows worker's static key from attestation)
 //!   4. All subsequent messages encrypted with Noise transport
 //!
 //! Once the session is established, the API can:
 //!   - Push job notifications (worker wakes and polls DB)
 //!   - Request direct TTS inference (text stays encrypted end-to-end)
-//!   - Poll health status
+//!   - Run LLM chat, with sentences streamed back as they are generated
+//!   - Poll health status and the worker's capabilities (engines, voices)
+//!   - Learn the name of a worker that dialed in (reverse connect)
+//!   - Cancel an in-flight request nobody is waiting for any more
 
+use bincode::Options;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
+use crate::worker_types::LlmMessage;
+
 /// TEE attestation bundle binding Noise static key to TEE identity.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AttestationBundle {
@@ -27,6 +37,9 @@ pub struct AttestationBundle {
     pub binding_sig: Vec<u8>,
     /// TEE type for verification dispatch
     pub tee_type: TeeType,
+    /// DER certificates supplied by the worker (e.g. SNP VCEK from the extended report)
+    #[serde(default)]
+    pub certs: Vec<Vec<u8>>,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
@@ -38,6 +51,9 @@ pub enum TeeType {
 }
 
 /// QUIC stream message envelope.
+///
+/// The binary wire format identifies variants by position: append new
+/// variants at the end and keep `Message::VARIANTS` in the same order.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Message {
     // ── Handshake ──────────────────────────────────
@@ -45,13 +61,20 @@ pub enum Message {
     AttestationRequest,
     /// Worker responding with attestation
     Attestation(AttestationBundle),
-    /// Noise handshake: client → server (→ e, es)
+    /// Noise handshake: client → server (NK/XK: → e, es; KK: → e, es, ss)
     NoiseHandshake(Vec<u8>),
-    /// Noise handshake response: server → client (← e, ee) + session ID
+    /// Noise handshake response: server → client (← e, ee; KK: + se) + session ID
     NoiseHandshakeResponse {
         handshake: Vec<u8>,
         session_id: Vec<u8>,
     },
+    /// Final XK handshake message: client → server (→ s, se)
+    NoiseHandshakeFinish {
+        handshake: Vec<u8>,
+        session_id: Vec<u8>,
+    },
+    /// Server accepted the client's static key; the XK session is ready
+    NoiseHandshakeComplete,
 
     // ── Job dispatch ──────────────────────────────
     /// API notifies worker of new job (encrypted payload: job_id)
@@ -68,14 +91,52 @@ pub enum Message {
     EncryptedAsrRequest(Vec<u8>),
     /// Encrypted ASR response (Noise ciphertext)
     EncryptedAsrResponse(Vec<u8>),
-    /// Encrypted streaming request
+    /// Encrypted streaming TTS request (Noise ciphertext of `EncryptedTtsRequest`)
     EncryptedStreamRequest(Vec<u8>),
-    /// Encrypted streaming audio chunk
+    /// Encrypted streaming audio chunk (Noise ciphertext of `StreamChunk`).
+    /// Sent in `sequence` order on the request's stream until `is_final`.
     EncryptedStreamChunk(Vec<u8>),
 
     // ── Health ────────────────────────────────────
     HealthRequest,
     HealthResponse(WorkerHealth),
+
+    // ── Cancellation ─────────────────────────────
+    /// API no longer wants the result of `request_id` (client went away,
+    /// or a hedged request lost): the worker aborts it. Sent on its own
+    /// stream, no response.
+    Cancel { request_id: [u8; 16] },
+
+    // ── Encrypted LLM ────────────────────────────
+    /// Encrypted LLM chat request (Noise ciphertext of `EncryptedLlmRequest`)
+    EncryptedLlmRequest(Vec<u8>),
+    /// Encrypted LLM output (Noise ciphertext of `LlmEvent`).
+    /// Sent in `sequence` order on the request's stream until `is_final`.
+    EncryptedLlmEvent(Vec<u8>),
+
+    // ── Capabilities ─────────────────────────────
+    /// API asking what the worker can serve (sent after connecting)
+    CapabilitiesRequest,
+    Capabilities(WorkerCapabilities),
+
+    // ── Enrollment ───────────────────────────────
+    /// API asking a worker that dialed in who it is (sent after the Noise
+    /// handshake). Workers only answer on connections they opened.
+    EnrollmentRequest,
+    /// Noise ciphertext of `WorkerEnrollment`
+    Enrollment(Vec<u8>),
+}
+
+/// Identity a reverse-connected worker presents to the API. Sent only
+/// inside the Noise session, whose KK/XK handshake has authenticated the
+/// API first, so the token only reaches the API it was issued by.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerEnrollment {
+    /// Pool name; a worker re-enrolling under the same name replaces its
+    /// previous connection
+    pub name: String,
+    /// Shared enrollment secret (`WORKER_ENROLL_TOKEN` on the API)
+    pub token: String,
 }
 
 /// Worker health status sent over QUIC.
@@ -83,10 +144,36 @@ pub enum Message {
 pub struct WorkerHealth {
     pub speech_ok: bool,
     pub llm_ok: bool,
+    /// Inference requests running right now, from every API
     pub jobs_processing: u32,
     pub uptime_secs: u64,
 }
 
+/// What a worker can serve. Workers that predate capabilities don't
+/// answer `CapabilitiesRequest` and are assumed to serve anything.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorkerCapabilities {
+    /// TTS engines (`qwen`, `vibevoice`, `vibevoice-streaming`)
+    pub engines: Vec<String>,
+    /// Voices; empty means whatever the engines accept
+    pub voices: Vec<String>,
+    /// Languages; empty means any
+    pub languages: Vec<String>,
+    /// Concurrent inference requests the worker accepts
+    pub max_concurrency: u32,
+}
+
+impl WorkerCapabilities {
+    /// Whether a TTS request for `engine`/`voice`/`language` can run here.
+    /// `auto` matches any language.
+    pub fn supports(&self, engine: &str, voice: &str, language: &str) -> bool {
+        let listed = |list: &[String], item: &str| list.is_empty() || list.iter().any(|x| x == item);
+        self.engines.iter().any(|e| e == engine)
+            && listed(&self.voices, voice)
+            && (language == "auto" || listed(&self.languages, language))
+    }
+}
+
 /// Direct TTS request (sent inside Noise channel, text never hits disk).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EncryptedTtsRequest {
@@ -95,6 +182,15 @@ pub struct EncryptedTtsRequest {
     pub voice: String,
     pub speed: f32,
     pub language: String,
+    /// Engine to synthesize with; empty leaves it to the speech service
+    #[serde(default)]
+    pub engine: String,
+    /// Style instruction (`SynthesisOptions::instruct`)
+    #[serde(default)]
+    pub instruct: Option<String>,
+    /// Guidance scale (`SynthesisOptions::cfg_scale`)
+    #[serde(default)]
+    pub cfg_scale: Option<f32>,
 }
 
 /// Direct TTS response (sent inside Noise channel).
@@ -122,7 +218,8 @@ pub struct EncryptedAsrResponse {
     pub error: Option<String>,
 }
 
-/// Streaming audio chunk.
+/// Streaming audio chunk. Each chunk carries a self-contained WAV for
+/// one text segment; `is_final` marks the last chunk (or the failing one).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamChunk {
     pub request_id: [u8; 16],
@@ -132,18 +229,173 @@ pub struct StreamChunk {
     pub error: Option<String>,
 }
 
+/// LLM chat request (sent inside Noise channel, the transcript never
+/// hits disk).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedLlmRequest {
+    pub request_id: [u8; 16],
+    pub messages: Vec<LlmMessage>,
+    pub max_tokens: u32,
+    pub temperature: f64,
+    /// Emit each sentence as soon as it is generated, rather than all of
+    /// them once the reply is complete
+    pub stream: bool,
+}
+
+/// LLM output: one event per sentence of the reply, then a final event
+/// with the full reply (or the error).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmEvent {
+    pub request_id: [u8; 16],
+    pub sequence: u32,
+    /// Next sentence; None on the final event
+    pub sentence: Option<String>,
+    pub is_final: bool,
+    /// Full reply, on the final event
+    pub full_response: Option<String>,
+    /// Tokens generated, on the final event (0 if the model didn't say)
+    pub tokens: u32,
+    pub error: Option<String>,
+}
+
+// ── Message kinds ────────────────────────────────────────────────
+
+/// Size class of a message, used for per-type frame limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    /// Attestation and Noise handshake messages
+    Handshake,
+    /// Job notifications, acks, health and cancellation
+    Control,
+    /// Encrypted inference requests (text, uploaded audio)
+    Request,
+    /// Encrypted inference results (audio)
+    Response,
+}
+
+impl Message {
+    /// Variant names in declaration order (the binary variant index) with
+    /// their size class.
+    pub const VARIANTS: &'static [(&'static str, MessageKind)] = &[
+        ("AttestationRequest", MessageKind::Handshake),
+        ("Attestation", MessageKind::Handshake),
+        ("NoiseHandshake", MessageKind::Handshake),
+        ("NoiseHandshakeResponse", MessageKind::Handshake),
+        ("NoiseHandshakeFinish", MessageKind::Handshake),
+        ("NoiseHandshakeComplete", MessageKind::Handshake),
+        ("JobNotify", MessageKind::Control),
+        ("JobAck", MessageKind::Control),
+        ("EncryptedRequest", MessageKind::Request),
+        ("EncryptedResponse", MessageKind::Response),
+        ("EncryptedAsrRequest", MessageKind::Request),
+        ("EncryptedAsrResponse", MessageKind::Response),
+        ("EncryptedStreamRequest", MessageKind::Request),
+        ("EncryptedStreamChunk", MessageKind::Response),
+        ("HealthRequest", MessageKind::Control),
+        ("HealthResponse", MessageKind::Control),
+        ("Cancel", MessageKind::Control),
+        ("EncryptedLlmRequest", MessageKind::Request),
+        ("EncryptedLlmEvent", MessageKind::Response),
+        ("CapabilitiesRequest", MessageKind::Control),
+        ("Capabilities", MessageKind::Control),
+        ("EnrollmentRequest", MessageKind::Control),
+        ("Enrollment", MessageKind::Control),
+    ];
+
+    /// Identify the variant of an encoded body from its first bytes,
+    /// without decoding (or buffering) the rest.
+    pub fn peek_variant(format: WireFormat, body: &[u8]) -> Option<(&'static str, MessageKind)> {
+        match format {
+            // bincode varint: indexes below 251 are a single byte
+            WireFormat::Binary => Self::VARIANTS.get(*body.first()? as usize).copied(),
+            // `"Unit"` or `{"Variant":...`
+            WireFormat::Json => {
+                let rest = std::str::from_utf8(body).unwrap_or_else(|e| {
+                    std::str::from_utf8(&body[..e.valid_up_to()]).unwrap_or_default()
+                });
+                let rest = rest.trim_start();
+                let rest = match rest.strip_prefix('{') {
+                    Some(inner) => inner.trim_start(),
+                    None => rest,
+                };
+                let name = rest.strip_prefix('"')?.split('"').next()?;
+                if !rest[1 + name.len()..].starts_with('"') {
+                    return None;
+                }
+                Self::VARIANTS.iter().find(|(n, _)| *n == name).copied()
+            }
+        }
+    }
+}
+
 // ── Wire encoding ────────────────────────────────────────────────
 
+/// Body encoding for messages and the Noise plaintexts they carry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    /// serde_json — every byte of audio/ciphertext becomes a decimal number.
+    /// Spoken by peers that only offer the `sonotxt-1` ALPN.
+    Json,
+    /// bincode (varint lengths), byte vectors are written raw. bincode
+    /// can't skip or default fields, so the ALPN id is bumped whenever a
+    /// payload struct changes shape; peers on an older revision fall back
+    /// to JSON, where new fields default.
+    Binary,
+}
+
+impl WireFormat {
+    /// All formats, most preferred first (ALPN offer order).
+    pub const ALL: [WireFormat; 2] = [WireFormat::Binary, WireFormat::Json];
+
+    /// ALPN protocol id selecting this format.
+    pub const fn alpn(self) -> &'static [u8] {
+        match self {
+            Self::Json => b"sonotxt-1",
+            // sonotxt-2: EncryptedTtsRequest without engine and options
+            Self::Binary => b"sonotxt-3",
+        }
+    }
+
+    pub fn from_alpn(protocol: &[u8]) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.alpn() == protocol)
+    }
+
+    /// Serialize a value (message body or Noise plaintext).
+    pub fn to_vec<T: Serialize>(self, value: &T) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(match self {
+            Self::Json => serde_json::to_vec(value)?,
+            Self::Binary => bincode::DefaultOptions::new().serialize(value)?,
+        })
+    }
+
+    pub fn from_slice<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(match self {
+            Self::Json => serde_json::from_slice(bytes)?,
+            Self::Binary => bincode::DefaultOptions::new().deserialize(bytes)?,
+        })
+    }
+}
+
 impl Message {
     /// Encode as length-prefixed JSON.
     pub fn encode(&self) -> Vec<u8> {
-        let data = serde_json::to_vec(self).expect("Message serialize");
+        self.encode_as(WireFormat::Json)
+    }
+
+    /// Encode as a length-prefixed body in `format`.
+    pub fn encode_as(&self, format: WireFormat) -> Vec<u8> {
+        let data = format.to_vec(self).expect("Message serialize");
         let len = (data.len() as u32).to_le_bytes();
         [len.as_slice(), &data].concat()
     }
 
-    /// Decode from length-prefixed buffer. Returns (message, bytes_consumed).
+    /// Decode from length-prefixed JSON. Returns (message, bytes_consumed).
     pub fn decode(bytes: &[u8]) -> Result<(Self, usize), &'static str> {
+        Self::decode_as(bytes, WireFormat::Json)
+    }
+
+    /// Decode from a length-prefixed body in `format`.
+    pub fn decode_as(bytes: &[u8], format: WireFormat) -> Result<(Self, usize), &'static str> {
         if bytes.len() < 4 {
             return Err("not enough bytes for length");
         }
@@ -151,8 +403,169 @@ impl Message {
         if bytes.len() < 4 + len {
             return Err("not enough bytes for message");
         }
-        let msg: Self = serde_json::from_slice(&bytes[4..4 + len])
+        let msg: Self = format
+            .from_slice(&bytes[4..4 + len])
             .map_err(|_| "decode failed")?;
         Ok((msg, 4 + len))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples() -> Vec<Message> {
+        vec![
+            Message::AttestationRequest,
+            Message::Attestation(AttestationBundle {
+                quote: vec![1, 2, 3],
+                static_key: vec![7; 32],
+                binding_sig: vec![9; 32],
+                tee_type: TeeType::SevSnp,
+                certs: vec![vec![0x30, 0x82]],
+            }),
+            Message::NoiseHandshake(vec![0xAB; 48]),
+            Message::NoiseHandshakeResponse {
+                handshake: vec![0xCD; 48],
+                session_id: vec![5; 16],
+            },
+            Message::NoiseHandshakeFinish {
+                handshake: vec![0xEF; 64],
+                session_id: vec![5; 16],
+            },
+            Message::NoiseHandshakeComplete,
+            Message::JobNotify(vec![1; 40]),
+            Message::JobAck { job_id: "job-1".into() },
+            Message::EncryptedRequest(vec![0xEE; 100]),
+            Message::EncryptedResponse(vec![0xFF; 1000]),
+            Message::EncryptedAsrRequest(vec![0xDD; 100]),
+            Message::EncryptedAsrResponse(vec![0xCC; 100]),
+            Message::EncryptedStreamRequest(vec![0xBB; 100]),
+            Message::EncryptedStreamChunk(vec![0xAA; 100]),
+            Message::HealthRequest,
+            Message::HealthResponse(WorkerHealth {
+                speech_ok: true,
+                llm_ok: false,
+                jobs_processing: 3,
+                uptime_secs: 86_400,
+            }),
+            Message::Cancel { request_id: [3; 16] },
+            Message::EncryptedLlmRequest(vec![0x99; 100]),
+            Message::EncryptedLlmEvent(vec![0x88; 100]),
+            Message::CapabilitiesRequest,
+            Message::Capabilities(WorkerCapabilities {
+                engines: vec!["qwen".into()],
+                voices: vec!["ryan".into(), "serena".into()],
+                languages: vec![],
+                max_concurrency: 4,
+            }),
+            Message::EnrollmentRequest,
+            Message::Enrollment(vec![0x77; 64]),
+        ]
+    }
+
+    #[test]
+    fn test_roundtrip_both_formats() {
+        for format in WireFormat::ALL {
+            for msg in samples() {
+                let encoded = msg.encode_as(format);
+                let (decoded, used) = Message::decode_as(&encoded, format).unwrap();
+                assert_eq!(used, encoded.len());
+                // Messages don't implement PartialEq; compare the canonical JSON
+                assert_eq!(
+                    serde_json::to_value(&decoded).unwrap(),
+                    serde_json::to_value(&msg).unwrap(),
+                    "{:?}",
+                    format
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_variant_table_matches_encoding() {
+        let samples = samples();
+        assert_eq!(samples.len(), Message::VARIANTS.len(), "one sample per variant");
+
+        for msg in samples {
+            let name = match serde_json::to_value(&msg).unwrap() {
+                serde_json::Value::String(name) => name,
+                serde_json::Value::Object(map) => map.keys().next().unwrap().clone(),
+                other => panic!("unexpected encoding {}", other),
+            };
+            for format in WireFormat::ALL {
+                let encoded = msg.encode_as(format);
+                let (variant, _) = Message::peek_variant(format, &encoded[4..]).unwrap();
+                assert_eq!(variant, name, "{:?}", format);
+            }
+        }
+    }
+
+    #[test]
+    fn test_binary_writes_bytes_raw() {
+        let audio = vec![200u8; 1 << 20];
+        let msg = Message::EncryptedResponse(audio.clone());
+
+        let binary = msg.encode_as(WireFormat::Binary);
+        let json = msg.encode_as(WireFormat::Json);
+        assert!(binary.len() < audio.len() + 16, "binary {} bytes", binary.len());
+        assert!(json.len() > 3 * audio.len(), "json {} bytes", json.len());
+
+        let chunk = StreamChunk {
+            request_id: [1; 16],
+            sequence: 0,
+            audio,
+            is_final: true,
+            error: None,
+        };
+        let payload = WireFormat::Binary.to_vec(&chunk).unwrap();
+        let back: StreamChunk = WireFormat::Binary.from_slice(&payload).unwrap();
+        assert_eq!(back.audio, chunk.audio);
+    }
+
+    #[test]
+    fn test_legacy_json_encoding_unchanged() {
+        // Old peers frame bodies with `encode`/`decode`; the JSON variant of
+        // the new API must stay byte-compatible with them.
+        let msg = Message::JobAck { job_id: "abc".into() };
+        assert_eq!(msg.encode(), msg.encode_as(WireFormat::Json));
+        assert_eq!(&msg.encode()[4..], br#"{"JobAck":{"job_id":"abc"}}"#);
+    }
+
+    #[test]
+    fn test_format_mismatch_rejected() {
+        let encoded = Message::EncryptedResponse(vec![1, 2, 3]).encode_as(WireFormat::Binary);
+        assert!(Message::decode_as(&encoded, WireFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_alpn_ids() {
+        assert_eq!(WireFormat::from_alpn(b"sonotxt-1"), Some(WireFormat::Json));
+        assert_eq!(WireFormat::from_alpn(b"sonotxt-3"), Some(WireFormat::Binary));
+        // Older binary revision: negotiates JSON instead
+        assert_eq!(WireFormat::from_alpn(b"sonotxt-2"), None);
+        assert_eq!(WireFormat::from_alpn(b"h3"), None);
+    }
+
+    #[test]
+    fn test_capabilities_match() {
+        let caps = WorkerCapabilities {
+            engines: vec!["vibevoice".into(), "vibevoice-streaming".into()],
+            voices: vec![],
+            languages: vec!["en".into(), "zh".into()],
+            max_concurrency: 2,
+        };
+        assert!(caps.supports("vibevoice", "en-Alice", "en"));
+        assert!(caps.supports("vibevoice-streaming", "anyone", "auto"));
+        assert!(!caps.supports("qwen", "ryan", "auto"));
+        assert!(!caps.supports("vibevoice", "en-Alice", "de"));
+
+        let caps = WorkerCapabilities {
+            engines: vec!["qwen".into()],
+            voices: vec!["ryan".into()],
+            ..Default::default()
+        };
+        assert!(caps.supports("qwen", "ryan", "de"));
+        assert!(!caps.supports("qwen", "serena", "auto"));
+    }
+}
@@ -4,74 +4,318 @@ use quinn::crypto::rustls::QuicClientConfig;
 use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::protocol::Message;
+use crate::protocol::{Message, MessageKind, WireFormat};
 
-pub const ALPN: &[u8] = b"sonotxt-1";
+/// Client keep-alive; well under quinn's default 30s idle timeout
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(10);
 
-/// Read one length-prefixed Message from a QUIC recv stream.
-pub async fn read_message(recv: &mut quinn::RecvStream) -> Result<Message, Box<dyn std::error::Error + Send + Sync>> {
+/// Bytes of a frame body inspected to identify the message type.
+const PEEK_LEN: usize = 64;
+/// Body bytes read per call, so buffers grow with the data actually
+/// received rather than the length the peer claims.
+const READ_PIECE: usize = 64 * 1024;
+
+/// Maximum frame body size per message kind.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameLimits {
+    pub handshake: usize,
+    pub control: usize,
+    pub request: usize,
+    pub response: usize,
+}
+
+impl FrameLimits {
+    pub fn limit(&self, kind: MessageKind) -> usize {
+        match kind {
+            MessageKind::Handshake => self.handshake,
+            MessageKind::Control => self.control,
+            MessageKind::Request => self.request,
+            MessageKind::Response => self.response,
+        }
+    }
+}
+
+impl Default for FrameLimits {
+    fn default() -> Self {
+        Self {
+            // attestation bundles carry certificate chains
+            handshake: 256 * 1024,
+            control: 64 * 1024,
+            request: 64 * 1024 * 1024,
+            response: 256 * 1024 * 1024,
+        }
+    }
+}
+
+/// Why a frame could not be read.
+#[derive(Debug, thiserror::Error)]
+pub enum FrameError {
+    #[error("{kind:?} frame of {len} bytes exceeds the {limit} byte limit")]
+    TooLarge {
+        kind: MessageKind,
+        len: usize,
+        limit: usize,
+    },
+    #[error("unknown message type")]
+    UnknownType,
+    #[error("stream ended mid-frame")]
+    Truncated,
+    #[error("read failed: {0}")]
+    Read(#[from] quinn::ReadError),
+    #[error("decode failed: {0}")]
+    Decode(String),
+}
+
+/// Length prefix plus the first bytes of the body.
+struct FrameHeader {
+    len: usize,
+    head: Vec<u8>,
+    variant: &'static str,
+}
+
+async fn read_exact(recv: &mut quinn::RecvStream, buf: &mut [u8]) -> Result<(), FrameError> {
+    recv.read_exact(buf).await.map_err(|e| match e {
+        quinn::ReadExactError::FinishedEarly(_) => FrameError::Truncated,
+        quinn::ReadExactError::ReadError(e) => FrameError::Read(e),
+    })
+}
+
+/// Read the length prefix and enough of the body to identify the message
+/// type, and reject the frame if it exceeds that type's limit.
+async fn read_header(
+    recv: &mut quinn::RecvStream,
+    format: WireFormat,
+    limits: &FrameLimits,
+) -> Result<FrameHeader, FrameError> {
     let mut len_buf = [0u8; 4];
-    recv.read_exact(&mut len_buf).await?;
-    let msg_len = u32::from_le_bytes(len_buf) as usize;
+    read_exact(recv, &mut len_buf).await?;
+    let len = u32::from_le_bytes(len_buf) as usize;
 
-    let mut body = vec![0u8; msg_len];
-    recv.read_exact(&mut body).await?;
+    // Read only as much as it takes to identify the type, so an oversized
+    // frame is rejected even if the peer sends nothing after the header.
+    let want = len.min(PEEK_LEN);
+    let mut head = Vec::with_capacity(want);
+    let (variant, kind) = loop {
+        if let Some(identified) = Message::peek_variant(format, &head) {
+            break identified;
+        }
+        if head.len() == want {
+            return Err(FrameError::UnknownType);
+        }
+        let mut buf = [0u8; PEEK_LEN];
+        match recv.read(&mut buf[..want - head.len()]).await? {
+            Some(n) => head.extend_from_slice(&buf[..n]),
+            None => return Err(FrameError::Truncated),
+        }
+    };
+    let limit = limits.limit(kind);
+    if len > limit {
+        return Err(FrameError::TooLarge { kind, len, limit });
+    }
+    Ok(FrameHeader { len, head, variant })
+}
 
-    // Reconstruct length-prefixed buffer for Message::decode
-    let mut buf = len_buf.to_vec();
-    buf.extend(body);
+/// Read one length-prefixed Message from a QUIC recv stream, enforcing
+/// `limits` before buffering the body.
+pub async fn read_message(
+    recv: &mut quinn::RecvStream,
+    format: WireFormat,
+    limits: &FrameLimits,
+) -> Result<Message, FrameError> {
+    let header = read_header(recv, format, limits).await?;
+    read_body(recv, format, header).await
+}
+
+async fn read_body(
+    recv: &mut quinn::RecvStream,
+    format: WireFormat,
+    header: FrameHeader,
+) -> Result<Message, FrameError> {
+    let mut body = header.head;
+    while body.len() < header.len {
+        let start = body.len();
+        body.resize(start + (header.len - start).min(READ_PIECE), 0);
+        read_exact(recv, &mut body[start..]).await?;
+    }
+    format.from_slice(&body).map_err(|e| FrameError::Decode(e.to_string()))
+}
+
+/// A frame returned by `read_frame`.
+pub enum Frame<'a> {
+    Message(Message),
+    /// Byte payload of a single-`Vec<u8>` variant, delivered as it arrives
+    Payload {
+        variant: &'static str,
+        payload: PayloadReader<'a>,
+    },
+}
 
-    let (msg, _) = Message::decode(&buf).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { e.into() })?;
-    Ok(msg)
+/// Incremental reader for the byte payload of a streamed frame.
+pub struct PayloadReader<'a> {
+    recv: &'a mut quinn::RecvStream,
+    /// Payload bytes already read with the frame header
+    pending: Vec<u8>,
+    remaining: usize,
+}
+
+impl PayloadReader<'_> {
+    /// Next piece of the payload (at most 64 KiB), or None at the end.
+    pub async fn next_piece(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        if !self.pending.is_empty() {
+            return Ok(Some(std::mem::take(&mut self.pending)));
+        }
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        let mut piece = vec![0u8; self.remaining.min(READ_PIECE)];
+        read_exact(self.recv, &mut piece).await?;
+        self.remaining -= piece.len();
+        Ok(Some(piece))
+    }
+}
+
+/// Like `read_message`, but frames of the `streamed` variants are returned
+/// as a `PayloadReader` instead of being buffered, so large uploads can be
+/// processed piece by piece. Streaming needs the binary format (JSON
+/// payloads are number arrays); JSON frames are always buffered.
+pub async fn read_frame<'a>(
+    recv: &'a mut quinn::RecvStream,
+    format: WireFormat,
+    limits: &FrameLimits,
+    streamed: &[&str],
+) -> Result<Frame<'a>, FrameError> {
+    let header = read_header(recv, format, limits).await?;
+    if format != WireFormat::Binary || !streamed.contains(&header.variant) {
+        return read_body(recv, format, header).await.map(Frame::Message);
+    }
+
+    // Body: variant index (1 byte), varint payload length (up to 9), payload
+    let mut head = header.head;
+    let start = head.len();
+    let want = header.len.min(10).max(start);
+    head.resize(want, 0);
+    read_exact(recv, &mut head[start..]).await?;
+
+    let (payload_len, used) =
+        read_varint(&head[1..]).ok_or_else(|| FrameError::Decode("invalid payload length".into()))?;
+    let prefix = 1 + used;
+    if prefix.checked_add(payload_len) != Some(header.len) {
+        return Err(FrameError::Decode("payload length does not match frame".into()));
+    }
+
+    let pending = head[prefix..].to_vec();
+    Ok(Frame::Payload {
+        variant: header.variant,
+        payload: PayloadReader {
+            recv,
+            remaining: payload_len - pending.len(),
+            pending,
+        },
+    })
+}
+
+/// bincode varint: one byte below 251, else a marker and a u16/u32/u64.
+fn read_varint(bytes: &[u8]) -> Option<(usize, usize)> {
+    let fixed = |n: usize| -> Option<(usize, usize)> {
+        let mut buf = [0u8; 8];
+        buf[..n].copy_from_slice(bytes.get(1..1 + n)?);
+        Some((usize::try_from(u64::from_le_bytes(buf)).ok()?, 1 + n))
+    };
+    match *bytes.first()? {
+        b @ 0..=250 => Some((b as usize, 1)),
+        251 => fixed(2),
+        252 => fixed(4),
+        253 => fixed(8),
+        _ => None,
+    }
 }
 
 /// Write one Message to a QUIC send stream.
-pub async fn write_message(send: &mut quinn::SendStream, msg: &Message) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    send.write_all(&msg.encode()).await?;
+pub async fn write_message(
+    send: &mut quinn::SendStream,
+    msg: &Message,
+    format: WireFormat,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    send.write_all(&msg.encode_as(format)).await?;
     Ok(())
 }
 
+/// Wire format negotiated for a connection via ALPN. Peers that predate
+/// ALPN-based negotiation only offer `sonotxt-1`, i.e. JSON.
+pub fn wire_format(conn: &quinn::Connection) -> WireFormat {
+    conn.handshake_data()
+        .and_then(|data| data.downcast::<quinn::crypto::rustls::HandshakeData>().ok())
+        .and_then(|data| data.protocol)
+        .and_then(|protocol| WireFormat::from_alpn(&protocol))
+        .unwrap_or(WireFormat::Json)
+}
+
+fn alpn_protocols(formats: &[WireFormat]) -> Vec<Vec<u8>> {
+    formats.iter().map(|f| f.alpn().to_vec()).collect()
+}
+
 // ── Server-side QUIC config ───────────────────────────────────
 
-/// Generate self-signed cert and build quinn ServerConfig.
+/// Generate self-signed cert and build quinn ServerConfig accepting
+/// `formats` (most preferred first).
 /// In production, attestation binding replaces cert trust.
-pub fn server_config() -> Result<quinn::ServerConfig, Box<dyn std::error::Error + Send + Sync>> {
+pub fn server_config(formats: &[WireFormat]) -> Result<quinn::ServerConfig, Box<dyn std::error::Error + Send + Sync>> {
     let (cert, key) = generate_self_signed_cert()?;
 
     let mut server_crypto = rustls::ServerConfig::builder()
         .with_no_client_auth()
         .with_single_cert(vec![cert], key)?;
 
-    server_crypto.alpn_protocols = vec![ALPN.to_vec()];
+    server_crypto.alpn_protocols = alpn_protocols(formats);
 
     let quic_config = quinn::crypto::rustls::QuicServerConfig::try_from(server_crypto)?;
     Ok(quinn::ServerConfig::with_crypto(Arc::new(quic_config)))
 }
 
-/// Create QUIC server endpoint.
+/// Create QUIC server endpoint speaking every wire format.
 pub fn server_endpoint(addr: SocketAddr) -> Result<quinn::Endpoint, Box<dyn std::error::Error + Send + Sync>> {
-    let config = server_config()?;
+    server_endpoint_with(addr, &WireFormat::ALL)
+}
+
+/// Create QUIC server endpoint restricted to `formats`.
+pub fn server_endpoint_with(
+    addr: SocketAddr,
+    formats: &[WireFormat],
+) -> Result<quinn::Endpoint, Box<dyn std::error::Error + Send + Sync>> {
+    let config = server_config(formats)?;
     let endpoint = quinn::Endpoint::server(config, addr)?;
     Ok(endpoint)
 }
 
 // ── Client-side QUIC config ──────────────────────────────────
 
-/// Create QUIC client endpoint.
-/// Skips TLS cert verification — trust comes from Noise attestation binding, not TLS PKI.
+/// Create QUIC client endpoint offering every wire format.
 pub fn client_endpoint() -> Result<quinn::Endpoint, Box<dyn std::error::Error + Send + Sync>> {
+    client_endpoint_with(&WireFormat::ALL)
+}
+
+/// Create QUIC client endpoint offering `formats` (most preferred first).
+/// Skips TLS cert verification — trust comes from Noise attestation binding, not TLS PKI.
+pub fn client_endpoint_with(formats: &[WireFormat]) -> Result<quinn::Endpoint, Box<dyn std::error::Error + Send + Sync>> {
     let mut crypto = rustls::ClientConfig::builder()
         .dangerous()
         .with_custom_certificate_verifier(Arc::new(SkipServerVerification))
         .with_no_client_auth();
 
-    crypto.alpn_protocols = vec![ALPN.to_vec()];
+    crypto.alpn_protocols = alpn_protocols(formats);
 
-    let client_config = quinn::ClientConfig::new(Arc::new(
+    let mut client_config = quinn::ClientConfig::new(Arc::new(
         QuicClientConfig::try_from(crypto)?
     ));
 
+    // Keep-alives let the idle timeout detect a worker that went away
+    // without closing the connection (crash, restart, network loss).
+    let mut transport = quinn::TransportConfig::default();
+    transport.keep_alive_interval(Some(KEEP_ALIVE_INTERVAL));
+    client_config.transport_config(Arc::new(transport));
+
     let mut endpoint = quinn::Endpoint::client("0.0.0.0:0".parse()?)?;
     endpoint.set_default_client_config(client_config);
     Ok(endpoint)
@@ -86,7 +330,7 @@ fn generate_self_signed_cert() -> Result<(CertificateDer<'static>, PrivateKeyDer
     Ok((cert, key))
 }
 
-/// Skip TLS cert verification. Trust is established through Noise_NK
+/// Skip TLS cert verification. Trust is established through Noise
 /// attestation binding, not through the TLS certificate chain.
 #[derive(Debug)]
 struct SkipServerVerification;
@@ -130,3 +374,252 @@ impl rustls::client::danger::ServerCertVerifier for SkipServerVerification {
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Connect `client` formats to `server` formats over loopback, exchange
+    /// one message and return the format both sides agreed on.
+    async fn negotiate(client: &[WireFormat], server: &[WireFormat]) -> WireFormat {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        let server = server_endpoint_with("127.0.0.1:0".parse().unwrap(), server).unwrap();
+        let addr = server.local_addr().unwrap();
+        let accept = tokio::spawn(async move {
+            let conn = server.accept().await.unwrap().await.unwrap();
+            let format = wire_format(&conn);
+            let (mut send, mut recv) = conn.accept_bi().await.unwrap();
+            let msg = read_message(&mut recv, format, &FrameLimits::default()).await.unwrap();
+            write_message(&mut send, &msg, format).await.unwrap();
+            send.finish().unwrap();
+            conn.closed().await;
+            format
+        });
+
+        let client = client_endpoint_with(client).unwrap();
+        let conn = client.connect(addr, "localhost").unwrap().await.unwrap();
+        let format = wire_format(&conn);
+        let (mut send, mut recv) = conn.open_bi().await.unwrap();
+        write_message(&mut send, &Message::EncryptedResponse(vec![7; 4096]), format).await.unwrap();
+        send.finish().unwrap();
+        match read_message(&mut recv, format, &FrameLimits::default()).await.unwrap() {
+            Message::EncryptedResponse(data) => assert_eq!(data, vec![7; 4096]),
+            other => panic!("unexpected echo: {:?}", other),
+        }
+        conn.close(0u32.into(), b"done");
+
+        assert_eq!(accept.await.unwrap(), format);
+        format
+    }
+
+    #[tokio::test]
+    async fn test_current_peers_use_binary() {
+        assert_eq!(negotiate(&WireFormat::ALL, &WireFormat::ALL).await, WireFormat::Binary);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_client_gets_json() {
+        assert_eq!(negotiate(&[WireFormat::Json], &WireFormat::ALL).await, WireFormat::Json);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_server_gets_json() {
+        assert_eq!(negotiate(&WireFormat::ALL, &[WireFormat::Json]).await, WireFormat::Json);
+    }
+
+    /// Send `raw` bytes on a fresh stream and run `read` on the receiving side.
+    async fn receive_raw<T, F>(raw: Vec<u8>, read: F) -> T
+    where
+        T: Send + 'static,
+        F: for<'a> FnOnce(&'a mut quinn::RecvStream) -> std::pin::Pin<Box<dyn std::future::Future<Output = T> + Send + 'a>>
+            + Send
+            + 'static,
+    {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        let server = server_endpoint("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = server.local_addr().unwrap();
+        let accept = tokio::spawn(async move {
+            let conn = server.accept().await.unwrap().await.unwrap();
+            let (_send, mut recv) = conn.accept_bi().await.unwrap();
+            read(&mut recv).await
+        });
+
+        let client = client_endpoint().unwrap();
+        let conn = client.connect(addr, "localhost").unwrap().await.unwrap();
+        let (mut send, _recv) = conn.open_bi().await.unwrap();
+        send.write_all(&raw).await.unwrap();
+        send.finish().unwrap();
+
+        let result = accept.await.unwrap();
+        conn.close(0u32.into(), b"done");
+        result
+    }
+
+    async fn read_raw(raw: Vec<u8>, format: WireFormat, limits: FrameLimits) -> Result<Message, FrameError> {
+        receive_raw(raw, move |recv| Box::pin(async move { read_message(recv, format, &limits).await })).await
+    }
+
+    /// A length prefix claiming `len` bytes followed by the start of a
+    /// `variant` body, but none of the claimed data.
+    fn hostile_frame(format: WireFormat, variant: Message, len: u32) -> Vec<u8> {
+        let body = &variant.encode_as(format)[4..];
+        let mut raw = len.to_le_bytes().to_vec();
+        raw.extend_from_slice(&body[..body.len().min(PEEK_LEN)]);
+        raw
+    }
+
+    #[tokio::test]
+    async fn test_hostile_length_prefix_rejected() {
+        for format in WireFormat::ALL {
+            // 4 GB health request: rejected from the header alone
+            let raw = hostile_frame(format, Message::HealthRequest, u32::MAX);
+            match read_raw(raw, format, FrameLimits::default()).await {
+                Err(FrameError::TooLarge { kind: MessageKind::Control, len, .. }) => {
+                    assert_eq!(len, u32::MAX as usize)
+                }
+                other => panic!("{:?}: expected TooLarge, got {:?}", format, other.map(|_| ())),
+            }
+
+            // Request-sized frame over a tightened request limit
+            let limits = FrameLimits { request: 1024, ..FrameLimits::default() };
+            let raw = hostile_frame(format, Message::EncryptedAsrRequest(vec![0; 2048]), 1 << 20);
+            assert!(matches!(
+                read_raw(raw, format, limits).await,
+                Err(FrameError::TooLarge { kind: MessageKind::Request, limit: 1024, .. })
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_claimed_length_without_data_is_truncated() {
+        // Within limits, but the peer never sends the claimed 32 MB
+        let raw = hostile_frame(WireFormat::Binary, Message::EncryptedAsrRequest(vec![0; 100]), 32 << 20);
+        assert!(matches!(
+            read_raw(raw, WireFormat::Binary, FrameLimits::default()).await,
+            Err(FrameError::Truncated)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_message_type_rejected() {
+        let mut raw = 8u32.to_le_bytes().to_vec();
+        raw.extend_from_slice(&[0xF0; 8]);
+        assert!(matches!(
+            read_raw(raw, WireFormat::Binary, FrameLimits::default()).await,
+            Err(FrameError::UnknownType)
+        ));
+
+        let body = br#"{"Bogus":[1,2,3]}"#;
+        let mut raw = (body.len() as u32).to_le_bytes().to_vec();
+        raw.extend_from_slice(body);
+        assert!(matches!(
+            read_raw(raw, WireFormat::Json, FrameLimits::default()).await,
+            Err(FrameError::UnknownType)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_payload_streamed_in_pieces() {
+        let payload: Vec<u8> = (0..300_000u32).map(|i| i as u8).collect();
+        let raw = Message::EncryptedAsrRequest(payload.clone()).encode_as(WireFormat::Binary);
+
+        let (variant, pieces) = receive_raw(raw, |recv| {
+            Box::pin(async move {
+                let frame = read_frame(recv, WireFormat::Binary, &FrameLimits::default(), &["EncryptedAsrRequest"])
+                    .await
+                    .unwrap();
+                let Frame::Payload { variant, mut payload } = frame else {
+                    panic!("expected a streamed payload");
+                };
+                let mut pieces = Vec::new();
+                while let Some(piece) = payload.next_piece().await.unwrap() {
+                    assert!(piece.len() <= READ_PIECE);
+                    pieces.push(piece);
+                }
+                (variant, pieces)
+            })
+        })
+        .await;
+
+        assert_eq!(variant, "EncryptedAsrRequest");
+        assert!(pieces.len() > 1);
+        assert_eq!(pieces.concat(), payload);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_noise_requests_on_one_connection() {
+        use crate::noise::{NoiseClient, NoiseServer};
+
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        let noise_server = Arc::new(NoiseServer::new().unwrap());
+        let mut noise_client = NoiseClient::new();
+        let handshake = noise_client.initiate_handshake(noise_server.static_public_key()).unwrap();
+        let (session_id, response) = noise_server.process_handshake(&handshake).unwrap();
+        noise_client.complete_handshake(&response, session_id).unwrap();
+        let noise_client = Arc::new(noise_client);
+
+        // Echo server: every stream is handled on its own task
+        let server = server_endpoint("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let conn = server.accept().await.unwrap().await.unwrap();
+            let format = wire_format(&conn);
+            while let Ok((mut send, mut recv)) = conn.accept_bi().await {
+                let noise = noise_server.clone();
+                tokio::spawn(async move {
+                    let Message::EncryptedRequest(ciphertext) =
+                        read_message(&mut recv, format, &FrameLimits::default()).await.unwrap()
+                    else {
+                        panic!("expected a request");
+                    };
+                    let mut plaintext = noise.decrypt(&session_id, &ciphertext).unwrap();
+                    plaintext.reverse();
+                    let reply = noise.encrypt(&session_id, &plaintext).unwrap();
+                    write_message(&mut send, &Message::EncryptedResponse(reply), format).await.unwrap();
+                    send.finish().unwrap();
+                });
+            }
+        });
+
+        let client = client_endpoint().unwrap();
+        let conn = client.connect(addr, "localhost").unwrap().await.unwrap();
+        let format = wire_format(&conn);
+
+        let requests: Vec<_> = (0..64u32)
+            .map(|i| {
+                let (conn, noise) = (conn.clone(), noise_client.clone());
+                tokio::spawn(async move {
+                    let request: Vec<u8> = (0..1000 + i * 1000).map(|j| (i + j) as u8).collect();
+                    let (mut send, mut recv) = conn.open_bi().await.unwrap();
+                    let ciphertext = noise.encrypt(&request).unwrap();
+                    write_message(&mut send, &Message::EncryptedRequest(ciphertext), format).await.unwrap();
+                    send.finish().unwrap();
+                    let Message::EncryptedResponse(reply) =
+                        read_message(&mut recv, format, &FrameLimits::default()).await.unwrap()
+                    else {
+                        panic!("expected a response");
+                    };
+                    let mut reply = noise.decrypt(&reply).unwrap();
+                    reply.reverse();
+                    assert_eq!(reply, request);
+                })
+            })
+            .collect();
+        for request in requests {
+            request.await.unwrap();
+        }
+        conn.close(0u32.into(), b"done");
+    }
+
+    #[test]
+    fn test_varint() {
+        assert_eq!(read_varint(&[250]), Some((250, 1)));
+        assert_eq!(read_varint(&[251, 0x10, 0x27]), Some((10_000, 3)));
+        assert_eq!(read_varint(&[252, 0xE0, 0x93, 0x04, 0x00]), Some((300_000, 5)));
+        assert_eq!(read_varint(&[252, 0xE0]), None);
+        assert_eq!(read_varint(&[254]), None);
+    }
+}
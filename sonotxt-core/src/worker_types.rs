@@ -6,6 +6,8 @@ pub enum ServiceError {
     Unavailable,
     Failed(String),
     Cancelled,
+    /// No worker can serve the request (engine, voice or language)
+    Unsupported(String),
 }
 
 impl std::fmt::Display for ServiceError {
@@ -15,15 +17,89 @@ impl std::fmt::Display for ServiceError {
             Self::Unavailable => write!(f, "service unavailable"),
             Self::Failed(msg) => write!(f, "{}", msg),
             Self::Cancelled => write!(f, "cancelled"),
+            Self::Unsupported(msg) => write!(f, "unsupported: {}", msg),
+        }
+    }
+}
+
+/// Engine for requests that don't name one.
+pub const DEFAULT_ENGINE: &str = "qwen";
+
+/// Per-request synthesis knobs. Which ones an engine honours is up to its
+/// adapter; unset means the engine's default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SynthesisOptions {
+    /// Playback speed factor, 1.0 is natural
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f32>,
+    /// Free-form style instruction ("whisper", "cheerful"), Qwen only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instruct: Option<String>,
+    /// Classifier-free guidance scale, VibeVoice only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cfg_scale: Option<f32>,
+}
+
+/// How a TTS request may travel to the worker.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Privacy {
+    /// QUIC+Noise when connected, plaintext HTTP otherwise
+    #[default]
+    Standard,
+    /// Attested Noise session only; fail rather than fall back to HTTP
+    Private,
+}
+
+impl Privacy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Standard => "standard",
+            Self::Private => "private",
+        }
+    }
+}
+
+impl std::str::FromStr for Privacy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(Self::Standard),
+            "private" => Ok(Self::Private),
+            other => Err(format!("unknown privacy policy: {}", other)),
+        }
+    }
+}
+
+/// How a request actually reached the worker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// Encrypted QUIC+Noise session
+    Quic,
+    /// Plaintext HTTP to the worker's speech service
+    Http,
+}
+
+impl Transport {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Quic => "quic",
+            Self::Http => "http",
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct TtsRequest {
+    /// TTS engine the voice belongs to (`qwen`, `vibevoice`, ...)
+    pub engine: String,
     pub text: String,
     pub speaker: String,
     pub language: String,
+    pub options: SynthesisOptions,
+    pub privacy: Privacy,
     pub api_key: Option<String>,
 }
 
@@ -33,6 +109,7 @@ pub struct TtsResponse {
     pub format: String,
     pub duration_seconds: f64,
     pub runtime_ms: u64,
+    pub transport: Transport,
 }
 
 #[derive(Debug, Clone)]
@@ -65,3 +142,67 @@ pub struct LlmResponse {
     pub tokens: u32,
     pub runtime_ms: u64,
 }
+
+/// Incremental parser for the LLM service's `/chat_stream` SSE output,
+/// one `data: {"event":"sentence","text":"..."}` event per sentence.
+/// Feed it bytes as they arrive; it returns the sentences completed so far.
+#[derive(Debug, Default)]
+pub struct SentenceParser {
+    buf: Vec<u8>,
+}
+
+impl SentenceParser {
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(bytes);
+
+        let mut sentences = Vec::new();
+        while let Some(pos) = self.buf.windows(2).position(|w| w == b"\n\n") {
+            let event: Vec<u8> = self.buf.drain(..pos + 2).collect();
+            let Ok(event) = std::str::from_utf8(&event[..pos]) else { continue };
+            for data in event.lines().filter_map(|line| line.strip_prefix("data: ")) {
+                let Ok(event) = serde_json::from_str::<serde_json::Value>(data) else { continue };
+                if event["event"] != "sentence" {
+                    continue;
+                }
+                match event["text"].as_str() {
+                    Some(text) if !text.is_empty() => sentences.push(text.to_string()),
+                    _ => {}
+                }
+            }
+        }
+        sentences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sentence_parser_across_pieces() {
+        let sse = "data: {\"event\":\"sentence\",\"text\":\"Grüß dich.\"}\n\n\
+                   data: {\"event\":\"sentence\",\"text\":\"\"}\n\n\
+                   data: {\"event\":\"done\"}\n\n\
+                   data: {\"event\":\"sentence\",\"text\":\"Bye!\"}\n\n";
+
+        // One byte at a time, splitting the multi-byte characters
+        let mut parser = SentenceParser::default();
+        let sentences: Vec<String> = sse.as_bytes().iter().flat_map(|b| parser.push(&[*b])).collect();
+        assert_eq!(sentences, ["Grüß dich.", "Bye!"]);
+
+        let mut parser = SentenceParser::default();
+        assert!(parser.push(b"data: {\"event\":\"sentence\",\"text\":\"Half").is_empty());
+        assert_eq!(parser.push(b"\"}\n\n"), ["Half"]);
+    }
+
+    #[test]
+    fn test_privacy_names() {
+        // The API body and the jobs.privacy column use the same names
+        for privacy in [Privacy::Standard, Privacy::Private] {
+            let json = serde_json::to_string(&privacy).unwrap();
+            assert_eq!(json, format!("\"{}\"", privacy.as_str()));
+            assert_eq!(privacy.as_str().parse::<Privacy>(), Ok(privacy));
+        }
+        assert!("public".parse::<Privacy>().is_err());
+    }
+}
@@ -0,0 +1,484 @@
+//! Attestation providers — where the worker's quote comes from.
+//!
+//! Every provider puts `report_data(noise_static_key)` into the hardware
+//! report, so the API can check the quote was produced for the key it is
+//! about to run a Noise handshake against. The static key is fixed for the
+//! life of the process (or across restarts with `NOISE_STATIC_KEY`), so
+//! the bundle is generated once at startup.
+//!
+//! - `insecure`: no TEE, development only
+//! - `sev-guest`: AMD SEV-SNP via the `/dev/sev-guest` ioctl
+//! - `tdx`: Intel TDX via the configfs-tsm report interface
+//! - `file`: replays a recorded quote from disk, for testing. The quote
+//!   only verifies if `NOISE_STATIC_KEY` is the key it was recorded for;
+//!   otherwise only an API with `ATTESTATION_REQUIRE_TEE=false` accepts it
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+use sonotxt_core::attestation::{report_data, snp::SnpReport, tdx::TdQuote};
+use sonotxt_core::protocol::{AttestationBundle, TeeType};
+
+use crate::config::WorkerConfig;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A hardware quote plus any certificates the verifier may need.
+pub struct Quote {
+    pub quote: Vec<u8>,
+    /// DER certificates (e.g. the SNP VCEK from the extended report)
+    pub certs: Vec<Vec<u8>>,
+}
+
+/// Source of attestation quotes for this worker.
+pub trait AttestationProvider: Send + Sync {
+    fn tee_type(&self) -> TeeType;
+
+    /// Obtain a quote whose report data is `report_data`.
+    fn quote(&self, report_data: &[u8; 64]) -> Result<Quote, BoxError>;
+}
+
+/// Select the provider configured by `ATTESTATION_PROVIDER`.
+pub fn from_config(config: &WorkerConfig) -> Result<Box<dyn AttestationProvider>, BoxError> {
+    let provider: Box<dyn AttestationProvider> = match config.attestation_provider.as_str() {
+        "insecure" => Box::new(InsecureProvider),
+        "sev-guest" => Box::new(SevGuestProvider {
+            device: config.sev_guest_device.clone().into(),
+        }),
+        "tdx" => Box::new(TsmProvider {
+            report_dir: config.tsm_report_dir.clone().into(),
+        }),
+        "file" => {
+            let path = config
+                .attestation_quote_file
+                .as_deref()
+                .ok_or("ATTESTATION_PROVIDER=file requires ATTESTATION_QUOTE_FILE")?;
+            if config.noise_static_key.is_none() {
+                warn!("ATTESTATION_PROVIDER=file without NOISE_STATIC_KEY: the recorded quote can't commit to this run's key");
+            }
+            Box::new(FileProvider::load(path)?)
+        }
+        other => return Err(format!("unknown attestation provider: {}", other).into()),
+    };
+    info!("attestation provider: {} ({:?})", config.attestation_provider, provider.tee_type());
+    Ok(provider)
+}
+
+/// Build the bundle sent in response to `AttestationRequest`.
+pub fn bundle(provider: &dyn AttestationProvider, static_key: &[u8]) -> Result<AttestationBundle, BoxError> {
+    let Quote { quote, certs } = provider.quote(&report_data(static_key))?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(&quote);
+    hasher.update(static_key);
+    let binding_sig = hasher.finalize().to_vec();
+
+    Ok(AttestationBundle {
+        quote,
+        static_key: static_key.to_vec(),
+        binding_sig,
+        tee_type: provider.tee_type(),
+        certs,
+    })
+}
+
+// ── Insecure ──────────────────────────────────────────────────────
+
+/// No TEE: the "quote" is just the report data. Only accepted by an API
+/// running without `ATTESTATION_REQUIRE_TEE`.
+pub struct InsecureProvider;
+
+impl AttestationProvider for InsecureProvider {
+    fn tee_type(&self) -> TeeType {
+        TeeType::Insecure
+    }
+
+    fn quote(&self, report_data: &[u8; 64]) -> Result<Quote, BoxError> {
+        warn!("using insecure attestation (development mode)");
+        Ok(Quote {
+            quote: report_data.to_vec(),
+            certs: vec![],
+        })
+    }
+}
+
+// ── AMD SEV-SNP (/dev/sev-guest) ──────────────────────────────────
+
+/// SEV-SNP guest: requests an extended report (report + VCEK) from the
+/// PSP via `/dev/sev-guest`, falling back to a plain report when the host
+/// does not supply certificates.
+pub struct SevGuestProvider {
+    pub device: PathBuf,
+}
+
+impl AttestationProvider for SevGuestProvider {
+    fn tee_type(&self) -> TeeType {
+        TeeType::SevSnp
+    }
+
+    #[cfg(target_os = "linux")]
+    fn quote(&self, report_data: &[u8; 64]) -> Result<Quote, BoxError> {
+        sev_guest::get_report(&self.device, report_data)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn quote(&self, _report_data: &[u8; 64]) -> Result<Quote, BoxError> {
+        Err("/dev/sev-guest is only available on Linux".into())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sev_guest {
+    //! Minimal bindings for `include/uapi/linux/sev-guest.h`.
+
+    use super::{BoxError, Quote};
+    use std::fs::OpenOptions;
+    use std::os::fd::AsRawFd;
+    use std::path::Path;
+    use tracing::warn;
+
+    const REPORT_SIZE: usize = 1184;
+    /// MSG_REPORT_RSP header: status, report_size, reserved[24]
+    const RESPONSE_HEADER: usize = 32;
+    const CERTS_BUF_LEN: usize = 4 * 4096;
+    /// GUID of the VCEK entry in the extended report certificate table
+    const VCEK_GUID: [u8; 16] = [
+        0x63, 0xda, 0x75, 0x8d, 0xe6, 0x64, 0x45, 0x64, 0xad, 0xc5, 0xf4, 0xb9, 0x3b, 0xe8, 0xac, 0xcd,
+    ];
+
+    #[repr(C)]
+    struct ReportReq {
+        user_data: [u8; 64],
+        vmpl: u32,
+        rsvd: [u8; 28],
+    }
+
+    #[repr(C)]
+    struct ExtReportReq {
+        data: ReportReq,
+        certs_address: u64,
+        certs_len: u32,
+    }
+
+    #[repr(C)]
+    struct ReportResp {
+        data: [u8; 4000],
+    }
+
+    #[repr(C)]
+    struct GuestRequest {
+        msg_version: u8,
+        req_data: u64,
+        resp_data: u64,
+        exitinfo2: u64,
+    }
+
+    /// `_IOWR('S', nr, struct snp_guest_request_ioctl)`
+    const fn iowr(nr: u64) -> u64 {
+        (3 << 30) | ((std::mem::size_of::<GuestRequest>() as u64) << 16) | ((b'S' as u64) << 8) | nr
+    }
+    const SNP_GET_REPORT: u64 = iowr(0x0);
+    const SNP_GET_EXT_REPORT: u64 = iowr(0x2);
+
+    pub fn get_report(device: &Path, report_data: &[u8; 64]) -> Result<Quote, BoxError> {
+        let file = OpenOptions::new().read(true).write(true).open(device)?;
+        let fd = file.as_raw_fd();
+
+        let mut certs = vec![0u8; CERTS_BUF_LEN];
+        let mut ext = ExtReportReq {
+            data: ReportReq {
+                user_data: *report_data,
+                vmpl: 0,
+                rsvd: [0; 28],
+            },
+            certs_address: certs.as_mut_ptr() as u64,
+            certs_len: CERTS_BUF_LEN as u32,
+        };
+        let mut resp = ReportResp { data: [0; 4000] };
+
+        let vcek = match ioctl(fd, SNP_GET_EXT_REPORT, &mut ext as *mut _ as u64, &mut resp) {
+            Ok(()) => find_vcek(&certs),
+            Err(e) => {
+                warn!("SNP extended report failed ({}), requesting plain report", e);
+                let mut req = ext.data;
+                ioctl(fd, SNP_GET_REPORT, &mut req as *mut _ as u64, &mut resp)?;
+                None
+            }
+        };
+
+        let status = u32::from_le_bytes(resp.data[0..4].try_into()?);
+        let size = u32::from_le_bytes(resp.data[4..8].try_into()?) as usize;
+        if status != 0 || size != REPORT_SIZE {
+            return Err(format!("SNP report failed: status {:#x}, size {}", status, size).into());
+        }
+
+        Ok(Quote {
+            quote: resp.data[RESPONSE_HEADER..RESPONSE_HEADER + REPORT_SIZE].to_vec(),
+            certs: vcek.into_iter().collect(),
+        })
+    }
+
+    fn ioctl(fd: i32, request: u64, req_data: u64, resp: &mut ReportResp) -> Result<(), BoxError> {
+        let mut guest_req = GuestRequest {
+            msg_version: 1,
+            req_data,
+            resp_data: resp as *mut _ as u64,
+            exitinfo2: 0,
+        };
+        // SAFETY: req_data and resp point to live, correctly laid out
+        // structs for the duration of the call.
+        let rc = unsafe { libc::ioctl(fd, request as _, &mut guest_req as *mut GuestRequest) };
+        if rc < 0 {
+            return Err(format!(
+                "{} (firmware error {:#x})",
+                std::io::Error::last_os_error(),
+                guest_req.exitinfo2
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Certificate table: `{ guid[16], offset: u32, length: u32 }` entries,
+    /// terminated by an all-zero entry. Offsets are from the table start.
+    pub(super) fn find_vcek(table: &[u8]) -> Option<Vec<u8>> {
+        table
+            .chunks_exact(24)
+            .take_while(|entry| entry.iter().any(|&b| b != 0))
+            .find(|entry| entry[..16] == VCEK_GUID)
+            .and_then(|entry| {
+                let offset = u32::from_le_bytes(entry[16..20].try_into().ok()?) as usize;
+                let length = u32::from_le_bytes(entry[20..24].try_into().ok()?) as usize;
+                table.get(offset..offset.checked_add(length)?).map(|c| c.to_vec())
+            })
+    }
+}
+
+// ── Intel TDX (configfs-tsm) ──────────────────────────────────────
+
+/// TDX guest: writes the report data to a configfs-tsm report entry
+/// (`/sys/kernel/config/tsm/report/<name>/inblob`) and reads the quote
+/// back from `outblob`. Requires a quote generation service on the host.
+pub struct TsmProvider {
+    pub report_dir: PathBuf,
+}
+
+impl AttestationProvider for TsmProvider {
+    fn tee_type(&self) -> TeeType {
+        TeeType::Tdx
+    }
+
+    fn quote(&self, report_data: &[u8; 64]) -> Result<Quote, BoxError> {
+        let entry = self.report_dir.join(format!("sonotxt-{}", std::process::id()));
+        std::fs::create_dir(&entry)?;
+
+        let result = (|| -> Result<Vec<u8>, BoxError> {
+            let provider = std::fs::read_to_string(entry.join("provider"))?;
+            if provider.trim() != "tdx_guest" {
+                return Err(format!("configfs-tsm provider is {}, expected tdx_guest", provider.trim()).into());
+            }
+            std::fs::write(entry.join("inblob"), report_data)?;
+            Ok(std::fs::read(entry.join("outblob"))?)
+        })();
+
+        // configfs entries are removed with rmdir even though they hold attributes
+        if let Err(e) = std::fs::remove_dir(&entry) {
+            warn!("failed to remove {}: {}", entry.display(), e);
+        }
+
+        Ok(Quote {
+            quote: result?,
+            certs: vec![],
+        })
+    }
+}
+
+// ── Recorded quote (testing) ──────────────────────────────────────
+
+/// A recorded quote on disk (JSON). The quote's report data is fixed, so
+/// the API only accepts it if the worker's Noise key (`NOISE_STATIC_KEY`)
+/// is the one it was recorded for.
+#[derive(Deserialize)]
+struct RecordedQuote {
+    tee_type: TeeType,
+    /// hex
+    quote: String,
+    /// hex DER certificates
+    #[serde(default)]
+    certs: Vec<String>,
+}
+
+/// Replays a recorded quote regardless of the requested report data.
+pub struct FileProvider {
+    tee_type: TeeType,
+    quote: Vec<u8>,
+    certs: Vec<Vec<u8>>,
+}
+
+impl FileProvider {
+    pub fn load(path: &str) -> Result<Self, BoxError> {
+        let recorded: RecordedQuote = serde_json::from_slice(&std::fs::read(path)?)?;
+        Ok(Self {
+            tee_type: recorded.tee_type,
+            quote: hex::decode(recorded.quote.trim())?,
+            certs: recorded
+                .certs
+                .iter()
+                .map(|c| hex::decode(c.trim()))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Whether the recorded quote carries `report_data`, i.e. was recorded
+    /// for the Noise key now in use.
+    fn commits_to(&self, report_data: &[u8; 64]) -> bool {
+        let recorded = match self.tee_type {
+            TeeType::SevSnp => SnpReport::parse(&self.quote).ok().map(|r| r.report_data),
+            TeeType::Tdx => TdQuote::parse(&self.quote).ok().map(|q| q.report_data),
+            TeeType::Insecure => self.quote.as_slice().try_into().ok(),
+        };
+        recorded.as_ref() == Some(report_data)
+    }
+}
+
+impl AttestationProvider for FileProvider {
+    fn tee_type(&self) -> TeeType {
+        self.tee_type
+    }
+
+    fn quote(&self, report_data: &[u8; 64]) -> Result<Quote, BoxError> {
+        warn!("replaying recorded {:?} quote", self.tee_type);
+        if !self.commits_to(report_data) {
+            warn!("recorded quote was made for another Noise key; set NOISE_STATIC_KEY to that key or it won't verify");
+        }
+        Ok(Quote {
+            quote: self.quote.clone(),
+            certs: self.certs.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn config(args: &[&str]) -> WorkerConfig {
+        WorkerConfig::parse_from(std::iter::once("sonotxt-worker").chain(args.iter().copied()))
+    }
+
+    /// Write a recording to a file unique to this test
+    fn recording(name: &str, json: serde_json::Value) -> String {
+        let path = std::env::temp_dir().join(format!("sonotxt-quote-{}-{}.json", std::process::id(), name));
+        std::fs::write(&path, json.to_string()).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn static_key() -> (Vec<u8>, Vec<u8>) {
+        let private = vec![7u8; 32];
+        let public = sonotxt_core::noise::public_key(&private).unwrap();
+        (private, public)
+    }
+
+    #[test]
+    fn test_from_config_selects_provider() {
+        for (name, tee_type) in [("insecure", TeeType::Insecure), ("sev-guest", TeeType::SevSnp), ("tdx", TeeType::Tdx)] {
+            let provider = from_config(&config(&["--attestation-provider", name])).unwrap();
+            assert_eq!(provider.tee_type(), tee_type, "{}", name);
+        }
+
+        let err = from_config(&config(&["--attestation-provider", "file"])).err().unwrap();
+        assert!(err.to_string().contains("ATTESTATION_QUOTE_FILE"));
+        assert!(from_config(&config(&["--attestation-provider", "sgx"])).is_err());
+    }
+
+    #[test]
+    fn test_file_provider_replays_recording() {
+        let (private, public) = static_key();
+        let path = recording(
+            "replay",
+            serde_json::json!({
+                "tee_type": "Insecure",
+                "quote": hex::encode(report_data(&public)),
+                "certs": ["30ff"],
+            }),
+        );
+        let config = config(&[
+            "--attestation-provider", "file",
+            "--attestation-quote-file", &path,
+            "--noise-static-key", &hex::encode(&private),
+        ]);
+        let provider = from_config(&config).unwrap();
+        assert_eq!(provider.tee_type(), TeeType::Insecure);
+
+        // The same quote whatever is asked for
+        let bundle = bundle(provider.as_ref(), &public).unwrap();
+        assert_eq!(bundle.quote, report_data(&public));
+        assert_eq!(bundle.certs, vec![vec![0x30, 0xff]]);
+        assert_eq!(bundle.static_key, public);
+        assert_eq!(provider.quote(&[0; 64]).unwrap().quote, bundle.quote);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_file_provider_checks_recorded_key() {
+        let (_, public) = static_key();
+        let mut report = vec![0u8; 1184];
+        report[0x50..0x90].copy_from_slice(&report_data(&public));
+        let path = recording(
+            "snp",
+            serde_json::json!({ "tee_type": "SevSnp", "quote": hex::encode(&report) }),
+        );
+        let provider = FileProvider::load(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(provider.tee_type(), TeeType::SevSnp);
+        assert!(provider.certs.is_empty());
+        assert!(provider.commits_to(&report_data(&public)));
+        assert!(!provider.commits_to(&report_data(b"another key")));
+    }
+
+    #[test]
+    fn test_file_provider_rejects_bad_recording() {
+        assert!(FileProvider::load("/nonexistent/quote.json").is_err());
+
+        let path = recording("bad-hex", serde_json::json!({ "tee_type": "Tdx", "quote": "not hex" }));
+        assert!(FileProvider::load(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+
+        let path = recording("bad-tee", serde_json::json!({ "tee_type": "Sgx", "quote": "00" }));
+        assert!(FileProvider::load(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_find_vcek() {
+        const ASK_GUID: [u8; 16] = [0x4a, 0xb7, 0xb3, 0x79, 0xbb, 0xac, 0x4f, 0xe4, 0xa0, 0x2f, 0x05, 0xae, 0xf3, 0x27, 0xc7, 0x82];
+        const VCEK_GUID: [u8; 16] = [0x63, 0xda, 0x75, 0x8d, 0xe6, 0x64, 0x45, 0x64, 0xad, 0xc5, 0xf4, 0xb9, 0x3b, 0xe8, 0xac, 0xcd];
+
+        fn entry(guid: [u8; 16], offset: u32, length: u32) -> Vec<u8> {
+            [guid.as_slice(), &offset.to_le_bytes(), &length.to_le_bytes()].concat()
+        }
+
+        // ASK, VCEK, terminator, then the certificates
+        let table = [entry(ASK_GUID, 72, 3), entry(VCEK_GUID, 75, 4), vec![0; 24], b"askvcek".to_vec()].concat();
+        assert_eq!(sev_guest::find_vcek(&table), Some(b"vcek".to_vec()));
+
+        // No VCEK entry
+        let table = [entry(ASK_GUID, 48, 3), vec![0; 24], b"ask".to_vec()].concat();
+        assert_eq!(sev_guest::find_vcek(&table), None);
+
+        // Entries after the terminator don't count
+        let table = [vec![0; 24], entry(VCEK_GUID, 48, 4), b"vcek".to_vec()].concat();
+        assert_eq!(sev_guest::find_vcek(&table), None);
+
+        // Out of bounds, or overflowing
+        let table = [entry(VCEK_GUID, 48, 100), vec![0; 24]].concat();
+        assert_eq!(sev_guest::find_vcek(&table), None);
+        let table = [entry(VCEK_GUID, u32::MAX, u32::MAX), vec![0; 24]].concat();
+        assert_eq!(sev_guest::find_vcek(&table), None);
+    }
+}
@@ -1,4 +1,5 @@
 use clap::Parser;
+use sonotxt_core::protocol::WorkerCapabilities;
 
 #[derive(Parser, Debug, Clone)]
 #[command(name = "sonotxt-worker")]
@@ -7,7 +8,7 @@ pub struct WorkerConfig {
     #[arg(long, env = "RUST_LOG", default_value = "info")]
     pub log_level: String,
 
-    /// QUIC server port (Noise_NK encrypted transport for API connections)
+    /// QUIC server port (Noise encrypted transport for API connections)
     #[arg(long, env = "QUIC_PORT", default_value = "4433")]
     pub quic_port: u16,
 
@@ -25,6 +26,70 @@ pub struct WorkerConfig {
     /// API key for speech service (Authorization: Bearer <key>)
     #[arg(long, env = "SPEECH_API_KEY")]
     pub speech_api_key: Option<String>,
+
+    /// Attestation provider: insecure, sev-guest, tdx, or file
+    #[arg(long, env = "ATTESTATION_PROVIDER", default_value = "insecure")]
+    pub attestation_provider: String,
+
+    /// SEV-SNP guest device (sev-guest provider)
+    #[arg(long, env = "SEV_GUEST_DEVICE", default_value = "/dev/sev-guest")]
+    pub sev_guest_device: String,
+
+    /// configfs-tsm report directory (tdx provider)
+    #[arg(long, env = "TSM_REPORT_DIR", default_value = "/sys/kernel/config/tsm/report")]
+    pub tsm_report_dir: String,
+
+    /// Recorded quote JSON to replay (file provider)
+    #[arg(long, env = "ATTESTATION_QUOTE_FILE")]
+    pub attestation_quote_file: Option<String>,
+
+    /// Noise handshake: nk (any client), kk or xk (only NOISE_AUTHORIZED_KEYS)
+    #[arg(long, env = "NOISE_PATTERN", default_value = "nk")]
+    pub noise_pattern: sonotxt_core::noise::HandshakePattern,
+
+    /// Hex X25519 static private key for Noise; a fresh key is generated on
+    /// every start if unset. Needed by the file provider, whose recorded
+    /// quote only commits to the key it was recorded with
+    #[arg(long, env = "NOISE_STATIC_KEY")]
+    pub noise_static_key: Option<String>,
+
+    /// Comma-separated hex X25519 public keys of APIs allowed to connect (kk/xk)
+    #[arg(long, env = "NOISE_AUTHORIZED_KEYS", default_value = "")]
+    pub noise_authorized_keys: String,
+
+    /// TTS engines served by the local speech service (comma-separated)
+    #[arg(long, env = "WORKER_ENGINES", default_value = "qwen")]
+    pub engines: String,
+
+    /// Voices served (comma-separated); empty advertises any voice
+    #[arg(long, env = "WORKER_VOICES", default_value = "")]
+    pub voices: String,
+
+    /// Languages served (comma-separated); empty advertises any language
+    #[arg(long, env = "WORKER_LANGUAGES", default_value = "")]
+    pub languages: String,
+
+    /// Concurrent inference requests advertised to the API
+    #[arg(long, env = "WORKER_MAX_CONCURRENCY", default_value = "4")]
+    pub max_concurrency: u32,
+
+    /// Largest encrypted TTS/ASR request frame accepted from the API (bytes)
+    #[arg(long, env = "QUIC_MAX_REQUEST_BYTES", default_value = "67108864")]
+    pub quic_max_request_bytes: usize,
+
+    /// API reverse-connect listener (host:port) to dial, for workers the API
+    /// can't reach (NAT); the inbound QUIC server keeps running as well.
+    /// Requires NOISE_PATTERN kk or xk, so the dialed API is authenticated
+    #[arg(long, env = "API_QUIC_ADDR")]
+    pub api_quic_addr: Option<String>,
+
+    /// Enrollment token the API's listener expects (API_QUIC_ADDR)
+    #[arg(long, env = "ENROLL_TOKEN")]
+    pub enroll_token: Option<String>,
+
+    /// Name this worker enrolls under (API_QUIC_ADDR)
+    #[arg(long, env = "WORKER_NAME")]
+    pub worker_name: Option<String>,
 }
 
 impl WorkerConfig {
@@ -32,4 +97,22 @@ impl WorkerConfig {
         dotenvy::dotenv().ok();
         Self::parse()
     }
+
+    /// Capabilities advertised to the API.
+    pub fn capabilities(&self) -> WorkerCapabilities {
+        WorkerCapabilities {
+            engines: split_list(&self.engines),
+            voices: split_list(&self.voices),
+            languages: split_list(&self.languages),
+            max_concurrency: self.max_concurrency.max(1),
+        }
+    }
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
 }
@@ -0,0 +1,153 @@
+//! In-flight request tracking, so the API can cancel work it no longer
+//! needs (client disconnected, hedged request lost).
+//!
+//! One registry per QUIC connection: an API can only cancel its own
+//! requests. A `Cancel` travels on its own stream and may overtake the
+//! request it refers to, so unknown ids are remembered for a while and the
+//! request is cancelled as soon as it registers.
+//!
+//! Registrations also feed the worker-wide count of running requests
+//! reported in health checks.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// Cancellations for requests not (yet) seen, oldest dropped first.
+const EARLY_CANCELS: usize = 256;
+
+#[derive(Default)]
+pub struct InFlight {
+    inner: Mutex<Inner>,
+    /// Shared by every connection's registry
+    active: Arc<AtomicU32>,
+}
+
+#[derive(Default)]
+struct Inner {
+    requests: HashMap<[u8; 16], Arc<Notify>>,
+    early: VecDeque<[u8; 16]>,
+}
+
+impl InFlight {
+    /// Registry counting its requests into the worker-wide `active`.
+    pub fn new(active: Arc<AtomicU32>) -> Self {
+        Self {
+            inner: Mutex::default(),
+            active,
+        }
+    }
+
+    /// Track `request_id` until the returned registration is dropped.
+    pub fn register(self: &Arc<Self>, request_id: [u8; 16]) -> Registration {
+        let notify = Arc::new(Notify::new());
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(pos) = inner.early.iter().position(|id| *id == request_id) {
+            inner.early.remove(pos);
+            notify.notify_one();
+        }
+        inner.requests.insert(request_id, notify.clone());
+        self.active.fetch_add(1, Ordering::Relaxed);
+        Registration {
+            inflight: self.clone(),
+            request_id,
+            notify,
+        }
+    }
+
+    /// Cancel `request_id`. Returns false if it isn't running (yet).
+    pub fn cancel(&self, request_id: &[u8; 16]) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(notify) = inner.requests.get(request_id) {
+            notify.notify_one();
+            return true;
+        }
+        if inner.early.len() == EARLY_CANCELS {
+            inner.early.pop_front();
+        }
+        inner.early.push_back(*request_id);
+        false
+    }
+}
+
+/// A tracked request. Dropping it stops tracking.
+pub struct Registration {
+    inflight: Arc<InFlight>,
+    request_id: [u8; 16],
+    notify: Arc<Notify>,
+}
+
+impl Registration {
+    /// Run `fut` unless the request is cancelled first, in which case `fut`
+    /// is dropped (aborting e.g. the local speech call) and None returned.
+    /// Once cancelled, every later call returns None immediately.
+    pub async fn run<F: Future>(&self, fut: F) -> Option<F::Output> {
+        tokio::select! {
+            biased;
+            _ = self.notify.notified() => {
+                // Keep the request cancelled for later segments
+                self.notify.notify_one();
+                None
+            }
+            out = fut => Some(out),
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.inflight.active.fetch_sub(1, Ordering::Relaxed);
+        let mut inner = self.inflight.inner.lock().unwrap();
+        // A reused id may have re-registered; only remove our own entry
+        if inner
+            .requests
+            .get(&self.request_id)
+            .is_some_and(|n| Arc::ptr_eq(n, &self.notify))
+        {
+            inner.requests.remove(&self.request_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_aborts_running_request() {
+        let inflight = Arc::new(InFlight::default());
+        let registration = inflight.register([1; 16]);
+
+        let canceller = inflight.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            assert!(canceller.cancel(&[1; 16]));
+        });
+
+        let slow = tokio::time::sleep(Duration::from_secs(60));
+        assert!(registration.run(slow).await.is_none());
+        // Stays cancelled for the next segment of a stream
+        assert!(registration.run(async { 1 }).await.is_none());
+
+        drop(registration);
+        assert!(inflight.inner.lock().unwrap().requests.is_empty());
+        assert_eq!(inflight.active.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_before_request_arrives() {
+        let inflight = Arc::new(InFlight::default());
+        assert!(!inflight.cancel(&[2; 16]));
+
+        let registration = inflight.register([2; 16]);
+        assert!(registration.run(async { 1 }).await.is_none());
+
+        // Other requests are unaffected
+        let other = inflight.register([3; 16]);
+        assert_eq!(other.run(async { 1 }).await, Some(1));
+        assert_eq!(inflight.active.load(Ordering::Relaxed), 2);
+    }
+}
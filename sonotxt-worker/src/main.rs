@@ -1,10 +1,14 @@
+mod attestation;
 mod config;
 mod health;
+mod inflight;
 mod processor;
 mod quic;
 
 use config::WorkerConfig;
 use processor::WorkerState;
+use sonotxt_core::noise::HandshakePattern;
+use sonotxt_core::WorkerEnrollment;
 use std::sync::Arc;
 use tracing::{error, info};
 
@@ -31,9 +35,38 @@ async fn main() {
     let health_port = config.health_port;
     let quic_port = config.quic_port;
 
+    let attestation = match attestation::from_config(&config) {
+        Ok(provider) => provider,
+        Err(e) => {
+            error!("attestation provider init failed: {}", e);
+            return;
+        }
+    };
+
+    info!("capabilities: {:?}", config.capabilities());
+
+    // Reverse connect: dial the API's listener and enroll there
+    let reverse = match config.api_quic_addr.clone() {
+        Some(api) => {
+            let (Some(name), Some(token)) = (config.worker_name.clone(), config.enroll_token.clone()) else {
+                error!("API_QUIC_ADDR needs WORKER_NAME and ENROLL_TOKEN");
+                return;
+            };
+            if config.noise_pattern == HandshakePattern::Nk {
+                // nk leaves the dialed peer unauthenticated, so anyone able
+                // to intercept the connection could collect the token
+                error!("API_QUIC_ADDR needs NOISE_PATTERN=kk or xk to authenticate the API");
+                return;
+            }
+            Some((api, WorkerEnrollment { name, token }))
+        }
+        None => None,
+    };
+
     let state = Arc::new(WorkerState {
         config,
         http,
+        active: Arc::default(),
     });
 
     // Spawn HTTP health server (for legacy monitoring / vast.ai health checks)
@@ -53,8 +86,14 @@ async fn main() {
     let addr: std::net::SocketAddr = format!("0.0.0.0:{}", quic_port).parse().unwrap();
     info!("sonotxt-worker starting (QUIC :{}, health :{})", quic_port, health_port);
 
-    match quic::QuicWorkerServer::new(state) {
+    match quic::QuicWorkerServer::new(state, attestation.as_ref()) {
         Ok(server) => {
+            let server = Arc::new(server);
+            if let Some((api, enrollment)) = reverse {
+                info!("reverse connect to API at {} as {}", api, enrollment.name);
+                let dialer = server.clone();
+                tokio::spawn(async move { dialer.dial(&api, enrollment).await });
+            }
             if let Err(e) = server.run(addr).await {
                 error!("QUIC server error: {:?}", e);
             }
@@ -1,4 +1,8 @@
-use sonotxt_core::protocol::{EncryptedTtsRequest, EncryptedTtsResponse};
+use sonotxt_core::protocol::{EncryptedLlmRequest, EncryptedTtsRequest, EncryptedTtsResponse};
+use sonotxt_core::{LlmMessage, SentenceParser};
+use std::sync::atomic::AtomicU32;
+use std::sync::Arc;
+use tokio::sync::mpsc;
 use tracing::{error, info};
 
 use crate::config::WorkerConfig;
@@ -6,15 +10,24 @@ use crate::config::WorkerConfig;
 pub struct WorkerState {
     pub config: WorkerConfig,
     pub http: reqwest::Client,
+    /// Inference requests running, across all connections
+    pub active: Arc<AtomicU32>,
 }
 
 /// Run TTS on local python service. Text exists only in memory.
 pub async fn run_tts(state: &WorkerState, request: &EncryptedTtsRequest) -> EncryptedTtsResponse {
     #[derive(serde::Serialize)]
-    struct SpeechReq {
-        text: String,
-        speaker: String,
-        language: String,
+    struct SpeechReq<'a> {
+        #[serde(skip_serializing_if = "str::is_empty")]
+        engine: &'a str,
+        text: &'a str,
+        speaker: &'a str,
+        language: &'a str,
+        speed: f32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        instruct: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cfg_scale: Option<f32>,
     }
 
     let start = std::time::Instant::now();
@@ -30,9 +43,13 @@ pub async fn run_tts(state: &WorkerState, request: &EncryptedTtsRequest) -> Encr
 
     let result = req
         .json(&SpeechReq {
-            text: request.text.clone(),
-            speaker: request.voice.clone(),
-            language: request.language.clone(),
+            engine: &request.engine,
+            text: &request.text,
+            speaker: &request.voice,
+            language: &request.language,
+            speed: request.speed,
+            instruct: request.instruct.as_deref(),
+            cfg_scale: request.cfg_scale,
         })
         .timeout(std::time::Duration::from_secs(180))
         .send()
@@ -101,6 +118,167 @@ pub async fn run_asr(state: &WorkerState, audio_base64: &str) -> Result<String,
     Ok(resp.text)
 }
 
+/// Run LLM chat on the local service, sending each sentence of the reply
+/// to `sentences` as it is produced. Returns the full reply and the token
+/// count (only reported by the non-streaming endpoint).
+pub async fn run_llm(
+    state: &WorkerState,
+    request: &EncryptedLlmRequest,
+    sentences: mpsc::Sender<String>,
+) -> Result<(String, u32), String> {
+    #[derive(serde::Serialize)]
+    struct ChatReq<'a> { messages: &'a [LlmMessage], max_tokens: u32, temperature: f64 }
+    #[derive(serde::Deserialize)]
+    struct ChatResp { sentences: Vec<String>, full_response: String, tokens: Option<u32> }
+
+    let endpoint = if request.stream { "chat_stream" } else { "chat_sentences" };
+    let mut response = state
+        .http
+        .post(format!("{}/{}", state.config.llm_url, endpoint))
+        .json(&ChatReq {
+            messages: &request.messages,
+            max_tokens: request.max_tokens,
+            temperature: request.temperature,
+        })
+        .timeout(std::time::Duration::from_secs(120))
+        .send()
+        .await
+        .map_err(|e| format!("llm request: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("llm {}: {}", status, body));
+    }
+
+    if !request.stream {
+        let resp: ChatResp = response.json().await.map_err(|e| format!("llm json: {}", e))?;
+        for sentence in resp.sentences {
+            let _ = sentences.send(sentence).await;
+        }
+        return Ok((resp.full_response, resp.tokens.unwrap_or(0)));
+    }
+
+    let mut parser = SentenceParser::default();
+    let mut full = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| format!("llm stream: {}", e))? {
+        for sentence in parser.push(&chunk) {
+            full.push(sentence.clone());
+            let _ = sentences.send(sentence).await;
+        }
+    }
+    Ok((full.join(" "), 0))
+}
+
+/// First streamed segment is kept short so playback starts quickly.
+const FIRST_SEGMENT_CHARS: usize = 160;
+/// Later segments are packed up to this size on sentence boundaries.
+const SEGMENT_CHARS: usize = 600;
+
+/// Split text into synthesis segments for streaming TTS.
+///
+/// Breaks on sentence terminators and packs whole sentences into segments;
+/// sentences longer than the limit are broken on word boundaries, and words
+/// longer than the limit are cut. Limits count chars, not bytes, so CJK
+/// text gets segments of the same length as Latin text. Pieces are joined
+/// with a space only where the text had whitespace between them, so CJK
+/// sentences and cut words come out as written.
+pub fn split_text(text: &str) -> Vec<String> {
+    let mut segments: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    // Whether the text had whitespace after what `current` ends with
+    let mut spaced = false;
+
+    for (sentence, sentence_spaced) in split_sentences(text) {
+        let limit = if segments.is_empty() { FIRST_SEGMENT_CHARS } else { SEGMENT_CHARS };
+        let pieces = split_words(&sentence, limit);
+        let last = pieces.len() - 1;
+        for (i, (piece, word_end)) in pieces.into_iter().enumerate() {
+            let limit = if segments.is_empty() { FIRST_SEGMENT_CHARS } else { SEGMENT_CHARS };
+            let piece_chars = piece.chars().count();
+            let separator = usize::from(current_chars > 0 && spaced);
+            if current_chars > 0 && current_chars + separator + piece_chars > limit {
+                segments.push(std::mem::take(&mut current));
+                current_chars = 0;
+            } else if separator > 0 {
+                current.push(' ');
+                current_chars += 1;
+            }
+            current.push_str(&piece);
+            current_chars += piece_chars;
+            spaced = if i == last { sentence_spaced } else { word_end };
+        }
+    }
+
+    if !current.is_empty() {
+        segments.push(current);
+    }
+    segments
+}
+
+/// Sentences, each with whether whitespace followed it in `text`.
+fn split_sentences(text: &str) -> Vec<(String, bool)> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        current.push(c);
+        let terminator = matches!(c, '.' | '!' | '?' | '\n' | '。' | '！' | '？');
+        // CJK terminators end a sentence without a space after them
+        let boundary = matches!(c, '。' | '！' | '？') || chars.peek().is_none_or(|n| n.is_whitespace());
+        if terminator && boundary {
+            let trimmed = current.trim();
+            if !trimmed.is_empty() {
+                let spaced = c.is_whitespace() || chars.peek().is_some_and(|n| n.is_whitespace());
+                sentences.push((trimmed.to_string(), spaced));
+            }
+            current.clear();
+        }
+    }
+
+    let trimmed = current.trim();
+    if !trimmed.is_empty() {
+        sentences.push((trimmed.to_string(), false));
+    }
+    sentences
+}
+
+/// Pieces of at most `limit` chars, each with whether it ends at the end
+/// of a word rather than inside one that was cut.
+fn split_words(sentence: &str, limit: usize) -> Vec<(String, bool)> {
+    if sentence.chars().count() <= limit {
+        return vec![(sentence.to_string(), true)];
+    }
+
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    let mut word_end = true;
+    for word in sentence.split_whitespace() {
+        let chars: Vec<char> = word.chars().collect();
+        let parts = chars.len().div_ceil(limit);
+        for (i, part) in chars.chunks(limit).enumerate() {
+            let separator = usize::from(current_chars > 0 && word_end);
+            if current_chars > 0 && current_chars + separator + part.len() > limit {
+                pieces.push((std::mem::take(&mut current), word_end));
+                current_chars = 0;
+            } else if separator > 0 {
+                current.push(' ');
+                current_chars += 1;
+            }
+            current.extend(part);
+            current_chars += part.len();
+            word_end = i + 1 == parts;
+        }
+    }
+    if !current.is_empty() {
+        pieces.push((current, word_end));
+    }
+    pieces
+}
+
 fn parse_wav_duration(wav_data: &[u8]) -> f64 {
     if wav_data.len() > 44 {
         let sr = u32::from_le_bytes([wav_data[24], wav_data[25], wav_data[26], wav_data[27]]);
@@ -120,3 +298,74 @@ fn err_response(request_id: [u8; 16], error: String) -> EncryptedTtsResponse {
         error: Some(error),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_segment_is_short() {
+        let text = "This sentence is about forty chars long. ".repeat(20);
+        let segments = split_text(&text);
+        assert!(segments.len() > 1);
+        assert!(segments[0].chars().count() <= FIRST_SEGMENT_CHARS);
+        assert!(segments[1..].iter().all(|s| s.chars().count() <= SEGMENT_CHARS));
+        assert!(segments[1].chars().count() > FIRST_SEGMENT_CHARS);
+    }
+
+    #[test]
+    fn test_splits_on_sentence_boundaries() {
+        let first = "a".repeat(100) + ".";
+        let second = "b".repeat(100) + "!";
+        let third = "Is this the end?";
+        let segments = split_text(&format!("{} {} {}", first, second, third));
+        assert_eq!(segments, vec![first, format!("{} {}", second, third)]);
+
+        // A period inside a word isn't a boundary
+        assert_eq!(
+            split_sentences("Version 1.2 is out. Upgrade now"),
+            vec![("Version 1.2 is out.".to_string(), true), ("Upgrade now".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn test_long_word_is_cut() {
+        let word = "x".repeat(1000);
+        let text = format!("start {} end", word);
+        let segments = split_text(&text);
+        assert_eq!(segments.concat().replace(' ', ""), format!("start{}end", word));
+        // No spaces that weren't in the text, e.g. inside the cut word
+        assert!(segments.iter().all(|s| text.contains(s.as_str())));
+        assert!(segments[0].chars().count() <= FIRST_SEGMENT_CHARS);
+        assert!(segments.iter().all(|s| s.chars().count() <= SEGMENT_CHARS));
+    }
+
+    #[test]
+    fn test_multibyte_text_counts_chars() {
+        // 3 bytes per char: byte lengths would cut segments to a third
+        let sentence = "今天天气很好我们去公园散步吧。";
+        let text = sentence.repeat(60);
+        let segments = split_text(&text);
+        assert_eq!(segments.concat(), text);
+        assert!(segments[0].chars().count() <= FIRST_SEGMENT_CHARS);
+        assert!(segments[0].chars().count() > FIRST_SEGMENT_CHARS - sentence.chars().count() - 1);
+        for segment in &segments[1..segments.len() - 1] {
+            let chars = segment.chars().count();
+            assert!(chars <= SEGMENT_CHARS && chars > SEGMENT_CHARS - sentence.chars().count() - 1);
+        }
+        // Whole sentences only
+        assert!(segments.iter().all(|s| s.ends_with('。')));
+    }
+
+    #[test]
+    fn test_spaces_only_where_written() {
+        let text = "你好。Hello there. 再见！Bye.";
+        assert_eq!(split_text(text), vec![text]);
+
+        // A CJK sentence too long for one segment is cut without spaces
+        let text = "字".repeat(400);
+        let segments = split_text(&text);
+        assert_eq!(segments.concat(), text);
+        assert_eq!(segments[0].chars().count(), FIRST_SEGMENT_CHARS);
+    }
+}
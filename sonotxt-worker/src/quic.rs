@@ -1,54 +1,94 @@
 //! QUIC server — the worker's only external interface.
 //!
-//! The API connects here over QUIC, establishes a Noise_NK session,
-//! and sends encrypted TTS/ASR requests. Results come back encrypted
-//! over the same channel. No DB, no Redis — pure service.
+//! The API connects here over QUIC, establishes a Noise session (NK, or
+//! KK/XK restricted to authorized API keys),
+//! and sends encrypted TTS/ASR/LLM requests. Results come back encrypted
+//! over the same channel — either as one response or, for streaming TTS,
+//! as a sequence of encrypted chunks on the request's stream. The API
+//! can cancel a request it no longer needs, aborting the local call.
+//! No DB, no Redis — pure service.
+//!
+//! Workers behind NAT dial the API instead (`API_QUIC_ADDR`). The dialed
+//! connection is served exactly like an inbound one; the API additionally
+//! asks for the worker's name and enrollment token, which are only ever
+//! sent inside a KK/XK Noise session, after the API has proven its key.
 
 use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use std::time::Instant;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, RwLock};
 use tracing::{error, info, warn};
 
-use sonotxt_core::noise::NoiseServer;
+use sonotxt_core::noise::{self, NoiseServer, StreamDecryptor};
 use sonotxt_core::protocol::{
-    AttestationBundle, EncryptedAsrRequest, EncryptedAsrResponse,
-    EncryptedTtsRequest, Message, TeeType, WorkerHealth,
+    AttestationBundle, EncryptedAsrRequest, EncryptedAsrResponse, EncryptedLlmRequest,
+    EncryptedTtsRequest, LlmEvent, Message, StreamChunk, WireFormat, WorkerEnrollment, WorkerHealth,
 };
-use sonotxt_core::quic::{read_message, write_message};
+use sonotxt_core::quic::{read_frame, wire_format, write_message, Frame, FrameLimits};
 
+use crate::attestation::AttestationProvider;
+use crate::inflight::InFlight;
 use crate::processor::WorkerState;
 
+/// Variants whose payload is decrypted as it arrives instead of being
+/// buffered whole (large audio uploads).
+const STREAMED: &[&str] = &["EncryptedAsrRequest"];
+
+/// First redial delay after losing the API; doubles up to the max.
+const DIAL_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const DIAL_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
 pub struct QuicWorkerServer {
-    noise: Arc<RwLock<NoiseServer>>,
+    noise: Arc<NoiseServer>,
+    /// Quote bound to the Noise static key, generated once at startup
+    attestation: Arc<AttestationBundle>,
     state: Arc<WorkerState>,
+    limits: FrameLimits,
     start_time: Instant,
 }
 
 impl QuicWorkerServer {
-    pub fn new(state: Arc<WorkerState>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let noise = NoiseServer::new()?;
+    pub fn new(
+        state: Arc<WorkerState>,
+        provider: &dyn AttestationProvider,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let authorized_keys = noise::parse_keys(&state.config.noise_authorized_keys)?;
+        let noise = match state.config.noise_static_key.as_deref() {
+            Some(key) => NoiseServer::with_static_key(state.config.noise_pattern, &hex::decode(key.trim())?, authorized_keys)?,
+            None => NoiseServer::with_authorized_keys(state.config.noise_pattern, authorized_keys)?,
+        };
+        info!("Noise pattern: {}", noise.pattern());
+        let attestation = crate::attestation::bundle(provider, noise.static_public_key())?;
+        info!("attestation ready: {:?}, {} byte quote", attestation.tee_type, attestation.quote.len());
         Ok(Self {
-            noise: Arc::new(RwLock::new(noise)),
+            noise: Arc::new(noise),
+            attestation: Arc::new(attestation),
+            limits: FrameLimits {
+                request: state.config.quic_max_request_bytes,
+                ..FrameLimits::default()
+            },
             state,
             start_time: Instant::now(),
         })
     }
 
-    pub async fn run(self, addr: SocketAddr) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn run(&self, addr: SocketAddr) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let endpoint = sonotxt_core::quic::server_endpoint(addr)?;
         info!("QUIC server listening on {}", addr);
 
         while let Some(incoming) = endpoint.accept().await {
             let noise = self.noise.clone();
+            let attestation = self.attestation.clone();
             let state = self.state.clone();
+            let limits = self.limits;
             let start_time = self.start_time;
 
             tokio::spawn(async move {
                 match incoming.await {
                     Ok(conn) => {
                         info!("QUIC connection from {}", conn.remote_address());
-                        if let Err(e) = handle_connection(conn, noise, state, start_time).await {
+                        if let Err(e) = handle_connection(conn, noise, attestation, state, limits, start_time, None).await {
                             error!("QUIC connection error: {:?}", e);
                         }
                     }
@@ -59,26 +99,99 @@ impl QuicWorkerServer {
 
         Ok(())
     }
+
+    /// Reverse connect: keep a connection to the API's listener at `api`
+    /// (host:port) and serve it, redialing with backoff when it drops.
+    pub async fn dial(&self, api: &str, enrollment: WorkerEnrollment) {
+        let enrollment = Arc::new(enrollment);
+        let mut backoff = DIAL_BACKOFF_MIN;
+
+        loop {
+            match self.dial_once(api, enrollment.clone()).await {
+                Ok(served) => {
+                    info!("API connection {} closed after {}s", api, served.as_secs());
+                    // A connection the API drops right away (e.g. a rejected
+                    // enrollment) counts as a failed attempt
+                    if served >= DIAL_BACKOFF_MAX {
+                        backoff = DIAL_BACKOFF_MIN;
+                    }
+                }
+                Err(e) => warn!("dialing API {} failed (retry in {}s): {}", api, backoff.as_secs(), e),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(DIAL_BACKOFF_MAX);
+        }
+    }
+
+    /// One reverse connection, served until it closes.
+    async fn dial_once(
+        &self,
+        api: &str,
+        enrollment: Arc<WorkerEnrollment>,
+    ) -> Result<Duration, Box<dyn std::error::Error + Send + Sync>> {
+        let addr = tokio::net::lookup_host(api).await?.next().ok_or("API address did not resolve")?;
+        let endpoint = sonotxt_core::quic::client_endpoint()?;
+        let conn = endpoint.connect(addr, "localhost")?.await?;
+        info!("connected to API at {} as {}", addr, enrollment.name);
+
+        let since = Instant::now();
+        handle_connection(
+            conn,
+            self.noise.clone(),
+            self.attestation.clone(),
+            self.state.clone(),
+            self.limits,
+            self.start_time,
+            Some(enrollment),
+        )
+        .await?;
+        Ok(since.elapsed())
+    }
+}
+
+/// Per-connection state shared by all of its streams.
+struct ConnSession {
+    /// Body encoding negotiated via ALPN
+    format: WireFormat,
+    /// Frame size limits for API messages
+    limits: FrameLimits,
+    /// Noise session, set once by the handshake stream
+    id: RwLock<Option<[u8; 16]>>,
+    /// Requests running on this connection, for `Cancel`
+    inflight: Arc<InFlight>,
+    /// Set on connections this worker dialed: answered to `EnrollmentRequest`
+    enrollment: Option<Arc<WorkerEnrollment>>,
 }
 
 async fn handle_connection(
     conn: quinn::Connection,
-    noise: Arc<RwLock<NoiseServer>>,
+    noise: Arc<NoiseServer>,
+    attestation: Arc<AttestationBundle>,
     state: Arc<WorkerState>,
+    limits: FrameLimits,
     start_time: Instant,
+    enrollment: Option<Arc<WorkerEnrollment>>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let session_id: Arc<RwLock<Option<[u8; 16]>>> = Arc::new(RwLock::new(None));
+    let session = Arc::new(ConnSession {
+        format: wire_format(&conn),
+        limits,
+        id: RwLock::new(None),
+        inflight: Arc::new(InFlight::new(state.active.clone())),
+        enrollment,
+    });
+    info!("QUIC wire format: {:?}", session.format);
 
     loop {
         match conn.accept_bi().await {
             Ok((mut send, mut recv)) => {
                 let noise = noise.clone();
+                let attestation = attestation.clone();
                 let state = state.clone();
-                let sid = session_id.clone();
+                let session = session.clone();
                 let start = start_time;
 
                 tokio::spawn(async move {
-                    if let Err(e) = handle_stream(&mut send, &mut recv, noise, state, sid, start).await {
+                    if let Err(e) = handle_stream(&mut send, &mut recv, noise, &attestation, state, &session, start).await {
                         error!("QUIC stream error: {:?}", e);
                     }
                 });
@@ -94,8 +207,8 @@ async fn handle_connection(
         }
     }
 
-    if let Some(sid) = *session_id.read().await {
-        noise.write().await.remove_session(&sid);
+    if let Some(sid) = *session.id.read().await {
+        noise.remove_session(&sid);
     }
 
     Ok(())
@@ -104,71 +217,180 @@ async fn handle_connection(
 async fn handle_stream(
     send: &mut quinn::SendStream,
     recv: &mut quinn::RecvStream,
-    noise: Arc<RwLock<NoiseServer>>,
+    noise: Arc<NoiseServer>,
+    attestation: &AttestationBundle,
     state: Arc<WorkerState>,
-    session_id: Arc<RwLock<Option<[u8; 16]>>>,
+    session: &ConnSession,
     start_time: Instant,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let msg = read_message(recv).await?;
+    let format = session.format;
+    let session_id = &session.id;
+
+    let msg = match read_frame(recv, format, &session.limits, STREAMED).await? {
+        Frame::Message(msg) => msg,
+        // Streamed ASR upload: decrypt each Noise chunk as it arrives
+        Frame::Payload { mut payload, .. } => {
+            let sid = session_id.read().await.ok_or("no session")?;
+            let mut decryptor = StreamDecryptor::new();
+            let mut plaintext = Vec::new();
+            while let Some(piece) = payload.next_piece().await? {
+                plaintext.extend(noise.decrypt_piece(&sid, &mut decryptor, &piece)?);
+            }
+            decryptor.finish()?;
+
+            respond_asr(send, session, &noise, &sid, &state, &plaintext).await?;
+            send.finish()?;
+            return Ok(());
+        }
+    };
 
     match msg {
         Message::AttestationRequest => {
-            let static_key = noise.read().await.static_public_key().to_vec();
-            let attestation = generate_attestation(&static_key);
-            write_message(send, &Message::Attestation(attestation)).await?;
+            write_message(send, &Message::Attestation(attestation.clone()), format).await?;
         }
 
         Message::NoiseHandshake(client_msg) => {
-            let (sid, response_msg) = noise.write().await.process_handshake(&client_msg)?;
-            *session_id.write().await = Some(sid);
-            info!("Noise session established");
+            // One session per connection, so closing it frees everything it made
+            let mut current = session_id.write().await;
+            if current.is_some() {
+                return Err("connection already has a Noise session".into());
+            }
+            let (sid, response_msg) = noise.process_handshake(&client_msg)?;
+            *current = Some(sid);
+            drop(current);
+            if noise.has_session(&sid) {
+                info!("Noise session established");
+            }
             write_message(
                 send,
                 &Message::NoiseHandshakeResponse {
                     handshake: response_msg,
                     session_id: sid.to_vec(),
                 },
+                format,
             )
             .await?;
         }
 
+        Message::NoiseHandshakeFinish { handshake, session_id: finish_sid } => {
+            let sid = session_id.read().await.ok_or("no handshake in progress")?;
+            if finish_sid != sid {
+                return Err("handshake session mismatch".into());
+            }
+            if let Err(e) = noise.finish_handshake(&sid, &handshake) {
+                warn!("Noise handshake rejected: {}", e);
+                return Err(e);
+            }
+            info!("Noise session established");
+            write_message(send, &Message::NoiseHandshakeComplete, format).await?;
+        }
+
         Message::EncryptedRequest(ciphertext) => {
             let sid = session_id.read().await.ok_or("no session")?;
-            let plaintext = noise.write().await.decrypt(&sid, &ciphertext)?;
-            let request: EncryptedTtsRequest = serde_json::from_slice(&plaintext)?;
+            let plaintext = noise.decrypt(&sid, &ciphertext)?;
+            let request: EncryptedTtsRequest = format.from_slice(&plaintext)?;
 
             info!("TTS: voice={}, len={}", request.voice, request.text.len());
 
-            let response = crate::processor::run_tts(&state, &request).await;
+            let registration = session.inflight.register(request.request_id);
+            let Some(response) = registration.run(crate::processor::run_tts(&state, &request)).await else {
+                info!("TTS cancelled");
+                return Ok(());
+            };
 
-            let response_bytes = serde_json::to_vec(&response)?;
-            let encrypted = noise.write().await.encrypt(&sid, &response_bytes)?;
-            write_message(send, &Message::EncryptedResponse(encrypted)).await?;
+            let response_bytes = format.to_vec(&response)?;
+            let encrypted = noise.encrypt(&sid, &response_bytes)?;
+            write_message(send, &Message::EncryptedResponse(encrypted), format).await?;
+        }
+
+        Message::EncryptedStreamRequest(ciphertext) => {
+            let sid = session_id.read().await.ok_or("no session")?;
+            let plaintext = noise.decrypt(&sid, &ciphertext)?;
+            let request: EncryptedTtsRequest = format.from_slice(&plaintext)?;
+
+            let segments = crate::processor::split_text(&request.text);
+            info!(
+                "TTS stream: voice={}, len={}, segments={}",
+                request.voice,
+                request.text.len(),
+                segments.len()
+            );
+
+            if segments.is_empty() {
+                let chunk = StreamChunk {
+                    request_id: request.request_id,
+                    sequence: 0,
+                    audio: vec![],
+                    is_final: true,
+                    error: Some("empty text".to_string()),
+                };
+                let encrypted = noise.encrypt(&sid, &format.to_vec(&chunk)?)?;
+                write_message(send, &Message::EncryptedStreamChunk(encrypted), format).await?;
+            }
+
+            // Synthesize segment by segment; each chunk is a self-contained WAV.
+            // A write error or a cancel means the API dropped the stream — stop.
+            let registration = session.inflight.register(request.request_id);
+            let last = segments.len().saturating_sub(1);
+            for (i, segment) in segments.into_iter().enumerate() {
+                let segment_request = EncryptedTtsRequest {
+                    text: segment,
+                    ..request.clone()
+                };
+                let Some(response) = registration.run(crate::processor::run_tts(&state, &segment_request)).await
+                else {
+                    info!("TTS stream cancelled after {} segments", i);
+                    break;
+                };
+                let failed = response.error.is_some();
+
+                let chunk = StreamChunk {
+                    request_id: request.request_id,
+                    sequence: i as u32,
+                    audio: response.audio,
+                    is_final: i == last || failed,
+                    error: response.error,
+                };
+
+                let chunk_bytes = format.to_vec(&chunk)?;
+                let encrypted = noise.encrypt(&sid, &chunk_bytes)?;
+                write_message(send, &Message::EncryptedStreamChunk(encrypted), format).await?;
+
+                if failed {
+                    break;
+                }
+            }
         }
 
         Message::EncryptedAsrRequest(ciphertext) => {
             let sid = session_id.read().await.ok_or("no session")?;
-            let plaintext = noise.write().await.decrypt(&sid, &ciphertext)?;
-            let request: EncryptedAsrRequest = serde_json::from_slice(&plaintext)?;
+            let plaintext = noise.decrypt(&sid, &ciphertext)?;
+            respond_asr(send, session, &noise, &sid, &state, &plaintext).await?;
+        }
 
-            info!("ASR: audio_len={}", request.audio_base64.len());
+        Message::EncryptedLlmRequest(ciphertext) => {
+            let sid = session_id.read().await.ok_or("no session")?;
+            let plaintext = noise.decrypt(&sid, &ciphertext)?;
+            let request: EncryptedLlmRequest = format.from_slice(&plaintext)?;
 
-            let response = match crate::processor::run_asr(&state, &request.audio_base64).await {
-                Ok(text) => EncryptedAsrResponse {
-                    request_id: request.request_id,
-                    text,
-                    error: None,
-                },
-                Err(e) => EncryptedAsrResponse {
-                    request_id: request.request_id,
-                    text: String::new(),
-                    error: Some(e),
-                },
+            info!("LLM: messages={}, stream={}", request.messages.len(), request.stream);
+
+            let registration = session.inflight.register(request.request_id);
+            let Some(result) = registration.run(stream_llm(send, format, &noise, &sid, &state, &request)).await else {
+                info!("LLM cancelled");
+                return Ok(());
             };
+            result?;
+        }
+
+        Message::CapabilitiesRequest => {
+            write_message(send, &Message::Capabilities(state.config.capabilities()), format).await?;
+        }
 
-            let response_bytes = serde_json::to_vec(&response)?;
-            let encrypted = noise.write().await.encrypt(&sid, &response_bytes)?;
-            write_message(send, &Message::EncryptedAsrResponse(encrypted)).await?;
+        Message::Cancel { request_id } => {
+            if session.inflight.cancel(&request_id) {
+                info!("request cancelled by API");
+            }
         }
 
         Message::HealthRequest => {
@@ -180,13 +402,26 @@ async fn handle_stream(
                 &Message::HealthResponse(WorkerHealth {
                     speech_ok,
                     llm_ok,
-                    jobs_processing: 0,
+                    jobs_processing: state.active.load(Ordering::Relaxed),
                     uptime_secs: start_time.elapsed().as_secs(),
                 }),
+                format,
             )
             .await?;
         }
 
+        Message::EnrollmentRequest => {
+            // Only an API this worker dialed itself learns the token
+            match session.enrollment {
+                Some(ref enrollment) => {
+                    let sid = session_id.read().await.ok_or("no session")?;
+                    let encrypted = noise.encrypt(&sid, &format.to_vec(enrollment.as_ref())?)?;
+                    write_message(send, &Message::Enrollment(encrypted), format).await?;
+                }
+                None => warn!("enrollment requested on an inbound connection, ignored"),
+            }
+        }
+
         _ => {
             warn!("unexpected QUIC message type");
         }
@@ -196,24 +431,112 @@ async fn handle_stream(
     Ok(())
 }
 
-fn generate_attestation(static_key: &[u8]) -> AttestationBundle {
-    use sha2::{Digest, Sha256};
+/// Run a decrypted ASR request and send back the encrypted result, unless
+/// the API cancels it first.
+async fn respond_asr(
+    send: &mut quinn::SendStream,
+    session: &ConnSession,
+    noise: &NoiseServer,
+    sid: &[u8; 16],
+    state: &WorkerState,
+    plaintext: &[u8],
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let format = session.format;
+    let request: EncryptedAsrRequest = format.from_slice(plaintext)?;
+
+    info!("ASR: audio_len={}", request.audio_base64.len());
+
+    let registration = session.inflight.register(request.request_id);
+    let Some(result) = registration.run(crate::processor::run_asr(state, &request.audio_base64)).await else {
+        info!("ASR cancelled");
+        return Ok(());
+    };
+    let response = match result {
+        Ok(text) => EncryptedAsrResponse {
+            request_id: request.request_id,
+            text,
+            error: None,
+        },
+        Err(e) => EncryptedAsrResponse {
+            request_id: request.request_id,
+            text: String::new(),
+            error: Some(e),
+        },
+    };
+
+    let response_bytes = format.to_vec(&response)?;
+    let encrypted = noise.encrypt(sid, &response_bytes)?;
+    write_message(send, &Message::EncryptedAsrResponse(encrypted), format).await?;
+    Ok(())
+}
 
-    let mut quote_hasher = Sha256::new();
-    quote_hasher.update(b"insecure-quote");
-    let quote = quote_hasher.finalize().to_vec();
+/// Run an LLM request, sending each sentence as an encrypted `LlmEvent`
+/// as soon as the model produces it, then the final event.
+async fn stream_llm(
+    send: &mut quinn::SendStream,
+    format: WireFormat,
+    noise: &NoiseServer,
+    sid: &[u8; 16],
+    state: &WorkerState,
+    request: &EncryptedLlmRequest,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (tx, mut rx) = mpsc::channel(16);
+    let llm = crate::processor::run_llm(state, request, tx);
+    tokio::pin!(llm);
+
+    let sentence_event = |sequence, sentence| LlmEvent {
+        request_id: request.request_id,
+        sequence,
+        sentence: Some(sentence),
+        is_final: false,
+        full_response: None,
+        tokens: 0,
+        error: None,
+    };
+
+    let mut sequence = 0u32;
+    let result = loop {
+        tokio::select! {
+            biased;
+            Some(sentence) = rx.recv() => {
+                write_llm_event(send, format, noise, sid, &sentence_event(sequence, sentence)).await?;
+                sequence += 1;
+            }
+            result = &mut llm => break result,
+        }
+    };
+    // Sentences queued before the call returned
+    while let Ok(sentence) = rx.try_recv() {
+        write_llm_event(send, format, noise, sid, &sentence_event(sequence, sentence)).await?;
+        sequence += 1;
+    }
 
-    let mut sig_hasher = Sha256::new();
-    sig_hasher.update(&quote);
-    sig_hasher.update(static_key);
-    let binding = sig_hasher.finalize().to_vec();
+    let (full_response, tokens, error) = match result {
+        Ok((full_response, tokens)) => (Some(full_response), tokens, None),
+        Err(e) => (None, 0, Some(e)),
+    };
+    let last = LlmEvent {
+        request_id: request.request_id,
+        sequence,
+        sentence: None,
+        is_final: true,
+        full_response,
+        tokens,
+        error,
+    };
+    write_llm_event(send, format, noise, sid, &last).await
+}
 
-    AttestationBundle {
-        quote,
-        static_key: static_key.to_vec(),
-        binding_sig: binding,
-        tee_type: TeeType::Insecure,
-    }
+async fn write_llm_event(
+    send: &mut quinn::SendStream,
+    format: WireFormat,
+    noise: &NoiseServer,
+    sid: &[u8; 16],
+    event: &LlmEvent,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let encrypted = noise.encrypt(sid, &format.to_vec(event)?)?;
+    write_message(send, &Message::EncryptedLlmEvent(encrypted), format).await?;
+    Ok(())
 }
 
 async fn check_local(http: &reqwest::Client, base_url: &str) -> bool {